@@ -1,6 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use hl_backtest::data::types::Candle;
-use hl_backtest::indicators2::create_indicator;
+use hl_backtest::indicators2::{create_indicator, ParamValue};
 use std::collections::HashMap;
 
 fn create_test_candle(time: u64, open: f64, high: f64, low: f64, close: f64, volume: f64) -> Candle {
@@ -42,8 +42,8 @@ fn bench_sma(c: &mut Criterion) {
     let candles = generate_candles(1000);
     
     for window in windows {
-        let mut params = HashMap::new();
-        params.insert("length".to_string(), window as f64);
+        let mut params: HashMap<String, ParamValue> = HashMap::new();
+        params.insert("length".to_string(), (window as f64).into());
         
         group.throughput(Throughput::Elements(window as u64));
         group.bench_with_input(
@@ -51,7 +51,7 @@ fn bench_sma(c: &mut Criterion) {
             &window,
             |b, &window| {
                 let mut params = HashMap::new();
-                params.insert("length".to_string(), window as f64);
+                params.insert("length".to_string(), (window as f64).into());
                 let mut indicator = create_indicator("SMA", &params).unwrap();
                 
                 b.iter(|| {
@@ -80,7 +80,7 @@ fn bench_ema(c: &mut Criterion) {
             &window,
             |b, &window| {
                 let mut params = HashMap::new();
-                params.insert("length".to_string(), window as f64);
+                params.insert("length".to_string(), (window as f64).into());
                 let mut indicator = create_indicator("EMA", &params).unwrap();
                 
                 b.iter(|| {
@@ -109,7 +109,7 @@ fn bench_wma(c: &mut Criterion) {
             &window,
             |b, &window| {
                 let mut params = HashMap::new();
-                params.insert("length".to_string(), window as f64);
+                params.insert("length".to_string(), (window as f64).into());
                 let mut indicator = create_indicator("WMA", &params).unwrap();
                 
                 b.iter(|| {
@@ -138,7 +138,7 @@ fn bench_rsi(c: &mut Criterion) {
             &window,
             |b, &window| {
                 let mut params = HashMap::new();
-                params.insert("length".to_string(), window as f64);
+                params.insert("length".to_string(), (window as f64).into());
                 let mut indicator = create_indicator("RSI", &params).unwrap();
                 
                 b.iter(|| {
@@ -171,9 +171,9 @@ fn bench_macd(c: &mut Criterion) {
             &(fast, slow, signal),
             |b, &(fast, slow, signal)| {
                 let mut params = HashMap::new();
-                params.insert("fast".to_string(), fast as f64);
-                params.insert("slow".to_string(), slow as f64);
-                params.insert("signal".to_string(), signal as f64);
+                params.insert("fast".to_string(), (fast as f64).into());
+                params.insert("slow".to_string(), (slow as f64).into());
+                params.insert("signal".to_string(), (signal as f64).into());
                 let mut indicator = create_indicator("MACD", &params).unwrap();
                 
                 b.iter(|| {
@@ -204,8 +204,8 @@ fn bench_bbands(c: &mut Criterion) {
             &window,
             |b, &window| {
                 let mut params = HashMap::new();
-                params.insert("length".to_string(), window as f64);
-                params.insert("std".to_string(), 2.0);
+                params.insert("length".to_string(), (window as f64).into());
+                params.insert("std".to_string(), 2.0.into());
                 let mut indicator = create_indicator("BBANDS", &params).unwrap();
                 
                 b.iter(|| {
@@ -240,9 +240,9 @@ fn bench_stoch(c: &mut Criterion) {
             &(k_period, k_smooth, d_period),
             |b, &(k_period, k_smooth, d_period)| {
                 let mut params = HashMap::new();
-                params.insert("k_period".to_string(), k_period as f64);
-                params.insert("k_smooth".to_string(), k_smooth as f64);
-                params.insert("d_period".to_string(), d_period as f64);
+                params.insert("k_period".to_string(), (k_period as f64).into());
+                params.insert("k_smooth".to_string(), (k_smooth as f64).into());
+                params.insert("d_period".to_string(), (d_period as f64).into());
                 let mut indicator = create_indicator("STOCH", &params).unwrap();
                 
                 b.iter(|| {
@@ -272,7 +272,7 @@ fn bench_atr(c: &mut Criterion) {
             &window,
             |b, &window| {
                 let mut params = HashMap::new();
-                params.insert("period".to_string(), window as f64);
+                params.insert("period".to_string(), (window as f64).into());
                 let mut indicator = create_indicator("ATR", &params).unwrap();
                 
                 b.iter(|| {
@@ -301,7 +301,7 @@ fn bench_adx(c: &mut Criterion) {
             &window,
             |b, &window| {
                 let mut params = HashMap::new();
-                params.insert("period".to_string(), window as f64);
+                params.insert("period".to_string(), (window as f64).into());
                 let mut indicator = create_indicator("ADX", &params).unwrap();
                 
                 b.iter(|| {
@@ -339,6 +339,231 @@ fn bench_obv(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_moving_average_pack(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Moving Average Pack");
+    
+    let candles = generate_candles(1000);
+    
+    for indicator_type in ["HMA", "DEMA", "TEMA", "KAMA"] {
+        for window in [10, 50, 200] {
+            group.bench_with_input(
+                BenchmarkId::new(indicator_type, window),
+                &window,
+                |b, &window| {
+                    let mut params = HashMap::new();
+                    params.insert("length".to_string(), (window as f64).into());
+                    let mut indicator = create_indicator(indicator_type, &params).unwrap();
+                    
+                    b.iter(|| {
+                        for candle in &candles {
+                            indicator.update(black_box(candle)).unwrap();
+                            black_box(indicator.value("value").unwrap());
+                        }
+                        indicator.reset();
+                    });
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_channels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Channels");
+    
+    let windows = vec![10, 20, 50, 100];
+    let candles = generate_candles(1000);
+    
+    for indicator_type in ["KELTNER", "DONCHIAN"] {
+        for &window in &windows {
+            group.bench_with_input(
+                BenchmarkId::new(indicator_type, window),
+                &window,
+                |b, &window| {
+                    let mut params = HashMap::new();
+                    params.insert("length".to_string(), (window as f64).into());
+                    let mut indicator = create_indicator(indicator_type, &params).unwrap();
+                    
+                    b.iter(|| {
+                        for candle in &candles {
+                            indicator.update(black_box(candle)).unwrap();
+                            black_box(indicator.value("upper").unwrap());
+                            black_box(indicator.value("middle").unwrap());
+                            black_box(indicator.value("lower").unwrap());
+                        }
+                        indicator.reset();
+                    });
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_supertrend(c: &mut Criterion) {
+    let mut group = c.benchmark_group("SuperTrend");
+    
+    let windows = vec![7, 10, 14, 21];
+    let candles = generate_candles(1000);
+    
+    for window in windows {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(window),
+            &window,
+            |b, &window| {
+                let mut params = HashMap::new();
+                params.insert("period".to_string(), (window as f64).into());
+                let mut indicator = create_indicator("SUPERTREND", &params).unwrap();
+                
+                b.iter(|| {
+                    for candle in &candles {
+                        indicator.update(black_box(candle)).unwrap();
+                        black_box(indicator.value("value").unwrap());
+                        black_box(indicator.value("direction").unwrap());
+                    }
+                    indicator.reset();
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_psar(c: &mut Criterion) {
+    let mut group = c.benchmark_group("PSAR");
+    
+    let candles = generate_candles(1000);
+    
+    group.bench_function("PSAR", |b| {
+        let mut indicator = create_indicator("PSAR", &HashMap::new()).unwrap();
+        
+        b.iter(|| {
+            for candle in &candles {
+                indicator.update(black_box(candle)).unwrap();
+                black_box(indicator.value("value").unwrap());
+            }
+            indicator.reset();
+        });
+    });
+    
+    group.finish();
+}
+
+fn bench_ichimoku(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Ichimoku");
+    
+    let configs = vec![
+        (9, 26, 52, 26),
+        (20, 60, 120, 30),
+    ];
+    let candles = generate_candles(1000);
+    
+    for (tenkan, kijun, senkou, displacement) in configs {
+        let id = format!("{}-{}-{}-{}", tenkan, kijun, senkou, displacement);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(id),
+            &(tenkan, kijun, senkou, displacement),
+            |b, &(tenkan, kijun, senkou, displacement)| {
+                let mut params = HashMap::new();
+                params.insert("tenkan".to_string(), (tenkan as f64).into());
+                params.insert("kijun".to_string(), (kijun as f64).into());
+                params.insert("senkou".to_string(), (senkou as f64).into());
+                params.insert("displacement".to_string(), (displacement as f64).into());
+                let mut indicator = create_indicator("ICHIMOKU", &params).unwrap();
+                
+                b.iter(|| {
+                    for candle in &candles {
+                        indicator.update(black_box(candle)).unwrap();
+                        black_box(indicator.value("tenkan").unwrap());
+                        black_box(indicator.value("kijun").unwrap());
+                        black_box(indicator.value("senkou_a").unwrap());
+                        black_box(indicator.value("senkou_b").unwrap());
+                    }
+                    indicator.reset();
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_vwap(c: &mut Criterion) {
+    let mut group = c.benchmark_group("VWAP");
+    
+    let candles = generate_candles(1000);
+    
+    for session in ["day", "week"] {
+        group.bench_function(session, |b| {
+            let mut params = HashMap::new();
+            params.insert("session".to_string(), session.into());
+            let mut indicator = create_indicator("VWAP", &params).unwrap();
+            
+            b.iter(|| {
+                for candle in &candles {
+                    indicator.update(black_box(candle)).unwrap();
+                    black_box(indicator.value("value").unwrap());
+                }
+                indicator.reset();
+            });
+        });
+    }
+    
+    group.finish();
+}
+
+fn bench_volume_pack(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Volume Pack");
+    
+    let candles = generate_candles(1000);
+    
+    // (indicator, window parameter)
+    let windowed = [
+        ("VWMA", "length"),
+        ("MFI", "period"),
+        ("CMF", "period"),
+        ("EOM", "period"),
+        ("FORCE", "length"),
+        ("RVOL", "length"),
+    ];
+    for (indicator_type, param) in windowed {
+        for window in [10, 20, 50] {
+            group.bench_with_input(
+                BenchmarkId::new(indicator_type, window),
+                &window,
+                |b, &window| {
+                    let mut params = HashMap::new();
+                    params.insert(param.to_string(), (window as f64).into());
+                    let mut indicator = create_indicator(indicator_type, &params).unwrap();
+                    
+                    b.iter(|| {
+                        for candle in &candles {
+                            indicator.update(black_box(candle)).unwrap();
+                            black_box(indicator.value("value").unwrap());
+                        }
+                        indicator.reset();
+                    });
+                },
+            );
+        }
+    }
+    
+    for indicator_type in ["AD", "VOLOSC"] {
+        group.bench_function(indicator_type, |b| {
+            let mut indicator = create_indicator(indicator_type, &HashMap::new()).unwrap();
+            
+            b.iter(|| {
+                for candle in &candles {
+                    indicator.update(black_box(candle)).unwrap();
+                    black_box(indicator.value("value").unwrap());
+                }
+                indicator.reset();
+            });
+        });
+    }
+    
+    group.finish();
+}
+
 fn bench_all_indicators_combined(c: &mut Criterion) {
     let mut group = c.benchmark_group("All Indicators Combined");
     
@@ -346,40 +571,40 @@ fn bench_all_indicators_combined(c: &mut Criterion) {
     
     group.bench_function("10 indicators", |b| {
         let mut params_sma = HashMap::new();
-        params_sma.insert("length".to_string(), 20.0);
+        params_sma.insert("length".to_string(), 20.0.into());
         let mut sma = create_indicator("SMA", &params_sma).unwrap();
         
         let mut params_ema = HashMap::new();
-        params_ema.insert("length".to_string(), 20.0);
+        params_ema.insert("length".to_string(), 20.0.into());
         let mut ema = create_indicator("EMA", &params_ema).unwrap();
         
         let mut params_rsi = HashMap::new();
-        params_rsi.insert("length".to_string(), 14.0);
+        params_rsi.insert("length".to_string(), 14.0.into());
         let mut rsi = create_indicator("RSI", &params_rsi).unwrap();
         
         let mut params_macd = HashMap::new();
-        params_macd.insert("fast".to_string(), 12.0);
-        params_macd.insert("slow".to_string(), 26.0);
-        params_macd.insert("signal".to_string(), 9.0);
+        params_macd.insert("fast".to_string(), 12.0.into());
+        params_macd.insert("slow".to_string(), 26.0.into());
+        params_macd.insert("signal".to_string(), 9.0.into());
         let mut macd = create_indicator("MACD", &params_macd).unwrap();
         
         let mut params_bb = HashMap::new();
-        params_bb.insert("length".to_string(), 20.0);
-        params_bb.insert("std".to_string(), 2.0);
+        params_bb.insert("length".to_string(), 20.0.into());
+        params_bb.insert("std".to_string(), 2.0.into());
         let mut bb = create_indicator("BBANDS", &params_bb).unwrap();
         
         let mut params_stoch = HashMap::new();
-        params_stoch.insert("k_period".to_string(), 14.0);
-        params_stoch.insert("k_smooth".to_string(), 1.0);
-        params_stoch.insert("d_period".to_string(), 3.0);
+        params_stoch.insert("k_period".to_string(), 14.0.into());
+        params_stoch.insert("k_smooth".to_string(), 1.0.into());
+        params_stoch.insert("d_period".to_string(), 3.0.into());
         let mut stoch = create_indicator("STOCH", &params_stoch).unwrap();
         
         let mut params_atr = HashMap::new();
-        params_atr.insert("period".to_string(), 14.0);
+        params_atr.insert("period".to_string(), 14.0.into());
         let mut atr = create_indicator("ATR", &params_atr).unwrap();
         
         let mut params_adx = HashMap::new();
-        params_adx.insert("period".to_string(), 14.0);
+        params_adx.insert("period".to_string(), 14.0.into());
         let mut adx = create_indicator("ADX", &params_adx).unwrap();
         
         let mut obv = create_indicator("OBV", &HashMap::new()).unwrap();
@@ -435,6 +660,13 @@ criterion_group!(
     bench_atr,
     bench_adx,
     bench_obv,
+    bench_moving_average_pack,
+    bench_channels,
+    bench_supertrend,
+    bench_psar,
+    bench_ichimoku,
+    bench_vwap,
+    bench_volume_pack,
     bench_all_indicators_combined
 );
 criterion_main!(benches);
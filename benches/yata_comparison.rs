@@ -1,4 +1,4 @@
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use hl_backtest::data::types::Candle;
 use hl_backtest::indicators2::create_indicator;
 use std::collections::HashMap;
@@ -26,7 +26,7 @@ fn create_single_candle() -> Candle {
 fn bench_sma_w10(c: &mut Criterion) {
     c.bench_function("sma_w10", |b| {
         let mut params = HashMap::new();
-        params.insert("length".to_string(), 10.0);
+        params.insert("length".to_string(), 10.0.into());
         let mut indicator = create_indicator("SMA", &params).unwrap();
         let candle = create_single_candle();
         
@@ -40,7 +40,7 @@ fn bench_sma_w10(c: &mut Criterion) {
 fn bench_sma_w100(c: &mut Criterion) {
     c.bench_function("sma_w100", |b| {
         let mut params = HashMap::new();
-        params.insert("length".to_string(), 100.0);
+        params.insert("length".to_string(), 100.0.into());
         let mut indicator = create_indicator("SMA", &params).unwrap();
         let candle = create_single_candle();
         
@@ -54,7 +54,7 @@ fn bench_sma_w100(c: &mut Criterion) {
 fn bench_ema_w10(c: &mut Criterion) {
     c.bench_function("ema_w10", |b| {
         let mut params = HashMap::new();
-        params.insert("length".to_string(), 10.0);
+        params.insert("length".to_string(), 10.0.into());
         let mut indicator = create_indicator("EMA", &params).unwrap();
         let candle = create_single_candle();
         
@@ -68,7 +68,7 @@ fn bench_ema_w10(c: &mut Criterion) {
 fn bench_ema_w100(c: &mut Criterion) {
     c.bench_function("ema_w100", |b| {
         let mut params = HashMap::new();
-        params.insert("length".to_string(), 100.0);
+        params.insert("length".to_string(), 100.0.into());
         let mut indicator = create_indicator("EMA", &params).unwrap();
         let candle = create_single_candle();
         
@@ -82,7 +82,7 @@ fn bench_ema_w100(c: &mut Criterion) {
 fn bench_wma_w10(c: &mut Criterion) {
     c.bench_function("wma_w10", |b| {
         let mut params = HashMap::new();
-        params.insert("length".to_string(), 10.0);
+        params.insert("length".to_string(), 10.0.into());
         let mut indicator = create_indicator("WMA", &params).unwrap();
         let candle = create_single_candle();
         
@@ -96,7 +96,7 @@ fn bench_wma_w10(c: &mut Criterion) {
 fn bench_wma_w100(c: &mut Criterion) {
     c.bench_function("wma_w100", |b| {
         let mut params = HashMap::new();
-        params.insert("length".to_string(), 100.0);
+        params.insert("length".to_string(), 100.0.into());
         let mut indicator = create_indicator("WMA", &params).unwrap();
         let candle = create_single_candle();
         
@@ -110,7 +110,7 @@ fn bench_wma_w100(c: &mut Criterion) {
 fn bench_rsi_w10(c: &mut Criterion) {
     c.bench_function("rsi_w10", |b| {
         let mut params = HashMap::new();
-        params.insert("length".to_string(), 10.0);
+        params.insert("length".to_string(), 10.0.into());
         let mut indicator = create_indicator("RSI", &params).unwrap();
         let candle = create_single_candle();
         
@@ -124,7 +124,7 @@ fn bench_rsi_w10(c: &mut Criterion) {
 fn bench_rsi_w100(c: &mut Criterion) {
     c.bench_function("rsi_w100", |b| {
         let mut params = HashMap::new();
-        params.insert("length".to_string(), 100.0);
+        params.insert("length".to_string(), 100.0.into());
         let mut indicator = create_indicator("RSI", &params).unwrap();
         let candle = create_single_candle();
         
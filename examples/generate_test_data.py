@@ -1,12 +1,18 @@
 #!/usr/bin/env python3
-"""Generate test candle data for backtesting"""
+"""Generate test candle data for backtesting.
+
+Writes a plain CSV to load into the candle cache with:
+
+    hl-backtest import --file data/ETH_1h.csv --asset ETH --interval 1h
+"""
 
 import csv
+import os
 import random
-from datetime import datetime, timedelta
+from datetime import datetime, timedelta, timezone
 
 # Generate 100 hours of candle data
-start_time = datetime(2024, 1, 1, 0, 0, 0)
+start_time = datetime(2024, 1, 1, 0, 0, 0, tzinfo=timezone.utc)
 base_price = 2500.0  # Starting ETH price
 
 candles = []
@@ -14,41 +20,36 @@ current_price = base_price
 
 for i in range(100):
     time_open = start_time + timedelta(hours=i)
-    time_close = time_open + timedelta(hours=1) - timedelta(seconds=1)
     
     # Random walk price movement
     change = random.uniform(-0.02, 0.02)  # ±2% per hour
     open_price = current_price
-    high_price = open_price * (1 + abs(random.uniform(0, 0.01)))
-    low_price = open_price * (1 - abs(random.uniform(0, 0.01)))
     close_price = open_price * (1 + change)
+    high_price = max(open_price, close_price) * (1 + abs(random.uniform(0, 0.01)))
+    low_price = min(open_price, close_price) * (1 - abs(random.uniform(0, 0.01)))
     current_price = close_price
     
     volume = random.uniform(1000, 10000)
-    num_trades = random.randint(50, 500)
     
+    # Column names match the import command's default mapping
     candles.append({
-        'time_open': int(time_open.timestamp() * 1000),
-        'time_close': int(time_close.timestamp() * 1000),
-        'coin': 'ETH',
-        'interval': '1h',
+        'timestamp': int(time_open.timestamp() * 1000),
         'open': f'{open_price:.2f}',
-        'close': f'{close_price:.2f}',
         'high': f'{high_price:.2f}',
         'low': f'{low_price:.2f}',
+        'close': f'{close_price:.2f}',
         'volume': f'{volume:.2f}',
-        'num_trades': num_trades,
     })
 
 # Write to CSV
-import os
-os.makedirs('data/hyperliquid/ETH', exist_ok=True)
+os.makedirs('data', exist_ok=True)
 
-with open('data/hyperliquid/ETH/1h.csv', 'w', newline='') as f:
+with open('data/ETH_1h.csv', 'w', newline='') as f:
     writer = csv.DictWriter(f, fieldnames=candles[0].keys())
     writer.writeheader()
     writer.writerows(candles)
 
-print(f"Generated {len(candles)} candles in data/hyperliquid/ETH/1h.csv")
-print(f"Date range: {start_time} to {time_close}")
+print(f"Generated {len(candles)} candles in data/ETH_1h.csv")
+print(f"Date range: {start_time} to {time_open + timedelta(hours=1)}")
+print("Load them with: hl-backtest import --file data/ETH_1h.csv --asset ETH --interval 1h")
 
@@ -5,11 +5,17 @@ use std::path::PathBuf;
 
 use crate::data::{
     export_candles_to_parquet, export_equity_to_parquet, export_trades_to_parquet, load_candles,
-    Cache,
+    import_candles, validate_candles, validate_l2_events, Cache, DataPolicy, ImportSpec,
+    MarketDataSource, SourceSpec, TimestampUnit,
 };
-use crate::ingest::{parse_l2_file, S3Downloader};
-use crate::strategy::Strategy;
-use crate::orders::simulate;
+use crate::data::TimeRange;
+use crate::ingest::{
+    build_candles, parse_l2_file, parse_l2_jsonl_file, parse_trades_jsonl_file, PriceSource,
+    S3Downloader,
+};
+use crate::strategy::{compile_strategy, Strategy};
+use crate::orders::simulate_with_references;
+use std::collections::HashMap;
 use crate::perps::funding::FundingSchedule;
 use crate::report::write_results;
 use std::fs;
@@ -34,10 +40,49 @@ fn validate_asset(asset: &str) -> Result<()> {
     Ok(())
 }
 
+/// Fill the lot size and max leverage the strategy does not set from the
+/// source's asset metadata. Without metadata sizes are not rounded.
+async fn apply_asset_meta(source: &dyn MarketDataSource, coin: &str, strategy: &mut Strategy) {
+    let instrument = &mut strategy.instrument;
+    if instrument.sz_decimals.is_some() && instrument.max_leverage.is_some() {
+        return;
+    }
+    let meta = match source.meta().await {
+        Ok(meta) => meta,
+        Err(e) => {
+            eprintln!("Warning: no asset metadata from {} ({:#}); order sizes are not rounded to lots", source.name(), e);
+            return;
+        }
+    };
+    let Some(asset) = meta.iter().find(|m| m.name.eq_ignore_ascii_case(coin)) else {
+        eprintln!("Warning: {} is not in the asset metadata; order sizes are not rounded to lots", coin);
+        return;
+    };
+    instrument.sz_decimals = instrument.sz_decimals.or(Some(asset.sz_decimals));
+    instrument.max_leverage = instrument.max_leverage.or(asset.max_leverage.map(f64::from));
+}
+
+/// `.jsonl` files in a directory, sorted by name
+fn jsonl_files(dir: &std::path::Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("jsonl"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
 #[derive(Parser)]
 #[command(name = "hl-backtest")]
 #[command(about = "Hyperliquid data ingestor and backtester")]
 pub struct Cli {
+    /// Candle cache directory (overrides HL_BACKTEST_CACHE_DIR and the config file)
+    #[arg(long, global = true)]
+    pub cache_dir: Option<PathBuf>,
+    /// Market data source: mainnet, testnet, local (cache directory only) or an API URL
+    #[arg(long, global = true, default_value = "mainnet")]
+    pub source: SourceSpec,
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -80,6 +125,33 @@ pub enum Commands {
         #[arg(long)]
         out: PathBuf,
     },
+    /// Import candles from an external CSV or Parquet file into the cache
+    Import {
+        /// CSV or Parquet file to import
+        #[arg(long)]
+        file: PathBuf,
+        /// Asset symbol to store the candles under
+        #[arg(long)]
+        asset: String,
+        /// Interval of the candles in the file (e.g. 1m, 1h, 2h)
+        #[arg(long)]
+        interval: String,
+        /// JSON column mapping spec (defaults to timestamp/open/high/low/close/volume)
+        #[arg(long)]
+        spec: Option<PathBuf>,
+        /// Timestamp unit (auto, s, ms, us, ns, iso8601); overrides the spec
+        #[arg(long)]
+        timestamp_unit: Option<TimestampUnit>,
+        /// How to handle rows that fail validation (fail, repair, warn)
+        #[arg(long, default_value = "fail")]
+        data_policy: DataPolicy,
+    },
+    /// Import a CSV candle cache from an earlier version into the Parquet cache
+    MigrateCache {
+        /// Root of the old CSV cache ({asset}/{interval}.csv files)
+        #[arg(long, default_value = "data/hyperliquid")]
+        from: PathBuf,
+    },
     /// Ingest L2 data from S3
     Ingest {
         #[command(subcommand)]
@@ -114,12 +186,18 @@ pub enum Commands {
         /// Slippage in basis points
         #[arg(long, default_value = "5")]
         slippage_bps: u16,
+        /// Minimum minutes between the last fill and a new entry (off by default)
+        #[arg(long)]
+        trade_cooldown_min: Option<u64>,
         /// Output file path (JSON)
         #[arg(long, default_value = "results.json")]
         out: PathBuf,
         /// Export results to Parquet (trades and equity curve)
         #[arg(long)]
         parquet_results: Option<PathBuf>,
+        /// How to handle data that fails validation (fail, repair, warn)
+        #[arg(long, default_value = "warn")]
+        data_policy: DataPolicy,
     },
     /// Run a perps backtest from L2 events
     RunPerps {
@@ -132,6 +210,10 @@ pub enum Commands {
         /// Path to events directory
         #[arg(long)]
         events: PathBuf,
+        /// Directory with trade print JSONL files ({coin}/*.jsonl), the
+        /// source of volume for indicators
+        #[arg(long)]
+        trades: Option<PathBuf>,
         /// Start date-hour (YYYYMMDD-HH)
         #[arg(long)]
         start: String,
@@ -153,15 +235,60 @@ pub enum Commands {
         /// Enable parallel indicator updates
         #[arg(long)]
         indicators_par: Option<bool>,
-        /// Trade cooldown period in minutes
-        #[arg(long)]
-        trade_cooldown_min: Option<u64>,
+        /// Minimum minutes between the last fill and a new entry (0 disables)
+        #[arg(long, default_value = "15")]
+        trade_cooldown_min: u64,
         /// Output file path (JSON)
         #[arg(long, default_value = "results.json")]
         out: PathBuf,
         /// Export results to Parquet (trades and equity curve)
         #[arg(long)]
         parquet_results: Option<PathBuf>,
+        /// How to handle data that fails validation (fail, repair, warn)
+        #[arg(long, default_value = "warn")]
+        data_policy: DataPolicy,
+    },
+    /// Check cached candles or L2 events for gaps, duplicates and bad values
+    ValidateData {
+        #[command(subcommand)]
+        source: ValidateSource,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ValidateSource {
+    /// Validate cached candles
+    Candles {
+        /// Asset symbol (e.g., ETH, BTC)
+        #[arg(long)]
+        asset: String,
+        /// Timeframe interval (1m, 5m, 15m, 1h, 4h, 1d, 1w)
+        #[arg(long)]
+        interval: String,
+        /// Start date (YYYY-MM-DD)
+        #[arg(long)]
+        start: String,
+        /// End date (YYYY-MM-DD)
+        #[arg(long)]
+        end: String,
+        /// Exit with an error if any issue is found
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Validate L2 event files produced by `ingest build-events`
+    L2 {
+        /// Coin symbol (e.g., BTC, ETH)
+        #[arg(long)]
+        coin: String,
+        /// Events directory
+        #[arg(long, default_value = "data/events")]
+        events: PathBuf,
+        /// Report pauses between snapshots longer than this many seconds as gaps
+        #[arg(long)]
+        max_gap_secs: Option<u64>,
+        /// Exit with an error if any issue is found
+        #[arg(long)]
+        strict: bool,
     },
 }
 
@@ -200,6 +327,30 @@ pub enum IngestSubcommand {
         #[arg(long, default_value = "data/events")]
         out: PathBuf,
     },
+    /// Build candles from L2 events (and trade prints, if available)
+    BuildCandles {
+        /// Coin symbol (e.g., BTC, ETH)
+        #[arg(long)]
+        coin: String,
+        /// Candle interval (any <n><s|m|h|d|w>, e.g. 1m, 30m, 2h)
+        #[arg(long)]
+        interval: String,
+        /// Events directory (as written by build-events)
+        #[arg(long, default_value = "data/events")]
+        events: PathBuf,
+        /// Directory with trade print JSONL files ({coin}/*.jsonl)
+        #[arg(long)]
+        trades: Option<PathBuf>,
+        /// Book price used when an interval has no trades (mid, microprice)
+        #[arg(long, default_value = "mid")]
+        price: PriceSource,
+        /// Also export the candles to this Parquet file
+        #[arg(long)]
+        parquet: Option<PathBuf>,
+        /// Don't write the candles into the candle cache
+        #[arg(long)]
+        no_cache: bool,
+    },
 }
 
 impl Cli {
@@ -232,15 +383,22 @@ impl Cli {
                     .timestamp() as u64
                     * 1000;
 
-                let cache = Cache::new()?;
-                cache
-                    .fetch_and_cache(&asset, &interval, start_ts, end_ts)
+                let cache = Cache::open(self.cache_dir.as_deref())?;
+                let source = self.source.build(cache.base_dir())?;
+                let report = cache
+                    .fetch_and_cache(source.as_ref(), &asset, &interval, start_ts, end_ts)
                     .await?;
-                println!("Fetched and cached candles for {asset} {interval}");
+                report.print_warnings(&asset, &interval);
+                println!(
+                    "Fetched and cached {} new candles for {asset} {interval} ({} requests)",
+                    report.candles, report.requests
+                );
 
                 // Export to Parquet if requested
                 if let Some(parquet_path) = parquet {
-                    let candles = load_candles(&cache, &asset, &interval, start_ts, end_ts).await?;
+                    let candles =
+                        load_candles(source.as_ref(), &cache, &asset, &interval, start_ts, end_ts)
+                            .await?;
                     export_candles_to_parquet(&candles, &parquet_path)?;
                     println!("Exported {} candles to {}", candles.len(), parquet_path.display());
                 }
@@ -276,8 +434,11 @@ impl Cli {
                     .timestamp() as u64
                     * 1000;
 
-                let cache = Cache::new()?;
-                let candles = load_candles(&cache, &asset, &interval, start_ts, end_ts).await?;
+                let cache = Cache::open(self.cache_dir.as_deref())?;
+                let source = self.source.build(cache.base_dir())?;
+                let candles =
+                    load_candles(source.as_ref(), &cache, &asset, &interval, start_ts, end_ts)
+                        .await?;
 
                 if candles.is_empty() {
                     anyhow::bail!("No candles found for {asset} {interval}. Run 'fetch' first.");
@@ -300,8 +461,10 @@ impl Cli {
                 maker_fee_bps,
                 taker_fee_bps,
                 slippage_bps,
+                trade_cooldown_min,
                 out,
                 parquet_results,
+                data_policy,
             } => {
                 let start_time = Instant::now();
                 validate_asset(&asset)?;
@@ -326,27 +489,41 @@ impl Cli {
                 // Load strategy
                 let strategy_str = std::fs::read_to_string(&strategy)
                     .with_context(|| format!("Failed to read strategy file: {}", strategy.display()))?;
-                let strategy_def: Strategy =
+                let mut strategy_def: Strategy =
                     serde_json::from_str(&strategy_str).context("Failed to parse strategy JSON")?;
 
                 // Load candles
-                let cache = Cache::new()?;
-                let candles = load_candles(&cache, &asset, &interval, start_ts, end_ts).await?;
+                let cache = Cache::open(self.cache_dir.as_deref())?;
+                let source = self.source.build(cache.base_dir())?;
+                apply_asset_meta(source.as_ref(), &asset, &mut strategy_def).await;
+                let candles =
+                    load_candles(source.as_ref(), &cache, &asset, &interval, start_ts, end_ts)
+                        .await?;
 
                 if candles.is_empty() {
                     anyhow::bail!("No candles found for {asset} {interval} in date range");
                 }
 
+                // Candles of other coins the indicators read
+                let mut references = HashMap::new();
+                for coin in compile_strategy(&strategy_def)?.reference_coins() {
+                    validate_asset(&coin)?;
+                    let reference =
+                        load_candles(source.as_ref(), &cache, &coin, &interval, start_ts, end_ts).await?;
+                    references.insert(coin, reference);
+                }
+
                 // Run simulation
                 let config = crate::orders::SimConfig {
                     initial_capital,
                     maker_fee_bps,
                     taker_fee_bps,
                     slippage_bps,
-                    trade_cooldown_ms: None,
+                    trade_cooldown_ms: trade_cooldown_min.map(|min| min * 60 * 1000),
+                    data_policy,
                 };
 
-                let result = simulate(&candles, &strategy_def, &config).await?;
+                let result = simulate_with_references(&candles, &references, &strategy_def, &config).await?;
 
                 // Write results
                 write_results(&result, &out)?;
@@ -369,6 +546,146 @@ impl Cli {
                 println!("Completed in {:.2}s", elapsed.as_secs_f64());
                 Ok(())
             }
+            Commands::ValidateData { source } => {
+                let start_time = Instant::now();
+                let (report, strict) = match source {
+                    ValidateSource::Candles {
+                        asset,
+                        interval,
+                        start,
+                        end,
+                        strict,
+                    } => {
+                        validate_asset(&asset)?;
+                        let start_date = NaiveDate::parse_from_str(&start, "%Y-%m-%d")
+                            .context("Invalid start date format (use YYYY-MM-DD)")?;
+                        let end_date = NaiveDate::parse_from_str(&end, "%Y-%m-%d")
+                            .context("Invalid end date format (use YYYY-MM-DD)")?;
+
+                        let start_ts = start_date
+                            .and_hms_opt(0, 0, 0)
+                            .unwrap()
+                            .and_utc()
+                            .timestamp() as u64
+                            * 1000;
+                        let end_ts = end_date
+                            .and_hms_opt(23, 59, 59)
+                            .unwrap()
+                            .and_utc()
+                            .timestamp() as u64
+                            * 1000;
+
+                        // Validate what is on disk; don't fetch
+                        let cache = Cache::open(self.cache_dir.as_deref())?;
+                        let candles = cache.load_range(&asset, &interval, start_ts, end_ts)?;
+                        if candles.is_empty() {
+                            anyhow::bail!(
+                                "No cached candles for {asset} {interval} in date range. Run 'fetch' first."
+                            );
+                        }
+
+                        println!("Validating {asset} {interval} candles");
+                        let interval_ms = crate::util::interval_to_ms(&interval).ok();
+                        (validate_candles(&candles, interval_ms), strict)
+                    }
+                    ValidateSource::L2 {
+                        coin,
+                        events,
+                        max_gap_secs,
+                        strict,
+                    } => {
+                        validate_asset(&coin)?;
+                        let events_dir = events.join(&coin);
+                        if !events_dir.exists() {
+                            anyhow::bail!(
+                                "Events directory does not exist: {}",
+                                events_dir.display()
+                            );
+                        }
+
+                        // Files are named by hour, so reading them in order gives the stream order
+                        let files = jsonl_files(&events_dir)?;
+                        let mut all_events = Vec::new();
+                        for file in &files {
+                            all_events.extend(parse_l2_jsonl_file(file).await?);
+                        }
+
+                        println!("Validating {} L2 events from {} files", all_events.len(), files.len());
+                        let max_gap_ms = max_gap_secs.map(|secs| secs * 1000);
+                        (validate_l2_events(&all_events, max_gap_ms), strict)
+                    }
+                };
+
+                println!("{}", report);
+
+                let elapsed = start_time.elapsed();
+                println!("Completed in {:.2}s", elapsed.as_secs_f64());
+
+                if strict && !report.is_clean() {
+                    anyhow::bail!("Data validation found issues");
+                }
+                Ok(())
+            }
+            Commands::Import {
+                file,
+                asset,
+                interval,
+                spec,
+                timestamp_unit,
+                data_policy,
+            } => {
+                let start_time = Instant::now();
+                validate_asset(&asset)?;
+
+                let mut spec = match spec {
+                    Some(path) => ImportSpec::from_file(&path)?,
+                    None => ImportSpec::default(),
+                };
+                if let Some(unit) = timestamp_unit {
+                    spec.timestamp_unit = unit;
+                }
+
+                let candles = import_candles(&file, &spec, &asset, &interval, data_policy)?;
+                if candles.is_empty() {
+                    anyhow::bail!("No candles found in {}", file.display());
+                }
+
+                // Only the imported bars count as covered, so gaps are still fetched
+                let cache = Cache::open(self.cache_dir.as_deref())?;
+                let covered: Vec<TimeRange> = candles
+                    .iter()
+                    .map(|c| TimeRange::new(c.time_open, c.time_close))
+                    .collect();
+                cache.store(&asset, &interval, &candles, &covered)?;
+                println!(
+                    "Imported {} candles for {asset} {interval} from {}",
+                    candles.len(),
+                    file.display()
+                );
+
+                let elapsed = start_time.elapsed();
+                println!("Completed in {:.2}s", elapsed.as_secs_f64());
+                Ok(())
+            }
+            Commands::MigrateCache { from } => {
+                let start_time = Instant::now();
+                if !from.exists() {
+                    anyhow::bail!("Legacy cache directory does not exist: {}", from.display());
+                }
+
+                let cache = Cache::open(self.cache_dir.as_deref())?;
+                let migrated = cache.migrate_from(&from)?;
+                println!(
+                    "Migrated {} cached series from {} to {}",
+                    migrated,
+                    from.display(),
+                    cache.base_dir().display()
+                );
+
+                let elapsed = start_time.elapsed();
+                println!("Completed in {:.2}s", elapsed.as_secs_f64());
+                Ok(())
+            }
             Commands::Ingest { subcommand } => {
                 match subcommand {
                     IngestSubcommand::S3 {
@@ -445,6 +762,78 @@ impl Cli {
 
                         println!("Built events in {}", coin_dir.display());
 
+                        let elapsed = start_time.elapsed();
+                        println!("Completed in {:.2}s", elapsed.as_secs_f64());
+                        Ok(())
+                    }
+                    IngestSubcommand::BuildCandles {
+                        coin,
+                        interval,
+                        events,
+                        trades,
+                        price,
+                        parquet,
+                        no_cache,
+                    } => {
+                        let start_time = Instant::now();
+                        validate_asset(&coin)?;
+
+                        let events_dir = events.join(&coin);
+                        if !events_dir.exists() {
+                            anyhow::bail!(
+                                "Events directory does not exist: {}",
+                                events_dir.display()
+                            );
+                        }
+
+                        let mut l2_events = Vec::new();
+                        for file in jsonl_files(&events_dir)? {
+                            l2_events.extend(parse_l2_jsonl_file(&file).await?);
+                        }
+                        l2_events.sort_by_key(|e| e.ts_ms);
+
+                        let mut trade_prints = Vec::new();
+                        if let Some(trades_dir) = trades {
+                            let trades_dir = trades_dir.join(&coin);
+                            for file in jsonl_files(&trades_dir)? {
+                                trade_prints.extend(parse_trades_jsonl_file(&file).await?);
+                            }
+                            trade_prints.sort_by_key(|t| t.ts_ms);
+                        }
+
+                        println!(
+                            "Building {} candles from {} L2 events and {} trades",
+                            interval,
+                            l2_events.len(),
+                            trade_prints.len()
+                        );
+                        let candles =
+                            build_candles(&coin, &interval, &l2_events, &trade_prints, price)?;
+                        if candles.is_empty() {
+                            anyhow::bail!("No complete candles could be built for {coin}");
+                        }
+
+                        if !no_cache {
+                            // Built candles replace cached ones for the same bars. Only
+                            // those bars count as covered, so gaps are still fetched.
+                            let cache = Cache::open(self.cache_dir.as_deref())?;
+                            let covered: Vec<TimeRange> = candles
+                                .iter()
+                                .map(|c| TimeRange::new(c.time_open, c.time_close))
+                                .collect();
+                            cache.store(&coin, &interval, &candles, &covered)?;
+                            println!(
+                                "Cached {} candles in {}",
+                                candles.len(),
+                                cache.partition_dir(&coin, &interval).display()
+                            );
+                        }
+
+                        if let Some(parquet_path) = parquet {
+                            export_candles_to_parquet(&candles, &parquet_path)?;
+                            println!("Exported {} candles to {}", candles.len(), parquet_path.display());
+                        }
+
                         let elapsed = start_time.elapsed();
                         println!("Completed in {:.2}s", elapsed.as_secs_f64());
                         Ok(())
@@ -455,6 +844,7 @@ impl Cli {
                 strategy,
                 coin,
                 events,
+                trades,
                 start,
                 end,
                 initial_capital,
@@ -465,6 +855,7 @@ impl Cli {
                 trade_cooldown_min,
                 out,
                 parquet_results,
+                data_policy,
             } => {
                 let start_time = Instant::now();
                 validate_asset(&coin)?;
@@ -498,40 +889,44 @@ impl Cli {
                 // Load strategy
                 let strategy_str = std::fs::read_to_string(&strategy)
                     .with_context(|| format!("Failed to read strategy file: {}", strategy.display()))?;
-                let strategy_def: Strategy =
+                let mut strategy_def: Strategy =
                     serde_json::from_str(&strategy_str).context("Failed to parse strategy JSON")?;
 
                 // Fetch funding schedule
-                let _funding = FundingSchedule::from_api(&coin, start_ts, end_ts).await?;
+                let cache = Cache::open(self.cache_dir.as_deref())?;
+                let source = self.source.build(cache.base_dir())?;
+                apply_asset_meta(source.as_ref(), &coin, &mut strategy_def).await;
+                let funding = FundingSchedule::from_source(source.as_ref(), &coin, start_ts, end_ts)
+                    .await
+                    .context("Failed to fetch funding history")?;
 
                 let events_dir = events.join(&coin);
                 if !events_dir.exists() {
                     anyhow::bail!("Events directory does not exist: {}", events_dir.display());
                 }
+                // Other coins the indicators read are loaded from next to it
+                for reference_coin in compile_strategy(&strategy_def)?.reference_coins() {
+                    validate_asset(&reference_coin)?;
+                }
 
                 let config = crate::orders::SimConfig {
                     initial_capital,
                     maker_fee_bps,
                     taker_fee_bps,
                     slippage_bps: 0,
-                    trade_cooldown_ms: trade_cooldown_min.map(|min| min * 60 * 1000),
+                    trade_cooldown_ms: Some(trade_cooldown_min * 60 * 1000).filter(|ms| *ms > 0),
+                    data_policy,
                 };
 
-                let indicators_parallel = indicators_par.unwrap_or_else(|| {
-                    #[cfg(not(debug_assertions))]
-                    {
-                        true
-                    }
-                    #[cfg(debug_assertions)]
-                    {
-                        false
-                    }
-                });
+                let indicators_parallel = indicators_par.unwrap_or(cfg!(not(debug_assertions)));
 
+                let trades_dir = trades.map(|dir| dir.join(&coin));
                 let result = crate::perps::engine::PerpsEngine::run(
                     &events_dir,
+                    trades_dir.as_deref(),
                     &strategy_def,
                     &config,
+                    funding,
                     &coin,
                     start_ts,
                     end_ts,
@@ -0,0 +1,236 @@
+//! Alignment of another coin's market data with the traded coin's, for
+//! strategies whose indicators read other markets.
+
+use anyhow::Result;
+use std::collections::HashSet;
+
+use crate::data::loader::format_ts;
+use crate::data::types::Candle;
+use crate::data::validate::{prepare_candles, prepare_l2_events, DataPolicy};
+use crate::ingest::L2Event;
+
+/// Bars of a reference series that do not line up with the traded series
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Misalignment {
+    /// Open times of traded bars with no reference bar at the same time
+    pub missing: Vec<u64>,
+    /// Open times of reference bars with no traded bar at the same time
+    pub unmatched: Vec<u64>,
+}
+
+impl Misalignment {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.unmatched.is_empty()
+    }
+
+    fn describe(&self) -> String {
+        let first = |times: &[u64]| times.first().map(|ts| format_ts(*ts)).unwrap_or_default();
+        let mut parts = Vec::new();
+        if !self.missing.is_empty() {
+            parts.push(format!("{} bars missing (first at {})", self.missing.len(), first(&self.missing)));
+        }
+        if !self.unmatched.is_empty() {
+            parts.push(format!(
+                "{} bars at times the traded coin has none (first at {})",
+                self.unmatched.len(),
+                first(&self.unmatched)
+            ));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Compare the open times of `reference` bars with the `base` bars
+pub fn check_alignment(base: &[Candle], reference: &[Candle]) -> Misalignment {
+    let base_times: HashSet<u64> = base.iter().map(|c| c.time_open).collect();
+    let reference_times: HashSet<u64> = reference.iter().map(|c| c.time_open).collect();
+    Misalignment {
+        missing: base
+            .iter()
+            .map(|c| c.time_open)
+            .filter(|ts| !reference_times.contains(ts))
+            .collect(),
+        unmatched: reference
+            .iter()
+            .map(|c| c.time_open)
+            .filter(|ts| !base_times.contains(ts))
+            .collect(),
+    }
+}
+
+/// Validate `coin`'s `reference` candles and line them up with the traded
+/// `base` candles, applying `policy` to both kinds of issues.
+///
+/// `Repair` drops reference bars at times the traded coin has none and fills
+/// missing ones with flat zero-volume bars at the previous close; bars missing
+/// before the first reference bar stay missing. Otherwise the bars are returned
+/// as they are, and indicators on them keep their last value over missing bars.
+pub fn align_reference_candles(
+    base: &[Candle],
+    reference: &[Candle],
+    coin: &str,
+    policy: DataPolicy,
+) -> Result<Vec<Candle>> {
+    let reference = prepare_candles(reference, policy)?;
+    let misalignment = check_alignment(base, &reference);
+    if misalignment.is_clean() {
+        return Ok(reference.into_owned());
+    }
+
+    match policy {
+        DataPolicy::Fail => anyhow::bail!(
+            "{} candles do not line up with the traded coin's: {}",
+            coin,
+            misalignment.describe()
+        ),
+        DataPolicy::Warn => {
+            eprintln!(
+                "Warning: {} candles do not line up with the traded coin's: {}",
+                coin,
+                misalignment.describe()
+            );
+            Ok(reference.into_owned())
+        }
+        DataPolicy::Repair => {
+            let mut by_time = reference.iter().map(|c| (c.time_open, c)).peekable();
+            let mut aligned: Vec<Candle> = Vec::with_capacity(base.len());
+            for bar in base {
+                while by_time.next_if(|(ts, _)| *ts < bar.time_open).is_some() {}
+                match by_time.next_if(|(ts, _)| *ts == bar.time_open) {
+                    Some((_, candle)) => aligned.push(candle.clone()),
+                    None => {
+                        let Some(prev) = aligned.last() else {
+                            continue;
+                        };
+                        aligned.push(Candle {
+                            time_open: bar.time_open,
+                            time_close: bar.time_close,
+                            open: prev.close,
+                            high: prev.close,
+                            low: prev.close,
+                            volume: 0.0,
+                            num_trades: 0,
+                            ..prev.clone()
+                        });
+                    }
+                }
+            }
+            eprintln!(
+                "Warning: aligned {} candles with the traded coin's ({} -> {} bars): {}",
+                coin,
+                reference.len(),
+                aligned.len(),
+                misalignment.describe()
+            );
+            Ok(aligned)
+        }
+    }
+}
+
+/// Validate `coin`'s `reference` book snapshots and check that they cover the
+/// traded coin's `base` snapshots, applying `policy`. Snapshots are read as of
+/// each traded snapshot, so they need not share timestamps; a stream that
+/// starts late or ends early leaves indicators without data or stale.
+pub fn prepare_reference_events(
+    base: &[L2Event],
+    reference: Vec<L2Event>,
+    coin: &str,
+    policy: DataPolicy,
+) -> Result<Vec<L2Event>> {
+    let reference = prepare_l2_events(reference, policy)?;
+    let (Some(base_first), Some(base_last)) = (base.first(), base.last()) else {
+        return Ok(reference);
+    };
+    let (Some(first), Some(last)) = (reference.first(), reference.last()) else {
+        anyhow::bail!("No {} events found in range", coin);
+    };
+
+    let mut issues = Vec::new();
+    if first.ts_ms > base_first.ts_ms {
+        issues.push(format!("starts at {}", format_ts(first.ts_ms)));
+    }
+    if last.ts_ms < base_last.ts_ms {
+        issues.push(format!("ends at {}", format_ts(last.ts_ms)));
+    }
+    if issues.is_empty() {
+        return Ok(reference);
+    }
+    let message = format!(
+        "{} events do not cover the traded coin's ({} to {}): {}",
+        coin,
+        format_ts(base_first.ts_ms),
+        format_ts(base_last.ts_ms),
+        issues.join(", ")
+    );
+    match policy {
+        DataPolicy::Fail => anyhow::bail!(message),
+        DataPolicy::Warn | DataPolicy::Repair => {
+            eprintln!("Warning: {}", message);
+            Ok(reference)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR_MS: u64 = 60 * 60 * 1000;
+
+    fn bar(coin: &str, hour: u64, close: f64) -> Candle {
+        Candle {
+            time_open: hour * HOUR_MS,
+            time_close: (hour + 1) * HOUR_MS - 1,
+            coin: coin.to_string(),
+            interval: "1h".to_string(),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1.0,
+            num_trades: 1,
+        }
+    }
+
+    #[test]
+    fn test_align_reference_candles() {
+        let base: Vec<Candle> = (0..5).map(|h| bar("ETH", h, 2000.0)).collect();
+        // No bar at 02:00, and one at 03:30
+        let mut reference = vec![bar("BTC", 0, 100.0), bar("BTC", 1, 101.0), bar("BTC", 3, 103.0)];
+        reference.push(Candle {
+            time_open: 3 * HOUR_MS + HOUR_MS / 2,
+            ..bar("BTC", 3, 104.0)
+        });
+        reference.push(bar("BTC", 4, 105.0));
+        reference.sort_by_key(|c| c.time_open);
+
+        let misalignment = check_alignment(&base, &reference);
+        assert_eq!(misalignment.missing, [2 * HOUR_MS]);
+        assert_eq!(misalignment.unmatched, [3 * HOUR_MS + HOUR_MS / 2]);
+        assert!(align_reference_candles(&base, &reference, "BTC", DataPolicy::Fail).is_err());
+        assert_eq!(
+            align_reference_candles(&base, &reference, "BTC", DataPolicy::Warn).unwrap().len(),
+            5
+        );
+
+        let aligned = align_reference_candles(&base, &reference, "BTC", DataPolicy::Repair).unwrap();
+        let times: Vec<u64> = aligned.iter().map(|c| c.time_open / HOUR_MS).collect();
+        assert_eq!(times, [0, 1, 2, 3, 4]);
+        assert_eq!((aligned[2].close, aligned[2].volume), (101.0, 0.0));
+        assert_eq!(aligned[2].coin, "BTC");
+        assert!(check_alignment(&base, &aligned).is_clean());
+    }
+
+    #[test]
+    fn test_reference_events_must_cover_the_traded_range() {
+        let event = |ts_ms: u64| L2Event {
+            ts_ms,
+            levels: vec![vec![], vec![]],
+        };
+        let base = vec![event(100), event(200)];
+        assert!(prepare_reference_events(&base, vec![event(50), event(250)], "BTC", DataPolicy::Fail).is_ok());
+        assert!(prepare_reference_events(&base, vec![event(150), event(250)], "BTC", DataPolicy::Fail).is_err());
+        assert!(prepare_reference_events(&base, vec![event(150)], "BTC", DataPolicy::Warn).is_ok());
+        assert!(prepare_reference_events(&base, vec![], "BTC", DataPolicy::Warn).is_err());
+    }
+}
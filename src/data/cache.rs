@@ -1,105 +1,506 @@
 use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use crate::data::coverage::{Coverage, TimeRange};
+use crate::data::loader::{fetch_candles_paginated, no_candles_message, FetchReport};
+use crate::data::parquet::{export_candles_to_parquet, read_candles_from_parquet};
+use crate::data::source::MarketDataSource;
 use crate::data::types::Candle;
-use crate::data::loader::fetch_candles_from_api;
 
+/// Environment variable overriding the cache root
+pub const CACHE_DIR_ENV: &str = "HL_BACKTEST_CACHE_DIR";
+/// Environment variable pointing at a JSON config file
+pub const CONFIG_FILE_ENV: &str = "HL_BACKTEST_CONFIG";
+/// Cache root used when no home directory can be determined
+pub const DEFAULT_CACHE_DIR: &str = "data/hyperliquid";
+
+const COVERAGE_FILE: &str = "coverage.json";
+
+/// Subset of the config file read by the cache
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    cache_dir: Option<PathBuf>,
+}
+
+/// Resolve the cache root.
+///
+/// Precedence: `explicit` (the `--cache-dir` flag), `HL_BACKTEST_CACHE_DIR`,
+/// `cache_dir` in the config file (`HL_BACKTEST_CONFIG`, else
+/// `$XDG_CONFIG_HOME/hl-backtest/config.json`), then `$XDG_CACHE_HOME/hl-backtest`.
+pub fn resolve_cache_dir(explicit: Option<&Path>) -> Result<PathBuf> {
+    if let Some(dir) = explicit {
+        return Ok(dir.to_path_buf());
+    }
+
+    if let Some(dir) = std::env::var_os(CACHE_DIR_ENV).filter(|v| !v.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+
+    let config_path = std::env::var_os(CONFIG_FILE_ENV)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| config_home().map(|d| d.join("hl-backtest").join("config.json")));
+    if let Some(config_path) = config_path.filter(|p| p.exists()) {
+        if let Some(dir) = read_config_cache_dir(&config_path)? {
+            return Ok(dir);
+        }
+    }
+
+    Ok(cache_home()
+        .map(|d| d.join("hl-backtest"))
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CACHE_DIR)))
+}
+
+/// `cache_dir` from a config file; relative paths are resolved against the file's directory
+fn read_config_cache_dir(config_path: &Path) -> Result<Option<PathBuf>> {
+    let json = fs::read_to_string(config_path)
+        .with_context(|| format!("Failed to read config file: {}", config_path.display()))?;
+    let config: ConfigFile = serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse config file: {}", config_path.display()))?;
+
+    Ok(config.cache_dir.map(|dir| match config_path.parent() {
+        Some(parent) if dir.is_relative() => parent.join(dir),
+        _ => dir,
+    }))
+}
+
+fn config_home() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|h| h.join(".config")))
+}
+
+fn cache_home() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|h| h.join(".cache")))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// On-disk candle cache.
+///
+/// Candles are stored as Parquet, partitioned by asset, interval and UTC month:
+/// `{root}/{asset}/{interval}/{YYYY-MM}.parquet`, with the fetched time ranges
+/// recorded in `{root}/{asset}/{interval}/coverage.json`.
 pub struct Cache {
     base_dir: PathBuf,
 }
 
 impl Cache {
+    /// Open the cache at the configured location (see [`resolve_cache_dir`])
     pub fn new() -> Result<Self> {
-        let base_dir = PathBuf::from("data/hyperliquid");
+        Self::open(None)
+    }
+
+    /// Open the cache at `cache_dir`, or at the configured location if `None`
+    pub fn open(cache_dir: Option<&Path>) -> Result<Self> {
+        Self::with_base_dir(resolve_cache_dir(cache_dir)?)
+    }
+
+    pub fn with_base_dir(base_dir: impl AsRef<Path>) -> Result<Self> {
+        let base_dir = base_dir.as_ref().to_path_buf();
         fs::create_dir_all(&base_dir)
             .with_context(|| format!("Failed to create cache directory: {}", base_dir.display()))?;
 
         Ok(Self { base_dir })
     }
 
-    pub fn cache_path(&self, asset: &str, interval: &str) -> PathBuf {
-        self.base_dir.join(asset).join(format!("{interval}.csv"))
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// Directory holding the monthly partitions of one asset/interval
+    pub fn partition_dir(&self, asset: &str, interval: &str) -> PathBuf {
+        self.base_dir.join(asset).join(interval)
+    }
+
+    /// Partition file for the UTC month containing `ts_ms`
+    pub fn partition_path(&self, asset: &str, interval: &str, ts_ms: u64) -> PathBuf {
+        self.partition_dir(asset, interval)
+            .join(format!("{}.parquet", month_key(ts_ms)))
+    }
+
+    pub fn coverage_path(&self, asset: &str, interval: &str) -> PathBuf {
+        self.partition_dir(asset, interval).join(COVERAGE_FILE)
+    }
+
+    /// Time ranges already fetched for an asset/interval
+    pub fn load_coverage(&self, asset: &str, interval: &str) -> Result<Coverage> {
+        self.migrate_legacy(asset, interval)?;
+        read_coverage(&self.coverage_path(asset, interval))
     }
 
+    /// Sub-ranges of `start_ts..=end_ts` that still need to be fetched
+    pub fn missing_ranges(
+        &self,
+        asset: &str,
+        interval: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Result<Vec<TimeRange>> {
+        Ok(self
+            .load_coverage(asset, interval)?
+            .missing(start_ts, end_ts))
+    }
+
+    /// Fetch the parts of `start_ts..=end_ts` missing from the cache and merge them in.
+    ///
+    /// Returns a report of what `source` returned for the missing ranges.
     pub async fn fetch_and_cache(
         &self,
+        source: &dyn MarketDataSource,
         asset: &str,
         interval: &str,
         start_ts: u64,
         end_ts: u64,
-    ) -> Result<()> {
-        let candles = fetch_candles_from_api(asset, interval, start_ts, end_ts).await?;
+    ) -> Result<FetchReport> {
+        let missing = self.missing_ranges(asset, interval, start_ts, end_ts)?;
+        let now_ms = chrono::Utc::now().timestamp_millis().max(0) as u64;
 
-        let cache_path = self.cache_path(asset, interval);
-        if let Some(parent) = cache_path.parent() {
-            fs::create_dir_all(parent)
-                .with_context(|| format!("Failed to create cache directory: {}", parent.display()))?;
-        }
+        let mut fetched = Vec::new();
+        let mut covered = Vec::new();
+        let mut report = FetchReport::default();
+        for range in missing {
+            let (mut candles, range_report) =
+                fetch_candles_paginated(source, asset, interval, range.start, range.end).await?;
+            report.merge(range_report);
 
-        let mut wtr = csv::Writer::from_path(&cache_path)
-            .with_context(|| format!("Failed to create CSV file: {}", cache_path.display()))?;
+            // The bar that is still open will change, so neither store it nor mark it covered
+            let mut covered_end = range.end.min(now_ms);
+            candles.retain(|c| {
+                if c.time_close > now_ms {
+                    covered_end = covered_end.min(c.time_open.saturating_sub(1));
+                    false
+                } else {
+                    true
+                }
+            });
 
-        wtr.write_record(&[
-            "time_open",
-            "time_close",
-            "coin",
-            "interval",
-            "open",
-            "close",
-            "high",
-            "low",
-            "volume",
-            "num_trades",
-        ])?;
+            if covered_end >= range.start {
+                covered.push(TimeRange::new(range.start, covered_end));
+            }
+            fetched.extend(candles);
+        }
 
-        for candle in &candles {
-            wtr.write_record(&[
-                candle.time_open.to_string(),
-                candle.time_close.to_string(),
-                candle.coin.clone(),
-                candle.interval.clone(),
-                candle.open.to_string(),
-                candle.close.to_string(),
-                candle.high.to_string(),
-                candle.low.to_string(),
-                candle.volume.to_string(),
-                candle.num_trades.to_string(),
-            ])?;
+        if fetched.is_empty()
+            && self
+                .load_range(asset, interval, start_ts, end_ts)?
+                .is_empty()
+        {
+            anyhow::bail!(no_candles_message(asset, interval, start_ts, end_ts));
         }
 
-        wtr.flush()?;
-        Ok(())
+        self.store(asset, interval, &fetched, &covered)?;
+        Ok(report)
     }
 
+    /// Merge candles into the cache and mark `covered` as fetched.
+    ///
+    /// Candles are deduplicated by `time_open`, with the incoming candle winning.
+    /// Each touched monthly partition and the coverage file are replaced atomically.
+    pub fn store(
+        &self,
+        asset: &str,
+        interval: &str,
+        candles: &[Candle],
+        covered: &[TimeRange],
+    ) -> Result<()> {
+        self.migrate_legacy(asset, interval)?;
+        self.write_partitions(asset, interval, candles.iter().cloned(), covered, true)
+    }
+
+    /// Load every cached candle for an asset/interval, sorted by `time_open`
     pub fn load_cached(&self, asset: &str, interval: &str) -> Result<Vec<Candle>> {
-        let cache_path = self.cache_path(asset, interval);
-        if !cache_path.exists() {
+        self.load_range(asset, interval, 0, u64::MAX)
+    }
+
+    /// Load cached candles with `time_open` in `start_ts..=end_ts`.
+    ///
+    /// Only the monthly partitions overlapping the range are read.
+    pub fn load_range(
+        &self,
+        asset: &str,
+        interval: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Result<Vec<Candle>> {
+        self.migrate_legacy(asset, interval)?;
+
+        let dir = self.partition_dir(asset, interval);
+        if !dir.exists() {
             return Ok(vec![]);
         }
 
-        let mut rdr = csv::Reader::from_path(&cache_path)
-            .with_context(|| format!("Failed to read CSV file: {}", cache_path.display()))?;
+        let first_month = month_key(start_ts);
+        let last_month = month_key(end_ts);
+
+        let mut partitions: Vec<PathBuf> = fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read cache directory: {}", dir.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.extension().and_then(|s| s.to_str()) == Some("parquet")
+                    && path
+                        .file_stem()
+                        .and_then(|s| s.to_str())
+                        .is_some_and(|month| {
+                            month >= first_month.as_str() && month <= last_month.as_str()
+                        })
+            })
+            .collect();
+        partitions.sort();
 
         let mut candles = Vec::new();
-        for result in rdr.deserialize() {
-            let record: CandleRecord = result?;
-            candles.push(Candle {
-                time_open: record.time_open,
-                time_close: record.time_close,
-                coin: record.coin,
-                interval: record.interval,
-                open: record.open,
-                close: record.close,
-                high: record.high,
-                low: record.low,
-                volume: record.volume,
-                num_trades: record.num_trades,
-            });
+        for path in partitions {
+            candles.extend(
+                read_candles_from_parquet(&path)?
+                    .into_iter()
+                    .filter(|c| c.time_open >= start_ts && c.time_open <= end_ts),
+            );
         }
+        candles.sort_by_key(|c| c.time_open);
 
         Ok(candles)
     }
+
+    /// Intervals with data cached for `asset`, including not yet migrated CSV caches
+    pub fn cached_intervals(&self, asset: &str) -> Result<Vec<String>> {
+        let asset_dir = self.base_dir.join(asset);
+        if !asset_dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut intervals: Vec<String> = fs::read_dir(&asset_dir)
+            .with_context(|| format!("Failed to read cache directory: {}", asset_dir.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter_map(|path| {
+                let name = path.file_name()?.to_str()?;
+                if path.is_dir() {
+                    Some(name.to_string())
+                } else {
+                    name.strip_suffix(".csv").map(str::to_string)
+                }
+            })
+            .collect();
+        intervals.sort();
+        intervals.dedup();
+        Ok(intervals)
+    }
+
+    /// Import every `{asset}/{interval}.csv` found under a CSV cache root
+    /// written by earlier versions. Returns the number of series imported.
+    pub fn migrate_from(&self, legacy_root: impl AsRef<Path>) -> Result<usize> {
+        let legacy_root = legacy_root.as_ref();
+        let mut migrated = 0;
+
+        let mut asset_dirs: Vec<PathBuf> = fs::read_dir(legacy_root)
+            .with_context(|| format!("Failed to read directory: {}", legacy_root.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_dir())
+            .collect();
+        asset_dirs.sort();
+
+        for asset_dir in asset_dirs {
+            let Some(asset) = asset_dir.file_name().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            let mut csv_files: Vec<PathBuf> = fs::read_dir(&asset_dir)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("csv"))
+                .collect();
+            csv_files.sort();
+
+            for csv_path in csv_files {
+                let Some(interval) = csv_path.file_stem().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                self.import_legacy_csv(asset, interval, &csv_path)?;
+                migrated += 1;
+            }
+        }
+
+        Ok(migrated)
+    }
+
+    /// Convert a CSV cache left in this root by an earlier version, then set it aside
+    fn migrate_legacy(&self, asset: &str, interval: &str) -> Result<()> {
+        let csv_path = self.base_dir.join(asset).join(format!("{interval}.csv"));
+        if !csv_path.exists() {
+            return Ok(());
+        }
+
+        self.import_legacy_csv(asset, interval, &csv_path)?;
+
+        let migrated_path = csv_path.with_extension("csv.migrated");
+        fs::rename(&csv_path, &migrated_path)
+            .with_context(|| format!("Failed to rename legacy cache: {}", csv_path.display()))?;
+        let legacy_coverage = legacy_coverage_path(&csv_path, interval);
+        if legacy_coverage.exists() {
+            fs::remove_file(&legacy_coverage)?;
+        }
+
+        Ok(())
+    }
+
+    fn import_legacy_csv(&self, asset: &str, interval: &str, csv_path: &Path) -> Result<()> {
+        let candles = read_legacy_csv(csv_path)?;
+
+        // CSV caches written before coverage tracking are assumed to cover
+        // the span between their first and last candle
+        let mut covered = read_coverage(&legacy_coverage_path(csv_path, interval))?
+            .ranges()
+            .to_vec();
+        if covered.is_empty() {
+            let first = candles.iter().map(|c| c.time_open).min();
+            let last = candles.iter().map(|c| c.time_close.max(c.time_open)).max();
+            if let (Some(first), Some(last)) = (first, last) {
+                covered.push(TimeRange::new(first, last));
+            }
+        }
+
+        // Data already in the Parquet cache is newer than the CSV, so it wins
+        self.write_partitions(asset, interval, candles, &covered, false)
+    }
+
+    fn write_partitions(
+        &self,
+        asset: &str,
+        interval: &str,
+        candles: impl IntoIterator<Item = Candle>,
+        covered: &[TimeRange],
+        incoming_wins: bool,
+    ) -> Result<()> {
+        let coverage_path = self.coverage_path(asset, interval);
+        let mut coverage = read_coverage(&coverage_path)?;
+
+        let mut by_month: BTreeMap<String, Vec<Candle>> = BTreeMap::new();
+        for candle in candles {
+            by_month
+                .entry(month_key(candle.time_open))
+                .or_default()
+                .push(candle);
+        }
+
+        let dir = self.partition_dir(asset, interval);
+        for (month, incoming) in by_month {
+            let path = dir.join(format!("{month}.parquet"));
+            let existing = if path.exists() {
+                read_candles_from_parquet(&path)?
+            } else {
+                Vec::new()
+            };
+            let merged = if incoming_wins {
+                merge_candles(existing, incoming)
+            } else {
+                merge_candles(incoming, existing)
+            };
+            write_atomic(&path, |tmp| export_candles_to_parquet(&merged, tmp))?;
+        }
+
+        for range in covered {
+            coverage.insert(range.start, range.end);
+        }
+        let json = serde_json::to_string_pretty(&coverage)?;
+        write_atomic(&coverage_path, |path| {
+            fs::write(path, &json)?;
+            Ok(())
+        })
+    }
+}
+
+/// UTC month (`YYYY-MM`) containing `ts_ms`, used as the partition key
+pub fn month_key(ts_ms: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(ts_ms.min(i64::MAX as u64) as i64)
+        .map(|dt| dt.format("%Y-%m").to_string())
+        .unwrap_or_else(|| "9999-12".to_string())
+}
+
+/// Merge two candle sets, sorted by `time_open`; on duplicate timestamps `incoming` wins
+pub fn merge_candles(
+    existing: impl IntoIterator<Item = Candle>,
+    incoming: impl IntoIterator<Item = Candle>,
+) -> Vec<Candle> {
+    let mut by_time: BTreeMap<u64, Candle> = existing
+        .into_iter()
+        .map(|c| (c.time_open, c))
+        .collect();
+    for candle in incoming {
+        by_time.insert(candle.time_open, candle);
+    }
+    by_time.into_values().collect()
+}
+
+fn read_coverage(path: &Path) -> Result<Coverage> {
+    if !path.exists() {
+        return Ok(Coverage::new());
+    }
+    let json = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read coverage file: {}", path.display()))?;
+    serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse coverage file: {}", path.display()))
+}
+
+fn legacy_coverage_path(csv_path: &Path, interval: &str) -> PathBuf {
+    csv_path.with_file_name(format!("{interval}.coverage.json"))
+}
+
+/// Write to a temporary sibling file and rename it over `path`,
+/// so readers never observe a partially written cache
+fn write_atomic(path: &Path, write: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create cache directory: {}", parent.display()))?;
+    }
+
+    let file_name = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .context("Invalid cache file name")?;
+    let tmp_path = path.with_file_name(format!(".{file_name}.tmp"));
+
+    if let Err(e) = write(&tmp_path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to replace cache file: {}", path.display()))?;
+    Ok(())
+}
+
+fn read_legacy_csv(path: &Path) -> Result<Vec<Candle>> {
+    let mut rdr = csv::Reader::from_path(path)
+        .with_context(|| format!("Failed to read CSV file: {}", path.display()))?;
+
+    let mut candles = Vec::new();
+    for result in rdr.deserialize() {
+        let record: CandleRecord = result?;
+        candles.push(Candle {
+            time_open: record.time_open,
+            time_close: record.time_close,
+            coin: record.coin,
+            interval: record.interval,
+            open: record.open,
+            close: record.close,
+            high: record.high,
+            low: record.low,
+            volume: record.volume,
+            num_trades: record.num_trades,
+        });
+    }
+
+    Ok(candles)
 }
 
 #[derive(serde::Deserialize)]
@@ -116,3 +517,168 @@ struct CandleRecord {
     num_trades: i64,
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const HOUR_MS: u64 = 60 * 60 * 1000;
+    // 2024-01-31 22:00:00 UTC, so a few hourly bars spill into February
+    const JAN_31_22H: u64 = 1706738400000;
+
+    fn candle(time_open: u64, close: f64) -> Candle {
+        Candle {
+            time_open,
+            time_close: time_open + HOUR_MS - 1,
+            coin: "BTC".to_string(),
+            interval: "1h".to_string(),
+            open: close,
+            close,
+            high: close,
+            low: close,
+            volume: 1.0,
+            num_trades: 1,
+        }
+    }
+
+    fn write_legacy_csv(path: &Path, candles: &[Candle]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut wtr = csv::Writer::from_path(path).unwrap();
+        wtr.write_record([
+            "time_open",
+            "time_close",
+            "coin",
+            "interval",
+            "open",
+            "close",
+            "high",
+            "low",
+            "volume",
+            "num_trades",
+        ])
+        .unwrap();
+        for c in candles {
+            wtr.write_record(&[
+                c.time_open.to_string(),
+                c.time_close.to_string(),
+                c.coin.clone(),
+                c.interval.clone(),
+                c.open.to_string(),
+                c.close.to_string(),
+                c.high.to_string(),
+                c.low.to_string(),
+                c.volume.to_string(),
+                c.num_trades.to_string(),
+            ])
+            .unwrap();
+        }
+        wtr.flush().unwrap();
+    }
+
+    #[test]
+    fn test_store_merges_and_dedupes() {
+        let dir = tempdir().unwrap();
+        let cache = Cache::with_base_dir(dir.path()).unwrap();
+
+        let first: Vec<Candle> = (0..3).map(|i| candle(i * HOUR_MS, 100.0)).collect();
+        cache
+            .store("BTC", "1h", &first, &[TimeRange::new(0, 3 * HOUR_MS - 1)])
+            .unwrap();
+
+        // Overlapping batch extends the history and replaces the overlapping bar
+        let second: Vec<Candle> = (2..5).map(|i| candle(i * HOUR_MS, 200.0)).collect();
+        cache
+            .store("BTC", "1h", &second, &[TimeRange::new(2 * HOUR_MS, 5 * HOUR_MS - 1)])
+            .unwrap();
+
+        let cached = cache.load_cached("BTC", "1h").unwrap();
+        assert_eq!(cached.len(), 5);
+        assert!(cached.windows(2).all(|w| w[0].time_open < w[1].time_open));
+        assert_eq!(cached[1].close, 100.0);
+        assert_eq!(cached[2].close, 200.0);
+
+        assert!(cache
+            .missing_ranges("BTC", "1h", 0, 5 * HOUR_MS - 1)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            cache.missing_ranges("BTC", "1h", 0, 6 * HOUR_MS - 1).unwrap(),
+            vec![TimeRange::new(5 * HOUR_MS, 6 * HOUR_MS - 1)]
+        );
+    }
+
+    #[test]
+    fn test_store_partitions_by_month() {
+        let dir = tempdir().unwrap();
+        let cache = Cache::with_base_dir(dir.path()).unwrap();
+
+        let candles: Vec<Candle> = (0..4)
+            .map(|i| candle(JAN_31_22H + i * HOUR_MS, 100.0))
+            .collect();
+        cache.store("BTC", "1h", &candles, &[]).unwrap();
+
+        let partitions = cache.partition_dir("BTC", "1h");
+        assert!(partitions.join("2024-01.parquet").exists());
+        assert!(partitions.join("2024-02.parquet").exists());
+        assert_eq!(
+            cache.partition_path("BTC", "1h", JAN_31_22H),
+            partitions.join("2024-01.parquet")
+        );
+
+        // A range inside February only reads the February partition
+        fs::write(partitions.join("2024-01.parquet"), b"not parquet").unwrap();
+        let february = cache
+            .load_range("BTC", "1h", JAN_31_22H + 2 * HOUR_MS, u64::MAX)
+            .unwrap();
+        assert_eq!(february.len(), 2);
+        assert!(cache.load_cached("BTC", "1h").is_err());
+    }
+
+    #[test]
+    fn test_legacy_csv_cache_is_migrated() {
+        let dir = tempdir().unwrap();
+        let legacy_path = dir.path().join("BTC").join("1h.csv");
+        let candles: Vec<Candle> = (0..3).map(|i| candle(i * HOUR_MS, 100.0)).collect();
+        write_legacy_csv(&legacy_path, &candles);
+
+        let cache = Cache::with_base_dir(dir.path()).unwrap();
+        let coverage = cache.load_coverage("BTC", "1h").unwrap();
+        assert_eq!(coverage.ranges(), &[TimeRange::new(0, 3 * HOUR_MS - 1)]);
+        assert_eq!(cache.load_cached("BTC", "1h").unwrap().len(), 3);
+
+        assert!(!legacy_path.exists());
+        assert!(legacy_path.with_extension("csv.migrated").exists());
+        assert!(cache.coverage_path("BTC", "1h").exists());
+    }
+
+    #[test]
+    fn test_migrate_from_other_root() {
+        let legacy = tempdir().unwrap();
+        let candles: Vec<Candle> = (0..2).map(|i| candle(i * HOUR_MS, 100.0)).collect();
+        write_legacy_csv(&legacy.path().join("BTC").join("1h.csv"), &candles);
+        write_legacy_csv(&legacy.path().join("ETH").join("1h.csv"), &candles);
+
+        let dir = tempdir().unwrap();
+        let cache = Cache::with_base_dir(dir.path()).unwrap();
+        assert_eq!(cache.migrate_from(legacy.path()).unwrap(), 2);
+        assert_eq!(cache.load_cached("ETH", "1h").unwrap().len(), 2);
+        // The source tree is left untouched
+        assert!(legacy.path().join("BTC").join("1h.csv").exists());
+    }
+
+    #[test]
+    fn test_config_file_cache_dir_is_relative_to_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        fs::write(&config_path, r#"{"cache_dir": "candles"}"#).unwrap();
+
+        assert_eq!(
+            read_config_cache_dir(&config_path).unwrap(),
+            Some(dir.path().join("candles"))
+        );
+        assert_eq!(
+            resolve_cache_dir(Some(Path::new("/tmp/explicit"))).unwrap(),
+            PathBuf::from("/tmp/explicit")
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+/// Inclusive time range in milliseconds (`start..=end`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl TimeRange {
+    pub fn new(start: u64, end: u64) -> Self {
+        Self { start, end }
+    }
+}
+
+/// Set of time ranges already fetched for one asset/interval.
+///
+/// Ranges are kept sorted and non-overlapping; adjacent ranges are merged on insert.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Coverage {
+    ranges: Vec<TimeRange>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self { ranges: Vec::new() }
+    }
+
+    pub fn ranges(&self) -> &[TimeRange] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Mark `start..=end` as covered, merging with overlapping or adjacent ranges
+    pub fn insert(&mut self, start: u64, end: u64) {
+        if start > end {
+            return;
+        }
+
+        self.ranges.push(TimeRange::new(start, end));
+        self.ranges.sort_by_key(|r| r.start);
+
+        let mut merged: Vec<TimeRange> = Vec::with_capacity(self.ranges.len());
+        for range in self.ranges.drain(..) {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end.saturating_add(1) => {
+                    last.end = last.end.max(range.end);
+                }
+                _ => merged.push(range),
+            }
+        }
+        self.ranges = merged;
+    }
+
+    /// Sub-ranges of `start..=end` that are not covered yet
+    pub fn missing(&self, start: u64, end: u64) -> Vec<TimeRange> {
+        let mut missing = Vec::new();
+        if start > end {
+            return missing;
+        }
+
+        let mut cursor = start;
+        for range in &self.ranges {
+            if range.end < cursor {
+                continue;
+            }
+            if range.start > end {
+                break;
+            }
+            if range.start > cursor {
+                missing.push(TimeRange::new(cursor, range.start - 1));
+            }
+            if range.end >= end {
+                return missing;
+            }
+            cursor = range.end + 1;
+        }
+
+        missing.push(TimeRange::new(cursor, end));
+        missing
+    }
+
+    pub fn covers(&self, start: u64, end: u64) -> bool {
+        self.missing(start, end).is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_merges_overlapping_and_adjacent() {
+        let mut coverage = Coverage::new();
+        coverage.insert(100, 200);
+        coverage.insert(300, 400);
+        assert_eq!(coverage.ranges().len(), 2);
+
+        coverage.insert(201, 299);
+        assert_eq!(coverage.ranges(), &[TimeRange::new(100, 400)]);
+
+        coverage.insert(50, 150);
+        assert_eq!(coverage.ranges(), &[TimeRange::new(50, 400)]);
+    }
+
+    #[test]
+    fn test_missing_ranges() {
+        let mut coverage = Coverage::new();
+        assert_eq!(coverage.missing(0, 99), vec![TimeRange::new(0, 99)]);
+
+        coverage.insert(100, 200);
+        coverage.insert(300, 400);
+
+        assert_eq!(
+            coverage.missing(0, 500),
+            vec![
+                TimeRange::new(0, 99),
+                TimeRange::new(201, 299),
+                TimeRange::new(401, 500),
+            ]
+        );
+        assert_eq!(coverage.missing(150, 350), vec![TimeRange::new(201, 299)]);
+        assert!(coverage.covers(120, 180));
+        assert!(!coverage.covers(120, 250));
+    }
+}
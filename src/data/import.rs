@@ -0,0 +1,478 @@
+use anyhow::{Context, Result};
+use arrow::array::{Array, StringArray};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+
+use crate::data::types::Candle;
+use crate::data::validate::{prepare_candles, DataPolicy};
+use crate::util::interval_to_ms;
+
+/// Unit of numeric timestamps in an imported file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampUnit {
+    /// Guess per value: numbers by magnitude, anything else as ISO 8601
+    #[default]
+    Auto,
+    #[serde(alias = "s")]
+    Seconds,
+    #[serde(alias = "ms")]
+    Millis,
+    #[serde(alias = "us")]
+    Micros,
+    #[serde(alias = "ns")]
+    Nanos,
+    #[serde(alias = "iso")]
+    Iso8601,
+}
+
+impl std::str::FromStr for TimestampUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.to_lowercase()))
+            .map_err(|_| format!("Unknown timestamp unit: {s} (expected auto, s, ms, us, ns or iso8601)"))
+    }
+}
+
+/// Source column for each candle field.
+///
+/// Every field defaults to the conventional lower-case name, so a spec only
+/// needs to list the columns that differ.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColumnMapping {
+    /// Bar open time
+    pub time: String,
+    /// Bar close time; derived from `time` and the interval when absent
+    pub time_close: Option<String>,
+    pub open: String,
+    pub high: String,
+    pub low: String,
+    pub close: String,
+    /// Volume in base units; zero when absent
+    pub volume: Option<String>,
+    /// Trade count; zero when absent
+    pub num_trades: Option<String>,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            time: "timestamp".to_string(),
+            time_close: None,
+            open: "open".to_string(),
+            high: "high".to_string(),
+            low: "low".to_string(),
+            close: "close".to_string(),
+            volume: Some("volume".to_string()),
+            num_trades: None,
+        }
+    }
+}
+
+/// How to read an external candle file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportSpec {
+    pub columns: ColumnMapping,
+    pub timestamp_unit: TimestampUnit,
+    /// CSV field delimiter (default `,`)
+    pub delimiter: Option<char>,
+}
+
+impl ImportSpec {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read import spec: {}", path.display()))?;
+        let spec: Self = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse import spec: {}", path.display()))?;
+        spec.csv_delimiter()
+            .with_context(|| format!("Invalid import spec: {}", path.display()))?;
+        Ok(spec)
+    }
+
+    /// CSV delimiter as the single byte the CSV reader expects
+    pub fn csv_delimiter(&self) -> Result<u8> {
+        let delimiter = self.delimiter.unwrap_or(',');
+        if !delimiter.is_ascii() {
+            anyhow::bail!("CSV delimiter must be an ASCII character, got {delimiter:?}");
+        }
+        Ok(delimiter as u8)
+    }
+}
+
+/// Read candles from an external CSV or Parquet file (chosen by extension).
+///
+/// Columns are mapped through `spec`, timestamps normalized to milliseconds,
+/// rows sorted by open time, and the result validated according to `policy`.
+pub fn import_candles(
+    path: impl AsRef<Path>,
+    spec: &ImportSpec,
+    coin: &str,
+    interval: &str,
+    policy: DataPolicy,
+) -> Result<Vec<Candle>> {
+    let path = path.as_ref();
+    let interval_ms = interval_to_ms(interval)?;
+
+    let rows = match path.extension().and_then(|s| s.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("parquet") => read_parquet_rows(path, &spec.columns)?,
+        _ => read_csv_rows(path, spec)?,
+    };
+
+    let mut candles = Vec::with_capacity(rows.len());
+    for (i, row) in rows.iter().enumerate() {
+        let candle = parse_row(row, spec.timestamp_unit, coin, interval, interval_ms)
+            .with_context(|| format!("Invalid row {} in {}", i + 1, path.display()))?;
+        candles.push(candle);
+    }
+    candles.sort_by_key(|c| c.time_open);
+
+    Ok(prepare_candles(&candles, policy)?.into_owned())
+}
+
+/// Raw values of the mapped columns of one row, in `ColumnMapping` field order
+#[derive(Debug, Default)]
+struct Row {
+    time: String,
+    time_close: Option<String>,
+    open: String,
+    high: String,
+    low: String,
+    close: String,
+    volume: Option<String>,
+    num_trades: Option<String>,
+}
+
+fn parse_row(
+    row: &Row,
+    unit: TimestampUnit,
+    coin: &str,
+    interval: &str,
+    interval_ms: u64,
+) -> Result<Candle> {
+    let time_open = parse_timestamp(&row.time, unit)?;
+    let time_close = match &row.time_close {
+        Some(value) => parse_timestamp(value, unit)?,
+        None => time_open + interval_ms - 1,
+    };
+
+    Ok(Candle {
+        time_open,
+        time_close,
+        coin: coin.to_string(),
+        interval: interval.to_string(),
+        open: parse_number(&row.open, "open")?,
+        high: parse_number(&row.high, "high")?,
+        low: parse_number(&row.low, "low")?,
+        close: parse_number(&row.close, "close")?,
+        volume: row
+            .volume
+            .as_deref()
+            .map(|v| parse_number(v, "volume"))
+            .transpose()?
+            .unwrap_or(0.0),
+        num_trades: row
+            .num_trades
+            .as_deref()
+            .map(|v| parse_number(v, "num_trades").map(|n| n as i64))
+            .transpose()?
+            .unwrap_or(0),
+    })
+}
+
+fn parse_number(value: &str, field: &str) -> Result<f64> {
+    value
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid {field}: {value:?}"))
+}
+
+/// Parse a timestamp into milliseconds since the epoch (UTC)
+pub fn parse_timestamp(value: &str, unit: TimestampUnit) -> Result<u64> {
+    let value = value.trim();
+    let numeric = || -> Result<f64> {
+        value
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite() && *v >= 0.0)
+            .with_context(|| format!("Invalid timestamp: {value:?}"))
+    };
+
+    let ms = match unit {
+        TimestampUnit::Seconds => numeric()? * 1e3,
+        TimestampUnit::Millis => numeric()?,
+        TimestampUnit::Micros => numeric()? / 1e3,
+        TimestampUnit::Nanos => numeric()? / 1e6,
+        TimestampUnit::Iso8601 => return parse_iso8601(value),
+        TimestampUnit::Auto => match value.parse::<f64>() {
+            // Seconds until ~5138, then ms, us and ns by magnitude
+            Ok(v) if (0.0..1e11).contains(&v) => v * 1e3,
+            Ok(v) if (1e11..1e14).contains(&v) => v,
+            Ok(v) if (1e14..1e17).contains(&v) => v / 1e3,
+            Ok(v) if v.is_finite() && v >= 1e17 => v / 1e6,
+            _ => return parse_iso8601(value),
+        },
+    };
+
+    Ok(ms.round() as u64)
+}
+
+fn parse_iso8601(value: &str) -> Result<u64> {
+    let parsed = chrono::DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.timestamp_millis())
+        .ok()
+        .or_else(|| {
+            ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+                .iter()
+                .find_map(|fmt| chrono::NaiveDateTime::parse_from_str(value, fmt).ok())
+                .map(|dt| dt.and_utc().timestamp_millis())
+        })
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|dt| dt.and_utc().timestamp_millis())
+        })
+        .with_context(|| format!("Invalid timestamp: {value:?}"))?;
+
+    u64::try_from(parsed).with_context(|| format!("Timestamp before 1970: {value:?}"))
+}
+
+fn read_csv_rows(path: &Path, spec: &ImportSpec) -> Result<Vec<Row>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(spec.csv_delimiter()?)
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .with_context(|| format!("Failed to read CSV file: {}", path.display()))?;
+
+    let headers = rdr.headers()?.clone();
+    let index_of = |name: &str| -> Result<usize> {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .with_context(|| format!("Column {name:?} not found in {}", path.display()))
+    };
+    let columns = &spec.columns;
+    let time = index_of(&columns.time)?;
+    let time_close = columns.time_close.as_deref().map(index_of).transpose()?;
+    let open = index_of(&columns.open)?;
+    let high = index_of(&columns.high)?;
+    let low = index_of(&columns.low)?;
+    let close = index_of(&columns.close)?;
+    let volume = columns.volume.as_deref().map(index_of).transpose()?;
+    let num_trades = columns.num_trades.as_deref().map(index_of).transpose()?;
+
+    let mut rows = Vec::new();
+    for record in rdr.records() {
+        let record = record?;
+        let field = |i: usize| record.get(i).unwrap_or_default().to_string();
+        rows.push(Row {
+            time: field(time),
+            time_close: time_close.map(field),
+            open: field(open),
+            high: field(high),
+            low: field(low),
+            close: field(close),
+            volume: volume.map(field),
+            num_trades: num_trades.map(field),
+        });
+    }
+
+    Ok(rows)
+}
+
+fn read_parquet_rows(path: &Path, columns: &ColumnMapping) -> Result<Vec<Row>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+
+    let mut rows = Vec::new();
+    for batch in reader {
+        let batch = batch?;
+        // Cast every mapped column to strings so CSV and Parquet share one parser
+        let column = |name: &str| -> Result<StringArray> {
+            let array = batch
+                .column_by_name(name)
+                .with_context(|| format!("Column {name:?} not found in {}", path.display()))?;
+            let strings = cast(array, &DataType::Utf8)
+                .with_context(|| format!("Unsupported type for column {name:?}"))?;
+            Ok(strings
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .context("Failed to convert column to strings")?
+                .clone())
+        };
+        let optional = |name: &Option<String>| name.as_deref().map(column).transpose();
+
+        let time = column(&columns.time)?;
+        let time_close = optional(&columns.time_close)?;
+        let open = column(&columns.open)?;
+        let high = column(&columns.high)?;
+        let low = column(&columns.low)?;
+        let close = column(&columns.close)?;
+        let volume = optional(&columns.volume)?;
+        let num_trades = optional(&columns.num_trades)?;
+
+        let value = |array: &StringArray, i: usize| {
+            if array.is_null(i) {
+                String::new()
+            } else {
+                array.value(i).to_string()
+            }
+        };
+        for i in 0..batch.num_rows() {
+            rows.push(Row {
+                time: value(&time, i),
+                time_close: time_close.as_ref().map(|a| value(a, i)),
+                open: value(&open, i),
+                high: value(&high, i),
+                low: value(&low, i),
+                close: value(&close, i),
+                volume: volume.as_ref().map(|a| value(a, i)),
+                num_trades: num_trades.as_ref().map(|a| value(a, i)),
+            });
+        }
+    }
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    // 2024-01-01 00:00:00 UTC
+    const JAN_1_MS: u64 = 1704067200000;
+    const HOUR_MS: u64 = 60 * 60 * 1000;
+
+    #[test]
+    fn test_parse_timestamp_units() {
+        let auto = TimestampUnit::Auto;
+        assert_eq!(parse_timestamp("1704067200", auto).unwrap(), JAN_1_MS);
+        assert_eq!(parse_timestamp("1704067200000", auto).unwrap(), JAN_1_MS);
+        assert_eq!(parse_timestamp("1704067200000000", auto).unwrap(), JAN_1_MS);
+        assert_eq!(parse_timestamp("2024-01-01T00:00:00Z", auto).unwrap(), JAN_1_MS);
+        assert_eq!(parse_timestamp("2024-01-01 01:00:00", auto).unwrap(), JAN_1_MS + HOUR_MS);
+        assert_eq!(parse_timestamp("2024-01-01", auto).unwrap(), JAN_1_MS);
+        assert_eq!(
+            parse_timestamp("1704067200.5", TimestampUnit::Seconds).unwrap(),
+            JAN_1_MS + 500
+        );
+        assert!(parse_timestamp("yesterday", auto).is_err());
+        assert_eq!("ms".parse::<TimestampUnit>().unwrap(), TimestampUnit::Millis);
+    }
+
+    #[test]
+    fn test_import_csv_with_mapping() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("external.csv");
+        // Newest first, semicolon separated, custom names
+        std::fs::write(
+            &path,
+            "date;o;h;l;c;vol\n\
+             2024-01-01T01:00:00Z;101;103;100;102;5\n\
+             2024-01-01T00:00:00Z;100;102;99;101;4\n",
+        )
+        .unwrap();
+
+        let spec: ImportSpec = serde_json::from_str(
+            r#"{"columns": {"time": "date", "open": "o", "high": "h", "low": "l", "close": "c", "volume": "vol"},
+                "timestamp_unit": "iso8601", "delimiter": ";"}"#,
+        )
+        .unwrap();
+        let candles = import_candles(&path, &spec, "BTC", "1h", DataPolicy::Fail).unwrap();
+
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0].time_open, JAN_1_MS);
+        assert_eq!(candles[0].time_close, JAN_1_MS + HOUR_MS - 1);
+        assert_eq!(candles[0].open, 100.0);
+        assert_eq!(candles[1].volume, 5.0);
+        assert_eq!(candles[1].coin, "BTC");
+        assert_eq!(candles[1].num_trades, 0);
+    }
+
+    #[test]
+    fn test_import_spec_rejects_non_ascii_delimiter() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("spec.json");
+        std::fs::write(&path, r#"{"delimiter": "§"}"#).unwrap();
+
+        let err = ImportSpec::from_file(&path).unwrap_err();
+        assert!(format!("{err:#}").contains("ASCII"));
+    }
+
+    #[test]
+    fn test_import_rejects_missing_column_and_bad_data() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("external.csv");
+        std::fs::write(&path, "timestamp,open,high,low,close\n1704067200,1,2,0.5,1\n").unwrap();
+        let err = import_candles(&path, &ImportSpec::default(), "BTC", "1h", DataPolicy::Fail)
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("volume"));
+
+        std::fs::write(
+            &path,
+            "timestamp,open,high,low,close,volume\n1704067200,1,0.5,2,1,1\n",
+        )
+        .unwrap();
+        assert!(
+            import_candles(&path, &ImportSpec::default(), "BTC", "1h", DataPolicy::Fail).is_err()
+        );
+    }
+
+    #[test]
+    fn test_import_parquet_by_column_name() {
+        use arrow::array::{Float32Array, Int64Array};
+        use arrow::datatypes::{Field, Schema};
+        use arrow::record_batch::RecordBatch;
+        use parquet::arrow::ArrowWriter;
+        use std::sync::Arc;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("external.parquet");
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("close", DataType::Float32, false),
+            Field::new("ts", DataType::Int64, false),
+            Field::new("open", DataType::Float32, false),
+            Field::new("high", DataType::Float32, false),
+            Field::new("low", DataType::Float32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Float32Array::from(vec![1.5, 2.5])),
+                Arc::new(Int64Array::from(vec![1704067200, 1704070800])),
+                Arc::new(Float32Array::from(vec![1.0, 2.0])),
+                Arc::new(Float32Array::from(vec![2.0, 3.0])),
+                Arc::new(Float32Array::from(vec![0.5, 1.5])),
+            ],
+        )
+        .unwrap();
+        let mut writer = ArrowWriter::try_new(File::create(&path).unwrap(), schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let spec = ImportSpec {
+            columns: ColumnMapping {
+                time: "ts".to_string(),
+                volume: None,
+                ..Default::default()
+            },
+            timestamp_unit: TimestampUnit::Seconds,
+            delimiter: None,
+        };
+        let candles = import_candles(&path, &spec, "ETH", "1h", DataPolicy::Fail).unwrap();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[1].time_open, JAN_1_MS + HOUR_MS);
+        assert_eq!(candles[1].close, 2.5);
+        assert_eq!(candles[0].volume, 0.0);
+    }
+}
@@ -1,73 +1,392 @@
 use anyhow::{Context, Result};
-use hyperliquid_rust_sdk::{BaseUrl, InfoClient};
+use std::time::Duration;
 
+use crate::data::coverage::TimeRange;
+use crate::data::resample::{bucket_start, can_resample, resample};
+use crate::data::source::MarketDataSource;
 use crate::data::types::Candle;
 use crate::data::Cache;
-use crate::util::map_timeframe_to_interval;
+use crate::util::{interval_to_ms, map_timeframe_to_interval, API_INTERVALS};
 
+/// Hyperliquid returns at most this many candles per `candleSnapshot` request
+pub const MAX_CANDLES_PER_REQUEST: u64 = 5000;
+/// Pause between paginated requests to stay under the info endpoint rate limit
+const REQUEST_DELAY_MS: u64 = 250;
+const MAX_RETRIES: u32 = 3;
+const RETRY_BASE_DELAY_MS: u64 = 500;
+
+/// Summary of a paginated candle fetch
+#[derive(Debug, Clone, Default)]
+pub struct FetchReport {
+    /// Number of API requests made
+    pub requests: usize,
+    /// Number of candles received
+    pub candles: usize,
+    /// Set when the exchange had nothing for the start of the range:
+    /// the time of the earliest candle it did return
+    pub no_data_before: Option<u64>,
+    /// Holes between consecutive candles inside the returned data
+    pub gaps: Vec<TimeRange>,
+}
+
+impl FetchReport {
+    /// Fold the report of another fetch (e.g. another missing range) into this one
+    pub fn merge(&mut self, other: FetchReport) {
+        self.requests += other.requests;
+        self.candles += other.candles;
+        self.no_data_before = match (self.no_data_before, other.no_data_before) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.gaps.extend(other.gaps);
+    }
+
+    pub fn print_warnings(&self, asset: &str, interval: &str) {
+        if let Some(first_ts) = self.no_data_before {
+            eprintln!(
+                "Warning: exchange has no {} {} candles before {} (older history is not served)",
+                asset,
+                interval,
+                format_ts(first_ts)
+            );
+        }
+        if !self.gaps.is_empty() {
+            eprintln!(
+                "Warning: {} gap(s) in {} {} candles returned by the exchange",
+                self.gaps.len(),
+                asset,
+                interval
+            );
+            for gap in &self.gaps {
+                eprintln!("  missing {} .. {}", format_ts(gap.start), format_ts(gap.end));
+            }
+        }
+    }
+}
+
+pub(crate) fn format_ts(ts_ms: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(ts_ms as i64)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| ts_ms.to_string())
+}
+
+/// Load candles for `start_ts..=end_ts`, fetching from `data_source` as needed.
+///
+/// When `interval` is not cached for the range it is resampled from the
+/// coarsest finer interval that is. Intervals the API does not serve
+/// (e.g. `2h`, `30m`) are resampled from the coarsest API interval dividing them.
 pub async fn load_candles(
+    data_source: &dyn MarketDataSource,
     cache: &Cache,
     asset: &str,
     interval: &str,
     start_ts: u64,
     end_ts: u64,
 ) -> Result<Vec<Candle>> {
-    // Try cache first
-    let mut candles = cache.load_cached(asset, interval)?;
+    if cache
+        .load_coverage(asset, interval)?
+        .covers(start_ts, end_ts)
+    {
+        return cache.load_range(asset, interval, start_ts, end_ts);
+    }
 
-    // Filter by time range
-    candles.retain(|c| c.time_open >= start_ts && c.time_open <= end_ts);
+    let target_ms = interval_to_ms(interval)?;
+    // Resample over whole buckets so the bars at the edges of the range are complete
+    let aligned_start = bucket_start(start_ts, target_ms);
+    let aligned_end = bucket_start(end_ts, target_ms).saturating_add(target_ms - 1);
 
-    // If cache is empty or incomplete, fetch from API
-    if candles.is_empty() {
-        candles = fetch_candles_from_api(asset, interval, start_ts, end_ts).await?;
-        // Cache the fetched data
-        cache
-            .fetch_and_cache(asset, interval, start_ts, end_ts)
+    let mut sources = cache.cached_intervals(asset)?;
+    sources.retain(|source| can_resample(source, interval));
+    sources.sort_by_key(|source| std::cmp::Reverse(interval_to_ms(source).unwrap_or(0)));
+    for source in sources {
+        if cache
+            .load_coverage(asset, &source)?
+            .covers(aligned_start, aligned_end)
+        {
+            let base = cache.load_range(asset, &source, aligned_start, aligned_end)?;
+            return resample_range(&base, interval, start_ts, end_ts);
+        }
+    }
+
+    if map_timeframe_to_interval(interval).is_err() {
+        let base = API_INTERVALS
+            .iter()
+            .rev()
+            .find(|base| can_resample(base, interval))
+            .with_context(|| format!("Unsupported timeframe: {}", interval))?;
+        let base =
+            Box::pin(load_candles(data_source, cache, asset, base, aligned_start, aligned_end))
+                .await?;
+        return resample_range(&base, interval, start_ts, end_ts);
+    }
+
+    // Only go to the API for the parts of the range the cache hasn't seen yet
+    let missing = cache.missing_ranges(asset, interval, start_ts, end_ts)?;
+    if !missing.is_empty() {
+        let report = cache
+            .fetch_and_cache(data_source, asset, interval, start_ts, end_ts)
             .await?;
+        report.print_warnings(asset, interval);
     }
 
-    // Sort by time
-    candles.sort_by_key(|c| c.time_open);
+    cache.load_range(asset, interval, start_ts, end_ts)
+}
 
+fn resample_range(
+    base: &[Candle],
+    interval: &str,
+    start_ts: u64,
+    end_ts: u64,
+) -> Result<Vec<Candle>> {
+    let mut candles = resample(base, interval)?;
+    candles.retain(|c| c.time_open >= start_ts && c.time_open <= end_ts);
     Ok(candles)
 }
 
 pub async fn fetch_candles_from_api(
+    source: &dyn MarketDataSource,
     asset: &str,
     interval: &str,
     start_ts: u64,
     end_ts: u64,
 ) -> Result<Vec<Candle>> {
-    let client = InfoClient::new(None, Some(BaseUrl::Mainnet))
-        .await
-        .context("Failed to create InfoClient")?;
+    let (candles, report) =
+        fetch_candles_paginated(source, asset, interval, start_ts, end_ts).await?;
 
+    if candles.is_empty() {
+        anyhow::bail!(no_candles_message(asset, interval, start_ts, end_ts));
+    }
+
+    report.print_warnings(asset, interval);
+    Ok(candles)
+}
+
+/// Fetch candles for a range of any length.
+///
+/// The range is split into chunks of at most [`MAX_CANDLES_PER_REQUEST`] candles,
+/// fetched oldest first with a delay between requests and retries on failure,
+/// then stitched together and checked for continuity. An empty result is not an error.
+pub async fn fetch_candles_paginated(
+    source: &dyn MarketDataSource,
+    asset: &str,
+    interval: &str,
+    start_ts: u64,
+    end_ts: u64,
+) -> Result<(Vec<Candle>, FetchReport)> {
     let hl_interval = map_timeframe_to_interval(interval)?;
-    let hl_interval_clone = hl_interval.clone();
-
-    let sdk_candles = client
-        .candles_snapshot(asset.to_string(), hl_interval, start_ts, end_ts)
-        .await
-        .with_context(|| {
-            format!(
-                "Failed to fetch candles from API: asset={}, interval={}, start={}, end={}",
-                asset, hl_interval_clone, start_ts, end_ts
-            )
-        })?;
-
-    if sdk_candles.is_empty() {
-        anyhow::bail!(
-            "API returned no candles for {} {} in range {} to {}.\n\
-            Note: Hyperliquid does not provide historical candle data for spot markets.\n\
-            See https://hyperliquid.gitbook.io/hyperliquid-docs/historical-data\n\
-            You may need to use cached data or generate test data.",
-            asset,
-            hl_interval_clone,
-            start_ts,
-            end_ts
+    let interval_ms = interval_to_ms(&hl_interval)?;
+
+    let chunks = chunk_range(start_ts, end_ts, interval_ms, MAX_CANDLES_PER_REQUEST);
+    let mut fetched = Vec::with_capacity(chunks.len());
+    for (i, chunk) in chunks.iter().enumerate() {
+        if i > 0 {
+            tokio::time::sleep(Duration::from_millis(REQUEST_DELAY_MS)).await;
+        }
+        fetched.push(fetch_chunk_with_retry(source, asset, &hl_interval, *chunk).await?);
+    }
+
+    let (candles, mut report) = stitch_chunks(fetched, start_ts, end_ts, interval_ms);
+    report.requests = chunks.len();
+    Ok((candles, report))
+}
+
+async fn fetch_chunk_with_retry(
+    source: &dyn MarketDataSource,
+    asset: &str,
+    hl_interval: &str,
+    range: TimeRange,
+) -> Result<Vec<Candle>> {
+    let mut attempt = 0;
+    loop {
+        let result = source
+            .candles(asset, hl_interval, range.start, range.end)
+            .await;
+
+        match result {
+            Ok(candles) => return Ok(candles),
+            Err(e) if attempt < MAX_RETRIES => {
+                let delay = RETRY_BASE_DELAY_MS * 2u64.pow(attempt);
+                eprintln!(
+                    "Warning: candle request failed ({}), retrying in {}ms",
+                    e, delay
+                );
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!(
+                        "Failed to fetch candles from API: asset={}, interval={}, start={}, end={}",
+                        asset, hl_interval, range.start, range.end
+                    )
+                });
+            }
+        }
+    }
+}
+
+/// Split `start_ts..=end_ts` into consecutive ranges of at most `max_candles` bars each
+pub fn chunk_range(start_ts: u64, end_ts: u64, interval_ms: u64, max_candles: u64) -> Vec<TimeRange> {
+    let span = interval_ms.saturating_mul(max_candles).max(1);
+    let mut chunks = Vec::new();
+    let mut chunk_start = start_ts;
+    while chunk_start <= end_ts {
+        let chunk_end = chunk_start.saturating_add(span - 1).min(end_ts);
+        chunks.push(TimeRange::new(chunk_start, chunk_end));
+        if chunk_end == u64::MAX {
+            break;
+        }
+        chunk_start = chunk_end + 1;
+    }
+    chunks
+}
+
+/// Join per-chunk results into one sorted, deduplicated series and check its continuity
+pub fn stitch_chunks(
+    chunks: Vec<Vec<Candle>>,
+    start_ts: u64,
+    end_ts: u64,
+    interval_ms: u64,
+) -> (Vec<Candle>, FetchReport) {
+    let mut candles: Vec<Candle> = chunks
+        .into_iter()
+        .flatten()
+        .filter(|c| c.time_open >= start_ts && c.time_open <= end_ts)
+        .collect();
+    candles.sort_by_key(|c| c.time_open);
+    candles.dedup_by_key(|c| c.time_open);
+
+    let mut report = FetchReport {
+        candles: candles.len(),
+        ..Default::default()
+    };
+
+    if let Some(first) = candles.first() {
+        if first.time_open >= start_ts.saturating_add(interval_ms) {
+            report.no_data_before = Some(first.time_open);
+        }
+    }
+
+    for pair in candles.windows(2) {
+        let expected = pair[0].time_open + interval_ms;
+        if pair[1].time_open > expected {
+            report
+                .gaps
+                .push(TimeRange::new(expected, pair[1].time_open - 1));
+        }
+    }
+
+    (candles, report)
+}
+
+pub(crate) fn no_candles_message(asset: &str, interval: &str, start_ts: u64, end_ts: u64) -> String {
+    format!(
+        "API returned no candles for {} {} in range {} to {}.\n\
+        Note: Hyperliquid does not provide historical candle data for spot markets.\n\
+        See https://hyperliquid.gitbook.io/hyperliquid-docs/historical-data\n\
+        You may need to use cached data or generate test data.",
+        asset, interval, start_ts, end_ts
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::source::LocalSource;
+
+    const MINUTE_MS: u64 = 60 * 1000;
+
+    fn candle(time_open: u64) -> Candle {
+        Candle {
+            time_open,
+            time_close: time_open + MINUTE_MS - 1,
+            coin: "BTC".to_string(),
+            interval: "1m".to_string(),
+            open: 1.0,
+            close: 1.0,
+            high: 1.0,
+            low: 1.0,
+            volume: 1.0,
+            num_trades: 1,
+        }
+    }
+
+    #[test]
+    fn test_chunk_range_respects_limit() {
+        let end = 12_000 * MINUTE_MS - 1;
+        let chunks = chunk_range(0, end, MINUTE_MS, 5000);
+        assert_eq!(
+            chunks,
+            vec![
+                TimeRange::new(0, 5000 * MINUTE_MS - 1),
+                TimeRange::new(5000 * MINUTE_MS, 10_000 * MINUTE_MS - 1),
+                TimeRange::new(10_000 * MINUTE_MS, end),
+            ]
         );
+
+        assert_eq!(chunk_range(10, 5, MINUTE_MS, 5000), vec![]);
     }
 
-    Ok(sdk_candles.iter().map(Candle::from_sdk_candle).collect())
+    #[test]
+    fn test_stitch_chunks_dedupes_and_finds_gaps() {
+        let chunks = vec![
+            vec![candle(0), candle(MINUTE_MS), candle(2 * MINUTE_MS)],
+            // Overlapping boundary candle, then a hole at minutes 3-4
+            vec![candle(2 * MINUTE_MS), candle(5 * MINUTE_MS)],
+        ];
+        let (candles, report) = stitch_chunks(chunks, 0, 6 * MINUTE_MS, MINUTE_MS);
+
+        assert_eq!(candles.len(), 4);
+        assert_eq!(report.candles, 4);
+        assert_eq!(report.no_data_before, None);
+        assert_eq!(
+            report.gaps,
+            vec![TimeRange::new(3 * MINUTE_MS, 5 * MINUTE_MS - 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_candles_resamples_from_cached_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::with_base_dir(dir.path()).unwrap();
+
+        // 2024-01-01 00:00 UTC, two days of 15m bars
+        let start = 1704067200000;
+        let base: Vec<Candle> = (0..192)
+            .map(|i| Candle {
+                interval: "15m".to_string(),
+                time_close: start + (i + 1) * 15 * MINUTE_MS - 1,
+                ..candle(start + i * 15 * MINUTE_MS)
+            })
+            .collect();
+        let end = start + 192 * 15 * MINUTE_MS - 1;
+        cache
+            .store("BTC", "15m", &base, &[TimeRange::new(start, end)])
+            .unwrap();
+
+        // Neither 30m (not served by the API) nor 4h is cached; the local source
+        // fails any request, so both must come from resampling
+        let source = LocalSource::new(dir.path().join("empty"));
+        let half_hourly = load_candles(&source, &cache, "BTC", "30m", start, end)
+            .await
+            .unwrap();
+        assert_eq!(half_hourly.len(), 96);
+        let four_hourly = load_candles(&source, &cache, "BTC", "4h", start, end)
+            .await
+            .unwrap();
+        assert_eq!(four_hourly.len(), 12);
+        assert_eq!(four_hourly[0].volume, 16.0);
+        assert_eq!(four_hourly[0].interval, "4h");
+    }
+
+    #[test]
+    fn test_stitch_chunks_reports_missing_history() {
+        // Exchange only has data from minute 100 onwards
+        let chunks = vec![vec![], vec![candle(100 * MINUTE_MS), candle(101 * MINUTE_MS)]];
+        let (candles, report) = stitch_chunks(chunks, 0, 200 * MINUTE_MS, MINUTE_MS);
+
+        assert_eq!(candles.len(), 2);
+        assert_eq!(report.no_data_before, Some(100 * MINUTE_MS));
+        assert!(report.gaps.is_empty());
+    }
 }
@@ -1,13 +1,29 @@
+pub mod align;
 pub mod cache;
+pub mod coverage;
+pub mod import;
 pub mod loader;
 pub mod parquet;
+pub mod resample;
+pub mod source;
 pub mod types;
+pub mod validate;
 
+pub use align::{align_reference_candles, prepare_reference_events};
 pub use cache::Cache;
-pub use loader::load_candles;
+pub use coverage::{Coverage, TimeRange};
+pub use import::{import_candles, ColumnMapping, ImportSpec, TimestampUnit};
+pub use loader::{load_candles, FetchReport};
 pub use parquet::{
     export_candles_to_parquet, export_equity_to_parquet, export_funding_to_parquet,
-    export_trades_to_parquet, read_candles_from_parquet, FundingPayment,
+    export_trades_to_parquet, read_candles_from_parquet, read_equity_from_parquet,
+    read_funding_from_parquet, read_trades_from_parquet, FundingPayment,
 };
+pub use resample::resample;
+pub use source::{AssetMeta, MarketDataSource, SourceSpec};
 pub use types::Candle;
+pub use validate::{
+    prepare_candles, prepare_l2_events, validate_candles, validate_l2_events, DataPolicy,
+    IssueKind, ValidationReport,
+};
 
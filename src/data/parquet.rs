@@ -1,17 +1,27 @@
 use crate::data::types::Candle;
 use crate::orders::types::{EquityPoint, Trade};
 use anyhow::{Context, Result};
-use arrow::array::{Float64Array, Int64Array, StringArray, UInt64Array};
+use arrow::array::{Array, ArrayRef, Float64Array, Int64Array, StringArray, UInt64Array};
+use arrow::compute::cast;
 use arrow::datatypes::{DataType, Field, Schema};
 use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
 use parquet::arrow::ArrowWriter;
 use parquet::basic::Compression;
+use parquet::file::metadata::KeyValue;
 use parquet::file::properties::WriterProperties;
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::path::Path;
 use std::sync::Arc;
 
+/// Key in the Parquet key-value metadata holding [`SCHEMA_VERSION`]
+pub const SCHEMA_VERSION_KEY: &str = "hl_backtest.schema_version";
+
+/// Version of the layouts written by this module. Readers accept files without a
+/// version and files up to this one; bump it when a change would misread old files.
+pub const SCHEMA_VERSION: u32 = 1;
+
 /// Funding payment record for Parquet export
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FundingPayment {
@@ -75,87 +85,30 @@ pub fn export_candles_to_parquet(candles: &[Candle], path: impl AsRef<Path>) ->
         ],
     )?;
 
-    // Configure writer properties
-    let props = WriterProperties::builder()
-        .set_compression(Compression::SNAPPY)
-        .build();
-
-    // Write to file
-    let file = File::create(path)
-        .with_context(|| format!("Failed to create file: {}", path.display()))?;
-    let mut writer = ArrowWriter::try_new(file, schema, Some(props))?;
-    writer.write(&batch)?;
-    writer.close()?;
-
-    Ok(())
+    write_batch(path, schema, &batch)
 }
 
-/// Read candles from Parquet format
+/// Read candles from Parquet format.
+///
+/// Columns are matched by name, so files written by other tools or with
+/// reordered columns are read correctly. `num_trades` may be missing (read as 0).
 pub fn read_candles_from_parquet(path: impl AsRef<Path>) -> Result<Vec<Candle>> {
-    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
-
-    let path = path.as_ref();
-    let file = File::open(path)
-        .with_context(|| format!("Failed to open file: {}", path.display()))?;
-
-    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
-    let reader = builder.build()?;
-
     let mut candles = Vec::new();
 
-    for batch_result in reader {
-        let batch = batch_result?;
-
-        let time_open = batch
-            .column(0)
-            .as_any()
-            .downcast_ref::<UInt64Array>()
-            .context("Failed to read time_open column")?;
-        let time_close = batch
-            .column(1)
-            .as_any()
-            .downcast_ref::<UInt64Array>()
-            .context("Failed to read time_close column")?;
-        let coin = batch
-            .column(2)
-            .as_any()
-            .downcast_ref::<StringArray>()
-            .context("Failed to read coin column")?;
-        let interval = batch
-            .column(3)
-            .as_any()
-            .downcast_ref::<StringArray>()
-            .context("Failed to read interval column")?;
-        let open = batch
-            .column(4)
-            .as_any()
-            .downcast_ref::<Float64Array>()
-            .context("Failed to read open column")?;
-        let high = batch
-            .column(5)
-            .as_any()
-            .downcast_ref::<Float64Array>()
-            .context("Failed to read high column")?;
-        let low = batch
-            .column(6)
-            .as_any()
-            .downcast_ref::<Float64Array>()
-            .context("Failed to read low column")?;
-        let close = batch
-            .column(7)
-            .as_any()
-            .downcast_ref::<Float64Array>()
-            .context("Failed to read close column")?;
-        let volume = batch
-            .column(8)
-            .as_any()
-            .downcast_ref::<Float64Array>()
-            .context("Failed to read volume column")?;
-        let num_trades = batch
-            .column(9)
-            .as_any()
-            .downcast_ref::<Int64Array>()
-            .context("Failed to read num_trades column")?;
+    for batch in read_batches(path.as_ref())? {
+        let time_open = u64_column(&batch, "time_open")?;
+        let time_close = u64_column(&batch, "time_close")?;
+        let coin = string_column(&batch, "coin")?;
+        let interval = string_column(&batch, "interval")?;
+        let open = f64_column(&batch, "open")?;
+        let high = f64_column(&batch, "high")?;
+        let low = f64_column(&batch, "low")?;
+        let close = f64_column(&batch, "close")?;
+        let volume = f64_column(&batch, "volume")?;
+        let num_trades = optional_column(&batch, "num_trades", &DataType::Int64)?;
+        let num_trades = num_trades
+            .as_ref()
+            .map(|array| array.as_any().downcast_ref::<Int64Array>().unwrap());
 
         for i in 0..batch.num_rows() {
             candles.push(Candle {
@@ -168,7 +121,7 @@ pub fn read_candles_from_parquet(path: impl AsRef<Path>) -> Result<Vec<Candle>>
                 low: low.value(i),
                 close: close.value(i),
                 volume: volume.value(i),
-                num_trades: num_trades.value(i),
+                num_trades: num_trades.map_or(0, |n| n.value(i)),
             });
         }
     }
@@ -193,6 +146,7 @@ pub fn export_trades_to_parquet(trades: &[Trade], path: impl AsRef<Path>) -> Res
         Field::new("price", DataType::Float64, false),
         Field::new("fee", DataType::Float64, false),
         Field::new("order_id", DataType::UInt64, false),
+        Field::new("tag", DataType::Utf8, true),
     ]));
 
     let timestamp: UInt64Array = trades.iter().map(|t| t.timestamp).collect();
@@ -202,6 +156,7 @@ pub fn export_trades_to_parquet(trades: &[Trade], path: impl AsRef<Path>) -> Res
     let price: Float64Array = trades.iter().map(|t| t.price).collect();
     let fee: Float64Array = trades.iter().map(|t| t.fee).collect();
     let order_id: UInt64Array = trades.iter().map(|t| t.order_id).collect();
+    let tag: StringArray = trades.iter().map(|t| t.tag.as_deref()).collect();
 
     let batch = RecordBatch::try_new(
         schema.clone(),
@@ -213,20 +168,11 @@ pub fn export_trades_to_parquet(trades: &[Trade], path: impl AsRef<Path>) -> Res
             Arc::new(price),
             Arc::new(fee),
             Arc::new(order_id),
+            Arc::new(tag),
         ],
     )?;
 
-    let props = WriterProperties::builder()
-        .set_compression(Compression::SNAPPY)
-        .build();
-
-    let file = File::create(path)
-        .with_context(|| format!("Failed to create file: {}", path.display()))?;
-    let mut writer = ArrowWriter::try_new(file, schema, Some(props))?;
-    writer.write(&batch)?;
-    writer.close()?;
-
-    Ok(())
+    write_batch(path, schema, &batch)
 }
 
 /// Export equity curve to Parquet format
@@ -260,17 +206,7 @@ pub fn export_equity_to_parquet(equity_curve: &[EquityPoint], path: impl AsRef<P
         ],
     )?;
 
-    let props = WriterProperties::builder()
-        .set_compression(Compression::SNAPPY)
-        .build();
-
-    let file = File::create(path)
-        .with_context(|| format!("Failed to create file: {}", path.display()))?;
-    let mut writer = ArrowWriter::try_new(file, schema, Some(props))?;
-    writer.write(&batch)?;
-    writer.close()?;
-
-    Ok(())
+    write_batch(path, schema, &batch)
 }
 
 /// Export funding payments to Parquet format
@@ -307,19 +243,260 @@ pub fn export_funding_to_parquet(payments: &[FundingPayment], path: impl AsRef<P
         ],
     )?;
 
+    write_batch(path, schema, &batch)
+}
+
+/// Read trades written by [`export_trades_to_parquet`]
+pub fn read_trades_from_parquet(path: impl AsRef<Path>) -> Result<Vec<Trade>> {
+    let mut trades = Vec::new();
+
+    for batch in read_batches(path.as_ref())? {
+        let timestamp = u64_column(&batch, "timestamp")?;
+        let symbol = string_column(&batch, "symbol")?;
+        let side = string_column(&batch, "side")?;
+        let size = f64_column(&batch, "size")?;
+        let price = f64_column(&batch, "price")?;
+        let fee = f64_column(&batch, "fee")?;
+        let order_id = u64_column(&batch, "order_id")?;
+        let tag = nullable_string_column(&batch, "tag")?;
+
+        for i in 0..batch.num_rows() {
+            trades.push(Trade {
+                timestamp: timestamp.value(i),
+                symbol: symbol.value(i).to_string(),
+                side: side.value(i).to_string(),
+                size: size.value(i),
+                price: price.value(i),
+                fee: fee.value(i),
+                order_id: order_id.value(i),
+                tag: tag
+                    .as_ref()
+                    .filter(|tag| tag.is_valid(i))
+                    .map(|tag| tag.value(i).to_string()),
+            });
+        }
+    }
+
+    Ok(trades)
+}
+
+/// Read an equity curve written by [`export_equity_to_parquet`]
+pub fn read_equity_from_parquet(path: impl AsRef<Path>) -> Result<Vec<EquityPoint>> {
+    let mut equity_curve = Vec::new();
+
+    for batch in read_batches(path.as_ref())? {
+        let timestamp = u64_column(&batch, "timestamp")?;
+        let equity = f64_column(&batch, "equity")?;
+        let cash = f64_column(&batch, "cash")?;
+        let position_value = f64_column(&batch, "position_value")?;
+
+        for i in 0..batch.num_rows() {
+            equity_curve.push(EquityPoint {
+                timestamp: timestamp.value(i),
+                equity: equity.value(i),
+                cash: cash.value(i),
+                position_value: position_value.value(i),
+            });
+        }
+    }
+
+    Ok(equity_curve)
+}
+
+/// Read funding payments written by [`export_funding_to_parquet`]
+pub fn read_funding_from_parquet(path: impl AsRef<Path>) -> Result<Vec<FundingPayment>> {
+    let mut payments = Vec::new();
+
+    for batch in read_batches(path.as_ref())? {
+        let timestamp = u64_column(&batch, "timestamp")?;
+        let coin = string_column(&batch, "coin")?;
+        let rate = f64_column(&batch, "rate")?;
+        let payment = f64_column(&batch, "payment")?;
+        let position_size = f64_column(&batch, "position_size")?;
+
+        for i in 0..batch.num_rows() {
+            payments.push(FundingPayment {
+                timestamp: timestamp.value(i),
+                coin: coin.value(i).to_string(),
+                rate: rate.value(i),
+                payment: payment.value(i),
+                position_size: position_size.value(i),
+            });
+        }
+    }
+
+    Ok(payments)
+}
+
+/// Schema version stored in the key-value metadata of every file written here
+pub fn schema_version(path: impl AsRef<Path>) -> Result<Option<u32>> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+        .with_context(|| format!("Failed to read Parquet file: {}", path.display()))?;
+    file_schema_version(
+        builder.metadata().file_metadata().key_value_metadata(),
+        path,
+    )
+}
+
+fn file_schema_version(metadata: Option<&Vec<KeyValue>>, path: &Path) -> Result<Option<u32>> {
+    let Some(value) = metadata
+        .into_iter()
+        .flatten()
+        .find(|kv| kv.key == SCHEMA_VERSION_KEY)
+        .and_then(|kv| kv.value.as_deref())
+    else {
+        return Ok(None);
+    };
+    let version: u32 = value.parse().with_context(|| {
+        format!(
+            "Invalid {SCHEMA_VERSION_KEY} '{value}' in {}",
+            path.display()
+        )
+    })?;
+    Ok(Some(version))
+}
+
+fn write_batch(path: &Path, schema: Arc<Schema>, batch: &RecordBatch) -> Result<()> {
     let props = WriterProperties::builder()
         .set_compression(Compression::SNAPPY)
+        .set_key_value_metadata(Some(vec![KeyValue::new(
+            SCHEMA_VERSION_KEY.to_string(),
+            SCHEMA_VERSION.to_string(),
+        )]))
         .build();
 
-    let file = File::create(path)
-        .with_context(|| format!("Failed to create file: {}", path.display()))?;
+    let file =
+        File::create(path).with_context(|| format!("Failed to create file: {}", path.display()))?;
     let mut writer = ArrowWriter::try_new(file, schema, Some(props))?;
-    writer.write(&batch)?;
+    writer.write(batch)?;
     writer.close()?;
 
     Ok(())
 }
 
+/// All record batches of a file, refusing files from a newer schema version.
+/// Files without a version (written by other tools) are read by column name.
+fn read_batches(path: &Path) -> Result<Vec<RecordBatch>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+        .with_context(|| format!("Failed to read Parquet file: {}", path.display()))?;
+
+    if let Some(version) = file_schema_version(
+        builder.metadata().file_metadata().key_value_metadata(),
+        path,
+    )? {
+        if version > SCHEMA_VERSION {
+            anyhow::bail!(
+                "{} was written with schema version {} but this build reads up to {}",
+                path.display(),
+                version,
+                SCHEMA_VERSION
+            );
+        }
+    }
+
+    builder
+        .build()?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to read Parquet file: {}", path.display()))
+}
+
+/// Column `name` cast to `data_type`, or `None` if the file has no such column.
+///
+/// Integer widths and signedness, Float32/Float64 and numeric strings are
+/// coerced; values that do not fit (e.g. negative timestamps) are an error.
+fn optional_column(
+    batch: &RecordBatch,
+    name: &str,
+    data_type: &DataType,
+) -> Result<Option<ArrayRef>> {
+    let Ok(index) = batch.schema().index_of(name) else {
+        return Ok(None);
+    };
+    let array = batch.column(index);
+    if array.null_count() > 0 {
+        anyhow::bail!("Column '{}' contains null values", name);
+    }
+    if array.data_type() == data_type {
+        return Ok(Some(array.clone()));
+    }
+
+    let cast_array = cast(array, data_type).with_context(|| {
+        format!(
+            "Column '{}' has type {} which cannot be read as {}",
+            name,
+            array.data_type(),
+            data_type
+        )
+    })?;
+    // Casts turn out-of-range or unparseable values into nulls
+    if cast_array.null_count() > 0 {
+        anyhow::bail!(
+            "Column '{}' has {} values that cannot be read as {}",
+            name,
+            array.data_type(),
+            data_type
+        );
+    }
+    Ok(Some(cast_array))
+}
+
+fn required_column(batch: &RecordBatch, name: &str, data_type: &DataType) -> Result<ArrayRef> {
+    optional_column(batch, name, data_type)?.with_context(|| {
+        let columns: Vec<String> = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        format!("Missing column '{}' (found: {})", name, columns.join(", "))
+    })
+}
+
+fn u64_column(batch: &RecordBatch, name: &str) -> Result<UInt64Array> {
+    let array = required_column(batch, name, &DataType::UInt64)?;
+    Ok(array
+        .as_any()
+        .downcast_ref::<UInt64Array>()
+        .unwrap()
+        .clone())
+}
+
+fn f64_column(batch: &RecordBatch, name: &str) -> Result<Float64Array> {
+    let array = required_column(batch, name, &DataType::Float64)?;
+    Ok(array
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .unwrap()
+        .clone())
+}
+
+fn string_column(batch: &RecordBatch, name: &str) -> Result<StringArray> {
+    let array = required_column(batch, name, &DataType::Utf8)?;
+    Ok(array
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap()
+        .clone())
+}
+
+/// String column `name` that may contain nulls, or `None` if the file has no
+/// such column (files written before it was added)
+fn nullable_string_column(batch: &RecordBatch, name: &str) -> Result<Option<StringArray>> {
+    let Ok(index) = batch.schema().index_of(name) else {
+        return Ok(None);
+    };
+    let array = batch.column(index);
+    let Some(strings) = array.as_any().downcast_ref::<StringArray>() else {
+        anyhow::bail!("Column '{}' has type {} which cannot be read as Utf8", name, array.data_type());
+    };
+    Ok(Some(strings.clone()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -365,4 +542,99 @@ mod tests {
         assert_eq!(loaded[0].coin, candles[0].coin);
         assert!((loaded[0].close - candles[0].close).abs() < 0.001);
     }
+
+    fn write_raw(path: &Path, schema: Schema, columns: Vec<ArrayRef>, version: Option<&str>) {
+        let schema = Arc::new(schema);
+        let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+        let props = WriterProperties::builder()
+            .set_key_value_metadata(
+                version.map(|v| vec![KeyValue::new(SCHEMA_VERSION_KEY.to_string(), v.to_string())]),
+            )
+            .build();
+        let mut writer =
+            ArrowWriter::try_new(File::create(path).unwrap(), schema, Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_read_candles_by_name_with_coercion() {
+        use arrow::array::Float32Array;
+
+        // Another tool's layout: reordered, signed timestamps, f32 prices, no num_trades
+        let schema = Schema::new(vec![
+            Field::new("close", DataType::Float32, false),
+            Field::new("coin", DataType::Utf8, false),
+            Field::new("time_open", DataType::Int64, false),
+            Field::new("open", DataType::Float32, false),
+            Field::new("high", DataType::Float64, false),
+            Field::new("low", DataType::Float64, false),
+            Field::new("volume", DataType::Int64, false),
+            Field::new("interval", DataType::Utf8, false),
+            Field::new("time_close", DataType::Int64, false),
+        ]);
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(Float32Array::from(vec![101.5])),
+            Arc::new(StringArray::from(vec!["ETH"])),
+            Arc::new(Int64Array::from(vec![1704067200000])),
+            Arc::new(Float32Array::from(vec![100.0])),
+            Arc::new(Float64Array::from(vec![102.0])),
+            Arc::new(Float64Array::from(vec![99.0])),
+            Arc::new(Int64Array::from(vec![42])),
+            Arc::new(StringArray::from(vec!["1h"])),
+            Arc::new(Int64Array::from(vec![1704070799999])),
+        ];
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("external.parquet");
+        write_raw(&path, schema, columns, None);
+
+        assert_eq!(schema_version(&path).unwrap(), None);
+        let candles = read_candles_from_parquet(&path).unwrap();
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].time_open, 1704067200000);
+        assert_eq!(candles[0].open, 100.0);
+        assert_eq!(candles[0].close, 101.5);
+        assert_eq!(candles[0].volume, 42.0);
+        assert_eq!(candles[0].num_trades, 0);
+        assert_eq!(candles[0].coin, "ETH");
+    }
+
+    #[test]
+    fn test_read_rejects_bad_columns_and_newer_versions() {
+        let dir = tempdir().unwrap();
+        let schema = || {
+            Schema::new(vec![
+                Field::new("timestamp", DataType::Int64, false),
+                Field::new("equity", DataType::Float64, false),
+                Field::new("cash", DataType::Float64, false),
+                Field::new("position_value", DataType::Float64, false),
+            ])
+        };
+        let columns = |ts: i64| -> Vec<ArrayRef> {
+            vec![
+                Arc::new(Int64Array::from(vec![ts])),
+                Arc::new(Float64Array::from(vec![1.0])),
+                Arc::new(Float64Array::from(vec![1.0])),
+                Arc::new(Float64Array::from(vec![0.0])),
+            ]
+        };
+
+        let negative = dir.path().join("negative.parquet");
+        write_raw(&negative, schema(), columns(-1), None);
+        let err = read_equity_from_parquet(&negative).unwrap_err();
+        assert!(err.to_string().contains("timestamp"), "{err}");
+
+        let newer = dir.path().join("newer.parquet");
+        let version = (SCHEMA_VERSION + 1).to_string();
+        write_raw(&newer, schema(), columns(1), Some(&version));
+        let err = read_equity_from_parquet(&newer).unwrap_err();
+        assert!(err.to_string().contains("schema version"), "{err}");
+
+        // Missing columns name what was found
+        let equity = dir.path().join("equity.parquet");
+        write_raw(&equity, schema(), columns(1), None);
+        assert_eq!(read_equity_from_parquet(&equity).unwrap()[0].timestamp, 1);
+        let err = read_trades_from_parquet(&equity).unwrap_err();
+        assert!(err.to_string().contains("Missing column 'symbol'"), "{err}");
+    }
 }
@@ -0,0 +1,284 @@
+use anyhow::{Context, Result};
+
+use crate::data::types::Candle;
+use crate::util::interval_to_ms;
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+const WEEK_MS: u64 = 7 * DAY_MS;
+/// 1970-01-05, the first Monday after the epoch; weekly bars start on Mondays
+const WEEK_ORIGIN_MS: u64 = 4 * DAY_MS;
+
+/// Start of the UTC-aligned bucket of length `interval_ms` containing `ts_ms`.
+///
+/// Buckets are aligned to the epoch (midnight UTC for daily and intraday
+/// intervals), except multiples of a week, which start on Monday 00:00 UTC.
+pub fn bucket_start(ts_ms: u64, interval_ms: u64) -> u64 {
+    let origin = if interval_ms.is_multiple_of(WEEK_MS) {
+        WEEK_ORIGIN_MS
+    } else {
+        0
+    };
+    if ts_ms < origin {
+        return ts_ms - ts_ms % interval_ms;
+    }
+    ts_ms - (ts_ms - origin) % interval_ms
+}
+
+/// Whether candles of `source` interval can be aggregated into `target` bars
+pub fn can_resample(source: &str, target: &str) -> bool {
+    match (interval_to_ms(source), interval_to_ms(target)) {
+        (Ok(source_ms), Ok(target_ms)) => target_ms > source_ms && target_ms.is_multiple_of(source_ms),
+        _ => false,
+    }
+}
+
+/// Aggregate candles into a coarser interval (e.g. `1h` -> `4h`, `15m` -> `30m`).
+///
+/// Input must be sorted by `time_open` and share one interval, which must divide
+/// `target_interval`. Open is the first open, close the last close, high/low the
+/// extremes, and volume and trade counts are summed. A bucket at either end of
+/// the input that is missing source bars is dropped, since it was cut off by the
+/// loaded range rather than by a gap in trading.
+pub fn resample(candles: &[Candle], target_interval: &str) -> Result<Vec<Candle>> {
+    let Some(first) = candles.first() else {
+        return Ok(vec![]);
+    };
+
+    let source_ms = interval_to_ms(&first.interval)
+        .with_context(|| format!("Cannot resample candles with interval {}", first.interval))?;
+    let target_ms = interval_to_ms(target_interval)?;
+    if target_ms == source_ms {
+        return Ok(candles.to_vec());
+    }
+    if target_ms < source_ms || !target_ms.is_multiple_of(source_ms) {
+        anyhow::bail!(
+            "Cannot resample {} candles to {}: target must be a multiple of the source interval",
+            first.interval,
+            target_interval
+        );
+    }
+    let bars_per_bucket = (target_ms / source_ms) as usize;
+
+    let mut buckets: Vec<(Candle, usize)> = Vec::new();
+    for candle in candles {
+        let start = bucket_start(candle.time_open, target_ms);
+        match buckets.last_mut() {
+            Some((bar, count)) if bar.time_open == start => {
+                bar.high = bar.high.max(candle.high);
+                bar.low = bar.low.min(candle.low);
+                bar.close = candle.close;
+                bar.volume += candle.volume;
+                bar.num_trades += candle.num_trades;
+                *count += 1;
+            }
+            Some((bar, _)) if start < bar.time_open => {
+                anyhow::bail!("Cannot resample unsorted candles");
+            }
+            _ => buckets.push((
+                Candle {
+                    time_open: start,
+                    time_close: start + target_ms - 1,
+                    coin: candle.coin.clone(),
+                    interval: target_interval.to_string(),
+                    open: candle.open,
+                    close: candle.close,
+                    high: candle.high,
+                    low: candle.low,
+                    volume: candle.volume,
+                    num_trades: candle.num_trades,
+                },
+                1,
+            )),
+        }
+    }
+
+    let last = buckets.len() - 1;
+    Ok(buckets
+        .into_iter()
+        .enumerate()
+        .filter(|(i, (_, count))| (*i != 0 && *i != last) || *count >= bars_per_bucket)
+        .map(|(_, (bar, _))| bar)
+        .collect())
+}
+
+/// Builds bars of a coarser interval from a stream of finer bars or ticks,
+/// handing each one out only once it has closed.
+///
+/// A bar closes with the first input that reaches the end of its bucket, or
+/// when an input starts a later bucket (after a gap, or for ticks). The bucket
+/// the stream starts in is dropped unless the stream starts at its beginning,
+/// as in [`resample`].
+pub struct BarBuilder {
+    interval: String,
+    interval_ms: u64,
+    bar: Option<Candle>,
+    started: bool,
+    partial: bool,
+}
+
+impl BarBuilder {
+    pub fn new(interval: &str) -> Result<Self> {
+        Ok(Self {
+            interval: interval.to_string(),
+            interval_ms: interval_to_ms(interval)?,
+            bar: None,
+            started: false,
+            partial: false,
+        })
+    }
+
+    /// Add an input covering `input_ms` from its `time_open` (0 for a tick)
+    /// and return the bars it closes, oldest first
+    pub fn push(&mut self, input: &Candle, input_ms: u64) -> Vec<Candle> {
+        let start = bucket_start(input.time_open, self.interval_ms);
+        let mut closed = Vec::new();
+        match &mut self.bar {
+            Some(bar) if bar.time_open == start => {
+                bar.high = bar.high.max(input.high);
+                bar.low = bar.low.min(input.low);
+                bar.close = input.close;
+                bar.volume += input.volume;
+                bar.num_trades += input.num_trades;
+            }
+            _ => {
+                closed.extend(self.take());
+                if !self.started {
+                    self.started = true;
+                    self.partial = input.time_open != start;
+                }
+                self.bar = Some(Candle {
+                    time_open: start,
+                    time_close: start + self.interval_ms - 1,
+                    coin: input.coin.clone(),
+                    interval: self.interval.clone(),
+                    ..input.clone()
+                });
+            }
+        }
+        if input.time_open + input_ms >= start + self.interval_ms {
+            closed.extend(self.take());
+        }
+        closed
+    }
+
+    /// The bar in progress, unless it is the partial first one
+    fn take(&mut self) -> Option<Candle> {
+        let bar = self.bar.take()?;
+        (!std::mem::take(&mut self.partial)).then_some(bar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR_MS: u64 = 60 * 60 * 1000;
+    // 2024-01-01 00:00:00 UTC (a Monday)
+    const JAN_1: u64 = 1704067200000;
+
+    fn hourly(time_open: u64, open: f64, close: f64) -> Candle {
+        Candle {
+            time_open,
+            time_close: time_open + HOUR_MS - 1,
+            coin: "BTC".to_string(),
+            interval: "1h".to_string(),
+            open,
+            close,
+            high: open.max(close) + 1.0,
+            low: open.min(close) - 1.0,
+            volume: 10.0,
+            num_trades: 2,
+        }
+    }
+
+    #[test]
+    fn test_bucket_alignment() {
+        assert_eq!(bucket_start(JAN_1 + 5 * HOUR_MS, 4 * HOUR_MS), JAN_1 + 4 * HOUR_MS);
+        assert_eq!(bucket_start(JAN_1 + 23 * HOUR_MS, DAY_MS), JAN_1);
+        assert_eq!(bucket_start(JAN_1 + 3 * DAY_MS, WEEK_MS), JAN_1);
+        assert_eq!(bucket_start(JAN_1 + 90 * 60 * 1000, 30 * 60 * 1000), JAN_1 + HOUR_MS + 30 * 60 * 1000);
+    }
+
+    #[test]
+    fn test_resample_aggregates_ohlcv() {
+        let candles: Vec<Candle> = (0..4)
+            .map(|i| hourly(JAN_1 + i * HOUR_MS, 100.0 + i as f64, 101.0 + i as f64))
+            .collect();
+        let bars = resample(&candles, "2h").unwrap();
+
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[0].time_open, JAN_1);
+        assert_eq!(bars[0].time_close, JAN_1 + 2 * HOUR_MS - 1);
+        assert_eq!(bars[0].interval, "2h");
+        assert_eq!(bars[0].open, 100.0);
+        assert_eq!(bars[0].close, 102.0);
+        assert_eq!(bars[0].high, 103.0);
+        assert_eq!(bars[0].low, 99.0);
+        assert_eq!(bars[0].volume, 20.0);
+        assert_eq!(bars[0].num_trades, 4);
+        assert_eq!(bars[1].open, 102.0);
+    }
+
+    #[test]
+    fn test_resample_drops_incomplete_edge_buckets() {
+        // 01:00..=08:00: the 00:00 and 08:00 buckets are cut off by the range
+        let candles: Vec<Candle> = (1..9)
+            .map(|i| hourly(JAN_1 + i * HOUR_MS, 100.0, 100.0))
+            .collect();
+        let bars = resample(&candles, "4h").unwrap();
+        assert_eq!(bars.len(), 1);
+        assert_eq!(bars[0].time_open, JAN_1 + 4 * HOUR_MS);
+
+        // Interior gaps still produce a bar
+        let gapped = vec![
+            hourly(JAN_1, 100.0, 100.0),
+            hourly(JAN_1 + HOUR_MS, 100.0, 100.0),
+            hourly(JAN_1 + 2 * HOUR_MS, 100.0, 100.0),
+            hourly(JAN_1 + 4 * HOUR_MS, 100.0, 100.0),
+            hourly(JAN_1 + 6 * HOUR_MS, 100.0, 100.0),
+            hourly(JAN_1 + 7 * HOUR_MS, 100.0, 100.0),
+            hourly(JAN_1 + 8 * HOUR_MS, 100.0, 100.0),
+        ];
+        let bars = resample(&gapped, "2h").unwrap();
+        let opens: Vec<u64> = bars.iter().map(|b| (b.time_open - JAN_1) / HOUR_MS).collect();
+        assert_eq!(opens, vec![0, 2, 4, 6]);
+    }
+
+    #[test]
+    fn test_bar_builder_closes_bars_without_look_ahead() {
+        let mut builder = BarBuilder::new("2h").unwrap();
+        // Starts mid-bucket: 01:00 completes a bucket that is then dropped
+        assert!(builder.push(&hourly(JAN_1 + HOUR_MS, 100.0, 100.0), HOUR_MS).is_empty());
+        assert!(builder.push(&hourly(JAN_1 + 2 * HOUR_MS, 100.0, 101.0), HOUR_MS).is_empty());
+        let closed = builder.push(&hourly(JAN_1 + 3 * HOUR_MS, 101.0, 103.0), HOUR_MS);
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].time_open, JAN_1 + 2 * HOUR_MS);
+        assert_eq!((closed[0].open, closed[0].close, closed[0].high), (100.0, 103.0, 104.0));
+        assert_eq!((closed[0].volume, closed[0].interval.as_str()), (20.0, "2h"));
+
+        // 05:00 is missing: the 04:00 bar closes when 06:00 arrives
+        assert!(builder.push(&hourly(JAN_1 + 4 * HOUR_MS, 103.0, 104.0), HOUR_MS).is_empty());
+        let closed = builder.push(&hourly(JAN_1 + 6 * HOUR_MS, 104.0, 104.0), HOUR_MS);
+        assert_eq!(closed.len(), 1);
+        assert_eq!((closed[0].time_open, closed[0].close), (JAN_1 + 4 * HOUR_MS, 104.0));
+
+        // Ticks close a bar once a later one arrives
+        let mut builder = BarBuilder::new("1h").unwrap();
+        let tick = |ts: u64| Candle {
+            time_close: ts,
+            ..hourly(ts, 100.0, 100.0)
+        };
+        assert!(builder.push(&tick(JAN_1), 0).is_empty());
+        assert!(builder.push(&tick(JAN_1 + HOUR_MS - 1), 0).is_empty());
+        assert_eq!(builder.push(&tick(JAN_1 + HOUR_MS), 0)[0].time_open, JAN_1);
+    }
+
+    #[test]
+    fn test_resample_rejects_incompatible_intervals() {
+        let candles = vec![hourly(JAN_1, 100.0, 100.0)];
+        assert!(resample(&candles, "90m").is_err());
+        assert!(resample(&candles, "30m").is_err());
+        assert!(can_resample("15m", "30m"));
+        assert!(!can_resample("4h", "1h"));
+    }
+}
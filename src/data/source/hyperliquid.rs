@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+use crate::data::source::{AssetMeta, MarketDataSource};
+use crate::data::types::Candle;
+use crate::perps::funding::FundingPoint;
+
+pub const MAINNET_API_URL: &str = "https://api.hyperliquid.xyz";
+pub const TESTNET_API_URL: &str = "https://api.hyperliquid-testnet.xyz";
+
+const REQUEST_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Debug, Deserialize)]
+struct FundingHistoryEntry {
+    #[serde(rename = "fundingRate")]
+    funding_rate: String,
+    time: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetaResponse {
+    universe: Vec<AssetMeta>,
+}
+
+/// Hyperliquid info endpoint (`POST {base_url}/info`)
+pub struct HyperliquidSource {
+    name: String,
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl HyperliquidSource {
+    pub fn mainnet() -> Result<Self> {
+        Self::new("mainnet", MAINNET_API_URL)
+    }
+
+    pub fn testnet() -> Result<Self> {
+        Self::new("testnet", TESTNET_API_URL)
+    }
+
+    /// Any server speaking the Hyperliquid info API, e.g. a [`super::MockInfoServer`]
+    pub fn with_base_url(base_url: &str) -> Result<Self> {
+        Self::new(base_url, base_url)
+    }
+
+    fn new(name: &str, base_url: &str) -> Result<Self> {
+        // reqwest validates certificates by default; the timeout prevents hanging
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(false)
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self {
+            name: name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn post_info<T: DeserializeOwned>(&self, request: serde_json::Value) -> Result<T> {
+        let request_type = request["type"].as_str().unwrap_or("info").to_string();
+        let response = self
+            .client
+            .post(format!("{}/info", self.base_url))
+            .json(&request)
+            .send()
+            .await
+            .with_context(|| format!("Failed to send {request_type} request to {}", self.name))?;
+
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "{} request to {} returned error: {}",
+                request_type,
+                self.name,
+                response.status()
+            );
+        }
+
+        response
+            .json()
+            .await
+            .with_context(|| format!("Failed to parse {request_type} response"))
+    }
+}
+
+impl MarketDataSource for HyperliquidSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn candles<'a>(
+        &'a self,
+        coin: &'a str,
+        interval: &'a str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> BoxFuture<'a, Result<Vec<Candle>>> {
+        Box::pin(async move {
+            let request = json!({
+                "type": "candleSnapshot",
+                "req": {
+                    "coin": coin,
+                    "interval": interval,
+                    "startTime": start_ts,
+                    "endTime": end_ts,
+                },
+            });
+            let candles: Vec<hyperliquid_rust_sdk::CandlesSnapshotResponse> =
+                self.post_info(request).await?;
+            Ok(candles.iter().map(Candle::from_sdk_candle).collect())
+        })
+    }
+
+    fn funding<'a>(
+        &'a self,
+        coin: &'a str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> BoxFuture<'a, Result<Vec<FundingPoint>>> {
+        Box::pin(async move {
+            let request = json!({
+                "type": "fundingHistory",
+                "coin": coin,
+                "startTime": start_ts,
+                "endTime": end_ts,
+            });
+            let entries: Vec<FundingHistoryEntry> = self.post_info(request).await?;
+            entries
+                .into_iter()
+                .map(|entry| {
+                    let rate = entry
+                        .funding_rate
+                        .parse::<f64>()
+                        .with_context(|| format!("Invalid funding rate: {}", entry.funding_rate))?;
+                    Ok(FundingPoint {
+                        ts_ms: entry.time,
+                        rate,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    fn meta(&self) -> BoxFuture<'_, Result<Vec<AssetMeta>>> {
+        Box::pin(async move {
+            let meta: MetaResponse = self.post_info(json!({ "type": "meta" })).await?;
+            Ok(meta.universe)
+        })
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::data::cache::Cache;
+use crate::data::source::{AssetMeta, MarketDataSource};
+use crate::data::types::Candle;
+use crate::perps::funding::FundingPoint;
+
+/// Serves data from files, without network access.
+///
+/// Layout under `root`: the candle cache (`{coin}/{interval}/...`, see [`Cache`]),
+/// `{coin}/funding.json` (`[{"ts_ms": ..., "rate": ...}]`) and `meta.json`
+/// (a list of [`AssetMeta`], or the API's `{"universe": [...]}`).
+pub struct LocalSource {
+    root: PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MetaFile {
+    Universe { universe: Vec<AssetMeta> },
+    List(Vec<AssetMeta>),
+}
+
+impl LocalSource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn read_json<T: for<'de> Deserialize<'de>>(&self, path: &Path, what: &str) -> Result<T> {
+        let json = std::fs::read_to_string(path).with_context(|| {
+            format!("No local {what} data (expected {})", path.display())
+        })?;
+        serde_json::from_str(&json).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+}
+
+impl MarketDataSource for LocalSource {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    fn candles<'a>(
+        &'a self,
+        coin: &'a str,
+        interval: &'a str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> BoxFuture<'a, Result<Vec<Candle>>> {
+        Box::pin(async move {
+            let cache = Cache::with_base_dir(&self.root)?;
+            // Refuse rather than return a partial range, which would be recorded as covered
+            if !cache
+                .load_coverage(coin, interval)?
+                .covers(start_ts, end_ts)
+            {
+                anyhow::bail!(
+                    "{} {} candles for {} .. {} are not available locally in {}",
+                    coin,
+                    interval,
+                    start_ts,
+                    end_ts,
+                    self.root.display()
+                );
+            }
+            cache.load_range(coin, interval, start_ts, end_ts)
+        })
+    }
+
+    fn funding<'a>(
+        &'a self,
+        coin: &'a str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> BoxFuture<'a, Result<Vec<FundingPoint>>> {
+        Box::pin(async move {
+            let path = self.root.join(coin).join("funding.json");
+            let mut points: Vec<FundingPoint> = self.read_json(&path, "funding")?;
+            points.retain(|p| p.ts_ms >= start_ts && p.ts_ms <= end_ts);
+            Ok(points)
+        })
+    }
+
+    fn meta(&self) -> BoxFuture<'_, Result<Vec<AssetMeta>>> {
+        Box::pin(async move {
+            let meta: MetaFile = self.read_json(&self.root.join("meta.json"), "meta")?;
+            Ok(match meta {
+                MetaFile::Universe { universe } => universe,
+                MetaFile::List(list) => list,
+            })
+        })
+    }
+}
@@ -0,0 +1,189 @@
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::data::loader::MAX_CANDLES_PER_REQUEST;
+use crate::data::source::AssetMeta;
+use crate::data::types::Candle;
+use crate::perps::funding::FundingPoint;
+
+/// Data served by a [`MockInfoServer`]
+#[derive(Debug, Clone, Default)]
+pub struct MockData {
+    pub candles: Vec<Candle>,
+    /// Funding rates per coin
+    pub funding: Vec<(String, FundingPoint)>,
+    pub meta: Vec<AssetMeta>,
+}
+
+/// In-process HTTP server answering Hyperliquid info requests
+/// (`candleSnapshot`, `fundingHistory`, `meta`) from [`MockData`].
+///
+/// Like the real endpoint it returns at most [`MAX_CANDLES_PER_REQUEST`] candles
+/// per request, so pagination is exercised too. Point a
+/// [`super::HyperliquidSource::with_base_url`] at [`MockInfoServer::url`].
+/// The server stops when dropped.
+pub struct MockInfoServer {
+    addr: SocketAddr,
+    requests: Arc<AtomicUsize>,
+    task: JoinHandle<()>,
+}
+
+impl MockInfoServer {
+    pub async fn start(data: MockData) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .context("Failed to bind mock info server")?;
+        let addr = listener.local_addr()?;
+        let requests = Arc::new(AtomicUsize::new(0));
+        let data = Arc::new(data);
+
+        let counter = requests.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let data = data.clone();
+                let counter = counter.clone();
+                tokio::spawn(async move {
+                    // A broken test connection only affects that request
+                    let _ = serve_connection(stream, &data, &counter).await;
+                });
+            }
+        });
+
+        Ok(Self {
+            addr,
+            requests,
+            task,
+        })
+    }
+
+    /// Base URL to pass to [`super::HyperliquidSource::with_base_url`]
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Number of info requests answered so far
+    pub fn request_count(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockInfoServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve_connection(stream: TcpStream, data: &MockData, counter: &AtomicUsize) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+
+    let mut content_length = 0usize;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).await?;
+
+    let (status, response) = if !request_line.starts_with("POST /info ") {
+        ("404 Not Found", json!({ "error": "not found" }))
+    } else {
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(request) => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                match handle_info(&request, data) {
+                    Some(response) => ("200 OK", response),
+                    None => ("422 Unprocessable Entity", json!({ "error": "bad request" })),
+                }
+            }
+            Err(_) => ("400 Bad Request", json!({ "error": "invalid json" })),
+        }
+    };
+
+    let payload = response.to_string();
+    let mut stream = reader.into_inner();
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{payload}",
+                payload.len()
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+fn handle_info(request: &Value, data: &MockData) -> Option<Value> {
+    match request["type"].as_str()? {
+        "candleSnapshot" => {
+            let req = &request["req"];
+            let coin = req["coin"].as_str()?;
+            let interval = req["interval"].as_str()?;
+            let start = req["startTime"].as_u64()?;
+            let end = req["endTime"].as_u64()?;
+
+            let candles: Vec<Value> = data
+                .candles
+                .iter()
+                .filter(|c| c.coin == coin && c.interval == interval)
+                .filter(|c| c.time_open >= start && c.time_open <= end)
+                .take(MAX_CANDLES_PER_REQUEST as usize)
+                .map(|c| {
+                    json!({
+                        "t": c.time_open,
+                        "T": c.time_close,
+                        "s": c.coin,
+                        "i": c.interval,
+                        "o": c.open.to_string(),
+                        "c": c.close.to_string(),
+                        "h": c.high.to_string(),
+                        "l": c.low.to_string(),
+                        "v": c.volume.to_string(),
+                        "n": c.num_trades,
+                    })
+                })
+                .collect();
+            Some(Value::Array(candles))
+        }
+        "fundingHistory" => {
+            let coin = request["coin"].as_str()?;
+            let start = request["startTime"].as_u64()?;
+            let end = request["endTime"].as_u64().unwrap_or(u64::MAX);
+
+            let entries: Vec<Value> = data
+                .funding
+                .iter()
+                .filter(|(c, p)| c == coin && p.ts_ms >= start && p.ts_ms <= end)
+                .map(|(c, p)| {
+                    json!({
+                        "coin": c,
+                        "fundingRate": p.rate.to_string(),
+                        "premium": "0.0",
+                        "time": p.ts_ms,
+                    })
+                })
+                .collect();
+            Some(Value::Array(entries))
+        }
+        "meta" => Some(json!({ "universe": data.meta })),
+        _ => None,
+    }
+}
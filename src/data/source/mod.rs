@@ -0,0 +1,120 @@
+//! Where market data comes from.
+//!
+//! Fetch paths (candle pagination, funding history, asset metadata) go through
+//! [`MarketDataSource`], so they can run against the Hyperliquid API on mainnet or
+//! testnet, local files, or the in-process [`MockInfoServer`] in tests.
+
+pub mod hyperliquid;
+pub mod local;
+pub mod mock;
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::data::types::Candle;
+use crate::perps::funding::FundingPoint;
+
+pub use hyperliquid::{HyperliquidSource, MAINNET_API_URL, TESTNET_API_URL};
+pub use local::LocalSource;
+pub use mock::{MockData, MockInfoServer};
+
+/// Static description of a perpetual market
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssetMeta {
+    pub name: String,
+    /// Number of decimals allowed in order sizes (lot size = 10^-sz_decimals)
+    #[serde(rename = "szDecimals")]
+    pub sz_decimals: u32,
+    #[serde(rename = "maxLeverage", default)]
+    pub max_leverage: Option<u32>,
+}
+
+/// A provider of candles, funding history and asset metadata.
+///
+/// Requests are made for one bounded range at a time; callers such as
+/// [`crate::data::loader::fetch_candles_paginated`] handle chunking and retries.
+pub trait MarketDataSource: Send + Sync {
+    /// Short name for messages (e.g. "mainnet", "local")
+    fn name(&self) -> &str;
+
+    /// Candles with `time_open` in `start_ts..=end_ts`; an empty result is not an error
+    fn candles<'a>(
+        &'a self,
+        coin: &'a str,
+        interval: &'a str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> BoxFuture<'a, Result<Vec<Candle>>>;
+
+    /// Funding rates with timestamps in `start_ts..=end_ts`
+    fn funding<'a>(
+        &'a self,
+        coin: &'a str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> BoxFuture<'a, Result<Vec<FundingPoint>>>;
+
+    /// Metadata for every listed perpetual
+    fn meta(&self) -> BoxFuture<'_, Result<Vec<AssetMeta>>>;
+}
+
+/// Source selected on the command line: `mainnet`, `testnet`, `local` or an API URL
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SourceSpec {
+    #[default]
+    Mainnet,
+    Testnet,
+    /// Serve from the candle cache and files next to it, without network access
+    Local,
+    /// Hyperliquid-compatible API at this base URL
+    Url(String),
+}
+
+impl FromStr for SourceSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mainnet" => Ok(Self::Mainnet),
+            "testnet" => Ok(Self::Testnet),
+            "local" => Ok(Self::Local),
+            _ if s.starts_with("http://") || s.starts_with("https://") => {
+                Ok(Self::Url(s.trim_end_matches('/').to_string()))
+            }
+            _ => Err(format!(
+                "Unknown data source: {s} (expected mainnet, testnet, local or an http(s) URL)"
+            )),
+        }
+    }
+}
+
+impl SourceSpec {
+    /// Build the source; `local_root` is the directory served by [`LocalSource`]
+    pub fn build(&self, local_root: impl Into<PathBuf>) -> Result<Box<dyn MarketDataSource>> {
+        Ok(match self {
+            SourceSpec::Mainnet => Box::new(HyperliquidSource::mainnet()?),
+            SourceSpec::Testnet => Box::new(HyperliquidSource::testnet()?),
+            SourceSpec::Local => Box::new(LocalSource::new(local_root)),
+            SourceSpec::Url(url) => Box::new(HyperliquidSource::with_base_url(url)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_source_spec() {
+        assert_eq!("Mainnet".parse::<SourceSpec>().unwrap(), SourceSpec::Mainnet);
+        assert_eq!("local".parse::<SourceSpec>().unwrap(), SourceSpec::Local);
+        assert_eq!(
+            "http://127.0.0.1:3001/".parse::<SourceSpec>().unwrap(),
+            SourceSpec::Url("http://127.0.0.1:3001".to_string())
+        );
+        assert!("devnet".parse::<SourceSpec>().is_err());
+    }
+}
@@ -21,13 +21,18 @@ impl Candle {
             time_close: sdk.time_close,
             coin: sdk.coin.clone(),
             interval: sdk.candle_interval.clone(),
-            open: sdk.open.parse().unwrap_or(0.0),
-            close: sdk.close.parse().unwrap_or(0.0),
-            high: sdk.high.parse().unwrap_or(0.0),
-            low: sdk.low.parse().unwrap_or(0.0),
-            volume: sdk.vlm.parse().unwrap_or(0.0),
+            open: parse_or_nan(&sdk.open),
+            close: parse_or_nan(&sdk.close),
+            high: parse_or_nan(&sdk.high),
+            low: parse_or_nan(&sdk.low),
+            volume: parse_or_nan(&sdk.vlm),
             num_trades: sdk.num_trades as i64,
         }
     }
 }
 
+/// Unparsable numbers become NaN rather than a plausible-looking 0.0,
+/// so data validation flags them instead of the backtest trading on them
+fn parse_or_nan(value: &str) -> f64 {
+    value.parse().unwrap_or(f64::NAN)
+}
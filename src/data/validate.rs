@@ -0,0 +1,480 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::data::coverage::{Coverage, TimeRange};
+use crate::data::loader::format_ts;
+use crate::data::types::Candle;
+use crate::ingest::L2Event;
+use crate::util::interval_to_ms;
+
+/// Maximum number of time ranges listed per issue kind when printing a report
+const MAX_RANGES_SHOWN: usize = 10;
+
+/// What to do when input data fails validation before a backtest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataPolicy {
+    /// Abort the backtest
+    Fail,
+    /// Sort, drop duplicate/invalid records and forward-fill candle gaps
+    Repair,
+    /// Print the issues and run on the data as is
+    #[default]
+    Warn,
+}
+
+impl FromStr for DataPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fail" => Ok(Self::Fail),
+            "repair" => Ok(Self::Repair),
+            "warn" => Ok(Self::Warn),
+            _ => Err(format!("Unknown data policy: {s} (expected fail, repair or warn)")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// Missing bars between consecutive candles (or a long pause between L2 snapshots)
+    Gap,
+    /// Two records with the same timestamp
+    DuplicateTimestamp,
+    /// A record older than the one before it
+    NonMonotonic,
+    /// Price or size that is NaN, infinite or not positive (e.g. failed to parse)
+    InvalidPrice,
+    /// Candle with high < low, or open/close outside the high-low range
+    InvalidRange,
+    /// L2 snapshot whose best bid is at or above its best ask
+    CrossedBook,
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IssueKind::Gap => "gaps",
+            IssueKind::DuplicateTimestamp => "duplicate timestamps",
+            IssueKind::NonMonotonic => "non-monotonic timestamps",
+            IssueKind::InvalidPrice => "invalid prices",
+            IssueKind::InvalidRange => "high/low inconsistencies",
+            IssueKind::CrossedBook => "crossed books",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Occurrences of one kind of issue
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IssueStats {
+    pub count: usize,
+    /// Affected time, with overlapping and adjacent ranges merged
+    pub ranges: Coverage,
+}
+
+impl IssueStats {
+    pub fn ranges(&self) -> &[TimeRange] {
+        self.ranges.ranges()
+    }
+}
+
+/// Result of validating a candle series or an L2 event stream
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// Number of records checked
+    pub records: usize,
+    pub issues: BTreeMap<IssueKind, IssueStats>,
+}
+
+impl ValidationReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn count(&self, kind: IssueKind) -> usize {
+        self.issues.get(&kind).map(|s| s.count).unwrap_or(0)
+    }
+
+    fn record(&mut self, kind: IssueKind, start: u64, end: u64) {
+        let stats = self.issues.entry(kind).or_default();
+        stats.count += 1;
+        stats.ranges.insert(start, end.max(start));
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_clean() {
+            return write!(f, "{} records checked, no issues found", self.records);
+        }
+
+        write!(f, "{} records checked:", self.records)?;
+        for (kind, stats) in &self.issues {
+            write!(f, "\n  {}: {}", kind, stats.count)?;
+            let ranges = stats.ranges.ranges();
+            for range in ranges.iter().take(MAX_RANGES_SHOWN) {
+                if range.start == range.end {
+                    write!(f, "\n    at {}", format_ts(range.start))?;
+                } else {
+                    write!(f, "\n    {} .. {}", format_ts(range.start), format_ts(range.end))?;
+                }
+            }
+            if ranges.len() > MAX_RANGES_SHOWN {
+                write!(f, "\n    ... and {} more", ranges.len() - MAX_RANGES_SHOWN)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn valid_price(px: f64) -> bool {
+    px.is_finite() && px > 0.0
+}
+
+fn candle_prices_valid(c: &Candle) -> bool {
+    [c.open, c.high, c.low, c.close].into_iter().all(valid_price)
+        && c.volume.is_finite()
+        && c.volume >= 0.0
+}
+
+fn candle_range_valid(c: &Candle) -> bool {
+    c.high >= c.low
+        && (c.low..=c.high).contains(&c.open)
+        && (c.low..=c.high).contains(&c.close)
+}
+
+/// Interval of a series in milliseconds, taken from the first candle's `interval` field
+fn series_interval_ms(candles: &[Candle]) -> Option<u64> {
+    candles.first().and_then(|c| interval_to_ms(&c.interval).ok())
+}
+
+/// Check a candle series in the given order.
+///
+/// Gaps are detected against `interval_ms`, or the interval named by the candles
+/// themselves when `None`; they are skipped if neither is known.
+pub fn validate_candles(candles: &[Candle], interval_ms: Option<u64>) -> ValidationReport {
+    let interval_ms = interval_ms.or_else(|| series_interval_ms(candles));
+    let mut report = ValidationReport {
+        records: candles.len(),
+        ..Default::default()
+    };
+
+    for c in candles {
+        if !candle_prices_valid(c) {
+            report.record(IssueKind::InvalidPrice, c.time_open, c.time_close);
+        } else if !candle_range_valid(c) {
+            report.record(IssueKind::InvalidRange, c.time_open, c.time_close);
+        }
+    }
+
+    for pair in candles.windows(2) {
+        let (prev, next) = (&pair[0], &pair[1]);
+        if next.time_open == prev.time_open {
+            report.record(IssueKind::DuplicateTimestamp, next.time_open, next.time_open);
+        } else if next.time_open < prev.time_open {
+            report.record(IssueKind::NonMonotonic, next.time_open, prev.time_open);
+        } else if let Some(interval_ms) = interval_ms {
+            let expected = prev.time_open + interval_ms;
+            if next.time_open > expected {
+                report.record(IssueKind::Gap, expected, next.time_open - 1);
+            }
+        }
+    }
+
+    report
+}
+
+/// Best bid and best ask of a snapshot (`levels[0]` bids, `levels[1]` asks)
+fn best_bid_ask(event: &L2Event) -> (Option<f64>, Option<f64>) {
+    let best_bid = event
+        .levels
+        .first()
+        .and_then(|bids| bids.iter().map(|l| l.px).reduce(f64::max));
+    let best_ask = event
+        .levels
+        .get(1)
+        .and_then(|asks| asks.iter().map(|l| l.px).reduce(f64::min));
+    (best_bid, best_ask)
+}
+
+fn levels_valid(event: &L2Event) -> bool {
+    event
+        .levels
+        .iter()
+        .flatten()
+        .all(|l| valid_price(l.px) && l.sz.is_finite() && l.sz >= 0.0)
+}
+
+fn book_crossed(event: &L2Event) -> bool {
+    matches!(best_bid_ask(event), (Some(bid), Some(ask)) if bid >= ask)
+}
+
+/// Check an L2 event stream in the given order.
+///
+/// Pauses longer than `max_gap_ms` between snapshots are reported as gaps.
+pub fn validate_l2_events(events: &[L2Event], max_gap_ms: Option<u64>) -> ValidationReport {
+    let mut report = ValidationReport {
+        records: events.len(),
+        ..Default::default()
+    };
+
+    for event in events {
+        if !levels_valid(event) {
+            report.record(IssueKind::InvalidPrice, event.ts_ms, event.ts_ms);
+        } else if book_crossed(event) {
+            report.record(IssueKind::CrossedBook, event.ts_ms, event.ts_ms);
+        }
+    }
+
+    for pair in events.windows(2) {
+        let (prev, next) = (pair[0].ts_ms, pair[1].ts_ms);
+        if next == prev {
+            report.record(IssueKind::DuplicateTimestamp, next, next);
+        } else if next < prev {
+            report.record(IssueKind::NonMonotonic, next, prev);
+        } else if max_gap_ms.is_some_and(|max_gap| next - prev > max_gap) {
+            report.record(IssueKind::Gap, prev + 1, next - 1);
+        }
+    }
+
+    report
+}
+
+/// Sort by time, drop duplicates (last one wins) and bars with invalid prices,
+/// then forward-fill gaps with flat zero-volume bars at the previous close
+pub fn repair_candles(candles: &[Candle], interval_ms: Option<u64>) -> Vec<Candle> {
+    let interval_ms = interval_ms.or_else(|| series_interval_ms(candles));
+
+    let mut by_time: BTreeMap<u64, Candle> = BTreeMap::new();
+    for c in candles {
+        if candle_prices_valid(c) && candle_range_valid(c) {
+            by_time.insert(c.time_open, c.clone());
+        }
+    }
+
+    let mut repaired: Vec<Candle> = Vec::with_capacity(by_time.len());
+    for candle in by_time.into_values() {
+        if let (Some(prev), Some(interval_ms)) = (repaired.last().cloned(), interval_ms) {
+            let mut time_open = prev.time_open + interval_ms;
+            while time_open < candle.time_open {
+                repaired.push(Candle {
+                    time_open,
+                    time_close: time_open + interval_ms - 1,
+                    open: prev.close,
+                    high: prev.close,
+                    low: prev.close,
+                    close: prev.close,
+                    volume: 0.0,
+                    num_trades: 0,
+                    ..prev.clone()
+                });
+                time_open += interval_ms;
+            }
+        }
+        repaired.push(candle);
+    }
+
+    repaired
+}
+
+/// Sort by time, keeping the last snapshot for duplicate timestamps,
+/// and drop snapshots with invalid levels or a crossed book
+pub fn repair_l2_events(events: Vec<L2Event>) -> Vec<L2Event> {
+    let mut by_time: BTreeMap<u64, L2Event> = BTreeMap::new();
+    for event in events {
+        if levels_valid(&event) && !book_crossed(&event) {
+            by_time.insert(event.ts_ms, event);
+        }
+    }
+    by_time.into_values().collect()
+}
+
+/// Validate candles and apply `policy` before a backtest.
+///
+/// Clean data is returned borrowed; repaired data is returned owned.
+pub fn prepare_candles(candles: &[Candle], policy: DataPolicy) -> Result<Cow<'_, [Candle]>> {
+    let report = validate_candles(candles, None);
+    if report.is_clean() {
+        return Ok(Cow::Borrowed(candles));
+    }
+
+    match policy {
+        DataPolicy::Fail => anyhow::bail!("Candle data failed validation: {}", report),
+        DataPolicy::Warn => {
+            eprintln!("Warning: candle data has quality issues: {}", report);
+            Ok(Cow::Borrowed(candles))
+        }
+        DataPolicy::Repair => {
+            let repaired = repair_candles(candles, None);
+            eprintln!(
+                "Warning: repaired candle data ({} -> {} bars): {}",
+                candles.len(),
+                repaired.len(),
+                report
+            );
+            Ok(Cow::Owned(repaired))
+        }
+    }
+}
+
+/// Validate an L2 event stream and apply `policy` before a backtest
+pub fn prepare_l2_events(events: Vec<L2Event>, policy: DataPolicy) -> Result<Vec<L2Event>> {
+    let report = validate_l2_events(&events, None);
+    if report.is_clean() {
+        return Ok(events);
+    }
+
+    match policy {
+        DataPolicy::Fail => anyhow::bail!("L2 data failed validation: {}", report),
+        DataPolicy::Warn => {
+            eprintln!("Warning: L2 data has quality issues: {}", report);
+            Ok(events)
+        }
+        DataPolicy::Repair => {
+            let before = events.len();
+            let repaired = repair_l2_events(events);
+            eprintln!(
+                "Warning: repaired L2 data ({} -> {} events): {}",
+                before,
+                repaired.len(),
+                report
+            );
+            Ok(repaired)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingest::OrderLevel;
+
+    const MINUTE_MS: u64 = 60 * 1000;
+
+    fn candle(time_open: u64, close: f64) -> Candle {
+        Candle {
+            time_open,
+            time_close: time_open + MINUTE_MS - 1,
+            coin: "BTC".to_string(),
+            interval: "1m".to_string(),
+            open: close,
+            close,
+            high: close + 1.0,
+            low: close - 1.0,
+            volume: 1.0,
+            num_trades: 1,
+        }
+    }
+
+    fn snapshot(ts_ms: u64, bid: f64, ask: f64) -> L2Event {
+        let level = |px| OrderLevel { px, sz: 1.0, n: 1 };
+        L2Event {
+            ts_ms,
+            levels: vec![vec![level(bid)], vec![level(ask)]],
+        }
+    }
+
+    #[test]
+    fn test_clean_series_has_no_issues() {
+        let candles: Vec<Candle> = (0..5).map(|i| candle(i * MINUTE_MS, 100.0)).collect();
+        let report = validate_candles(&candles, None);
+        assert!(report.is_clean());
+        assert_eq!(report.records, 5);
+    }
+
+    #[test]
+    fn test_detects_candle_issues() {
+        let mut bad_range = candle(3 * MINUTE_MS, 100.0);
+        bad_range.high = 90.0;
+        let mut bad_price = candle(6 * MINUTE_MS, 100.0);
+        bad_price.close = f64::NAN;
+
+        let candles = vec![
+            candle(0, 100.0),
+            candle(MINUTE_MS, 100.0),
+            candle(MINUTE_MS, 101.0),
+            candle(2 * MINUTE_MS, 100.0),
+            bad_range,
+            // minutes 4 and 5 missing
+            bad_price,
+            candle(2 * MINUTE_MS, 100.0),
+        ];
+        let report = validate_candles(&candles, None);
+
+        assert_eq!(report.count(IssueKind::DuplicateTimestamp), 1);
+        assert_eq!(report.count(IssueKind::InvalidRange), 1);
+        assert_eq!(report.count(IssueKind::InvalidPrice), 1);
+        assert_eq!(report.count(IssueKind::NonMonotonic), 1);
+        assert_eq!(
+            report.issues[&IssueKind::Gap].ranges(),
+            &[TimeRange::new(4 * MINUTE_MS, 6 * MINUTE_MS - 1)]
+        );
+    }
+
+    #[test]
+    fn test_repair_candles_dedupes_drops_and_fills() {
+        let mut bad = candle(2 * MINUTE_MS, 100.0);
+        bad.low = f64::NAN;
+        let candles = vec![
+            candle(MINUTE_MS, 101.0),
+            candle(0, 100.0),
+            candle(MINUTE_MS, 102.0),
+            bad,
+            candle(4 * MINUTE_MS, 105.0),
+        ];
+
+        let repaired = repair_candles(&candles, None);
+        assert!(validate_candles(&repaired, None).is_clean());
+        assert_eq!(repaired.len(), 5);
+        assert_eq!(repaired[1].close, 102.0);
+        // Minutes 2 and 3 are flat bars at the previous close
+        assert_eq!(repaired[2].open, 102.0);
+        assert_eq!(repaired[3].close, 102.0);
+        assert_eq!(repaired[3].volume, 0.0);
+        assert_eq!(repaired[4].close, 105.0);
+    }
+
+    #[test]
+    fn test_policies() {
+        let candles = vec![candle(0, 100.0), candle(0, 101.0)];
+
+        assert!(prepare_candles(&candles, DataPolicy::Fail).is_err());
+        assert_eq!(prepare_candles(&candles, DataPolicy::Warn).unwrap().len(), 2);
+        let repaired = prepare_candles(&candles, DataPolicy::Repair).unwrap();
+        assert_eq!(repaired.len(), 1);
+        assert_eq!(repaired[0].close, 101.0);
+
+        assert_eq!("Repair".parse::<DataPolicy>().unwrap(), DataPolicy::Repair);
+        assert!("skip".parse::<DataPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_l2_validation_and_repair() {
+        let events = vec![
+            snapshot(1000, 99.0, 101.0),
+            snapshot(2000, 101.0, 100.0),
+            snapshot(2000, 99.0, 101.0),
+            snapshot(1500, 99.0, 101.0),
+            snapshot(9000, 0.0, 101.0),
+        ];
+
+        let report = validate_l2_events(&events, Some(5000));
+        assert_eq!(report.count(IssueKind::CrossedBook), 1);
+        assert_eq!(report.count(IssueKind::DuplicateTimestamp), 1);
+        assert_eq!(report.count(IssueKind::NonMonotonic), 1);
+        assert_eq!(report.count(IssueKind::InvalidPrice), 1);
+        assert_eq!(report.count(IssueKind::Gap), 1);
+
+        let repaired = prepare_l2_events(events, DataPolicy::Repair).unwrap();
+        let timestamps: Vec<u64> = repaired.iter().map(|e| e.ts_ms).collect();
+        assert_eq!(timestamps, vec![1000, 1500, 2000]);
+        assert!(validate_l2_events(&repaired, None).is_clean());
+    }
+}
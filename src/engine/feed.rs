@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+
+use crate::data::types::Candle;
+use crate::ingest::{L2Event, TradePrint};
+use crate::perps::funding::FundingPoint;
+
+/// One input to the backtest engine
+#[derive(Debug, Clone)]
+pub enum MarketEvent {
+    /// A finished candle, timestamped at its open
+    Bar(Candle),
+    /// An L2 book snapshot
+    Book(L2Event),
+    /// A trade print
+    Trade(TradePrint),
+    /// A funding settlement at the given rate
+    Funding(FundingPoint),
+}
+
+impl MarketEvent {
+    pub fn ts_ms(&self) -> u64 {
+        match self {
+            MarketEvent::Bar(candle) => candle.time_open,
+            MarketEvent::Book(event) => event.ts_ms,
+            MarketEvent::Trade(trade) => trade.ts_ms,
+            MarketEvent::Funding(point) => point.ts_ms,
+        }
+    }
+
+    /// Order of events sharing a timestamp: market data first, so funding
+    /// settles at the price of that instant
+    fn rank(&self) -> u8 {
+        match self {
+            MarketEvent::Bar(_) | MarketEvent::Book(_) => 0,
+            MarketEvent::Trade(_) => 1,
+            MarketEvent::Funding(_) => 2,
+        }
+    }
+}
+
+/// A time-ordered stream of [`MarketEvent`]s for one coin
+pub trait DataFeed {
+    /// Coin the events belong to (the portfolio symbol)
+    fn coin(&self) -> &str;
+
+    /// Next event, or `None` when the feed is exhausted
+    fn next_event(&mut self) -> Option<MarketEvent>;
+}
+
+/// In-memory [`DataFeed`] merging candles, book snapshots, trades and funding by time
+#[derive(Debug, Clone, Default)]
+pub struct MarketFeed {
+    coin: String,
+    events: VecDeque<MarketEvent>,
+}
+
+impl MarketFeed {
+    pub fn new(coin: impl Into<String>) -> Self {
+        Self {
+            coin: coin.into(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn from_candles(coin: impl Into<String>, candles: Vec<Candle>) -> Self {
+        Self::new(coin).with_events(candles.into_iter().map(MarketEvent::Bar))
+    }
+
+    pub fn from_l2_events(coin: impl Into<String>, events: Vec<L2Event>) -> Self {
+        Self::new(coin).with_events(events.into_iter().map(MarketEvent::Book))
+    }
+
+    pub fn with_trades(self, trades: Vec<TradePrint>) -> Self {
+        self.with_events(trades.into_iter().map(MarketEvent::Trade))
+    }
+
+    pub fn with_funding(self, points: Vec<FundingPoint>) -> Self {
+        self.with_events(points.into_iter().map(MarketEvent::Funding))
+    }
+
+    /// Merge in more events; the order of events with equal keys is preserved
+    pub fn with_events(mut self, events: impl IntoIterator<Item = MarketEvent>) -> Self {
+        self.events.extend(events);
+        self.events
+            .make_contiguous()
+            .sort_by_key(|e| (e.ts_ms(), e.rank()));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+impl DataFeed for MarketFeed {
+    fn coin(&self) -> &str {
+        &self.coin
+    }
+
+    fn next_event(&mut self) -> Option<MarketEvent> {
+        self.events.pop_front()
+    }
+}
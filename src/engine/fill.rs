@@ -0,0 +1,228 @@
+use crate::data::types::Candle;
+use crate::engine::feed::MarketEvent;
+use crate::fees::FeeCalculator;
+use crate::orderbook::OrderBook;
+use crate::orders::fills::{process_order_fill, FillResult};
+use crate::orders::types::{Action, Order, OrderStatus, Side, Tif};
+use crate::perps::execution::PerpsExecution;
+use crate::portfolio::Portfolio;
+
+/// How orders are matched against the market.
+///
+/// The engine passes every [`MarketEvent`] to the model, then offers each open
+/// order with [`FillModel::try_fill`]. The model updates partial fills and
+/// triggers on the order in place.
+pub trait FillModel {
+    /// Absorb a market event; models ignore the kinds they don't use
+    fn on_event(&mut self, event: &MarketEvent);
+
+    /// Price used for sizing, equity and funding; `None` until the market is known
+    fn mark_price(&self) -> Option<f64>;
+
+    /// Try to fill `order` against the current market.
+    ///
+    /// `None` means the order keeps resting unchanged.
+    fn try_fill(&mut self, order: &mut Order, portfolio: &Portfolio) -> Option<FillResult>;
+}
+
+/// Fills orders against the current candle (see [`process_order_fill`]).
+///
+/// Market orders fill at the bar's open plus slippage; limit, stop and take
+/// orders fill when the bar's range touches their price.
+pub struct OhlcFillModel {
+    fee_calc: FeeCalculator,
+    bar: Option<Candle>,
+}
+
+impl OhlcFillModel {
+    pub fn new(fee_calc: FeeCalculator) -> Self {
+        Self { fee_calc, bar: None }
+    }
+}
+
+impl FillModel for OhlcFillModel {
+    fn on_event(&mut self, event: &MarketEvent) {
+        if let MarketEvent::Bar(candle) = event {
+            self.bar = Some(candle.clone());
+        }
+    }
+
+    fn mark_price(&self) -> Option<f64> {
+        self.bar.as_ref().map(|bar| bar.close)
+    }
+
+    fn try_fill(&mut self, order: &mut Order, portfolio: &Portfolio) -> Option<FillResult> {
+        let bar = self.bar.as_ref()?;
+        process_order_fill(order, bar, portfolio, &self.fee_calc)
+    }
+}
+
+/// Fills orders against a reconstructed L2 book (see [`PerpsExecution`]).
+///
+/// Market orders sweep the book, limit orders fill against the touch. Stop and
+/// take orders trigger on the mid price and then behave as market or limit orders.
+#[derive(Default)]
+pub struct BookFillModel {
+    book: OrderBook,
+}
+
+impl BookFillModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn book(&self) -> &OrderBook {
+        &self.book
+    }
+}
+
+impl FillModel for BookFillModel {
+    fn on_event(&mut self, event: &MarketEvent) {
+        if let MarketEvent::Book(snapshot) = event {
+            self.book.apply_snapshot(&snapshot.levels);
+        }
+    }
+
+    fn mark_price(&self) -> Option<f64> {
+        self.book.mid_price()
+    }
+
+    fn try_fill(&mut self, order: &mut Order, _portfolio: &Portfolio) -> Option<FillResult> {
+        if !trigger(order, self.book.mid_price()?) {
+            return None;
+        }
+
+        match &order.action {
+            Action::Market { .. } => PerpsExecution::execute_market(order, &self.book),
+            Action::Limit { px, tif, .. } => {
+                let (px, tif) = (*px, *tif);
+                let fill = PerpsExecution::check_limit_fill(order, &self.book);
+                if fill.is_none() && tif == Tif::Ioc {
+                    order.status = OrderStatus::Canceled;
+                    return Some(FillResult {
+                        filled_sz: 0.0,
+                        fill_price: px,
+                        is_maker: false,
+                        order_status: OrderStatus::Canceled,
+                    });
+                }
+                fill
+            }
+            // Not simulated yet, as in the OHLC model
+            _ => None,
+        }
+    }
+}
+
+/// Turn a stop or take order whose trigger `price` has crossed into the market or
+/// limit order it becomes. Returns false while the trigger has not been reached.
+fn trigger(order: &mut Order, price: f64) -> bool {
+    let triggered = match &order.action {
+        Action::StopMarket { side, trigger, .. } | Action::StopLimit { side, trigger, .. } => {
+            match side {
+                Side::Buy => price >= *trigger,
+                Side::Sell => price <= *trigger,
+            }
+        }
+        Action::TakeMarket { side, trigger, .. } | Action::TakeLimit { side, trigger, .. } => {
+            match side {
+                Side::Buy => price <= *trigger,
+                Side::Sell => price >= *trigger,
+            }
+        }
+        _ => return true,
+    };
+    if !triggered {
+        return false;
+    }
+
+    order.action = match order.action {
+        Action::StopMarket { side, sz, .. } | Action::TakeMarket { side, sz, .. } => {
+            Action::Market { side, sz }
+        }
+        Action::StopLimit {
+            side, px, sz, tif, ..
+        }
+        | Action::TakeLimit {
+            side, px, sz, tif, ..
+        } => Action::Limit {
+            side,
+            px,
+            sz,
+            tif,
+            post_only: false,
+            reduce_only: false,
+        },
+        _ => unreachable!("only trigger orders reach here"),
+    };
+    order.status = OrderStatus::Triggered;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingest::{L2Event, OrderLevel};
+
+    fn book_event(bid: f64, ask: f64) -> MarketEvent {
+        MarketEvent::Book(L2Event {
+            ts_ms: 0,
+            levels: vec![
+                vec![OrderLevel { px: bid, sz: 1.0, n: 1 }],
+                vec![OrderLevel { px: ask, sz: 1.0, n: 1 }],
+            ],
+        })
+    }
+
+    fn order(action: Action) -> Order {
+        Order {
+            id: 1,
+            action,
+            created_at: 0,
+            filled_sz: 0.0,
+            status: OrderStatus::Pending,
+            tag: None,
+        }
+    }
+
+    #[test]
+    fn test_book_model_triggers_stop_on_mid() {
+        let portfolio = Portfolio::new(1000.0, FeeCalculator::new(0, 0, 0));
+        let mut model = BookFillModel::new();
+        let mut stop = order(Action::StopMarket {
+            side: Side::Sell,
+            trigger: 99.0,
+            sz: 0.5,
+        });
+
+        model.on_event(&book_event(100.0, 101.0));
+        assert!(model.try_fill(&mut stop, &portfolio).is_none());
+        assert!(matches!(stop.action, Action::StopMarket { .. }));
+
+        // Mid 98.5 is through the trigger: the stop becomes a market sell at the bid
+        model.on_event(&book_event(98.0, 99.0));
+        let fill = model.try_fill(&mut stop, &portfolio).unwrap();
+        assert_eq!(fill.fill_price, 98.0);
+        assert_eq!(fill.filled_sz, 0.5);
+        assert!(matches!(stop.action, Action::Market { .. }));
+    }
+
+    #[test]
+    fn test_book_model_cancels_unfilled_ioc() {
+        let portfolio = Portfolio::new(1000.0, FeeCalculator::new(0, 0, 0));
+        let mut model = BookFillModel::new();
+        model.on_event(&book_event(100.0, 101.0));
+
+        let mut ioc = order(Action::Limit {
+            side: Side::Buy,
+            px: 100.5,
+            sz: 1.0,
+            tif: Tif::Ioc,
+            post_only: false,
+            reduce_only: false,
+        });
+        let fill = model.try_fill(&mut ioc, &portfolio).unwrap();
+        assert_eq!(fill.order_status, OrderStatus::Canceled);
+        assert_eq!(fill.filled_sz, 0.0);
+    }
+}
@@ -0,0 +1,585 @@
+use anyhow::{bail, Context, Result};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::data::resample::BarBuilder;
+use crate::data::types::Candle;
+use crate::engine::logic::{StrategyContext, StrategyLogic};
+use crate::indicators2::{IndicatorEvaluator, IndicatorRegistry};
+use crate::ingest::{L2Event, TradePrint};
+use crate::orderbook::OrderBook;
+use crate::orders::types::{Action, Side, SizingRecord, Trade};
+use crate::strategy::expr::{insert_builtin_fields, Expr, PositionState};
+use crate::strategy::sizing::{apply_limits, round_to_lot, target_size, SizingInputs};
+use crate::strategy::{
+    compile_strategy_with, Action as StrategyAction, CompiledRule, CompiledStrategy, EvalState, Strategy,
+};
+use crate::util::interval_to_ms;
+
+const YEAR_MS: f64 = 365.0 * 24.0 * 60.0 * 60.0 * 1000.0;
+
+/// Book snapshots are re-evaluated only once the mid moves by this fraction
+const PRICE_CHANGE_THRESHOLD: f64 = 0.0001;
+
+/// [`StrategyLogic`] for a JSON [`Strategy`]: keeps its indicators up to date
+/// and turns the entry/exit rules into market orders tagged with the rule's
+/// name.
+///
+/// Indicators update on every bar, and on every book snapshot as a one-tick bar
+/// at the mid whose volume is that of the trade prints since the previous
+/// snapshot (0 without prints). Indicators on a higher timeframe update on bars
+/// built from those, once each has closed, and keep their value in between.
+/// Indicators on another coin read the bars or snapshots given with
+/// [`set_reference_candles`](Self::set_reference_candles) or
+/// [`set_reference_events`](Self::set_reference_events), up to the time of
+/// each event. Indicators on another indicator's output update after it, once
+/// it is warmed up, on a flat bar at its value.
+///
+/// Rules are evaluated once the indicators are warmed up; on books, only when
+/// the mid has moved. Entries wait out the cooldown, exits don't. At most one
+/// rule fires per evaluation: the first, by priority, whose condition holds and
+/// whose action has something to trade. In a position, exits come first, then
+/// entries add to it as far as the strategy's
+/// [`Pyramiding`](crate::strategy::Pyramiding) allows.
+///
+/// Buys are sized by the rule's [`Sizing`](crate::strategy::Sizing) policy,
+/// or the pyramiding's `add_sizing` for adds, and every order gets a
+/// [`SizingRecord`].
+pub struct JsonStrategy {
+    compiled: CompiledStrategy,
+    groups: Vec<IndicatorGroup>,
+    references: Vec<ReferenceSeries>,
+    /// Length of the instrument's bars, 0 if unknown
+    bar_ms: u64,
+    eval_state: EvalState,
+    parallel: bool,
+    updates: usize,
+    last_evaluated_price: Option<f64>,
+    /// Size and count of the trade prints since the last book snapshot
+    tick_volume: f64,
+    tick_trades: i64,
+    bars_per_year: Option<f64>,
+    /// Value of `updates` when the position was last entered or added to
+    last_entry_update: usize,
+    sizing_records: Vec<SizingRecord>,
+}
+
+impl JsonStrategy {
+    /// Compile `strategy` with the built-in indicators; `parallel` updates
+    /// indicators on the rayon pool
+    pub fn new(strategy: &Strategy, parallel: bool) -> Result<Self> {
+        Self::with_registry(strategy, &IndicatorRegistry::new(), parallel)
+    }
+
+    /// [`new`](Self::new) with the indicator types of `registry`, which may
+    /// include ones registered by the caller
+    pub fn with_registry(strategy: &Strategy, registry: &IndicatorRegistry, parallel: bool) -> Result<Self> {
+        let compiled = compile_strategy_with(strategy, registry)?;
+
+        let mut groups: Vec<IndicatorGroup> = Vec::new();
+        for ind in &compiled.indicators {
+            let evaluator = registry
+                .create(&ind.indicator_type, &ind.params)
+                .with_context(|| format!("Failed to create indicator: {}", ind.indicator_type))?;
+            // The primary output is also read as the id alone and as `value`
+            let primary = evaluator.outputs().first().copied();
+            let mut reads = Vec::new();
+            for output in &ind.outputs {
+                reads.push((format!("{}.{}", ind.id, output), output.clone()));
+                if primary == Some(output.as_str()) {
+                    reads.push((ind.id.clone(), output.clone()));
+                    if output != "value" {
+                        reads.push((format!("{}.value", ind.id), output.clone()));
+                    }
+                }
+            }
+            let indicator = GroupIndicator {
+                id: ind.id.clone(),
+                evaluator,
+                reads,
+                warmup: registry.get_lookback(&ind.indicator_type, &ind.params)?,
+                updates: 0,
+                source: None,
+            };
+            let index = match groups
+                .iter()
+                .position(|g| g.coin == ind.coin && g.timeframe == ind.timeframe)
+            {
+                Some(index) => index,
+                None => {
+                    groups.push(IndicatorGroup {
+                        coin: ind.coin.clone(),
+                        timeframe: ind.timeframe.clone(),
+                        bars: ind.timeframe.as_deref().map(BarBuilder::new).transpose()?,
+                        indicators: Vec::new(),
+                    });
+                    groups.len() - 1
+                }
+            };
+            groups[index].indicators.push(indicator);
+        }
+        // Indicators on prices first; the rest stay after their sources, which
+        // share their group
+        let source_of = |id: &str| {
+            let ind = compiled.indicators.iter().find(|c| c.id == id);
+            ind.and_then(|c| c.source.as_ref())
+        };
+        for group in &mut groups {
+            group.indicators.sort_by_key(|i| source_of(&i.id).is_some());
+            for index in 0..group.indicators.len() {
+                let Some(source) = source_of(&group.indicators[index].id) else {
+                    continue;
+                };
+                let source_index = group
+                    .indicators
+                    .iter()
+                    .position(|i| i.id == source.indicator)
+                    .with_context(|| format!("Source '{}' is on another coin or timeframe", source.indicator))?;
+                group.indicators[index].source = Some((source_index, source.output.clone()));
+            }
+        }
+        let references = compiled
+            .reference_coins()
+            .into_iter()
+            .map(|coin| ReferenceSeries {
+                coin,
+                bars: None,
+                bar_ms: 0,
+                next: 0,
+            })
+            .collect();
+
+        let eval_state = EvalState::with_depths(compiled.history_depths.clone());
+        let bar_ms = interval_to_ms(&compiled.instrument.timeframe).unwrap_or(0);
+        let bars_per_year = (bar_ms > 0).then(|| YEAR_MS / bar_ms as f64);
+        Ok(Self {
+            compiled,
+            groups,
+            references,
+            bar_ms,
+            eval_state,
+            parallel,
+            updates: 0,
+            last_evaluated_price: None,
+            tick_volume: 0.0,
+            tick_trades: 0,
+            bars_per_year,
+            last_entry_update: 0,
+            sizing_records: Vec::new(),
+        })
+    }
+
+    /// Coins other than the traded one that indicators read. Each needs its
+    /// market data before the run, from
+    /// [`set_reference_candles`](Self::set_reference_candles) or
+    /// [`set_reference_events`](Self::set_reference_events).
+    pub fn reference_coins(&self) -> Vec<String> {
+        self.compiled.reference_coins()
+    }
+
+    /// Candles of the instrument's timeframe for `coin`, sorted by time (see
+    /// [`align_reference_candles`](crate::data::align_reference_candles))
+    pub fn set_reference_candles(&mut self, coin: &str, candles: Vec<Candle>) -> Result<()> {
+        let bar_ms = self.bar_ms;
+        let reference = self.reference(coin)?;
+        reference.bars = Some(candles);
+        reference.bar_ms = bar_ms;
+        Ok(())
+    }
+
+    /// Book snapshots for `coin`, sorted by time; indicators see their mids
+    /// as one-tick bars
+    pub fn set_reference_events(&mut self, coin: &str, events: &[L2Event]) -> Result<()> {
+        let mut book = OrderBook::new();
+        let bars = events
+            .iter()
+            .filter_map(|event| {
+                book.apply_snapshot(&event.levels);
+                Some(tick_bar(coin, event.ts_ms, book.mid_price()?))
+            })
+            .collect();
+        let reference = self.reference(coin)?;
+        reference.bars = Some(bars);
+        reference.bar_ms = 0;
+        Ok(())
+    }
+
+    fn reference(&mut self, coin: &str) -> Result<&mut ReferenceSeries> {
+        match self.references.iter_mut().find(|r| r.coin == coin) {
+            Some(reference) => Ok(reference),
+            None => bail!("The strategy has no indicators on {}", coin),
+        }
+    }
+
+    /// How each order placed so far was sized; leaves the list empty
+    pub fn take_sizing_records(&mut self) -> Vec<SizingRecord> {
+        std::mem::take(&mut self.sizing_records)
+    }
+
+    /// Order of the first of `rules` whose condition holds and that has
+    /// something to trade, and how it was sized. `add` is the number of the
+    /// pyramiding add the order would be.
+    fn first_order(
+        &self,
+        rules: &[CompiledRule],
+        add: Option<usize>,
+        bar: &Candle,
+        ctx: &StrategyContext,
+        values: &HashMap<String, f64>,
+    ) -> Option<(Action, SizingRecord)> {
+        rules
+            .iter()
+            .filter(|rule| self.eval_state.evaluate(&rule.condition, values))
+            .find_map(|rule| self.order_for(rule, add, bar, ctx, values))
+    }
+
+    /// Number of the add entries would make to the open position, if the
+    /// pyramiding allows one now. Entries buy, so only longs are added to.
+    fn next_add(&self, price: f64, ctx: &StrategyContext) -> Option<usize> {
+        let pyramiding = self.compiled.pyramiding.as_ref()?;
+        let trip = ctx.open_trip().filter(|trip| trip.side == "LONG")?;
+        let moved_pct = (price - trip.last_entry_price).abs() / trip.last_entry_price * 100.0;
+        let allowed = !ctx.in_cooldown()
+            && trip.entries < pyramiding.max_entries
+            && self.updates - self.last_entry_update >= pyramiding.min_bars
+            && moved_pct >= pyramiding.min_price_move_pct;
+        allowed.then_some(trip.entries)
+    }
+
+    /// Market order for `rule` at the bar close and how it was sized, or `None`
+    /// if there is nothing to trade (e.g. closing when flat, or a size that is
+    /// zero after rounding to the lot size)
+    fn order_for(
+        &self,
+        rule: &CompiledRule,
+        add: Option<usize>,
+        bar: &Candle,
+        ctx: &StrategyContext,
+        values: &HashMap<String, f64>,
+    ) -> Option<(Action, SizingRecord)> {
+        let price = bar.close;
+        let position = ctx.position();
+        let equity = ctx.portfolio().total_equity(ctx.coin(), price);
+        let round_trips: Vec<f64> = ctx.round_trips().iter().map(|t| t.return_pct / 100.0).collect();
+        let inputs = SizingInputs {
+            price,
+            equity,
+            position,
+            round_trips: &round_trips,
+            bars_per_year: self.bars_per_year,
+        };
+
+        let mut used = BTreeMap::new();
+        let mut adjustments = Vec::new();
+        if let Some(add) = add {
+            used.insert("add".to_string(), add as f64);
+        }
+        let (side, policy, target_size, sz) = match &rule.action {
+            StrategyAction::Buy { sizing, .. } => {
+                let sizing = match (add, self.compiled.pyramiding.as_ref()) {
+                    (Some(add), Some(pyramiding)) if !pyramiding.add_sizing.is_empty() => {
+                        let adds = &pyramiding.add_sizing;
+                        &adds[(add - 1).min(adds.len() - 1)]
+                    }
+                    _ => sizing.as_ref().expect("compiled buys carry a sizing policy"),
+                };
+                let eval = |expr: &Expr| self.eval_state.evaluate_expr(expr, values);
+                let (target, policy_inputs) = target_size(sizing, &inputs, &eval)?;
+                let (sz, limits) = apply_limits(target, &inputs, &self.compiled.instrument);
+                used.extend(policy_inputs);
+                adjustments = limits;
+                (Side::Buy, sizing.name(), target, sz)
+            }
+            StrategyAction::Sell { size_pct } => {
+                let target = position.abs() * size_pct / 100.0;
+                used.insert("size_pct".to_string(), *size_pct);
+                let sz = match self.compiled.instrument.sz_decimals {
+                    // A full exit is never rounded
+                    Some(decimals) if target < position.abs() => round_to_lot(target, decimals),
+                    _ => target,
+                };
+                if sz != target {
+                    adjustments.push("rounded down to the lot size".to_string());
+                }
+                (Side::Sell, "position_pct", target, sz)
+            }
+            StrategyAction::Close => {
+                if position.abs() < 1e-10 {
+                    return None;
+                }
+                let side = if position > 0.0 { Side::Sell } else { Side::Buy };
+                (side, "close", position.abs(), position.abs())
+            }
+        };
+
+        if sz <= 0.0 || !sz.is_finite() {
+            return None;
+        }
+        let after = match side {
+            Side::Buy => position + sz,
+            Side::Sell => position - sz,
+        };
+        let record = SizingRecord {
+            order_id: 0,
+            timestamp: ctx.ts_ms(),
+            rule: rule.name.clone(),
+            policy: policy.to_string(),
+            price,
+            equity,
+            target_size,
+            size: sz,
+            notional: sz * price,
+            leverage: if equity > 0.0 { after.abs() * price / equity } else { 0.0 },
+            inputs: used,
+            adjustments,
+        };
+        Some((Action::Market { side, sz }, record))
+    }
+
+    /// Update the traded coin's indicators with `bar`, which covers `bar_ms`
+    /// (0 for a tick), and other coins' with their bars up to its open time
+    fn update_indicators(&mut self, bar: &Candle, bar_ms: u64) -> Result<()> {
+        for group in self.groups.iter_mut().filter(|g| g.coin.is_none()) {
+            group.update(bar, bar_ms, self.parallel)?;
+        }
+        for reference in &mut self.references {
+            let Some(bars) = &reference.bars else {
+                continue;
+            };
+            let count = bars[reference.next..]
+                .iter()
+                .take_while(|b| b.time_open <= bar.time_open)
+                .count();
+            for reference_bar in &bars[reference.next..reference.next + count] {
+                let coin = Some(&reference.coin);
+                for group in self.groups.iter_mut().filter(|g| g.coin.as_ref() == coin) {
+                    group.update(reference_bar, reference.bar_ms, self.parallel)?;
+                }
+            }
+            reference.next += count;
+        }
+        self.updates += 1;
+        Ok(())
+    }
+
+    /// Evaluate the entry rules when flat (outside the cooldown), or the exit
+    /// rules and then any pyramiding add when in a position, and place the
+    /// order of the first that fires.
+    ///
+    /// Once indicators are warm, every evaluation step feeds the history that
+    /// offsets read; rules only run once that history is deep enough.
+    fn evaluate(&mut self, bar: &Candle, ctx: &mut StrategyContext) -> Result<()> {
+        if self.updates <= self.compiled.indicator_lookback() {
+            return Ok(());
+        }
+        let mut indicator_values = get_indicator_values(self.groups.iter().flat_map(|g| &g.indicators))?;
+        let position = ctx.portfolio().positions.get(ctx.coin()).map(|p| PositionState {
+            size: p.size,
+            entry_price: p.entry_price,
+            entries: ctx.open_trip().map_or(0, |trip| trip.entries),
+            bars_since_entry: self.updates - self.last_entry_update,
+        });
+        insert_builtin_fields(&mut indicator_values, bar, position.as_ref());
+        let is_flat = ctx.position().abs() < 1e-10;
+
+        let order = if self.updates <= self.compiled.lookback() {
+            None
+        } else if is_flat {
+            let entries = if ctx.in_cooldown() { &[][..] } else { &self.compiled.entries };
+            self.first_order(entries, None, bar, ctx, &indicator_values)
+        } else {
+            self.first_order(&self.compiled.exits, None, bar, ctx, &indicator_values).or_else(|| {
+                let add = self.next_add(bar.close, ctx)?;
+                self.first_order(&self.compiled.entries, Some(add), bar, ctx, &indicator_values)
+            })
+        };
+        if let Some((action, mut record)) = order {
+            record.order_id = ctx.place_tagged(action, record.rule.as_str());
+            self.sizing_records.push(record);
+        }
+
+        // Temporal conditions count every bar, whichever rules are active
+        for rule in self.compiled.entries.iter().chain(&self.compiled.exits) {
+            self.eval_state.observe(&rule.condition, &indicator_values);
+        }
+        // Update eval state with current values for crossovers and offsets
+        self.eval_state.update(&indicator_values);
+        self.last_evaluated_price = Some(bar.close);
+        Ok(())
+    }
+}
+
+impl StrategyLogic for JsonStrategy {
+    fn warmup(&self) -> usize {
+        self.compiled.lookback()
+    }
+
+    fn on_start(&mut self, _ctx: &mut StrategyContext) -> Result<()> {
+        if let Some(reference) = self.references.iter().find(|r| r.bars.is_none()) {
+            bail!("The strategy has indicators on {} but no market data for it was given", reference.coin);
+        }
+        Ok(())
+    }
+
+    fn on_bar(&mut self, bar: &Candle, ctx: &mut StrategyContext) -> Result<()> {
+        self.update_indicators(bar, self.bar_ms)?;
+        self.evaluate(bar, ctx)
+    }
+
+    fn on_fill(&mut self, fill: &Trade, ctx: &mut StrategyContext) -> Result<()> {
+        if fill.side == "BUY" && ctx.position() > 0.0 {
+            self.last_entry_update = self.updates;
+        }
+        Ok(())
+    }
+
+    fn on_book(&mut self, _book: &L2Event, ctx: &mut StrategyContext) -> Result<()> {
+        let Some(price) = ctx.mark_price() else {
+            return Ok(());
+        };
+        let mut bar = tick_bar(ctx.coin(), ctx.ts_ms(), price);
+        bar.volume = std::mem::take(&mut self.tick_volume);
+        bar.num_trades = std::mem::take(&mut self.tick_trades);
+        self.update_indicators(&bar, 0)?;
+
+        let moved = self.last_evaluated_price.is_none_or(|last| {
+            (price - last).abs() / last.max(1.0) > PRICE_CHANGE_THRESHOLD
+        });
+        if moved {
+            self.evaluate(&bar, ctx)?;
+        }
+        Ok(())
+    }
+
+    fn on_trade(&mut self, trade: &TradePrint, _ctx: &mut StrategyContext) -> Result<()> {
+        if trade.sz.is_finite() {
+            self.tick_volume += trade.sz.abs();
+            self.tick_trades += 1;
+        }
+        Ok(())
+    }
+}
+
+/// One-tick bar at `price`, used to drive indicators from book snapshots
+fn tick_bar(coin: &str, ts_ms: u64, price: f64) -> Candle {
+    Candle {
+        time_open: ts_ms,
+        time_close: ts_ms,
+        coin: coin.to_string(),
+        interval: "tick".to_string(),
+        open: price,
+        high: price,
+        low: price,
+        close: price,
+        volume: 0.0,
+        num_trades: 0,
+    }
+}
+
+/// Indicators on the same coin and timeframe, which update together
+struct IndicatorGroup {
+    /// `None` for the traded coin
+    coin: Option<String>,
+    /// `None` for the instrument's timeframe
+    timeframe: Option<String>,
+    /// Builds the higher-timeframe bars
+    bars: Option<BarBuilder>,
+    /// Indicators on prices, then those on other indicators' outputs, each
+    /// after its source
+    indicators: Vec<GroupIndicator>,
+}
+
+struct GroupIndicator {
+    id: String,
+    evaluator: Box<dyn IndicatorEvaluator>,
+    /// Names the strategy reads the indicator's outputs under, and the outputs
+    reads: Vec<(String, String)>,
+    /// Updates before its output can feed other indicators
+    warmup: usize,
+    updates: usize,
+    /// Index of the indicator in the group whose output it is computed on,
+    /// and the output
+    source: Option<(usize, String)>,
+}
+
+impl GroupIndicator {
+    fn update(&mut self, bar: &Candle) -> Result<()> {
+        self.updates += 1;
+        self.evaluator.update(bar)
+    }
+}
+
+impl IndicatorGroup {
+    /// Update with a bar of the coin covering `bar_ms`, or with the
+    /// higher-timeframe bars it closes
+    fn update(&mut self, bar: &Candle, bar_ms: u64, parallel: bool) -> Result<()> {
+        let Some(builder) = &mut self.bars else {
+            return update_all(&mut self.indicators, bar, parallel);
+        };
+        for closed in builder.push(bar, bar_ms) {
+            update_all(&mut self.indicators, &closed, parallel)?;
+        }
+        Ok(())
+    }
+}
+
+/// Market data of another coin that indicators read
+struct ReferenceSeries {
+    coin: String,
+    /// Bars, or one-tick bars at the mid of book snapshots; `None` until given
+    bars: Option<Vec<Candle>>,
+    /// Length of the bars, 0 for ticks
+    bar_ms: u64,
+    /// First bar not read yet
+    next: usize,
+}
+
+/// Update the indicators on prices with `bar`, then each indicator on another
+/// indicator's output with a flat bar at that output, once it is warmed up
+fn update_all(indicators: &mut [GroupIndicator], bar: &Candle, parallel: bool) -> Result<()> {
+    let chained = indicators
+        .iter()
+        .position(|i| i.source.is_some())
+        .unwrap_or(indicators.len());
+    let on_prices = &mut indicators[..chained];
+    if parallel && on_prices.len() > 1 {
+        on_prices.par_iter_mut().try_for_each(|indicator| indicator.update(bar))?;
+    } else {
+        for indicator in on_prices {
+            indicator.update(bar)?;
+        }
+    }
+
+    for index in chained..indicators.len() {
+        let (before, rest) = indicators.split_at_mut(index);
+        let Some((source, output)) = &rest[0].source else {
+            continue;
+        };
+        let source = &before[*source];
+        if source.updates < source.warmup {
+            continue;
+        }
+        let value = source.evaluator.value(output)?;
+        rest[0].update(&Candle {
+            open: value,
+            high: value,
+            low: value,
+            close: value,
+            ..bar.clone()
+        })?;
+    }
+    Ok(())
+}
+
+/// Values of the outputs the strategy reads, by the names it reads them under
+fn get_indicator_values<'a>(
+    indicators: impl Iterator<Item = &'a GroupIndicator>,
+) -> Result<HashMap<String, f64>> {
+    let mut values = HashMap::new();
+    for indicator in indicators {
+        for (name, output) in &indicator.reads {
+            values.insert(name.clone(), indicator.evaluator.value(output)?);
+        }
+    }
+    Ok(values)
+}
@@ -0,0 +1,184 @@
+use crate::orders::types::{RoundTrip, Trade};
+
+const MIN_SIZE: f64 = 1e-10;
+
+/// Builds [`RoundTrip`]s from fills: a trip opens when the position leaves
+/// zero and closes when it returns there. A fill that flips the position
+/// closes the trip and opens one on the other side with the rest.
+#[derive(Debug, Default)]
+pub struct RoundTripLedger {
+    closed: Vec<RoundTrip>,
+    open: Option<RoundTrip>,
+}
+
+impl RoundTripLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Closed round trips, oldest first
+    pub fn closed(&self) -> &[RoundTrip] {
+        &self.closed
+    }
+
+    /// The position's round trip, if it is open
+    pub fn open(&self) -> Option<&RoundTrip> {
+        self.open.as_ref()
+    }
+
+    /// Apply a fill
+    pub fn record(&mut self, fill: &Trade) {
+        let direction = if fill.side == "BUY" { 1.0 } else { -1.0 };
+        let mut size = fill.size;
+
+        if let Some(trip) = &mut self.open {
+            let trip_direction = if trip.side == "LONG" { 1.0 } else { -1.0 };
+            if direction == trip_direction {
+                add(trip, fill, size, fill.fee);
+                return;
+            }
+
+            let closed = size.min(trip.size);
+            let fee = fill.fee * closed / fill.size;
+            trip.exit_price = (trip.exit_price * trip.exited_size + closed * fill.price)
+                / (trip.exited_size + closed);
+            trip.exited_size += closed;
+            trip.exits += 1;
+            trip.size -= closed;
+            trip.fees += fee;
+            trip.pnl += closed * (fill.price - trip.entry_price) * trip_direction - fee;
+            trip.return_pct = trip.pnl / trip.cost * 100.0;
+            size -= closed;
+            if trip.size > MIN_SIZE {
+                return;
+            }
+            trip.size = 0.0;
+            trip.close_ts = Some(fill.timestamp);
+            self.closed.extend(self.open.take());
+        }
+
+        if size > MIN_SIZE {
+            let mut trip = RoundTrip {
+                symbol: fill.symbol.clone(),
+                side: if direction > 0.0 { "LONG" } else { "SHORT" }.to_string(),
+                open_ts: fill.timestamp,
+                close_ts: None,
+                entries: 0,
+                exits: 0,
+                size: 0.0,
+                max_size: 0.0,
+                exited_size: 0.0,
+                entry_price: 0.0,
+                exit_price: 0.0,
+                last_entry_ts: fill.timestamp,
+                last_entry_price: fill.price,
+                cost: 0.0,
+                fees: 0.0,
+                pnl: 0.0,
+                return_pct: 0.0,
+            };
+            add(&mut trip, fill, size, fill.fee * size / fill.size);
+            self.open = Some(trip);
+        }
+    }
+
+    /// All round trips, the open one last
+    pub fn into_round_trips(self) -> Vec<RoundTrip> {
+        let mut trips = self.closed;
+        trips.extend(self.open);
+        trips
+    }
+}
+
+/// Add `size` of `fill` to the position at its average cost
+fn add(trip: &mut RoundTrip, fill: &Trade, size: f64, fee: f64) {
+    trip.entry_price = (trip.entry_price * trip.size + fill.price * size) / (trip.size + size);
+    trip.entries += 1;
+    trip.size += size;
+    trip.max_size = trip.max_size.max(trip.size);
+    trip.last_entry_ts = fill.timestamp;
+    trip.last_entry_price = fill.price;
+    trip.cost += size * fill.price;
+    trip.fees += fee;
+    trip.pnl -= fee;
+    trip.return_pct = trip.pnl / trip.cost * 100.0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(timestamp: u64, side: &str, size: f64, price: f64) -> Trade {
+        Trade {
+            timestamp,
+            symbol: "BTC".to_string(),
+            side: side.to_string(),
+            size,
+            price,
+            fee: size * price * 0.001,
+            order_id: timestamp,
+            tag: None,
+        }
+    }
+
+    #[test]
+    fn test_pyramid_and_scale_out() {
+        let mut ledger = RoundTripLedger::new();
+        ledger.record(&fill(1, "BUY", 1.0, 100.0));
+        ledger.record(&fill(2, "BUY", 1.0, 110.0));
+        let trip = ledger.open().unwrap();
+        assert_eq!((trip.entries, trip.size), (2, 2.0));
+        assert_eq!(trip.entry_price, 105.0);
+        assert_eq!(trip.last_entry_price, 110.0);
+
+        // Half out at 120, the rest at 100
+        ledger.record(&fill(3, "SELL", 1.0, 120.0));
+        let trip = ledger.open().unwrap();
+        assert_eq!((trip.exits, trip.size), (1, 1.0));
+        assert!((trip.pnl - (15.0 - 0.1 - 0.11 - 0.12)).abs() < 1e-9);
+        ledger.record(&fill(4, "SELL", 1.0, 100.0));
+        assert!(ledger.open().is_none());
+
+        let trip = &ledger.closed()[0];
+        assert_eq!(trip.close_ts, Some(4));
+        assert_eq!((trip.entries, trip.exits, trip.max_size), (2, 2, 2.0));
+        assert_eq!(trip.exit_price, 110.0);
+        assert_eq!(trip.exited_size, 2.0);
+        let fees = 0.1 + 0.11 + 0.12 + 0.1;
+        assert!((trip.fees - fees).abs() < 1e-9);
+        assert!((trip.pnl - (10.0 - fees)).abs() < 1e-9);
+        assert!((trip.return_pct - (10.0 - fees) / 210.0 * 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_add_after_partial_exit() {
+        let mut ledger = RoundTripLedger::new();
+        ledger.record(&fill(1, "BUY", 2.0, 100.0));
+        ledger.record(&fill(2, "SELL", 1.0, 110.0));
+        ledger.record(&fill(3, "BUY", 1.0, 100.0));
+        ledger.record(&fill(4, "SELL", 2.0, 120.0));
+
+        let trip = &ledger.closed()[0];
+        assert_eq!((trip.entries, trip.exits, trip.max_size), (2, 2, 2.0));
+        assert_eq!(trip.exited_size, 3.0);
+        assert!((trip.exit_price - 350.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_flip_opens_the_other_side() {
+        let mut ledger = RoundTripLedger::new();
+        ledger.record(&fill(1, "BUY", 1.0, 100.0));
+        ledger.record(&fill(2, "SELL", 3.0, 90.0));
+
+        let closed = &ledger.closed()[0];
+        assert!((closed.pnl - (-10.0 - 0.1 - 0.09)).abs() < 1e-9);
+        let open = ledger.open().unwrap();
+        assert_eq!(open.side, "SHORT");
+        assert_eq!((open.size, open.entry_price), (2.0, 90.0));
+        assert!((open.fees - 0.18).abs() < 1e-9);
+
+        let trips = ledger.into_round_trips();
+        assert_eq!(trips.len(), 2);
+        assert_eq!(trips[1].close_ts, None);
+    }
+}
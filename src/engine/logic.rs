@@ -0,0 +1,232 @@
+use anyhow::{bail, Result};
+
+use crate::data::types::Candle;
+use crate::ingest::{L2Event, TradePrint};
+use crate::engine::ledger::RoundTripLedger;
+use crate::orders::types::{Action, Order, OrderStatus, RoundTrip, Trade};
+use crate::perps::funding::FundingPoint;
+use crate::portfolio::Portfolio;
+
+/// Trading logic driven by the backtest engine.
+///
+/// The engine calls [`on_start`](StrategyLogic::on_start) once, then
+/// [`on_bar`](StrategyLogic::on_bar) for every candle or
+/// [`on_book`](StrategyLogic::on_book) for every book snapshot, followed by
+/// [`on_fill`](StrategyLogic::on_fill) for each fill,
+/// [`on_trade`](StrategyLogic::on_trade) for each trade print and
+/// [`on_funding`](StrategyLogic::on_funding) for each settlement. Orders are
+/// placed, amended and canceled through the [`StrategyContext`]; new orders
+/// are offered to the fill model on the same event.
+///
+/// [`JsonStrategy`](crate::engine::JsonStrategy) implements this for JSON strategies.
+pub trait StrategyLogic {
+    /// Bars or snapshots the logic needs before it can trade. The engine fills
+    /// no orders and records no equity until that many have been seen, and
+    /// fails the backtest if the feed is shorter.
+    fn warmup(&self) -> usize {
+        0
+    }
+
+    /// Called once before the first event; there is no mark price yet
+    fn on_start(&mut self, _ctx: &mut StrategyContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for every candle
+    fn on_bar(&mut self, bar: &Candle, ctx: &mut StrategyContext) -> Result<()>;
+
+    /// Called for every book snapshot once the book has a mid price (the
+    /// context's mark price). Does nothing by default.
+    fn on_book(&mut self, _book: &L2Event, _ctx: &mut StrategyContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for every trade print in the feed, once the market has a mark
+    /// price. Prints don't fill orders. Does nothing by default.
+    fn on_trade(&mut self, _trade: &TradePrint, _ctx: &mut StrategyContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after each fill has been applied to the portfolio
+    fn on_fill(&mut self, _fill: &Trade, _ctx: &mut StrategyContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after a funding settlement; `payment` is the cash paid (negative
+    /// when received)
+    fn on_funding(
+        &mut self,
+        _point: &FundingPoint,
+        _payment: f64,
+        _ctx: &mut StrategyContext,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// View of the simulation handed to [`StrategyLogic`] callbacks, and the way
+/// to manage orders
+pub struct StrategyContext<'a> {
+    pub(crate) coin: &'a str,
+    pub(crate) ts_ms: u64,
+    pub(crate) mark_price: Option<f64>,
+    pub(crate) in_cooldown: bool,
+    pub(crate) portfolio: &'a Portfolio,
+    pub(crate) ledger: &'a RoundTripLedger,
+    pub(crate) orders: &'a mut Vec<Order>,
+    pub(crate) next_order_id: &'a mut u64,
+}
+
+impl StrategyContext<'_> {
+    /// Coin being traded (the portfolio symbol)
+    pub fn coin(&self) -> &str {
+        self.coin
+    }
+
+    /// Timestamp of the current event
+    pub fn ts_ms(&self) -> u64 {
+        self.ts_ms
+    }
+
+    /// Current mark price: the bar close or the book mid
+    pub fn mark_price(&self) -> Option<f64> {
+        self.mark_price
+    }
+
+    /// True while `SimConfig::trade_cooldown_ms` has not passed since the last fill.
+    /// The engine does not enforce it; logic should hold back new entries.
+    pub fn in_cooldown(&self) -> bool {
+        self.in_cooldown
+    }
+
+    pub fn portfolio(&self) -> &Portfolio {
+        self.portfolio
+    }
+
+    /// Signed position in the traded coin
+    pub fn position(&self) -> f64 {
+        self.portfolio.get_position(self.coin)
+    }
+
+    /// Closed round trips, oldest first
+    pub fn round_trips(&self) -> &[RoundTrip] {
+        self.ledger.closed()
+    }
+
+    /// Round trip of the open position, with its entries and exits so far
+    pub fn open_trip(&self) -> Option<&RoundTrip> {
+        self.ledger.open()
+    }
+
+    /// Orders that are still working, in placement order
+    pub fn open_orders(&self) -> &[Order] {
+        self.orders
+    }
+
+    /// Place an order, returning its id
+    pub fn place(&mut self, action: Action) -> u64 {
+        self.push(action, None)
+    }
+
+    /// Place an order labeled with `tag`, which its fills carry in [`Trade::tag`]
+    pub fn place_tagged(&mut self, action: Action, tag: impl Into<String>) -> u64 {
+        self.push(action, Some(tag.into()))
+    }
+
+    fn push(&mut self, action: Action, tag: Option<String>) -> u64 {
+        let id = *self.next_order_id;
+        *self.next_order_id += 1;
+        self.orders.push(Order {
+            id,
+            action,
+            created_at: self.ts_ms,
+            filled_sz: 0.0,
+            status: OrderStatus::Pending,
+            tag,
+        });
+        id
+    }
+
+    /// Replace the action of open order `id`. The new action's size is the
+    /// remaining size; what has already filled and the tag are kept.
+    pub fn amend(&mut self, id: u64, action: Action) -> Result<()> {
+        let Some(order) = self.orders.iter_mut().find(|o| o.id == id) else {
+            bail!("No open order with id {}", id);
+        };
+        order.action = action;
+        order.status = if order.filled_sz > 0.0 {
+            OrderStatus::PartiallyFilled
+        } else {
+            OrderStatus::Pending
+        };
+        Ok(())
+    }
+
+    /// Cancel open order `id`; returns false if it is not open
+    pub fn cancel(&mut self, id: u64) -> bool {
+        let len = self.orders.len();
+        self.orders.retain(|o| o.id != id);
+        self.orders.len() != len
+    }
+
+    /// Cancel every open order
+    pub fn cancel_all(&mut self) {
+        self.orders.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fees::FeeCalculator;
+    use crate::orders::types::{Side, Tif};
+
+    fn limit(px: f64, sz: f64) -> Action {
+        Action::Limit {
+            side: Side::Buy,
+            px,
+            sz,
+            tif: Tif::Gtc,
+            post_only: false,
+            reduce_only: false,
+        }
+    }
+
+    #[test]
+    fn test_place_amend_cancel() {
+        let portfolio = Portfolio::new(1000.0, FeeCalculator::new(0, 0, 0));
+        let mut orders = Vec::new();
+        let ledger = RoundTripLedger::new();
+        let mut next_order_id = 1;
+        let mut ctx = StrategyContext {
+            coin: "BTC",
+            ts_ms: 5,
+            mark_price: Some(100.0),
+            in_cooldown: false,
+            portfolio: &portfolio,
+            ledger: &ledger,
+            orders: &mut orders,
+            next_order_id: &mut next_order_id,
+        };
+
+        let first = ctx.place(limit(99.0, 1.0));
+        let second = ctx.place(limit(98.0, 1.0));
+        assert_eq!((first, second), (1, 2));
+        assert_eq!(ctx.open_orders()[1].created_at, 5);
+        assert_eq!(ctx.open_orders()[1].tag, None);
+
+        ctx.amend(first, limit(99.5, 2.0)).unwrap();
+        assert!(matches!(ctx.open_orders()[0].action, Action::Limit { px, sz, .. } if px == 99.5 && sz == 2.0));
+        assert!(ctx.amend(7, limit(1.0, 1.0)).is_err());
+
+        assert!(ctx.cancel(first));
+        assert!(!ctx.cancel(first));
+        assert_eq!(ctx.open_orders().len(), 1);
+        ctx.cancel_all();
+        assert!(ctx.open_orders().is_empty());
+        assert_eq!(ctx.place(limit(97.0, 1.0)), 3);
+        let tagged = ctx.place_tagged(limit(96.0, 1.0), "dip");
+        ctx.amend(tagged, limit(95.0, 1.0)).unwrap();
+        assert_eq!(ctx.open_orders()[1].tag.as_deref(), Some("dip"));
+    }
+}
@@ -0,0 +1,426 @@
+//! Event-driven backtest core shared by the candle and L2 modes.
+//!
+//! A [`DataFeed`] supplies time-ordered [`MarketEvent`]s and a [`FillModel`]
+//! decides how orders execute against them: [`OhlcFillModel`] for candles,
+//! [`BookFillModel`] for L2 snapshots. Trading decisions come from a
+//! [`StrategyLogic`], either a JSON strategy ([`JsonStrategy`]) or any Rust
+//! implementation. Cooldowns, funding, equity recording and metrics live here
+//! once, so both modes behave the same. [`crate::orders::simulate`] and
+//! [`crate::perps::PerpsEngine`] are thin wrappers.
+
+pub mod feed;
+pub mod fill;
+mod json;
+pub mod ledger;
+pub mod logic;
+
+pub use feed::{DataFeed, MarketEvent, MarketFeed};
+pub use fill::{BookFillModel, FillModel, OhlcFillModel};
+pub use json::JsonStrategy;
+pub use ledger::RoundTripLedger;
+pub use logic::{StrategyContext, StrategyLogic};
+
+use anyhow::Result;
+
+use crate::fees::FeeCalculator;
+use crate::metrics::summarize;
+use crate::orders::fills::FillResult;
+use crate::orders::types::{Action, EquityPoint, Order, OrderStatus, SimConfig, SimResult, Trade};
+use crate::perps::trade_utils::side_to_string;
+use crate::portfolio::Portfolio;
+
+const DEFAULT_ORDERS_CAPACITY: usize = 100;
+const MIN_FILL_SIZE: f64 = 1e-10;
+/// Equity is recorded on every bar, but at most this often for book snapshots
+const EQUITY_RECORDING_INTERVAL_MS: u64 = 60 * 1000;
+
+/// Open orders the engine lends to [`StrategyLogic`] callbacks
+struct OpenOrders {
+    orders: Vec<Order>,
+    next_order_id: u64,
+}
+
+impl OpenOrders {
+    fn context<'a>(
+        &'a mut self,
+        coin: &'a str,
+        ts_ms: u64,
+        mark_price: Option<f64>,
+        in_cooldown: bool,
+        portfolio: &'a Portfolio,
+        ledger: &'a RoundTripLedger,
+    ) -> StrategyContext<'a> {
+        StrategyContext {
+            coin,
+            ts_ms,
+            mark_price,
+            in_cooldown,
+            portfolio,
+            ledger,
+            orders: &mut self.orders,
+            next_order_id: &mut self.next_order_id,
+        }
+    }
+}
+
+/// Run `logic` over `feed`, executing its orders with `fill_model`.
+///
+/// Per market event: the fill model absorbs it, the logic sees the bar or book
+/// snapshot, open orders are offered to the fill model (once the logic's
+/// warmup is over) and fills are reported back, then equity is recorded.
+/// Funding events settle against the open position at the mark price, and
+/// trade prints are only shown to the logic. Fills are also grouped into round
+/// trips, which the logic can read back from the context and which end up in
+/// the result.
+pub fn run_backtest(
+    feed: &mut dyn DataFeed,
+    fill_model: &mut dyn FillModel,
+    logic: &mut dyn StrategyLogic,
+    config: &SimConfig,
+) -> Result<SimResult> {
+    let coin = feed.coin().to_string();
+    let warmup = logic.warmup();
+
+    let fee_calc = FeeCalculator::new(
+        config.maker_fee_bps,
+        config.taker_fee_bps,
+        config.slippage_bps,
+    );
+    let mut portfolio = Portfolio::new(config.initial_capital, fee_calc.clone());
+
+    let mut orders = OpenOrders {
+        orders: Vec::with_capacity(DEFAULT_ORDERS_CAPACITY),
+        next_order_id: 1,
+    };
+    let mut ledger = RoundTripLedger::new();
+    let mut trades = Vec::new();
+    let mut equity_curve = Vec::new();
+
+    let mut updates = 0usize;
+    let mut last_fill_ts: Option<u64> = None;
+    let mut last_equity_ts: Option<u64> = None;
+
+    logic.on_start(&mut orders.context(&coin, 0, None, false, &portfolio, &ledger))?;
+
+    while let Some(event) = feed.next_event() {
+        let ts_ms = event.ts_ms();
+        fill_model.on_event(&event);
+        let Some(price) = fill_model.mark_price() else {
+            continue;
+        };
+        let in_cooldown = cooldown_active(config, last_fill_ts, ts_ms);
+
+        if let MarketEvent::Funding(point) = &event {
+            let payment = settle_funding(&mut portfolio, &coin, price, point.rate);
+            let mut ctx = orders.context(&coin, ts_ms, Some(price), in_cooldown, &portfolio, &ledger);
+            logic.on_funding(point, payment, &mut ctx)?;
+            continue;
+        }
+        if let MarketEvent::Trade(trade) = &event {
+            let mut ctx = orders.context(&coin, ts_ms, Some(price), in_cooldown, &portfolio, &ledger);
+            logic.on_trade(trade, &mut ctx)?;
+            continue;
+        }
+        let mut ctx = orders.context(&coin, ts_ms, Some(price), in_cooldown, &portfolio, &ledger);
+        match &event {
+            MarketEvent::Bar(candle) => logic.on_bar(candle, &mut ctx)?,
+            MarketEvent::Book(snapshot) => logic.on_book(snapshot, &mut ctx)?,
+            MarketEvent::Trade(_) | MarketEvent::Funding(_) => continue,
+        }
+        let is_tick = matches!(event, MarketEvent::Book(_));
+        updates += 1;
+        if updates <= warmup {
+            continue;
+        }
+
+        // Process open orders in placement order
+        let mut fills = Vec::new();
+        let mut idx = 0;
+        while idx < orders.orders.len() {
+            let Some(fill) = fill_model.try_fill(&mut orders.orders[idx], &portfolio) else {
+                idx += 1;
+                continue;
+            };
+            let order = &orders.orders[idx];
+            if fill.filled_sz > MIN_FILL_SIZE {
+                let trade = record_fill(&fill, order, ts_ms, &coin, &fee_calc, &mut portfolio);
+                ledger.record(&trade);
+                fills.push(trade);
+                last_fill_ts = Some(ts_ms);
+            }
+
+            // Market orders never rest: whatever the book could not fill is dropped
+            let done = matches!(fill.order_status, OrderStatus::Filled | OrderStatus::Canceled)
+                || matches!(order.action, Action::Market { .. });
+            if done {
+                orders.orders.remove(idx);
+            } else {
+                idx += 1;
+            }
+        }
+
+        // Orders placed from fill callbacks are offered on the next event
+        for trade in fills {
+            let in_cooldown = cooldown_active(config, last_fill_ts, ts_ms);
+            let mut ctx = orders.context(&coin, ts_ms, Some(price), in_cooldown, &portfolio, &ledger);
+            logic.on_fill(&trade, &mut ctx)?;
+            trades.push(trade);
+        }
+
+        // Record equity
+        let record = !is_tick
+            || last_equity_ts.is_none_or(|last| ts_ms >= last + EQUITY_RECORDING_INTERVAL_MS);
+        if record {
+            equity_curve.push(EquityPoint {
+                timestamp: ts_ms,
+                equity: portfolio.total_equity(&coin, price),
+                cash: portfolio.cash,
+                position_value: portfolio.get_position_value(&coin, price),
+            });
+            last_equity_ts = Some(ts_ms);
+        }
+    }
+
+    if updates < warmup {
+        anyhow::bail!(
+            "Not enough market data: need at least {} bars to warm up indicators, got {}",
+            warmup,
+            updates
+        );
+    }
+
+    let final_equity = match fill_model.mark_price() {
+        Some(price) => portfolio.total_equity(&coin, price),
+        None => portfolio.cash,
+    };
+    let mut result = summarize(trades, equity_curve, config.initial_capital, final_equity);
+    result.round_trips = ledger.into_round_trips();
+    Ok(result)
+}
+
+fn record_fill(
+    fill: &FillResult,
+    order: &Order,
+    timestamp: u64,
+    coin: &str,
+    fee_calc: &FeeCalculator,
+    portfolio: &mut Portfolio,
+) -> Trade {
+    let notional = fill.filled_sz * fill.fill_price;
+    let fee = fee_calc.calculate_fee(notional, fill.is_maker);
+
+    let trade = Trade {
+        timestamp,
+        symbol: coin.to_string(),
+        side: side_to_string(order.action.side()).to_string(),
+        size: fill.filled_sz,
+        price: fill.fill_price,
+        fee,
+        order_id: order.id,
+        tag: order.tag.clone(),
+    };
+
+    portfolio.execute_trade(&trade, fill.fill_price);
+    trade
+}
+
+/// Whether an entry at `ts_ms` is still inside the cooldown after the last fill
+fn cooldown_active(config: &SimConfig, last_fill_ts: Option<u64>, ts_ms: u64) -> bool {
+    match (config.trade_cooldown_ms, last_fill_ts) {
+        (Some(cooldown), Some(last)) => ts_ms < last + cooldown,
+        _ => false,
+    }
+}
+
+/// Longs pay and shorts receive a positive funding rate; returns the payment
+fn settle_funding(portfolio: &mut Portfolio, coin: &str, price: f64, rate: f64) -> f64 {
+    let size = portfolio.get_position(coin);
+    if size.abs() <= MIN_FILL_SIZE {
+        return 0.0;
+    }
+    let payment = size * price * rate;
+    portfolio.cash -= payment;
+    payment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::types::Candle;
+    use crate::data::validate::DataPolicy;
+    use crate::ingest::{L2Event, OrderLevel};
+    use crate::perps::funding::FundingPoint;
+    use crate::strategy::Strategy;
+
+    const MINUTE_MS: u64 = 60 * 1000;
+
+    fn config(trade_cooldown_ms: Option<u64>) -> SimConfig {
+        SimConfig {
+            initial_capital: 10000.0,
+            maker_fee_bps: 0,
+            taker_fee_bps: 0,
+            slippage_bps: 0,
+            trade_cooldown_ms,
+            data_policy: DataPolicy::Warn,
+        }
+    }
+
+    /// Buys whenever flat and price is above 0, sells when price is above 0
+    fn always_strategy() -> Strategy {
+        serde_json::from_value(serde_json::json!({
+            "name": "always",
+            "instrument": {"symbol": "BTC-PERP", "coin": "BTC", "venue": "HL", "timeframe": "1m"},
+            "indicators": [{"id": "sma", "type": "sma", "params": {"length": 1}, "outputs": ["value"]}],
+            "entry": {
+                "condition": {"type": "threshold", "indicator": "sma", "op": "gt", "value": 0.0},
+                "action": {"type": "buy", "size_pct": 50.0}
+            },
+            "exit": {
+                "condition": {"type": "threshold", "indicator": "sma", "op": "gt", "value": 0.0},
+                "action": {"type": "close"}
+            }
+        }))
+        .unwrap()
+    }
+
+    fn bar(i: u64, price: f64) -> Candle {
+        Candle {
+            time_open: i * MINUTE_MS,
+            time_close: (i + 1) * MINUTE_MS - 1,
+            coin: "BTC".to_string(),
+            interval: "1m".to_string(),
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: 1.0,
+            num_trades: 1,
+        }
+    }
+
+    fn snapshot(i: u64, mid: f64) -> L2Event {
+        L2Event {
+            ts_ms: i * MINUTE_MS,
+            levels: vec![
+                vec![OrderLevel { px: mid - 0.5, sz: 100.0, n: 1 }],
+                vec![OrderLevel { px: mid + 0.5, sz: 100.0, n: 1 }],
+            ],
+        }
+    }
+
+    fn run(feed: MarketFeed, fill_model: &mut dyn FillModel, config: &SimConfig) -> SimResult {
+        let mut feed = feed;
+        let mut logic = JsonStrategy::new(&always_strategy(), false).unwrap();
+        run_backtest(&mut feed, fill_model, &mut logic, config).unwrap()
+    }
+
+    #[test]
+    fn test_cooldown_applies_to_bars_and_books() {
+        let prices: Vec<f64> = (0..20).map(|i| 100.0 + i as f64).collect();
+        let bars: Vec<Candle> = prices.iter().enumerate().map(|(i, p)| bar(i as u64, *p)).collect();
+        let books: Vec<L2Event> = prices
+            .iter()
+            .enumerate()
+            .map(|(i, p)| snapshot(i as u64, *p))
+            .collect();
+
+        for cooldown in [None, Some(5 * MINUTE_MS)] {
+            let cfg = config(cooldown);
+            let candle_result = run(
+                MarketFeed::from_candles("BTC", bars.clone()),
+                &mut OhlcFillModel::new(FeeCalculator::new(0, 0, 0)),
+                &cfg,
+            );
+            let book_result = run(
+                MarketFeed::from_l2_events("BTC", books.clone()),
+                &mut BookFillModel::new(),
+                &cfg,
+            );
+            assert_eq!(candle_result.num_trades, book_result.num_trades);
+            assert_eq!(candle_result.equity_curve.len(), book_result.equity_curve.len());
+        }
+
+        // Buy on bar 1 (after the 1-bar warmup), sell on 2, then alternate; the
+        // cooldown makes the next entry wait five minutes after the exit
+        let without = run(
+            MarketFeed::from_candles("BTC", bars.clone()),
+            &mut OhlcFillModel::new(FeeCalculator::new(0, 0, 0)),
+            &config(None),
+        );
+        let with = run(
+            MarketFeed::from_candles("BTC", bars),
+            &mut OhlcFillModel::new(FeeCalculator::new(0, 0, 0)),
+            &config(Some(5 * MINUTE_MS)),
+        );
+        assert_eq!(without.num_trades, 19);
+        assert!(with.num_trades < without.num_trades);
+        assert!(with.num_trades > 0);
+    }
+
+    #[test]
+    fn test_funding_settles_against_position() {
+        let bars: Vec<Candle> = (0..4).map(|i| bar(i, 100.0)).collect();
+        let strategy: Strategy = serde_json::from_value(serde_json::json!({
+            "name": "hold",
+            "instrument": {"symbol": "BTC-PERP", "coin": "BTC", "venue": "HL", "timeframe": "1m"},
+            "indicators": [],
+            "entry": {
+                "condition": {"type": "and", "conditions": []},
+                "action": {"type": "buy", "size_pct": 100.0}
+            },
+            "exit": null
+        }))
+        .unwrap();
+
+        // Long 100 BTC-notional at 100 after the first bar; 1% funding is paid on 10000
+        let mut feed = MarketFeed::from_candles("BTC", bars).with_funding(vec![FundingPoint {
+            ts_ms: 2 * MINUTE_MS,
+            rate: 0.01,
+        }]);
+        let result = run_backtest(
+            &mut feed,
+            &mut OhlcFillModel::new(FeeCalculator::new(0, 0, 0)),
+            &mut JsonStrategy::new(&strategy, false).unwrap(),
+            &config(None),
+        )
+        .unwrap();
+
+        assert_eq!(result.num_trades, 1);
+        assert!((result.final_equity - 9900.0).abs() < 1e-6);
+        assert!(result.max_drawdown > 0.0);
+    }
+
+    #[test]
+    fn test_first_rule_by_priority_fires_and_tags_trades() {
+        let strategy: Strategy = serde_json::from_value(serde_json::json!({
+            "name": "rules",
+            "instrument": {"symbol": "BTC-PERP", "coin": "BTC", "venue": "HL", "timeframe": "1m"},
+            "indicators": [],
+            "entries": [
+                {"condition": {"type": "and", "conditions": []}, "action": {"type": "buy", "size_pct": 10.0}},
+                {"name": "big", "priority": 1, "condition": {"type": "and", "conditions": []},
+                 "action": {"type": "buy", "size_pct": 50.0}}
+            ],
+            "exits": [
+                // An empty "or" never holds, so the next exit fires
+                {"name": "never", "priority": 2, "condition": {"type": "or", "conditions": []},
+                 "action": {"type": "close"}},
+                {"condition": {"type": "and", "conditions": []}, "action": {"type": "close"}}
+            ]
+        }))
+        .unwrap();
+
+        let mut feed = MarketFeed::from_candles("BTC", (0..3).map(|i| bar(i, 100.0)).collect());
+        let result = run_backtest(
+            &mut feed,
+            &mut OhlcFillModel::new(FeeCalculator::new(0, 0, 0)),
+            &mut JsonStrategy::new(&strategy, false).unwrap(),
+            &config(None),
+        )
+        .unwrap();
+
+        let tags: Vec<_> = result.trades.iter().map(|t| t.tag.as_deref()).collect();
+        assert_eq!(tags, [Some("big"), Some("exit_2"), Some("big")]);
+        assert!((result.trades[0].size - 50.0).abs() < 1e-9);
+    }
+}
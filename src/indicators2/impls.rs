@@ -1,6 +1,6 @@
 use crate::data::types::Candle;
 use crate::indicators2::registry::IndicatorEvaluator;
-use crate::indicators2::utils::{get_price, MinMaxDeque, RingBuffer, RollingStdDev};
+use crate::indicators2::utils::{get_price, HighLowWindow, MinMaxDeque, RingBuffer, RollingStdDev};
 use anyhow::Result;
 
 // SMA - Simple Moving Average
@@ -41,6 +41,10 @@ impl IndicatorEvaluator for SmaIndicator {
         Ok(())
     }
 
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
     #[inline]
     fn value(&self, output: &str) -> Result<f64> {
         match output {
@@ -75,16 +79,10 @@ impl EmaIndicator {
             initialized: false,
         })
     }
-}
-
-impl IndicatorEvaluator for EmaIndicator {
-    fn warmup(&self) -> usize {
-        self.length * 3
-    }
 
+    /// Add a price, returning the new average
     #[inline]
-    fn update(&mut self, candle: &Candle) -> Result<()> {
-        let price = get_price(candle, &self.source);
+    pub fn push(&mut self, price: f64) -> f64 {
         if !self.initialized {
             self.value = price;
             self.initialized = true;
@@ -92,9 +90,25 @@ impl IndicatorEvaluator for EmaIndicator {
             // Use fused multiply-add for better performance: (price - value) * alpha + value
             self.value = (price - self.value).mul_add(self.alpha, self.value);
         }
+        self.value
+    }
+}
+
+impl IndicatorEvaluator for EmaIndicator {
+    fn warmup(&self) -> usize {
+        self.length * 3
+    }
+
+    #[inline]
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        self.push(get_price(candle, &self.source));
         Ok(())
     }
 
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
     #[inline]
     fn value(&self, output: &str) -> Result<f64> {
         match output {
@@ -136,16 +150,10 @@ impl WmaIndicator {
             source,
         })
     }
-}
-
-impl IndicatorEvaluator for WmaIndicator {
-    fn warmup(&self) -> usize {
-        self.length
-    }
 
+    /// Add a price, returning the new average
     #[inline]
-    fn update(&mut self, candle: &Candle) -> Result<()> {
-        let price = get_price(candle, &self.source);
+    pub fn push(&mut self, price: f64) -> f64 {
         let prev_value = self.buffer.push(price);
 
         // O(1) incremental update using rolling numerator and total
@@ -153,9 +161,25 @@ impl IndicatorEvaluator for WmaIndicator {
         // total += prev_value - price
         self.numerator += self.float_length.mul_add(price, self.total);
         self.total += prev_value - price;
+        self.numerator * self.invert_sum
+    }
+}
+
+impl IndicatorEvaluator for WmaIndicator {
+    fn warmup(&self) -> usize {
+        self.length
+    }
+
+    #[inline]
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        self.push(get_price(candle, &self.source));
         Ok(())
     }
 
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
     #[inline]
     fn value(&self, output: &str) -> Result<f64> {
         match output {
@@ -234,6 +258,10 @@ impl IndicatorEvaluator for RsiIndicator {
         Ok(())
     }
 
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
     fn value(&self, output: &str) -> Result<f64> {
         match output {
             "value" => Ok(self.rsi),
@@ -307,6 +335,10 @@ impl IndicatorEvaluator for MacdIndicator {
         Ok(())
     }
 
+    fn outputs(&self) -> &'static [&'static str] {
+        &["macd", "signal", "histogram"]
+    }
+
     fn value(&self, output: &str) -> Result<f64> {
         match output {
             "macd" => Ok(self.macd),
@@ -369,6 +401,10 @@ impl IndicatorEvaluator for BBandsIndicator {
         Ok(())
     }
 
+    fn outputs(&self) -> &'static [&'static str] {
+        &["middle", "upper", "lower"]
+    }
+
     fn value(&self, output: &str) -> Result<f64> {
         match output {
             "upper" => Ok(self.upper),
@@ -395,7 +431,8 @@ pub struct StochIndicator {
     d_period: usize,
     high_deque: MinMaxDeque,
     low_deque: MinMaxDeque,
-    k_values: Vec<f64>,
+    k_values: Vec<f64>, // Raw %K, for smoothing
+    d_values: Vec<f64>, // %K, for %D
     k: f64,
     d: f64,
 }
@@ -409,6 +446,7 @@ impl StochIndicator {
             high_deque: MinMaxDeque::new(k_period),
             low_deque: MinMaxDeque::new(k_period),
             k_values: Vec::new(),
+            d_values: Vec::new(),
             k: 0.0,
             d: 0.0,
         })
@@ -417,7 +455,7 @@ impl StochIndicator {
 
 impl IndicatorEvaluator for StochIndicator {
     fn warmup(&self) -> usize {
-        self.k_period + self.d_period
+        self.k_period + self.k_smooth - 1 + self.d_period - 1
     }
 
     fn update(&mut self, candle: &Candle) -> Result<()> {
@@ -429,29 +467,37 @@ impl IndicatorEvaluator for StochIndicator {
                 let raw_k = 100.0 * (candle.close - low) / (high - low);
 
                 // Smooth %K if needed
-                if self.k_smooth > 1 {
+                let k = if self.k_smooth > 1 {
                     self.k_values.push(raw_k);
                     if self.k_values.len() > self.k_smooth {
                         self.k_values.remove(0);
                     }
-                    if self.k_values.len() == self.k_smooth {
-                        self.k = self.k_values.iter().sum::<f64>() / self.k_smooth as f64;
-                    }
+                    (self.k_values.len() == self.k_smooth)
+                        .then(|| self.k_values.iter().sum::<f64>() / self.k_smooth as f64)
                 } else {
-                    self.k = raw_k;
-                }
+                    Some(raw_k)
+                };
 
                 // Calculate %D (SMA of %K)
-                if self.k_values.len() >= self.d_period {
-                    let start = self.k_values.len().saturating_sub(self.d_period);
-                    let sum: f64 = self.k_values[start..].iter().sum();
-                    self.d = sum / self.d_period as f64;
+                if let Some(k) = k {
+                    self.k = k;
+                    self.d_values.push(k);
+                    if self.d_values.len() > self.d_period {
+                        self.d_values.remove(0);
+                    }
+                    if self.d_values.len() == self.d_period {
+                        self.d = self.d_values.iter().sum::<f64>() / self.d_period as f64;
+                    }
                 }
             }
         }
         Ok(())
     }
 
+    fn outputs(&self) -> &'static [&'static str] {
+        &["k", "d"]
+    }
+
     fn value(&self, output: &str) -> Result<f64> {
         match output {
             "k" | "value" => Ok(self.k),
@@ -464,6 +510,7 @@ impl IndicatorEvaluator for StochIndicator {
         self.high_deque = MinMaxDeque::new(self.k_period);
         self.low_deque = MinMaxDeque::new(self.k_period);
         self.k_values.clear();
+        self.d_values.clear();
         self.k = 0.0;
         self.d = 0.0;
     }
@@ -472,7 +519,8 @@ impl IndicatorEvaluator for StochIndicator {
 // ATR - Average True Range
 pub struct AtrIndicator {
     period: usize,
-    tr_values: Vec<f64>,
+    tr_sum: f64,  // Sum of the first `period` true ranges
+    count: usize, // True ranges seen, up to `period`
     atr: f64,
     prev_close: Option<f64>,
     initialized: bool,
@@ -482,7 +530,8 @@ impl AtrIndicator {
     pub fn new(period: usize) -> Result<Self> {
         Ok(Self {
             period,
-            tr_values: Vec::new(),
+            tr_sum: 0.0,
+            count: 0,
             atr: 0.0,
             prev_close: None,
             initialized: false,
@@ -505,16 +554,15 @@ impl IndicatorEvaluator for AtrIndicator {
             candle.high - candle.low
         };
 
-        self.tr_values.push(tr);
-        if self.tr_values.len() > self.period {
-            self.tr_values.remove(0);
-        }
-
-        if !self.initialized && self.tr_values.len() == self.period {
-            // Initial ATR: simple average
-            self.atr = self.tr_values.iter().sum::<f64>() / self.period as f64;
-            self.initialized = true;
-        } else if self.initialized {
+        if !self.initialized {
+            self.tr_sum += tr;
+            self.count += 1;
+            if self.count == self.period {
+                // Initial ATR: simple average
+                self.atr = self.tr_sum / self.period as f64;
+                self.initialized = true;
+            }
+        } else {
             // Wilder's smoothing
             self.atr = (self.atr * (self.period - 1) as f64 + tr) / self.period as f64;
         }
@@ -523,6 +571,10 @@ impl IndicatorEvaluator for AtrIndicator {
         Ok(())
     }
 
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
     fn value(&self, output: &str) -> Result<f64> {
         match output {
             "value" | "atr" => Ok(self.atr),
@@ -531,7 +583,8 @@ impl IndicatorEvaluator for AtrIndicator {
     }
 
     fn reset(&mut self) {
-        self.tr_values.clear();
+        self.tr_sum = 0.0;
+        self.count = 0;
         self.atr = 0.0;
         self.prev_close = None;
         self.initialized = false;
@@ -644,6 +697,10 @@ impl IndicatorEvaluator for AdxIndicator {
         Ok(())
     }
 
+    fn outputs(&self) -> &'static [&'static str] {
+        &["adx", "plus_di", "minus_di", "dx"]
+    }
+
     fn value(&self, output: &str) -> Result<f64> {
         match output {
             "adx" | "value" => Ok(self.adx),
@@ -704,6 +761,10 @@ impl IndicatorEvaluator for ObvIndicator {
         Ok(())
     }
 
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
     fn value(&self, output: &str) -> Result<f64> {
         match output {
             "value" | "obv" => Ok(self.obv),
@@ -716,3 +777,1185 @@ impl IndicatorEvaluator for ObvIndicator {
         self.prev_close = None;
     }
 }
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+const WEEK_MS: u64 = 7 * DAY_MS;
+/// The epoch fell on a Thursday; shifting by 3 days starts weeks on Monday
+const WEEK_OFFSET_MS: u64 = 3 * DAY_MS;
+
+/// When a VWAP starts accumulating again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VwapAnchor {
+    /// Every UTC day
+    Day,
+    /// Every week, from Monday 00:00 UTC
+    Week,
+    /// Once, at the first bar opening at or after this time (ms)
+    Time(u64),
+}
+
+// VWAP - Volume Weighted Average Price
+pub struct VwapIndicator {
+    anchor: VwapAnchor,
+    source: String,
+    session: Option<u64>, // Session of the last bar
+    price_volume: f64,
+    volume: f64,
+    value: f64,
+}
+
+impl VwapIndicator {
+    pub fn new(anchor: VwapAnchor, source: String) -> Result<Self> {
+        Ok(Self {
+            anchor,
+            source,
+            session: None,
+            price_volume: 0.0,
+            volume: 0.0,
+            value: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for VwapIndicator {
+    fn warmup(&self) -> usize {
+        1
+    }
+
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        let session = match self.anchor {
+            VwapAnchor::Day => candle.time_open / DAY_MS,
+            VwapAnchor::Week => (candle.time_open + WEEK_OFFSET_MS) / WEEK_MS,
+            // Bars before the anchor are not part of the VWAP
+            VwapAnchor::Time(anchor_ms) if candle.time_open < anchor_ms => return Ok(()),
+            VwapAnchor::Time(_) => 0,
+        };
+        if self.session != Some(session) {
+            self.session = Some(session);
+            self.price_volume = 0.0;
+            self.volume = 0.0;
+        }
+
+        let price = get_price(candle, &self.source);
+        self.price_volume = price.mul_add(candle.volume, self.price_volume);
+        self.volume += candle.volume;
+        // Until the session trades, the VWAP is the price
+        self.value = if self.volume > 0.0 {
+            self.price_volume / self.volume
+        } else {
+            price
+        };
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            _ => anyhow::bail!("Unknown VWAP output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.session = None;
+        self.price_volume = 0.0;
+        self.volume = 0.0;
+        self.value = 0.0;
+    }
+}
+
+// Keltner Channels - EMA with bands a multiple of ATR away
+pub struct KeltnerIndicator {
+    ema: EmaIndicator,
+    atr: AtrIndicator,
+    multiplier: f64,
+    middle: f64,
+    upper: f64,
+    lower: f64,
+}
+
+impl KeltnerIndicator {
+    pub fn new(length: usize, multiplier: f64, atr_length: usize, source: String) -> Result<Self> {
+        Ok(Self {
+            ema: EmaIndicator::new(length, source)?,
+            atr: AtrIndicator::new(atr_length)?,
+            multiplier,
+            middle: 0.0,
+            upper: 0.0,
+            lower: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for KeltnerIndicator {
+    fn warmup(&self) -> usize {
+        self.ema.warmup().max(self.atr.warmup())
+    }
+
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        self.ema.update(candle)?;
+        self.atr.update(candle)?;
+
+        self.middle = self.ema.value;
+        let width = self.multiplier * self.atr.atr;
+        self.upper = self.middle + width;
+        self.lower = self.middle - width;
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["middle", "upper", "lower"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "middle" => Ok(self.middle),
+            "upper" => Ok(self.upper),
+            "lower" => Ok(self.lower),
+            _ => anyhow::bail!("Unknown Keltner output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.ema.reset();
+        self.atr.reset();
+        self.middle = 0.0;
+        self.upper = 0.0;
+        self.lower = 0.0;
+    }
+}
+
+// Donchian Channels - highest high and lowest low over a period
+pub struct DonchianIndicator {
+    length: usize,
+    window: HighLowWindow,
+}
+
+impl DonchianIndicator {
+    pub fn new(length: usize) -> Result<Self> {
+        Ok(Self {
+            length,
+            window: HighLowWindow::new(length),
+        })
+    }
+}
+
+impl IndicatorEvaluator for DonchianIndicator {
+    fn warmup(&self) -> usize {
+        self.length
+    }
+
+    #[inline]
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        self.window.push(candle);
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["middle", "upper", "lower"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "middle" => Ok(self.window.mid()),
+            "upper" => Ok(self.window.high()),
+            "lower" => Ok(self.window.low()),
+            _ => anyhow::bail!("Unknown Donchian output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.window = HighLowWindow::new(self.length);
+    }
+}
+
+// SuperTrend - ATR bands around hl2 that only tighten until price closes through them
+pub struct SuperTrendIndicator {
+    atr: AtrIndicator,
+    multiplier: f64,
+    bars: usize,
+    prev_close: f64,
+    upper: f64,
+    lower: f64,
+    direction: f64, // 1 while the trend is up, -1 while down
+    value: f64,
+}
+
+impl SuperTrendIndicator {
+    pub fn new(period: usize, multiplier: f64) -> Result<Self> {
+        Ok(Self {
+            atr: AtrIndicator::new(period)?,
+            multiplier,
+            bars: 0,
+            prev_close: 0.0,
+            upper: 0.0,
+            lower: 0.0,
+            direction: 0.0,
+            value: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for SuperTrendIndicator {
+    fn warmup(&self) -> usize {
+        self.atr.warmup()
+    }
+
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        self.atr.update(candle)?;
+        self.bars += 1;
+
+        // The bands start once ATR has its first average
+        if self.atr.initialized {
+            let hl2 = (candle.high + candle.low) * 0.5;
+            let width = self.multiplier * self.atr.atr;
+            let (upper, lower) = (hl2 + width, hl2 - width);
+
+            if self.bars == self.atr.period {
+                self.upper = upper;
+                self.lower = lower;
+                self.direction = 1.0;
+            } else {
+                let (prev_upper, prev_lower) = (self.upper, self.lower);
+                self.upper = if self.prev_close < prev_upper { upper.min(prev_upper) } else { upper };
+                self.lower = if self.prev_close > prev_lower { lower.max(prev_lower) } else { lower };
+                if self.direction < 0.0 && candle.close > prev_upper {
+                    self.direction = 1.0;
+                } else if self.direction > 0.0 && candle.close < prev_lower {
+                    self.direction = -1.0;
+                }
+            }
+            self.value = if self.direction > 0.0 { self.lower } else { self.upper };
+        }
+
+        self.prev_close = candle.close;
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value", "direction", "upper", "lower"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            "direction" => Ok(self.direction),
+            "upper" => Ok(self.upper),
+            "lower" => Ok(self.lower),
+            _ => anyhow::bail!("Unknown SuperTrend output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.atr.reset();
+        self.bars = 0;
+        self.prev_close = 0.0;
+        self.upper = 0.0;
+        self.lower = 0.0;
+        self.direction = 0.0;
+        self.value = 0.0;
+    }
+}
+
+// Parabolic SAR - Wilder's stop and reverse
+pub struct PsarIndicator {
+    start: f64,
+    increment: f64,
+    max: f64,
+    bars: usize,
+    long: bool,
+    sar: f64,
+    extreme: f64, // Highest high of a long, lowest low of a short
+    af: f64,      // Acceleration factor
+    prev_high: [f64; 2], // Highs of the last two bars, latest first
+    prev_low: [f64; 2],
+    prev_close: f64,
+}
+
+impl PsarIndicator {
+    pub fn new(start: f64, increment: f64, max: f64) -> Result<Self> {
+        Ok(Self {
+            start,
+            increment,
+            max,
+            bars: 0,
+            long: true,
+            sar: 0.0,
+            extreme: 0.0,
+            af: start,
+            prev_high: [0.0; 2],
+            prev_low: [0.0; 2],
+            prev_close: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for PsarIndicator {
+    fn warmup(&self) -> usize {
+        2
+    }
+
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        match self.bars {
+            0 => {}
+            1 => {
+                // Start in the direction of the first close-to-close move
+                self.long = candle.close >= self.prev_close;
+                if self.long {
+                    self.sar = self.prev_low[0].min(candle.low);
+                    self.extreme = candle.high;
+                } else {
+                    self.sar = self.prev_high[0].max(candle.high);
+                    self.extreme = candle.low;
+                }
+                self.af = self.start;
+            }
+            _ => {
+                let mut sar = (self.extreme - self.sar).mul_add(self.af, self.sar);
+                if self.long {
+                    // Never above the last two lows
+                    sar = sar.min(self.prev_low[0]).min(self.prev_low[1]);
+                    if candle.low < sar {
+                        self.long = false;
+                        sar = self.extreme;
+                        self.extreme = candle.low;
+                        self.af = self.start;
+                    } else if candle.high > self.extreme {
+                        self.extreme = candle.high;
+                        self.af = (self.af + self.increment).min(self.max);
+                    }
+                } else {
+                    // Never below the last two highs
+                    sar = sar.max(self.prev_high[0]).max(self.prev_high[1]);
+                    if candle.high > sar {
+                        self.long = true;
+                        sar = self.extreme;
+                        self.extreme = candle.high;
+                        self.af = self.start;
+                    } else if candle.low < self.extreme {
+                        self.extreme = candle.low;
+                        self.af = (self.af + self.increment).min(self.max);
+                    }
+                }
+                self.sar = sar;
+            }
+        }
+
+        self.bars += 1;
+        self.prev_high = [candle.high, self.prev_high[0]];
+        self.prev_low = [candle.low, self.prev_low[0]];
+        self.prev_close = candle.close;
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value", "direction"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.sar),
+            "direction" => Ok(match self.bars {
+                0 | 1 => 0.0,
+                _ if self.long => 1.0,
+                _ => -1.0,
+            }),
+            _ => anyhow::bail!("Unknown PSAR output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.bars = 0;
+        self.long = true;
+        self.sar = 0.0;
+        self.extreme = 0.0;
+        self.af = self.start;
+        self.prev_high = [0.0; 2];
+        self.prev_low = [0.0; 2];
+        self.prev_close = 0.0;
+    }
+}
+
+// Ichimoku Cloud
+pub struct IchimokuIndicator {
+    tenkan_period: usize,
+    kijun_period: usize,
+    senkou_period: usize,
+    displacement: usize,
+    tenkan_window: HighLowWindow,
+    kijun_window: HighLowWindow,
+    senkou_window: HighLowWindow,
+    span_a: RingBuffer, // Spans of the last `displacement` bars, to be shown now
+    span_b: RingBuffer,
+    tenkan: f64,
+    kijun: f64,
+    senkou_a: f64,
+    senkou_b: f64,
+    chikou: f64,
+}
+
+impl IchimokuIndicator {
+    pub fn new(tenkan: usize, kijun: usize, senkou: usize, displacement: usize) -> Result<Self> {
+        Ok(Self {
+            tenkan_period: tenkan,
+            kijun_period: kijun,
+            senkou_period: senkou,
+            displacement,
+            tenkan_window: HighLowWindow::new(tenkan),
+            kijun_window: HighLowWindow::new(kijun),
+            senkou_window: HighLowWindow::new(senkou),
+            span_a: RingBuffer::new(displacement),
+            span_b: RingBuffer::new(displacement),
+            tenkan: 0.0,
+            kijun: 0.0,
+            senkou_a: 0.0,
+            senkou_b: 0.0,
+            chikou: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for IchimokuIndicator {
+    fn warmup(&self) -> usize {
+        self.tenkan_period.max(self.kijun_period).max(self.senkou_period) + self.displacement
+    }
+
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        self.tenkan_window.push(candle);
+        self.kijun_window.push(candle);
+        self.senkou_window.push(candle);
+
+        self.tenkan = self.tenkan_window.mid();
+        self.kijun = self.kijun_window.mid();
+        // The spans computed now are plotted `displacement` bars ahead, so
+        // the ones for this bar are those computed `displacement` bars ago
+        self.senkou_a = self.span_a.push((self.tenkan + self.kijun) * 0.5);
+        self.senkou_b = self.span_b.push(self.senkou_window.mid());
+        // Plotted `displacement` bars back, the lagging span's newest point
+        // is this bar's close
+        self.chikou = candle.close;
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["tenkan", "kijun", "senkou_a", "senkou_b", "chikou"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "tenkan" => Ok(self.tenkan),
+            "kijun" => Ok(self.kijun),
+            "senkou_a" => Ok(self.senkou_a),
+            "senkou_b" => Ok(self.senkou_b),
+            "chikou" => Ok(self.chikou),
+            _ => anyhow::bail!("Unknown Ichimoku output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.tenkan_window = HighLowWindow::new(self.tenkan_period);
+        self.kijun_window = HighLowWindow::new(self.kijun_period);
+        self.senkou_window = HighLowWindow::new(self.senkou_period);
+        self.span_a = RingBuffer::new(self.displacement);
+        self.span_b = RingBuffer::new(self.displacement);
+        self.tenkan = 0.0;
+        self.kijun = 0.0;
+        self.senkou_a = 0.0;
+        self.senkou_b = 0.0;
+        self.chikou = 0.0;
+    }
+}
+
+// HMA - Hull Moving Average: WMA(2 * WMA(n / 2) - WMA(n), sqrt(n))
+pub struct HmaIndicator {
+    half: WmaIndicator,
+    full: WmaIndicator,
+    smooth: WmaIndicator,
+    length: usize,
+    source: String,
+    value: f64,
+}
+
+impl HmaIndicator {
+    pub fn new(length: usize, source: String) -> Result<Self> {
+        let smooth_length = ((length as f64).sqrt() as usize).max(1);
+        Ok(Self {
+            half: WmaIndicator::new((length / 2).max(1), String::new())?,
+            full: WmaIndicator::new(length, String::new())?,
+            smooth: WmaIndicator::new(smooth_length, String::new())?,
+            length,
+            source,
+            value: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for HmaIndicator {
+    fn warmup(&self) -> usize {
+        self.length + self.smooth.length - 1
+    }
+
+    #[inline]
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        let price = get_price(candle, &self.source);
+        let half = self.half.push(price);
+        let full = self.full.push(price);
+        self.value = self.smooth.push(2.0f64.mul_add(half, -full));
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            _ => anyhow::bail!("Unknown HMA output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.half.reset();
+        self.full.reset();
+        self.smooth.reset();
+        self.value = 0.0;
+    }
+}
+
+// DEMA - Double EMA: 2 * EMA - EMA(EMA)
+pub struct DemaIndicator {
+    ema1: EmaIndicator,
+    ema2: EmaIndicator,
+    source: String,
+    value: f64,
+}
+
+impl DemaIndicator {
+    pub fn new(length: usize, source: String) -> Result<Self> {
+        Ok(Self {
+            ema1: EmaIndicator::new(length, String::new())?,
+            ema2: EmaIndicator::new(length, String::new())?,
+            source,
+            value: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for DemaIndicator {
+    fn warmup(&self) -> usize {
+        // Each EMA settles over its own warmup, the second on the first's output
+        self.ema1.warmup() * 2
+    }
+
+    #[inline]
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        let e1 = self.ema1.push(get_price(candle, &self.source));
+        let e2 = self.ema2.push(e1);
+        self.value = 2.0f64.mul_add(e1, -e2);
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            _ => anyhow::bail!("Unknown DEMA output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.ema1.reset();
+        self.ema2.reset();
+        self.value = 0.0;
+    }
+}
+
+// TEMA - Triple EMA: 3 * EMA - 3 * EMA(EMA) + EMA(EMA(EMA))
+pub struct TemaIndicator {
+    ema1: EmaIndicator,
+    ema2: EmaIndicator,
+    ema3: EmaIndicator,
+    source: String,
+    value: f64,
+}
+
+impl TemaIndicator {
+    pub fn new(length: usize, source: String) -> Result<Self> {
+        Ok(Self {
+            ema1: EmaIndicator::new(length, String::new())?,
+            ema2: EmaIndicator::new(length, String::new())?,
+            ema3: EmaIndicator::new(length, String::new())?,
+            source,
+            value: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for TemaIndicator {
+    fn warmup(&self) -> usize {
+        self.ema1.warmup() * 3
+    }
+
+    #[inline]
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        let e1 = self.ema1.push(get_price(candle, &self.source));
+        let e2 = self.ema2.push(e1);
+        let e3 = self.ema3.push(e2);
+        self.value = 3.0f64.mul_add(e1 - e2, e3);
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            _ => anyhow::bail!("Unknown TEMA output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.ema1.reset();
+        self.ema2.reset();
+        self.ema3.reset();
+        self.value = 0.0;
+    }
+}
+
+// KAMA - Kaufman Adaptive Moving Average
+pub struct KamaIndicator {
+    length: usize,
+    slow: usize,
+    fast_sc: f64, // 2 / (fast + 1)
+    slow_sc: f64, // 2 / (slow + 1)
+    prices: RingBuffer,
+    changes: RingBuffer, // |price - previous price| over the window
+    count: usize,
+    prev_price: f64,
+    source: String,
+    value: f64,
+}
+
+impl KamaIndicator {
+    pub fn new(length: usize, fast: usize, slow: usize, source: String) -> Result<Self> {
+        Ok(Self {
+            length,
+            slow,
+            fast_sc: 2.0 / (fast as f64 + 1.0),
+            slow_sc: 2.0 / (slow as f64 + 1.0),
+            prices: RingBuffer::new(length),
+            changes: RingBuffer::new(length),
+            count: 0,
+            prev_price: 0.0,
+            source,
+            value: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for KamaIndicator {
+    fn warmup(&self) -> usize {
+        self.length + self.slow
+    }
+
+    #[inline]
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        let price = get_price(candle, &self.source);
+        if self.count > 0 {
+            self.changes.push((price - self.prev_price).abs());
+        }
+        let old_price = self.prices.push(price);
+        self.count += 1;
+        self.prev_price = price;
+
+        if self.count <= self.length {
+            // Seeded with the price until a full window of changes is in
+            self.value = price;
+            return Ok(());
+        }
+
+        // Efficiency ratio: net move over the sum of bar-to-bar moves
+        let volatility = self.changes.sum();
+        let er = if volatility > 0.0 {
+            (price - old_price).abs() / volatility
+        } else {
+            0.0
+        };
+        let sc = er.mul_add(self.fast_sc - self.slow_sc, self.slow_sc).powi(2);
+        self.value = (price - self.value).mul_add(sc, self.value);
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            _ => anyhow::bail!("Unknown KAMA output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.prices = RingBuffer::new(self.length);
+        self.changes = RingBuffer::new(self.length);
+        self.count = 0;
+        self.prev_price = 0.0;
+        self.value = 0.0;
+    }
+}
+
+/// Money flow multiplier: where the close sits in the bar's range, from -1 at
+/// the low to 1 at the high (0 for a bar without range)
+#[inline]
+fn money_flow_multiplier(candle: &Candle) -> f64 {
+    let range = candle.high - candle.low;
+    if range > 0.0 {
+        ((candle.close - candle.low) - (candle.high - candle.close)) / range
+    } else {
+        0.0
+    }
+}
+
+// MFI - Money Flow Index: RSI of typical price weighted by volume
+pub struct MfiIndicator {
+    period: usize,
+    positive: RingBuffer, // Money flow of bars where typical price rose
+    negative: RingBuffer, // ...and fell
+    prev_typical: Option<f64>,
+    value: f64,
+}
+
+impl MfiIndicator {
+    pub fn new(period: usize) -> Result<Self> {
+        Ok(Self {
+            period,
+            positive: RingBuffer::new(period),
+            negative: RingBuffer::new(period),
+            prev_typical: None,
+            value: 50.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for MfiIndicator {
+    fn warmup(&self) -> usize {
+        self.period + 1
+    }
+
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        let typical = get_price(candle, "hlc3");
+        if let Some(prev) = self.prev_typical {
+            let flow = typical * candle.volume;
+            self.positive.push(if typical > prev { flow } else { 0.0 });
+            self.negative.push(if typical < prev { flow } else { 0.0 });
+
+            let (positive, negative) = (self.positive.sum(), self.negative.sum());
+            self.value = if negative > 0.0 {
+                100.0 - 100.0 / (1.0 + positive / negative)
+            } else if positive > 0.0 {
+                100.0
+            } else {
+                50.0
+            };
+        }
+        self.prev_typical = Some(typical);
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            _ => anyhow::bail!("Unknown MFI output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.positive = RingBuffer::new(self.period);
+        self.negative = RingBuffer::new(self.period);
+        self.prev_typical = None;
+        self.value = 50.0;
+    }
+}
+
+// CMF - Chaikin Money Flow: money flow volume over volume, over a period
+pub struct CmfIndicator {
+    period: usize,
+    flow_volume: RingBuffer,
+    volume: RingBuffer,
+    value: f64,
+}
+
+impl CmfIndicator {
+    pub fn new(period: usize) -> Result<Self> {
+        Ok(Self {
+            period,
+            flow_volume: RingBuffer::new(period),
+            volume: RingBuffer::new(period),
+            value: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for CmfIndicator {
+    fn warmup(&self) -> usize {
+        self.period
+    }
+
+    #[inline]
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        self.flow_volume.push(money_flow_multiplier(candle) * candle.volume);
+        self.volume.push(candle.volume);
+        let volume = self.volume.sum();
+        self.value = if volume > 0.0 {
+            self.flow_volume.sum() / volume
+        } else {
+            0.0
+        };
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            _ => anyhow::bail!("Unknown CMF output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.flow_volume = RingBuffer::new(self.period);
+        self.volume = RingBuffer::new(self.period);
+        self.value = 0.0;
+    }
+}
+
+// A/D - Accumulation/Distribution line: cumulative money flow volume
+pub struct AdIndicator {
+    value: f64,
+}
+
+impl AdIndicator {
+    pub fn new() -> Result<Self> {
+        Ok(Self { value: 0.0 })
+    }
+}
+
+impl IndicatorEvaluator for AdIndicator {
+    fn warmup(&self) -> usize {
+        1
+    }
+
+    #[inline]
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        self.value = money_flow_multiplier(candle).mul_add(candle.volume, self.value);
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            _ => anyhow::bail!("Unknown A/D output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.value = 0.0;
+    }
+}
+
+// VWMA - Volume Weighted Moving Average
+pub struct VwmaIndicator {
+    length: usize,
+    source: String,
+    price_volume: RingBuffer,
+    volume: RingBuffer,
+    prices: RingBuffer, // For the plain average while the window has no volume
+    value: f64,
+}
+
+impl VwmaIndicator {
+    pub fn new(length: usize, source: String) -> Result<Self> {
+        Ok(Self {
+            length,
+            source,
+            price_volume: RingBuffer::new(length),
+            volume: RingBuffer::new(length),
+            prices: RingBuffer::new(length),
+            value: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for VwmaIndicator {
+    fn warmup(&self) -> usize {
+        self.length
+    }
+
+    #[inline]
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        let price = get_price(candle, &self.source);
+        self.price_volume.push(price * candle.volume);
+        self.volume.push(candle.volume);
+        self.prices.push(price);
+        let volume = self.volume.sum();
+        self.value = if volume > 0.0 {
+            self.price_volume.sum() / volume
+        } else {
+            self.prices.mean()
+        };
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            _ => anyhow::bail!("Unknown VWMA output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.price_volume = RingBuffer::new(self.length);
+        self.volume = RingBuffer::new(self.length);
+        self.prices = RingBuffer::new(self.length);
+        self.value = 0.0;
+    }
+}
+
+// Volume Oscillator - percentage difference of a fast and a slow volume EMA
+pub struct VolumeOscillatorIndicator {
+    fast: EmaIndicator,
+    slow: EmaIndicator,
+    value: f64,
+}
+
+impl VolumeOscillatorIndicator {
+    pub fn new(fast: usize, slow: usize) -> Result<Self> {
+        Ok(Self {
+            fast: EmaIndicator::new(fast, String::new())?,
+            slow: EmaIndicator::new(slow, String::new())?,
+            value: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for VolumeOscillatorIndicator {
+    fn warmup(&self) -> usize {
+        self.fast.warmup().max(self.slow.warmup())
+    }
+
+    #[inline]
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        let fast = self.fast.push(candle.volume);
+        let slow = self.slow.push(candle.volume);
+        self.value = if slow > 0.0 {
+            100.0 * (fast - slow) / slow
+        } else {
+            0.0
+        };
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            _ => anyhow::bail!("Unknown Volume Oscillator output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.fast.reset();
+        self.slow.reset();
+        self.value = 0.0;
+    }
+}
+
+// EOM - Ease of Movement: midpoint move per unit of volume over range, averaged
+pub struct EomIndicator {
+    period: usize,
+    divisor: f64,
+    sma: RingBuffer,
+    prev_mid: Option<f64>,
+    value: f64,
+}
+
+impl EomIndicator {
+    pub fn new(period: usize, divisor: f64) -> Result<Self> {
+        Ok(Self {
+            period,
+            divisor,
+            sma: RingBuffer::new(period),
+            prev_mid: None,
+            value: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for EomIndicator {
+    fn warmup(&self) -> usize {
+        self.period + 1
+    }
+
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        let mid = (candle.high + candle.low) * 0.5;
+        if let Some(prev_mid) = self.prev_mid {
+            let range = candle.high - candle.low;
+            // A bar without range or volume moved freely nowhere
+            let emv = if range > 0.0 && candle.volume > 0.0 {
+                (mid - prev_mid) * range * self.divisor / candle.volume
+            } else {
+                0.0
+            };
+            self.sma.push(emv);
+            self.value = self.sma.mean();
+        }
+        self.prev_mid = Some(mid);
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            _ => anyhow::bail!("Unknown EOM output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.sma = RingBuffer::new(self.period);
+        self.prev_mid = None;
+        self.value = 0.0;
+    }
+}
+
+// Force Index - EMA of price change times volume
+pub struct ForceIndexIndicator {
+    ema: EmaIndicator,
+    prev_close: Option<f64>,
+    value: f64,
+}
+
+impl ForceIndexIndicator {
+    pub fn new(length: usize) -> Result<Self> {
+        Ok(Self {
+            ema: EmaIndicator::new(length, String::new())?,
+            prev_close: None,
+            value: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for ForceIndexIndicator {
+    fn warmup(&self) -> usize {
+        self.ema.warmup() + 1
+    }
+
+    #[inline]
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        if let Some(prev_close) = self.prev_close {
+            self.value = self.ema.push((candle.close - prev_close) * candle.volume);
+        }
+        self.prev_close = Some(candle.close);
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            _ => anyhow::bail!("Unknown Force Index output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.ema.reset();
+        self.prev_close = None;
+        self.value = 0.0;
+    }
+}
+
+// Relative Volume - volume over the average volume of the bars before it
+pub struct RelativeVolumeIndicator {
+    length: usize,
+    volume: RingBuffer, // The `length` bars before the current one
+    value: f64,
+}
+
+impl RelativeVolumeIndicator {
+    pub fn new(length: usize) -> Result<Self> {
+        Ok(Self {
+            length,
+            volume: RingBuffer::new(length),
+            value: 0.0,
+        })
+    }
+}
+
+impl IndicatorEvaluator for RelativeVolumeIndicator {
+    fn warmup(&self) -> usize {
+        self.length + 1
+    }
+
+    #[inline]
+    fn update(&mut self, candle: &Candle) -> Result<()> {
+        let average = self.volume.mean();
+        self.value = if average > 0.0 {
+            candle.volume / average
+        } else {
+            0.0
+        };
+        self.volume.push(candle.volume);
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "value" => Ok(self.value),
+            _ => anyhow::bail!("Unknown RVOL output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.volume = RingBuffer::new(self.length);
+        self.value = 0.0;
+    }
+}
@@ -1,10 +1,12 @@
 pub mod registry;
 pub mod impls;
 pub mod utils;
+pub mod params;
 
 #[cfg(test)]
 mod tests;
 
-pub use registry::{create_indicator, IndicatorRegistry, IndicatorEvaluator};
+pub use registry::{create_indicator, output_name, IndicatorDefinition, IndicatorEvaluator, IndicatorRegistry};
 pub use impls::*;
+pub use params::{IndicatorParams, ParamKind, ParamSpec, ParamValue};
 
@@ -0,0 +1,311 @@
+//! Typed indicator parameters and the schemas they are checked against
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Prices an indicator can be computed on (see [`get_price`](super::utils::get_price))
+pub const PRICE_SOURCES: &[&str] = &["open", "high", "low", "close", "hl2", "hlc3", "ohlc4"];
+
+/// Longest period any indicator accepts
+pub const MAX_PERIOD: f64 = 10_000.0;
+
+/// Value of an indicator parameter, as written in the strategy JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Bool(bool),
+    Number(f64),
+    Text(Cow<'static, str>),
+}
+
+impl From<f64> for ParamValue {
+    fn from(value: f64) -> Self {
+        ParamValue::Number(value)
+    }
+}
+
+impl From<bool> for ParamValue {
+    fn from(value: bool) -> Self {
+        ParamValue::Bool(value)
+    }
+}
+
+impl From<&str> for ParamValue {
+    fn from(value: &str) -> Self {
+        ParamValue::Text(Cow::Owned(value.to_string()))
+    }
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamValue::Bool(value) => write!(f, "{}", value),
+            ParamValue::Number(value) => write!(f, "{}", value),
+            ParamValue::Text(value) => write!(f, "\"{}\"", value),
+        }
+    }
+}
+
+/// What values a parameter takes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamKind {
+    /// Whole number in `min..=max`
+    Int { min: f64, max: f64 },
+    /// Number in `min..=max`
+    Number { min: f64, max: f64 },
+    Bool,
+    /// One of the listed strings, matched case-insensitively
+    Enum(&'static [&'static str]),
+}
+
+impl fmt::Display for ParamKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamKind::Int { min, max } => write!(f, "a whole number from {} to {}", min, max),
+            ParamKind::Number { min, max } => write!(f, "a number from {} to {}", min, max),
+            ParamKind::Bool => write!(f, "true or false"),
+            ParamKind::Enum(choices) => write!(f, "one of {}", choices.join(", ")),
+        }
+    }
+}
+
+/// A parameter an indicator accepts
+#[derive(Debug, Clone)]
+pub struct ParamSpec {
+    pub name: &'static str,
+    /// Other names accepted for the parameter
+    pub aliases: &'static [&'static str],
+    pub kind: ParamKind,
+    pub default: ParamValue,
+    pub description: &'static str,
+}
+
+impl ParamSpec {
+    /// Period in bars, at least 1
+    pub const fn period(name: &'static str, default: f64, description: &'static str) -> Self {
+        Self {
+            name,
+            aliases: &[],
+            kind: ParamKind::Int { min: 1.0, max: MAX_PERIOD },
+            default: ParamValue::Number(default),
+            description,
+        }
+    }
+
+    pub const fn number(name: &'static str, default: f64, min: f64, max: f64, description: &'static str) -> Self {
+        Self {
+            name,
+            aliases: &[],
+            kind: ParamKind::Number { min, max },
+            default: ParamValue::Number(default),
+            description,
+        }
+    }
+
+    /// One of `choices`, matched case-insensitively
+    pub const fn choice(
+        name: &'static str,
+        choices: &'static [&'static str],
+        default: &'static str,
+        description: &'static str,
+    ) -> Self {
+        Self {
+            name,
+            aliases: &[],
+            kind: ParamKind::Enum(choices),
+            default: ParamValue::Text(Cow::Borrowed(default)),
+            description,
+        }
+    }
+
+    /// The price the indicator is computed on, `close` by default
+    pub const fn source() -> Self {
+        Self::choice("source", PRICE_SOURCES, "close", "Price to compute on")
+    }
+
+    pub const fn alias(mut self, aliases: &'static [&'static str]) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.contains(&name)
+    }
+
+    /// Check `value` against the kind, normalizing enum choices to their
+    /// listed spelling
+    fn check(&self, value: &ParamValue) -> Result<ParamValue> {
+        let valid = match (self.kind, value) {
+            (ParamKind::Int { min, max }, ParamValue::Number(n)) => {
+                (n.fract() == 0.0 && (min..=max).contains(n)).then(|| value.clone())
+            }
+            (ParamKind::Number { min, max }, ParamValue::Number(n)) => {
+                (min..=max).contains(n).then(|| value.clone())
+            }
+            (ParamKind::Bool, ParamValue::Bool(_)) => Some(value.clone()),
+            (ParamKind::Enum(choices), ParamValue::Text(text)) => choices
+                .iter()
+                .find(|choice| choice.eq_ignore_ascii_case(text))
+                .map(|choice| ParamValue::Text(Cow::Borrowed(choice))),
+            _ => None,
+        };
+        valid.with_context(|| format!("'{}' must be {}, got {}", self.name, self.kind, value))
+    }
+}
+
+/// Parameters checked against an indicator's schema, under their canonical
+/// names, with defaults filled in
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndicatorParams {
+    values: HashMap<String, ParamValue>,
+}
+
+impl IndicatorParams {
+    /// Check `given` against `schema`: every parameter must be known, under
+    /// its name or one alias, and of the right kind
+    pub fn resolve(schema: &[ParamSpec], given: &HashMap<String, ParamValue>) -> Result<Self> {
+        let mut values = HashMap::new();
+        let mut names: Vec<&String> = given.keys().collect();
+        names.sort();
+        for name in names {
+            let Some(spec) = schema.iter().find(|spec| spec.matches(name)) else {
+                bail!("{}", unknown_param(name, schema));
+            };
+            if values.contains_key(spec.name) {
+                bail!("'{}' is given more than once, under its aliases", spec.name);
+            }
+            values.insert(spec.name.to_string(), spec.check(&given[name])?);
+        }
+        for spec in schema {
+            values
+                .entry(spec.name.to_string())
+                .or_insert_with(|| spec.default.clone());
+        }
+        Ok(Self { values })
+    }
+
+    pub fn values(&self) -> &HashMap<String, ParamValue> {
+        &self.values
+    }
+
+    pub fn into_values(self) -> HashMap<String, ParamValue> {
+        self.values
+    }
+
+    fn get(&self, name: &str) -> Result<&ParamValue> {
+        self.values
+            .get(name)
+            .with_context(|| format!("No parameter '{}'", name))
+    }
+
+    pub fn f64(&self, name: &str) -> Result<f64> {
+        match self.get(name)? {
+            ParamValue::Number(value) => Ok(*value),
+            other => bail!("'{}' is not a number: {}", name, other),
+        }
+    }
+
+    pub fn usize(&self, name: &str) -> Result<usize> {
+        Ok(self.f64(name)? as usize)
+    }
+
+    pub fn bool(&self, name: &str) -> Result<bool> {
+        match self.get(name)? {
+            ParamValue::Bool(value) => Ok(*value),
+            other => bail!("'{}' is not true or false: {}", name, other),
+        }
+    }
+
+    pub fn str(&self, name: &str) -> Result<&str> {
+        match self.get(name)? {
+            ParamValue::Text(value) => Ok(value),
+            other => bail!("'{}' is not a string: {}", name, other),
+        }
+    }
+}
+
+/// Error message for a parameter the schema doesn't have, suggesting the
+/// closest name when it looks misspelled
+fn unknown_param(name: &str, schema: &[ParamSpec]) -> String {
+    let closest = schema
+        .iter()
+        .flat_map(|spec| std::iter::once(&spec.name).chain(spec.aliases))
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .min();
+    match closest {
+        Some((distance, candidate)) if distance <= 2 => {
+            format!("Unknown parameter '{}', did you mean '{}'?", name, candidate)
+        }
+        _ if schema.is_empty() => format!("Unknown parameter '{}', none are accepted", name),
+        _ => {
+            let known: Vec<&str> = schema.iter().map(|spec| spec.name).collect();
+            format!("Unknown parameter '{}', expected one of {}", name, known.join(", "))
+        }
+    }
+}
+
+/// Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: [ParamSpec; 3] = [
+        ParamSpec::period("length", 20.0, "Bars averaged").alias(&["period"]),
+        ParamSpec::number("std", 2.0, 0.0, 10.0, "Band width in deviations"),
+        ParamSpec::source(),
+    ];
+
+    fn given(params: &[(&str, ParamValue)]) -> HashMap<String, ParamValue> {
+        params.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    #[test]
+    fn test_resolve_fills_defaults_and_aliases() {
+        let params = IndicatorParams::resolve(&SCHEMA, &given(&[("period", 5.0.into()), ("source", "HL2".into())])).unwrap();
+        assert_eq!(params.usize("length").unwrap(), 5);
+        assert_eq!(params.f64("std").unwrap(), 2.0);
+        assert_eq!(params.str("source").unwrap(), "hl2");
+        assert!(!params.values().contains_key("period"));
+    }
+
+    #[test]
+    fn test_resolve_rejects_bad_params() {
+        let error = |params: &[(&str, ParamValue)]| {
+            IndicatorParams::resolve(&SCHEMA, &given(params)).unwrap_err().to_string()
+        };
+        assert_eq!(error(&[("lenght", 5.0.into())]), "Unknown parameter 'lenght', did you mean 'length'?");
+        assert_eq!(error(&[("window", 5.0.into())]), "Unknown parameter 'window', expected one of length, std, source");
+        assert!(error(&[("length", 2.5.into())]).contains("whole number from 1 to 10000"));
+        assert!(error(&[("length", 0.0.into())]).contains("got 0"));
+        assert!(error(&[("std", "wide".into())]).contains("got \"wide\""));
+        assert!(error(&[("source", "vwap".into())]).contains("one of open, high"));
+        assert!(error(&[("length", 5.0.into()), ("period", 5.0.into())]).contains("more than once"));
+    }
+
+    #[test]
+    fn test_param_values_from_json() {
+        let params: HashMap<String, ParamValue> =
+            serde_json::from_str(r#"{ "length": 14, "source": "hlc3", "adjust": true }"#).unwrap();
+        assert_eq!(params["length"], ParamValue::Number(14.0));
+        assert_eq!(params["source"], "hlc3".into());
+        assert_eq!(params["adjust"], ParamValue::Bool(true));
+    }
+}
@@ -1,139 +1,425 @@
 use std::collections::HashMap;
-use anyhow::Result;
+use std::sync::Arc;
+use anyhow::{Context, Result};
 
 use crate::data::types::Candle;
 use crate::indicators2::impls::*;
+use crate::indicators2::params::{IndicatorParams, ParamSpec, ParamValue, PRICE_SOURCES};
 
 pub trait IndicatorEvaluator: Send + Sync {
     fn warmup(&self) -> usize;
     fn update(&mut self, candle: &Candle) -> Result<()>;
+    /// Names of the outputs [`value`](Self::value) reads; the first is the
+    /// primary output, read by the indicator's id alone or as `value`
+    fn outputs(&self) -> &'static [&'static str];
     fn value(&self, output: &str) -> Result<f64>;
     fn reset(&mut self);
-}
 
-pub struct IndicatorRegistry;
+    /// Listed name of `output`, with `value` naming the primary output
+    fn output_name(&self, output: &str) -> Option<&'static str> {
+        output_name(self.outputs(), output)
+    }
+}
 
-impl IndicatorRegistry {
-    pub fn new() -> Self {
-        Self
-    }
-
-    pub fn get_lookback(&self, indicator_type: &str, params: &HashMap<String, f64>) -> Result<usize> {
-        match indicator_type.to_uppercase().as_str() {
-            "SMA" => {
-                let length = params.get("length").copied().unwrap_or(20.0) as usize;
-                Ok(length)
-            }
-            "EMA" => {
-                let length = params.get("length").copied().unwrap_or(20.0) as usize;
-                Ok(length * 3) // Safe warmup
-            }
-            "WMA" => {
-                let length = params.get("length").copied().unwrap_or(20.0) as usize;
-                Ok(length)
-            }
-            "RSI" => {
-                let length = params.get("length").copied().unwrap_or(14.0) as usize;
-                Ok(length + 1)
-            }
-            "MACD" => {
-                let slow = params.get("slow").copied().unwrap_or(26.0) as usize;
-                let signal = params.get("signal").copied().unwrap_or(9.0) as usize;
-                Ok(slow + signal * 3)
-            }
-            "BBANDS" => {
-                let length = params.get("length").copied().unwrap_or(20.0) as usize;
-                Ok(length)
-            }
-            "STOCH" | "STOCHASTIC" => {
-                let k_period = params.get("k_period").or(params.get("period")).copied().unwrap_or(14.0) as usize;
-                let d_period = params.get("d_period").or(params.get("smooth")).copied().unwrap_or(3.0) as usize;
-                Ok(k_period + d_period)
-            }
-            "ATR" => {
-                let period = params.get("period").or(params.get("length")).copied().unwrap_or(14.0) as usize;
-                Ok(period + 1)
-            }
-            "ADX" => {
-                let period = params.get("period").or(params.get("length")).copied().unwrap_or(14.0) as usize;
-                Ok(period * 2) // ADX needs more warmup
-            }
-            "OBV" => {
-                Ok(1) // OBV starts immediately
-            }
-            _ => anyhow::bail!("Unknown indicator type: {}", indicator_type),
-        }
+/// Name in `outputs` of `output`, with `value` naming the first
+pub fn output_name(outputs: &[&'static str], output: &str) -> Option<&'static str> {
+    if output == "value" {
+        return outputs.first().copied();
     }
+    outputs.iter().find(|name| **name == output).copied()
 }
 
-pub fn create_indicator(
-    indicator_type: &str,
-    params: &HashMap<String, f64>,
-) -> Result<Box<dyn IndicatorEvaluator>> {
-    match indicator_type.to_uppercase().as_str() {
-        "SMA" => {
-            let length = params.get("length").copied().unwrap_or(20.0) as usize;
-            let source = params.get("source")
-                .and_then(|v| v.to_string().parse().ok())
-                .unwrap_or_else(|| "close".to_string());
-            Ok(Box::new(SmaIndicator::new(length, source)?))
-        }
-        "EMA" => {
-            let length = params.get("length").copied().unwrap_or(20.0) as usize;
-            let source = params.get("source")
-                .and_then(|v| v.to_string().parse().ok())
-                .unwrap_or_else(|| "close".to_string());
-            Ok(Box::new(EmaIndicator::new(length, source)?))
-        }
-        "WMA" => {
-            let length = params.get("length").copied().unwrap_or(20.0) as usize;
-            let source = params.get("source")
-                .and_then(|v| v.to_string().parse().ok())
-                .unwrap_or_else(|| "close".to_string());
-            Ok(Box::new(WmaIndicator::new(length, source)?))
-        }
-        "RSI" => {
-            let length = params.get("length").copied().unwrap_or(14.0) as usize;
-            let source = params.get("source")
-                .and_then(|v| v.to_string().parse().ok())
-                .unwrap_or_else(|| "close".to_string());
-            Ok(Box::new(RsiIndicator::new(length, source)?))
+type Factory = dyn Fn(&IndicatorParams) -> Result<Box<dyn IndicatorEvaluator>> + Send + Sync;
+type Lookback = dyn Fn(&IndicatorParams) -> Result<usize> + Send + Sync;
+
+/// Everything the registry knows about an indicator type: how to build its
+/// evaluators, the parameters it takes, the bars it needs to warm up and the
+/// outputs it has
+#[derive(Clone)]
+pub struct IndicatorDefinition {
+    name: String,
+    aliases: Vec<String>,
+    params: Vec<ParamSpec>,
+    outputs: &'static [&'static str],
+    factory: Arc<Factory>,
+    lookback: Option<Arc<Lookback>>,
+}
+
+impl IndicatorDefinition {
+    /// An indicator type `name` with `outputs`, the first being the primary
+    /// one, whose evaluators `factory` builds from checked parameters. It takes
+    /// no parameters and warms up over its evaluator's
+    /// [`warmup`](IndicatorEvaluator::warmup) until given others.
+    pub fn new(
+        name: &str,
+        outputs: &'static [&'static str],
+        factory: impl Fn(&IndicatorParams) -> Result<Box<dyn IndicatorEvaluator>> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            aliases: Vec::new(),
+            params: Vec::new(),
+            outputs,
+            factory: Arc::new(factory),
+            lookback: None,
         }
-        "MACD" => {
-            let fast = params.get("fast").copied().unwrap_or(12.0) as usize;
-            let slow = params.get("slow").copied().unwrap_or(26.0) as usize;
-            let signal = params.get("signal").copied().unwrap_or(9.0) as usize;
-            let source = params.get("source")
-                .and_then(|v| v.to_string().parse().ok())
-                .unwrap_or_else(|| "close".to_string());
-            Ok(Box::new(MacdIndicator::new(fast, slow, signal, source)?))
+    }
+
+    /// Other names strategies can give as the type
+    pub fn with_aliases(mut self, aliases: &[&str]) -> Self {
+        self.aliases = aliases.iter().map(|alias| alias.to_string()).collect();
+        self
+    }
+
+    pub fn with_params(mut self, params: impl IntoIterator<Item = ParamSpec>) -> Self {
+        self.params = params.into_iter().collect();
+        self
+    }
+
+    /// Bars the indicator needs before its outputs are usable
+    pub fn with_lookback(mut self, lookback: impl Fn(&IndicatorParams) -> Result<usize> + Send + Sync + 'static) -> Self {
+        self.lookback = Some(Arc::new(lookback));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    pub fn params(&self) -> &[ParamSpec] {
+        &self.params
+    }
+
+    pub fn outputs(&self) -> &'static [&'static str] {
+        self.outputs
+    }
+
+    fn create(&self, params: &IndicatorParams) -> Result<Box<dyn IndicatorEvaluator>> {
+        let evaluator = (self.factory)(params)?;
+        if evaluator.outputs() != self.outputs {
+            anyhow::bail!(
+                "{} evaluator has outputs {:?} but is registered with {:?}",
+                self.name,
+                evaluator.outputs(),
+                self.outputs
+            );
         }
-        "BBANDS" | "BB" => {
-            let length = params.get("length").copied().unwrap_or(20.0) as usize;
-            let std_dev = params.get("std").or(params.get("std_dev")).copied().unwrap_or(2.0);
-            let source = params.get("source")
-                .and_then(|v| v.to_string().parse().ok())
-                .unwrap_or_else(|| "close".to_string());
-            Ok(Box::new(BBandsIndicator::new(length, std_dev, source)?))
+        Ok(evaluator)
+    }
+
+    fn lookback(&self, params: &IndicatorParams) -> Result<usize> {
+        match &self.lookback {
+            Some(lookback) => lookback(params),
+            None => Ok(self.create(params)?.warmup()),
         }
-        "STOCH" | "STOCHASTIC" => {
-            let k_period = params.get("k_period").or(params.get("period")).copied().unwrap_or(14.0) as usize;
-            let k_smooth = params.get("k_smooth").copied().unwrap_or(1.0) as usize;
-            let d_period = params.get("d_period").or(params.get("smooth")).copied().unwrap_or(3.0) as usize;
-            Ok(Box::new(StochIndicator::new(k_period, k_smooth, d_period)?))
+    }
+}
+
+/// Indicator types strategies can use, by name or alias in any case: the
+/// built-in ones and any registered with [`register`](Self::register)
+#[derive(Clone)]
+pub struct IndicatorRegistry {
+    /// By upper-cased name and alias
+    definitions: HashMap<String, Arc<IndicatorDefinition>>,
+}
+
+impl Default for IndicatorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IndicatorRegistry {
+    /// Registry of the built-in indicators
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        for definition in builtin_definitions() {
+            registry.register(definition).expect("built-in indicator names are unique");
         }
-        "ATR" => {
-            let period = params.get("period").or(params.get("length")).copied().unwrap_or(14.0) as usize;
-            Ok(Box::new(AtrIndicator::new(period)?))
+        registry
+    }
+
+    /// Registry without any indicators
+    pub fn empty() -> Self {
+        Self {
+            definitions: HashMap::new(),
         }
-        "ADX" => {
-            let period = params.get("period").or(params.get("length")).copied().unwrap_or(14.0) as usize;
-            Ok(Box::new(AdxIndicator::new(period)?))
+    }
+
+    /// Add an indicator type; its name and aliases must not be taken
+    pub fn register(&mut self, definition: IndicatorDefinition) -> Result<()> {
+        let names: Vec<String> = std::iter::once(&definition.name)
+            .chain(&definition.aliases)
+            .map(|name| name.to_uppercase())
+            .collect();
+        if let Some(name) = names.iter().find(|name| self.definitions.contains_key(*name)) {
+            anyhow::bail!("Indicator type {} is already registered", name);
         }
-        "OBV" => {
-            Ok(Box::new(ObvIndicator::new()?))
+        let definition = Arc::new(definition);
+        for name in names {
+            self.definitions.insert(name, definition.clone());
         }
-        _ => anyhow::bail!("Unknown indicator type: {}", indicator_type),
+        Ok(())
+    }
+
+    pub fn definition(&self, indicator_type: &str) -> Result<&IndicatorDefinition> {
+        self.definitions
+            .get(&indicator_type.to_uppercase())
+            .map(Arc::as_ref)
+            .with_context(|| format!("Unknown indicator type: {}", indicator_type))
+    }
+
+    /// Names of the registered indicator types, without aliases, sorted
+    pub fn types(&self) -> Vec<&str> {
+        let mut types: Vec<&str> = self.definitions.values().map(|d| d.name.as_str()).collect();
+        types.sort();
+        types.dedup();
+        types
+    }
+
+    /// Parameters `indicator_type` accepts
+    pub fn params(&self, indicator_type: &str) -> Result<&[ParamSpec]> {
+        Ok(self.definition(indicator_type)?.params())
+    }
+
+    /// Outputs of `indicator_type`, the primary one first
+    pub fn outputs(&self, indicator_type: &str) -> Result<&'static [&'static str]> {
+        Ok(self.definition(indicator_type)?.outputs())
+    }
+
+    /// Check `params` against the schema of `indicator_type`, filling in defaults
+    pub fn resolve_params(
+        &self,
+        indicator_type: &str,
+        params: &HashMap<String, ParamValue>,
+    ) -> Result<IndicatorParams> {
+        let definition = self.definition(indicator_type)?;
+        IndicatorParams::resolve(definition.params(), params)
+            .with_context(|| format!("Invalid {} parameters", definition.name))
+    }
+
+    pub fn get_lookback(&self, indicator_type: &str, params: &HashMap<String, ParamValue>) -> Result<usize> {
+        let params = self.resolve_params(indicator_type, params)?;
+        self.definition(indicator_type)?.lookback(&params)
+    }
+
+    pub fn create(
+        &self,
+        indicator_type: &str,
+        params: &HashMap<String, ParamValue>,
+    ) -> Result<Box<dyn IndicatorEvaluator>> {
+        let params = self.resolve_params(indicator_type, params)?;
+        self.definition(indicator_type)?.create(&params)
     }
 }
 
+/// Create a built-in indicator
+pub fn create_indicator(
+    indicator_type: &str,
+    params: &HashMap<String, ParamValue>,
+) -> Result<Box<dyn IndicatorEvaluator>> {
+    IndicatorRegistry::new().create(indicator_type, params)
+}
+
+/// Latest time an anchored VWAP can start from, the largest integer f64 holds exactly
+const MAX_TIMESTAMP_MS: f64 = 9_007_199_254_740_991.0;
+
+fn source(params: &IndicatorParams) -> Result<String> {
+    params.str("source").map(str::to_string)
+}
+
+fn builtin_definitions() -> Vec<IndicatorDefinition> {
+    vec![
+        IndicatorDefinition::new("SMA", &["value"], |p| {
+            Ok(Box::new(SmaIndicator::new(p.usize("length")?, source(p)?)?))
+        })
+        .with_params([ParamSpec::period("length", 20.0, "Bars averaged").alias(&["period"]), ParamSpec::source()])
+        .with_lookback(|p| p.usize("length")),
+        IndicatorDefinition::new("EMA", &["value"], |p| {
+            Ok(Box::new(EmaIndicator::new(p.usize("length")?, source(p)?)?))
+        })
+        .with_params([
+            ParamSpec::period("length", 20.0, "Bars for the smoothing factor 2 / (length + 1)").alias(&["period"]),
+            ParamSpec::source(),
+        ])
+        .with_lookback(|p| Ok(p.usize("length")? * 3)), // Safe warmup
+        IndicatorDefinition::new("WMA", &["value"], |p| {
+            Ok(Box::new(WmaIndicator::new(p.usize("length")?, source(p)?)?))
+        })
+        .with_params([ParamSpec::period("length", 20.0, "Bars averaged").alias(&["period"]), ParamSpec::source()])
+        .with_lookback(|p| p.usize("length")),
+        IndicatorDefinition::new("RSI", &["value"], |p| {
+            Ok(Box::new(RsiIndicator::new(p.usize("length")?, source(p)?)?))
+        })
+        .with_params([ParamSpec::period("length", 14.0, "Bars of Wilder smoothing").alias(&["period"]), ParamSpec::source()])
+        .with_lookback(|p| Ok(p.usize("length")? + 1)),
+        IndicatorDefinition::new("MACD", &["macd", "signal", "histogram"], |p| {
+            Ok(Box::new(MacdIndicator::new(
+                p.usize("fast")?,
+                p.usize("slow")?,
+                p.usize("signal")?,
+                source(p)?,
+            )?))
+        })
+        .with_params([
+            ParamSpec::period("fast", 12.0, "Fast EMA length"),
+            ParamSpec::period("slow", 26.0, "Slow EMA length"),
+            ParamSpec::period("signal", 9.0, "Signal EMA length"),
+            ParamSpec::source(),
+        ])
+        .with_lookback(|p| Ok(p.usize("slow")? + p.usize("signal")? * 3)),
+        IndicatorDefinition::new("BBANDS", &["middle", "upper", "lower"], |p| {
+            Ok(Box::new(BBandsIndicator::new(p.usize("length")?, p.f64("std")?, source(p)?)?))
+        })
+        .with_aliases(&["BB"])
+        .with_params([
+            ParamSpec::period("length", 20.0, "Bars of the middle band SMA").alias(&["period"]),
+            ParamSpec::number("std", 2.0, 0.0, 100.0, "Band width in standard deviations").alias(&["std_dev"]),
+            ParamSpec::source(),
+        ])
+        .with_lookback(|p| p.usize("length")),
+        IndicatorDefinition::new("STOCH", &["k", "d"], |p| {
+            Ok(Box::new(StochIndicator::new(p.usize("k_period")?, p.usize("k_smooth")?, p.usize("d_period")?)?))
+        })
+        .with_aliases(&["STOCHASTIC"])
+        .with_params([
+            ParamSpec::period("k_period", 14.0, "Bars of the high-low range").alias(&["period"]),
+            ParamSpec::period("k_smooth", 1.0, "Bars smoothing %K"),
+            ParamSpec::period("d_period", 3.0, "Bars of the %D average").alias(&["smooth"]),
+        ])
+        // %D is valid once k_smooth %K values have filled d_period
+        .with_lookback(|p| Ok(p.usize("k_period")? + p.usize("k_smooth")? - 1 + p.usize("d_period")? - 1)),
+        IndicatorDefinition::new("ATR", &["value"], |p| Ok(Box::new(AtrIndicator::new(p.usize("period")?)?)))
+            .with_params([ParamSpec::period("period", 14.0, "Bars of Wilder smoothing").alias(&["length"])])
+            .with_lookback(|p| Ok(p.usize("period")? + 1)),
+        IndicatorDefinition::new("ADX", &["adx", "plus_di", "minus_di", "dx"], |p| {
+            Ok(Box::new(AdxIndicator::new(p.usize("period")?)?))
+        })
+        .with_params([ParamSpec::period("period", 14.0, "Bars of Wilder smoothing").alias(&["length"])])
+        .with_lookback(|p| Ok(p.usize("period")? * 2)), // ADX needs more warmup
+        // OBV starts immediately
+        IndicatorDefinition::new("OBV", &["value"], |_| Ok(Box::new(ObvIndicator::new()?))).with_lookback(|_| Ok(1)),
+        IndicatorDefinition::new("VWAP", &["value"], |p| {
+            let anchor = match (p.f64("anchor")?, p.str("session")?) {
+                (anchor_ms, _) if anchor_ms > 0.0 => VwapAnchor::Time(anchor_ms as u64),
+                (_, "week") => VwapAnchor::Week,
+                _ => VwapAnchor::Day,
+            };
+            Ok(Box::new(VwapIndicator::new(anchor, source(p)?)?))
+        })
+        .with_params([
+            ParamSpec::choice("session", &["day", "week"], "day", "UTC session after which the VWAP starts over"),
+            ParamSpec::number("anchor", 0.0, 0.0, MAX_TIMESTAMP_MS, "Open time (ms) to accumulate from without resetting; 0 for sessions"),
+            ParamSpec::choice("source", PRICE_SOURCES, "hlc3", "Price to compute on"),
+        ]),
+        IndicatorDefinition::new("KELTNER", &["middle", "upper", "lower"], |p| {
+            Ok(Box::new(KeltnerIndicator::new(
+                p.usize("length")?,
+                p.f64("multiplier")?,
+                p.usize("atr_length")?,
+                source(p)?,
+            )?))
+        })
+        .with_aliases(&["KC"])
+        .with_params([
+            ParamSpec::period("length", 20.0, "Bars of the middle line EMA").alias(&["period"]),
+            ParamSpec::number("multiplier", 2.0, 0.0, 100.0, "Band width in ATRs").alias(&["mult"]),
+            ParamSpec::period("atr_length", 10.0, "Bars of the ATR").alias(&["atr_period"]),
+            ParamSpec::source(),
+        ]),
+        IndicatorDefinition::new("DONCHIAN", &["middle", "upper", "lower"], |p| {
+            Ok(Box::new(DonchianIndicator::new(p.usize("length")?)?))
+        })
+        .with_aliases(&["DC"])
+        .with_params([ParamSpec::period("length", 20.0, "Bars of the high-low range").alias(&["period"])]),
+        IndicatorDefinition::new("SUPERTREND", &["value", "direction", "upper", "lower"], |p| {
+            Ok(Box::new(SuperTrendIndicator::new(p.usize("period")?, p.f64("multiplier")?)?))
+        })
+        .with_params([
+            ParamSpec::period("period", 10.0, "Bars of the ATR").alias(&["length"]),
+            ParamSpec::number("multiplier", 3.0, 0.0, 100.0, "Band distance from hl2 in ATRs").alias(&["mult"]),
+        ]),
+        IndicatorDefinition::new("PSAR", &["value", "direction"], |p| {
+            Ok(Box::new(PsarIndicator::new(p.f64("start")?, p.f64("increment")?, p.f64("max")?)?))
+        })
+        .with_aliases(&["SAR"])
+        .with_params([
+            ParamSpec::number("start", 0.02, 0.0, 1.0, "Acceleration factor after each reversal"),
+            ParamSpec::number("increment", 0.02, 0.0, 1.0, "Added to the factor at each new extreme").alias(&["step"]),
+            ParamSpec::number("max", 0.2, 0.0, 1.0, "Largest acceleration factor").alias(&["maximum"]),
+        ]),
+        IndicatorDefinition::new("ICHIMOKU", &["tenkan", "kijun", "senkou_a", "senkou_b", "chikou"], |p| {
+            Ok(Box::new(IchimokuIndicator::new(
+                p.usize("tenkan")?,
+                p.usize("kijun")?,
+                p.usize("senkou")?,
+                p.usize("displacement")?,
+            )?))
+        })
+        .with_params([
+            ParamSpec::period("tenkan", 9.0, "Bars of the conversion line range"),
+            ParamSpec::period("kijun", 26.0, "Bars of the base line range"),
+            ParamSpec::period("senkou", 52.0, "Bars of the leading span B range"),
+            ParamSpec::period("displacement", 26.0, "Bars the leading spans are shifted ahead"),
+        ]),
+        IndicatorDefinition::new("HMA", &["value"], |p| {
+            Ok(Box::new(HmaIndicator::new(p.usize("length")?, source(p)?)?))
+        })
+        .with_params([ParamSpec::period("length", 20.0, "Bars averaged").alias(&["period"]), ParamSpec::source()]),
+        IndicatorDefinition::new("DEMA", &["value"], |p| {
+            Ok(Box::new(DemaIndicator::new(p.usize("length")?, source(p)?)?))
+        })
+        .with_params([ParamSpec::period("length", 20.0, "Bars of each EMA").alias(&["period"]), ParamSpec::source()]),
+        IndicatorDefinition::new("TEMA", &["value"], |p| {
+            Ok(Box::new(TemaIndicator::new(p.usize("length")?, source(p)?)?))
+        })
+        .with_params([ParamSpec::period("length", 20.0, "Bars of each EMA").alias(&["period"]), ParamSpec::source()]),
+        IndicatorDefinition::new("KAMA", &["value"], |p| {
+            Ok(Box::new(KamaIndicator::new(p.usize("length")?, p.usize("fast")?, p.usize("slow")?, source(p)?)?))
+        })
+        .with_params([
+            ParamSpec::period("length", 10.0, "Bars of the efficiency ratio").alias(&["period"]),
+            ParamSpec::period("fast", 2.0, "EMA length at full efficiency"),
+            ParamSpec::period("slow", 30.0, "EMA length at no efficiency"),
+            ParamSpec::source(),
+        ]),
+        IndicatorDefinition::new("MFI", &["value"], |p| Ok(Box::new(MfiIndicator::new(p.usize("period")?)?)))
+            .with_params([ParamSpec::period("period", 14.0, "Bars of money flow summed").alias(&["length"])]),
+        IndicatorDefinition::new("CMF", &["value"], |p| Ok(Box::new(CmfIndicator::new(p.usize("period")?)?)))
+            .with_params([ParamSpec::period("period", 20.0, "Bars of money flow summed").alias(&["length"])]),
+        IndicatorDefinition::new("AD", &["value"], |_| Ok(Box::new(AdIndicator::new()?))).with_aliases(&["ADL"]),
+        IndicatorDefinition::new("VWMA", &["value"], |p| {
+            Ok(Box::new(VwmaIndicator::new(p.usize("length")?, source(p)?)?))
+        })
+        .with_params([ParamSpec::period("length", 20.0, "Bars averaged").alias(&["period"]), ParamSpec::source()]),
+        IndicatorDefinition::new("VOLOSC", &["value"], |p| {
+            Ok(Box::new(VolumeOscillatorIndicator::new(p.usize("fast")?, p.usize("slow")?)?))
+        })
+        .with_aliases(&["VO"])
+        .with_params([
+            ParamSpec::period("fast", 5.0, "Bars of the fast volume EMA"),
+            ParamSpec::period("slow", 10.0, "Bars of the slow volume EMA"),
+        ]),
+        IndicatorDefinition::new("EOM", &["value"], |p| {
+            Ok(Box::new(EomIndicator::new(p.usize("period")?, p.f64("divisor")?)?))
+        })
+        .with_aliases(&["EMV"])
+        .with_params([
+            ParamSpec::period("period", 14.0, "Bars averaged").alias(&["length"]),
+            ParamSpec::number("divisor", 10_000.0, 1e-9, 1e15, "Volume units per box; scales the values only"),
+        ]),
+        IndicatorDefinition::new("FORCE", &["value"], |p| {
+            Ok(Box::new(ForceIndexIndicator::new(p.usize("length")?)?))
+        })
+        .with_aliases(&["FI"])
+        .with_params([ParamSpec::period("length", 13.0, "Bars of the EMA").alias(&["period"])]),
+        IndicatorDefinition::new("RVOL", &["value"], |p| {
+            Ok(Box::new(RelativeVolumeIndicator::new(p.usize("length")?)?))
+        })
+        .with_params([ParamSpec::period("length", 20.0, "Earlier bars averaged").alias(&["period"])]),
+    ]
+}
@@ -1,178 +1,356 @@
-#[cfg(test)]
-mod tests {
-    use crate::data::types::Candle;
-    use crate::indicators2::create_indicator;
-    use std::collections::HashMap;
-
-    fn create_test_candle(time: u64, open: f64, high: f64, low: f64, close: f64, volume: f64) -> Candle {
-        Candle {
-            time_open: time,
-            time_close: time + 60000,
-            coin: "ETH".to_string(),
-            interval: "1m".to_string(),
-            open,
-            close,
-            high,
-            low,
-            volume,
-            num_trades: 100,
-        }
+use crate::data::types::Candle;
+use crate::indicators2::{create_indicator, IndicatorEvaluator, ParamValue};
+use std::collections::HashMap;
+
+fn create_test_candle(time: u64, open: f64, high: f64, low: f64, close: f64, volume: f64) -> Candle {
+    Candle {
+        time_open: time,
+        time_close: time + 60000,
+        coin: "ETH".to_string(),
+        interval: "1m".to_string(),
+        open,
+        close,
+        high,
+        low,
+        volume,
+        num_trades: 100,
+    }
+}
+
+#[test]
+fn test_sma() {
+    let mut params = HashMap::new();
+    params.insert("length".to_string(), 3.0.into());
+    let mut sma = create_indicator("SMA", &params).unwrap();
+    
+    let candles = vec![
+        create_test_candle(1000, 10.0, 12.0, 9.0, 11.0, 1000.0),
+        create_test_candle(2000, 11.0, 13.0, 10.0, 12.0, 1100.0),
+        create_test_candle(3000, 12.0, 14.0, 11.0, 13.0, 1200.0),
+    ];
+
+    for candle in &candles {
+        sma.update(candle).unwrap();
+    }
+
+    // SMA of [11, 12, 13] = 12.0
+    assert!((sma.value("value").unwrap() - 12.0).abs() < 0.001);
+}
+
+#[test]
+fn test_ema() {
+    let mut params = HashMap::new();
+    params.insert("length".to_string(), 3.0.into());
+    let mut ema = create_indicator("EMA", &params).unwrap();
+    
+    let candles = vec![
+        create_test_candle(1000, 10.0, 12.0, 9.0, 11.0, 1000.0),
+        create_test_candle(2000, 11.0, 13.0, 10.0, 12.0, 1100.0),
+        create_test_candle(3000, 12.0, 14.0, 11.0, 13.0, 1200.0),
+    ];
+
+    for candle in &candles {
+        ema.update(candle).unwrap();
+    }
+
+    // EMA should be close to recent values
+    let value = ema.value("value").unwrap();
+    assert!(value > 11.0 && value < 13.5);
+}
+
+#[test]
+fn test_rsi() {
+    let mut params = HashMap::new();
+    params.insert("length".to_string(), 14.0.into());
+    let mut rsi = create_indicator("RSI", &params).unwrap();
+    
+    // Create upward trending candles
+    let mut candles = Vec::new();
+    for i in 0..20 {
+        let price = 100.0 + i as f64;
+        candles.push(create_test_candle(
+            (i as u64) * 1000,
+            price - 1.0,
+            price + 1.0,
+            price - 2.0,
+            price,
+            1000.0,
+        ));
     }
 
-    #[test]
-    fn test_sma() {
-        let mut params = HashMap::new();
-        params.insert("length".to_string(), 3.0);
-        let mut sma = create_indicator("SMA", &params).unwrap();
-        
-        let candles = vec![
-            create_test_candle(1000, 10.0, 12.0, 9.0, 11.0, 1000.0),
-            create_test_candle(2000, 11.0, 13.0, 10.0, 12.0, 1100.0),
-            create_test_candle(3000, 12.0, 14.0, 11.0, 13.0, 1200.0),
-        ];
-
-        for candle in &candles {
-            sma.update(candle).unwrap();
-        }
-
-        // SMA of [11, 12, 13] = 12.0
-        assert!((sma.value("value").unwrap() - 12.0).abs() < 0.001);
+    for candle in &candles {
+        rsi.update(candle).unwrap();
     }
 
-    #[test]
-    fn test_ema() {
-        let mut params = HashMap::new();
-        params.insert("length".to_string(), 3.0);
-        let mut ema = create_indicator("EMA", &params).unwrap();
-        
-        let candles = vec![
-            create_test_candle(1000, 10.0, 12.0, 9.0, 11.0, 1000.0),
-            create_test_candle(2000, 11.0, 13.0, 10.0, 12.0, 1100.0),
-            create_test_candle(3000, 12.0, 14.0, 11.0, 13.0, 1200.0),
-        ];
-
-        for candle in &candles {
-            ema.update(candle).unwrap();
-        }
-
-        // EMA should be close to recent values
-        let value = ema.value("value").unwrap();
-        assert!(value > 11.0 && value < 13.5);
+    // RSI should be high (above 50) for upward trend
+    let value = rsi.value("value").unwrap();
+    assert!(value > 50.0 && value <= 100.0);
+}
+
+#[test]
+fn test_macd() {
+    let mut params = HashMap::new();
+    params.insert("fast".to_string(), 12.0.into());
+    params.insert("slow".to_string(), 26.0.into());
+    params.insert("signal".to_string(), 9.0.into());
+    let mut macd = create_indicator("MACD", &params).unwrap();
+
+    // Create enough candles for MACD warmup
+    let mut candles = Vec::new();
+    for i in 0..50 {
+        let price = 100.0 + (i as f64) * 0.5;
+        candles.push(create_test_candle(
+            (i as u64) * 1000,
+            price - 1.0,
+            price + 1.0,
+            price - 2.0,
+            price,
+            1000.0,
+        ));
+    }
+
+    for candle in &candles {
+        macd.update(candle).unwrap();
+    }
+
+    // Check MACD outputs exist
+    let macd_value = macd.value("macd").unwrap();
+    let signal_value = macd.value("signal").unwrap();
+    let histogram_value = macd.value("histogram").unwrap();
+
+    assert!((histogram_value - (macd_value - signal_value)).abs() < 0.001);
+}
+
+#[test]
+fn test_bbands() {
+    let mut params = HashMap::new();
+    params.insert("length".to_string(), 20.0.into());
+    params.insert("std".to_string(), 2.0.into());
+    let mut bb = create_indicator("BBANDS", &params).unwrap();
+
+    // Create enough candles
+    let mut candles = Vec::new();
+    for i in 0..30 {
+        let price = 100.0 + (i % 5) as f64;
+        candles.push(create_test_candle(
+            (i as u64) * 1000,
+            price - 1.0,
+            price + 1.0,
+            price - 2.0,
+            price,
+            1000.0,
+        ));
     }
 
-    #[test]
-    fn test_rsi() {
-        let mut params = HashMap::new();
-        params.insert("length".to_string(), 14.0);
-        let mut rsi = create_indicator("RSI", &params).unwrap();
-        
-        // Create upward trending candles
-        let mut candles = Vec::new();
-        for i in 0..20 {
-            let price = 100.0 + i as f64;
-            candles.push(create_test_candle(
-                (i as u64) * 1000,
-                price - 1.0,
-                price + 1.0,
-                price - 2.0,
-                price,
-                1000.0,
-            ));
-        }
-
-        for candle in &candles {
-            rsi.update(candle).unwrap();
-        }
-
-        // RSI should be high (above 50) for upward trend
-        let value = rsi.value("value").unwrap();
-        assert!(value > 50.0 && value <= 100.0);
+    for candle in &candles {
+        bb.update(candle).unwrap();
     }
 
-    #[test]
-    fn test_macd() {
-        let mut params = HashMap::new();
-        params.insert("fast".to_string(), 12.0);
-        params.insert("slow".to_string(), 26.0);
-        params.insert("signal".to_string(), 9.0);
-        let mut macd = create_indicator("MACD", &params).unwrap();
-
-        // Create enough candles for MACD warmup
-        let mut candles = Vec::new();
-        for i in 0..50 {
-            let price = 100.0 + (i as f64) * 0.5;
-            candles.push(create_test_candle(
-                (i as u64) * 1000,
-                price - 1.0,
-                price + 1.0,
-                price - 2.0,
-                price,
-                1000.0,
-            ));
-        }
-
-        for candle in &candles {
-            macd.update(candle).unwrap();
-        }
-
-        // Check MACD outputs exist
-        let macd_value = macd.value("macd").unwrap();
-        let signal_value = macd.value("signal").unwrap();
-        let histogram_value = macd.value("histogram").unwrap();
-
-        assert!((histogram_value - (macd_value - signal_value)).abs() < 0.001);
+    let upper = bb.value("upper").unwrap();
+    let middle = bb.value("middle").unwrap();
+    let lower = bb.value("lower").unwrap();
+
+    assert!(upper > middle);
+    assert!(middle > lower);
+}
+
+#[test]
+fn test_obv() {
+    let mut obv = create_indicator("OBV", &std::collections::HashMap::new()).unwrap();
+
+    let candles = vec![
+        create_test_candle(1000, 10.0, 12.0, 9.0, 11.0, 1000.0), // Price up
+        create_test_candle(2000, 11.0, 13.0, 10.0, 10.0, 2000.0), // Price down
+        create_test_candle(3000, 10.0, 12.0, 9.0, 12.0, 1500.0), // Price up
+    ];
+
+    for candle in &candles {
+        obv.update(candle).unwrap();
     }
 
-    #[test]
-    fn test_bbands() {
-        let mut params = HashMap::new();
-        params.insert("length".to_string(), 20.0);
-        params.insert("std".to_string(), 2.0);
-        let mut bb = create_indicator("BBANDS", &params).unwrap();
-
-        // Create enough candles
-        let mut candles = Vec::new();
-        for i in 0..30 {
-            let price = 100.0 + (i % 5) as f64;
-            candles.push(create_test_candle(
-                (i as u64) * 1000,
-                price - 1.0,
-                price + 1.0,
-                price - 2.0,
-                price,
-                1000.0,
-            ));
-        }
-
-        for candle in &candles {
-            bb.update(candle).unwrap();
-        }
-
-        let upper = bb.value("upper").unwrap();
-        let middle = bb.value("middle").unwrap();
-        let lower = bb.value("lower").unwrap();
-
-        assert!(upper > middle);
-        assert!(middle > lower);
+    // OBV should accumulate: +1000 - 2000 + 1500 = 500
+    let value = obv.value("value").unwrap();
+    assert!((value - 500.0).abs() < 0.001);
+}
+
+#[test]
+fn test_stoch_d_averages_k() {
+    let mut params = HashMap::new();
+    params.insert("k_period".to_string(), 2.0.into());
+    params.insert("d_period".to_string(), 2.0.into());
+    let mut stoch = create_indicator("STOCH", &params).unwrap();
+    assert_eq!(stoch.outputs(), ["k", "d"]);
+
+    // %K: (11 - 9) / (13 - 9) = 50, then (13 - 10) / (14 - 10) = 75
+    let candles = vec![
+        create_test_candle(1000, 10.0, 12.0, 9.0, 11.0, 1000.0),
+        create_test_candle(2000, 11.0, 13.0, 10.0, 11.0, 1100.0),
+        create_test_candle(3000, 12.0, 14.0, 11.0, 13.0, 1200.0),
+    ];
+    for candle in &candles {
+        stoch.update(candle).unwrap();
     }
+    assert!((stoch.value("k").unwrap() - 75.0).abs() < 1e-9);
+    assert!((stoch.value("d").unwrap() - 62.5).abs() < 1e-9);
+}
+
+/// 30 bars of a rising wave, with ranges and volumes that vary bar to bar
+fn wave_candles() -> Vec<Candle> {
+    let mut prev_close = None;
+    (0..30)
+        .map(|i| {
+            let close = 100.0 + 10.0 * (i as f64 / 3.0).sin() + i as f64 * 0.5;
+            let open = prev_close.replace(close).unwrap_or(close);
+            let high = close + 1.0 + (i % 3) as f64 * 0.5;
+            let low = close - 1.0 - (i % 2) as f64 * 0.5;
+            create_test_candle(i * 60000, open, high, low, close, 100.0 + 10.0 * (i % 5) as f64)
+        })
+        .collect()
+}
+
+/// Feed `candles` to a new `indicator_type`, returning the evaluator and the
+/// value of `output` after each bar
+fn run(
+    indicator_type: &str,
+    params: &[(&str, ParamValue)],
+    candles: &[Candle],
+    output: &str,
+) -> (Box<dyn IndicatorEvaluator>, Vec<f64>) {
+    let params: HashMap<String, ParamValue> = params.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+    let mut indicator = create_indicator(indicator_type, &params).unwrap();
+    let values = candles
+        .iter()
+        .map(|candle| {
+            indicator.update(candle).unwrap();
+            indicator.value(output).unwrap()
+        })
+        .collect();
+    (indicator, values)
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+}
+
+// Reference values below come from straightforward (non-streaming)
+// implementations of each indicator's textbook definition
+#[test]
+fn test_channel_indicators_reference_values() {
+    let candles = wave_candles();
+
+    let (donchian, _) = run("DONCHIAN", &[("length", 5.0.into())], &candles, "middle");
+    assert_close(donchian.value("upper").unwrap(), 122.87294108094694);
+    assert_close(donchian.value("lower").unwrap(), 110.60463226869685);
+    assert_close(donchian.value("middle").unwrap(), (122.87294108094694 + 110.60463226869685) / 2.0);
 
-    #[test]
-    fn test_obv() {
-        let mut obv = create_indicator("OBV", &std::collections::HashMap::new()).unwrap();
+    let params = [("length", 5.0.into()), ("multiplier", 2.0.into()), ("atr_length", 3.0.into())];
+    let (keltner, _) = run("KELTNER", &params, &candles, "middle");
+    assert_close(keltner.value("middle").unwrap(), 115.72483961055254);
+    assert_close(keltner.value("upper").unwrap(), 123.2598251819219);
+    assert_close(keltner.value("lower").unwrap(), 108.18985403918317);
 
-        let candles = vec![
-            create_test_candle(1000, 10.0, 12.0, 9.0, 11.0, 1000.0), // Price up
-            create_test_candle(2000, 11.0, 13.0, 10.0, 10.0, 2000.0), // Price down
-            create_test_candle(3000, 10.0, 12.0, 9.0, 12.0, 1500.0), // Price up
-        ];
+    let params = [("tenkan", 3.0.into()), ("kijun", 5.0.into()), ("senkou", 8.0.into()), ("displacement", 4.0.into())];
+    let (ichimoku, _) = run("ICHIMOKU", &params, &candles, "tenkan");
+    assert_close(ichimoku.value("tenkan").unwrap(), 114.6129085605572);
+    assert_close(ichimoku.value("kijun").unwrap(), 116.7387866748219);
+    assert_close(ichimoku.value("senkou_a").unwrap(), 120.51127489919477);
+    assert_close(ichimoku.value("senkou_b").unwrap(), 114.26546144393723);
+    assert_close(ichimoku.value("chikou").unwrap(), 112.10463226869685);
+    assert_eq!(ichimoku.warmup(), 12);
+}
+
+#[test]
+fn test_trend_following_reference_values() {
+    let candles = wave_candles();
 
-        for candle in &candles {
-            obv.update(candle).unwrap();
-        }
+    let params = [("period", 3.0.into()), ("multiplier", 2.0.into())];
+    let (supertrend, directions) = run("SUPERTREND", &params, &candles, "direction");
+    assert_close(supertrend.value("value").unwrap(), 119.88961784006621);
+    assert_close(supertrend.value("upper").unwrap(), 119.88961784006621);
+    assert_close(supertrend.value("lower").unwrap(), 104.81964669732749);
+    let flips: Vec<usize> = (1..directions.len()).filter(|&i| directions[i] * directions[i - 1] < 0.0).collect();
+    assert_eq!(flips, [10, 18, 28]);
+
+    let (psar, directions) = run("PSAR", &[], &candles, "direction");
+    assert_close(psar.value("value").unwrap(), 123.32507786986373);
+    let flips: Vec<usize> = (1..directions.len()).filter(|&i| directions[i] * directions[i - 1] < 0.0).collect();
+    assert_eq!(flips, [9, 18, 29]);
+    assert_eq!(directions[..2], [0.0, 1.0]);
+}
 
-        // OBV should accumulate: +1000 - 2000 + 1500 = 500
-        let value = obv.value("value").unwrap();
-        assert!((value - 500.0).abs() < 0.001);
+#[test]
+fn test_moving_average_pack_reference_values() {
+    let candles = wave_candles();
+    let length = |n: f64| [("length", ParamValue::from(n))];
+
+    assert_close(*run("HMA", &length(9.0), &candles, "value").1.last().unwrap(), 114.68014700799095);
+    assert_close(*run("DEMA", &length(5.0), &candles, "value").1.last().unwrap(), 114.53206385880534);
+    assert_close(*run("TEMA", &length(5.0), &candles, "value").1.last().unwrap(), 112.49026568384956);
+    let params = [("length", 5.0.into()), ("fast", 2.0.into()), ("slow", 10.0.into())];
+    assert_close(*run("KAMA", &params, &candles, "value").1.last().unwrap(), 115.51087008253695);
+
+    // A reset indicator gives the same values again
+    let (mut hma, values) = run("HMA", &length(9.0), &candles, "value");
+    hma.reset();
+    for candle in &candles {
+        hma.update(candle).unwrap();
     }
+    assert_close(hma.value("value").unwrap(), *values.last().unwrap());
+}
+
+#[test]
+fn test_vwap_sessions_and_anchor() {
+    const HOUR_MS: u64 = 60 * 60 * 1000;
+    const DAY_MS: u64 = 24 * HOUR_MS;
+    // Two bars before midnight UTC on Friday 1970-01-02, two after
+    let candles = vec![
+        create_test_candle(DAY_MS - 2 * HOUR_MS, 10.0, 10.0, 10.0, 10.0, 1.0),
+        create_test_candle(DAY_MS - HOUR_MS, 20.0, 20.0, 20.0, 20.0, 3.0),
+        create_test_candle(DAY_MS, 30.0, 30.0, 30.0, 30.0, 1.0),
+        create_test_candle(DAY_MS + HOUR_MS, 40.0, 40.0, 40.0, 40.0, 1.0),
+    ];
+
+    let (_, daily) = run("VWAP", &[], &candles, "value");
+    assert_eq!(daily, [10.0, 17.5, 30.0, 35.0]);
+    // The week started on Monday 1969-12-29, so it does not reset
+    let (_, weekly) = run("VWAP", &[("session", "WEEK".into())], &candles, "value");
+    assert_close(weekly[3], 140.0 / 6.0);
+    let anchor = (DAY_MS - HOUR_MS) as f64;
+    let (_, anchored) = run("VWAP", &[("anchor", anchor.into())], &candles, "value");
+    assert_eq!(anchored, [0.0, 20.0, 22.5, 26.0]);
 }
 
+#[test]
+fn test_volume_indicators_reference_values() {
+    let candles = wave_candles();
+    let period = |n: f64| [("period", ParamValue::from(n))];
+    let length = |n: f64| [("length", ParamValue::from(n))];
+    let last = |(_, values): (Box<dyn IndicatorEvaluator>, Vec<f64>)| *values.last().unwrap();
+
+    let (_, mfi) = run("MFI", &period(5.0), &candles, "value");
+    assert_close(mfi[7], 61.656289532960244);
+    assert_close(mfi[15], 39.71675961828083);
+    assert_eq!((mfi[12], mfi[20]), (0.0, 100.0));
+    assert_close(last(run("CMF", &period(5.0), &candles, "value")), -0.09777777777777777);
+    assert_close(last(run("AD", &[], &candles, "value")), -285.7142857142857);
+    assert_close(last(run("VWMA", &length(5.0), &candles, "value")), 116.78583925141318);
+    let params = [("fast", 3.0.into()), ("slow", 6.0.into())];
+    assert_close(last(run("VOLOSC", &params, &candles, "value")), 4.108464657713811);
+    assert_close(last(run("EOM", &period(5.0), &candles, "value")), -436.1745634983325);
+    assert_close(last(run("FORCE", &length(5.0), &candles, "value")), -239.36770252969657);
+    // 140 against the 100..=130 of the five bars before it
+    assert_close(last(run("RVOL", &length(5.0), &candles, "value")), 140.0 / 120.0);
+}
+
+#[test]
+fn test_volume_indicators_without_volume() {
+    let candles: Vec<Candle> = wave_candles().into_iter().map(|c| Candle { volume: 0.0, ..c }).collect();
+    for indicator_type in ["MFI", "CMF", "AD", "VOLOSC", "EOM", "FORCE", "RVOL"] {
+        let (_, values) = run(indicator_type, &[], &candles, "value");
+        assert!(values.iter().all(|v| v.is_finite()), "{}", indicator_type);
+    }
+    // VWMA falls back to the plain average
+    let (_, vwma) = run("VWMA", &[("length", 3.0.into())], &candles, "value");
+    let (_, sma) = run("SMA", &[("length", 3.0.into())], &candles, "value");
+    assert_close(vwma[29], sma[29]);
+}
@@ -1,4 +1,5 @@
 use crate::data::types::Candle;
+use std::collections::VecDeque;
 
 /// Ring buffer for efficient rolling window calculations
 /// Optimized for cache locality and branchless operations
@@ -41,6 +42,10 @@ impl RingBuffer {
         self.sum / self.size as f64
     }
 
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
     pub fn get(&self, offset: usize) -> Option<f64> {
         if offset >= self.size {
             return None;
@@ -104,71 +109,99 @@ impl RollingStdDev {
     }
 }
 
-/// Deque for tracking min/max in a sliding window
+/// Deque for tracking min/max in a sliding window, in amortized O(1) per
+/// push and memory bounded by the window
 pub struct MinMaxDeque {
-    values: Vec<f64>,
-    max_deque: Vec<usize>, // Indices of max values
-    min_deque: Vec<usize>, // Indices of min values
+    max_deque: VecDeque<(usize, f64)>, // (index, value) of max candidates
+    min_deque: VecDeque<(usize, f64)>, // (index, value) of min candidates
+    count: usize,
     window_size: usize,
 }
 
 impl MinMaxDeque {
     pub fn new(window_size: usize) -> Self {
         Self {
-            values: Vec::with_capacity(window_size * 2),
-            max_deque: Vec::new(),
-            min_deque: Vec::new(),
+            max_deque: VecDeque::with_capacity(window_size),
+            min_deque: VecDeque::with_capacity(window_size),
+            count: 0,
             window_size,
         }
     }
 
     pub fn push(&mut self, value: f64) {
-        let idx = self.values.len();
-        self.values.push(value);
+        let idx = self.count;
+        self.count += 1;
 
         // Remove indices outside window
-        while let Some(&front_idx) = self.max_deque.first() {
-            if idx - front_idx >= self.window_size {
-                self.max_deque.remove(0);
-            } else {
-                break;
-            }
+        while self.max_deque.front().is_some_and(|&(front_idx, _)| idx - front_idx >= self.window_size) {
+            self.max_deque.pop_front();
         }
-        while let Some(&front_idx) = self.min_deque.first() {
-            if idx - front_idx >= self.window_size {
-                self.min_deque.remove(0);
-            } else {
-                break;
-            }
+        while self.min_deque.front().is_some_and(|&(front_idx, _)| idx - front_idx >= self.window_size) {
+            self.min_deque.pop_front();
         }
 
         // Remove smaller values from max deque
-        while let Some(&back_idx) = self.max_deque.last() {
-            if self.values[back_idx] <= value {
-                self.max_deque.pop();
-            } else {
-                break;
-            }
+        while self.max_deque.back().is_some_and(|&(_, back)| back <= value) {
+            self.max_deque.pop_back();
         }
-        self.max_deque.push(idx);
+        self.max_deque.push_back((idx, value));
 
         // Remove larger values from min deque
-        while let Some(&back_idx) = self.min_deque.last() {
-            if self.values[back_idx] >= value {
-                self.min_deque.pop();
-            } else {
-                break;
-            }
+        while self.min_deque.back().is_some_and(|&(_, back)| back >= value) {
+            self.min_deque.pop_back();
         }
-        self.min_deque.push(idx);
+        self.min_deque.push_back((idx, value));
     }
 
     pub fn max(&self) -> Option<f64> {
-        self.max_deque.first().map(|&idx| self.values[idx])
+        self.max_deque.front().map(|&(_, value)| value)
     }
 
     pub fn min(&self) -> Option<f64> {
-        self.min_deque.first().map(|&idx| self.values[idx])
+        self.min_deque.front().map(|&(_, value)| value)
+    }
+
+    /// Whether a full window of values has been pushed
+    pub fn is_full(&self) -> bool {
+        self.count >= self.window_size
+    }
+}
+
+/// Highest high and lowest low over a sliding window of candles
+pub struct HighLowWindow {
+    highs: MinMaxDeque,
+    lows: MinMaxDeque,
+}
+
+impl HighLowWindow {
+    pub fn new(window_size: usize) -> Self {
+        Self {
+            highs: MinMaxDeque::new(window_size),
+            lows: MinMaxDeque::new(window_size),
+        }
+    }
+
+    #[inline]
+    pub fn push(&mut self, candle: &Candle) {
+        self.highs.push(candle.high);
+        self.lows.push(candle.low);
+    }
+
+    pub fn high(&self) -> f64 {
+        self.highs.max().unwrap_or(0.0)
+    }
+
+    pub fn low(&self) -> f64 {
+        self.lows.min().unwrap_or(0.0)
+    }
+
+    /// Midpoint of the highest high and lowest low
+    pub fn mid(&self) -> f64 {
+        (self.high() + self.low()) * 0.5
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.highs.is_full()
     }
 }
 
@@ -0,0 +1,251 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::data::resample::bucket_start;
+use crate::data::types::Candle;
+use crate::ingest::{L2Event, TradePrint};
+use crate::orderbook::OrderBook;
+use crate::util::interval_to_ms;
+
+/// Book price used for candles in intervals without trade prints
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PriceSource {
+    /// Midpoint of best bid and best ask
+    #[default]
+    Mid,
+    /// Size-weighted mid at the touch
+    Microprice,
+}
+
+impl FromStr for PriceSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mid" => Ok(Self::Mid),
+            "microprice" => Ok(Self::Microprice),
+            _ => Err(format!("Unknown price source: {s} (expected mid or microprice)")),
+        }
+    }
+}
+
+/// OHLC accumulator for one bucket
+#[derive(Debug, Clone, Copy)]
+struct Ohlc {
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+}
+
+impl Ohlc {
+    fn new(px: f64) -> Self {
+        Self {
+            open: px,
+            high: px,
+            low: px,
+            close: px,
+        }
+    }
+
+    fn update(&mut self, px: f64) {
+        self.high = self.high.max(px);
+        self.low = self.low.min(px);
+        self.close = px;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Bucket {
+    book: Option<Ohlc>,
+    trades: Option<Ohlc>,
+    volume: f64,
+    num_trades: i64,
+}
+
+/// Build candles at `interval` from L2 snapshots and, optionally, trade prints.
+///
+/// A candle's OHLC comes from trade prices when the interval saw trades, and
+/// from the book price (`source`) otherwise; volume and trade count come from
+/// the trade prints only. Both inputs must be sorted by time. Intervals with
+/// neither a valid book nor a trade produce no candle, and the intervals at
+/// either edge are dropped unless the input spans them completely.
+pub fn build_candles(
+    coin: &str,
+    interval: &str,
+    events: &[L2Event],
+    trades: &[TradePrint],
+    source: PriceSource,
+) -> Result<Vec<Candle>> {
+    let interval_ms = interval_to_ms(interval)?;
+    let first_ts = [events.first().map(|e| e.ts_ms), trades.first().map(|t| t.ts_ms)]
+        .into_iter()
+        .flatten()
+        .min();
+    let last_ts = [events.last().map(|e| e.ts_ms), trades.last().map(|t| t.ts_ms)]
+        .into_iter()
+        .flatten()
+        .max();
+    let (Some(first_ts), Some(last_ts)) = (first_ts, last_ts) else {
+        return Ok(vec![]);
+    };
+    let mut buckets: BTreeMap<u64, Bucket> = BTreeMap::new();
+
+    let mut book = OrderBook::new();
+    for event in events {
+        book.apply_snapshot(&event.levels);
+        let price = match source {
+            PriceSource::Mid => book.mid_price(),
+            PriceSource::Microprice => book.microprice(),
+        };
+        let Some(price) = price.filter(|p| p.is_finite() && *p > 0.0) else {
+            continue;
+        };
+
+        let bucket = buckets
+            .entry(bucket_start(event.ts_ms, interval_ms))
+            .or_default();
+        match &mut bucket.book {
+            Some(ohlc) => ohlc.update(price),
+            None => bucket.book = Some(Ohlc::new(price)),
+        }
+    }
+
+    for trade in trades {
+        if !(trade.px.is_finite() && trade.px > 0.0 && trade.sz.is_finite()) {
+            continue;
+        }
+
+        let bucket = buckets
+            .entry(bucket_start(trade.ts_ms, interval_ms))
+            .or_default();
+        match &mut bucket.trades {
+            Some(ohlc) => ohlc.update(trade.px),
+            None => bucket.trades = Some(Ohlc::new(trade.px)),
+        }
+        bucket.volume += trade.sz.abs();
+        bucket.num_trades += 1;
+    }
+
+    Ok(buckets
+        .into_iter()
+        .filter_map(|(time_open, bucket)| {
+            let time_close = time_open + interval_ms - 1;
+            // Partial edge intervals would pass for complete bars
+            if time_open < first_ts || time_close > last_ts {
+                return None;
+            }
+            let ohlc = bucket.trades.or(bucket.book)?;
+            Some(Candle {
+                time_open,
+                time_close,
+                coin: coin.to_string(),
+                interval: interval.to_string(),
+                open: ohlc.open,
+                close: ohlc.close,
+                high: ohlc.high,
+                low: ohlc.low,
+                volume: bucket.volume,
+                num_trades: bucket.num_trades,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingest::OrderLevel;
+
+    const MINUTE_MS: u64 = 60 * 1000;
+
+    fn snapshot(ts_ms: u64, bid: (f64, f64), ask: (f64, f64)) -> L2Event {
+        L2Event {
+            ts_ms,
+            levels: vec![
+                vec![OrderLevel { px: bid.0, sz: bid.1, n: 1 }],
+                vec![OrderLevel { px: ask.0, sz: ask.1, n: 1 }],
+            ],
+        }
+    }
+
+    fn trade(ts_ms: u64, px: f64, sz: f64) -> TradePrint {
+        TradePrint {
+            ts_ms,
+            px,
+            sz,
+            side: None,
+        }
+    }
+
+    #[test]
+    fn test_candles_from_book_mid() {
+        let events = vec![
+            snapshot(0, (99.0, 1.0), (101.0, 1.0)),
+            snapshot(20_000, (103.0, 1.0), (105.0, 1.0)),
+            snapshot(40_000, (97.0, 1.0), (99.0, 1.0)),
+            snapshot(MINUTE_MS + 1, (101.0, 1.0), (103.0, 1.0)),
+            snapshot(2 * MINUTE_MS, (101.0, 1.0), (103.0, 1.0)),
+        ];
+        let candles = build_candles("BTC", "1m", &events, &[], PriceSource::Mid).unwrap();
+
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0].open, 100.0);
+        assert_eq!(candles[0].high, 104.0);
+        assert_eq!(candles[0].low, 98.0);
+        assert_eq!(candles[0].close, 98.0);
+        assert_eq!(candles[0].volume, 0.0);
+        assert_eq!(candles[1].time_open, MINUTE_MS);
+        assert_eq!(candles[1].time_close, 2 * MINUTE_MS - 1);
+        assert_eq!(candles[1].close, 102.0);
+    }
+
+    #[test]
+    fn test_microprice_source() {
+        let events = vec![
+            snapshot(0, (100.0, 3.0), (102.0, 1.0)),
+            snapshot(MINUTE_MS, (100.0, 3.0), (102.0, 1.0)),
+        ];
+        let candles = build_candles("BTC", "1m", &events, &[], PriceSource::Microprice).unwrap();
+        assert_eq!(candles[0].close, 101.5);
+    }
+
+    #[test]
+    fn test_trades_take_precedence_and_supply_volume() {
+        let events = vec![
+            snapshot(0, (99.0, 1.0), (101.0, 1.0)),
+            snapshot(MINUTE_MS, (99.0, 1.0), (101.0, 1.0)),
+            snapshot(2 * MINUTE_MS, (99.0, 1.0), (101.0, 1.0)),
+        ];
+        let trades = vec![trade(1_000, 100.5, 2.0), trade(2_000, 99.5, 1.0)];
+        let candles = build_candles("BTC", "1m", &events, &trades, PriceSource::Mid).unwrap();
+
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0].open, 100.5);
+        assert_eq!(candles[0].close, 99.5);
+        assert_eq!(candles[0].volume, 3.0);
+        assert_eq!(candles[0].num_trades, 2);
+        // No trades in the second minute: book price, no volume
+        assert_eq!(candles[1].close, 100.0);
+        assert_eq!(candles[1].num_trades, 0);
+    }
+
+    #[test]
+    fn test_partial_edge_intervals_dropped() {
+        let events = vec![
+            snapshot(30_000, (99.0, 1.0), (101.0, 1.0)),
+            snapshot(MINUTE_MS, (101.0, 1.0), (103.0, 1.0)),
+            snapshot(2 * MINUTE_MS + 30_000, (103.0, 1.0), (105.0, 1.0)),
+        ];
+        let candles = build_candles("BTC", "1m", &events, &[], PriceSource::Mid).unwrap();
+
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].time_open, MINUTE_MS);
+        assert_eq!(candles[0].close, 102.0);
+
+        assert!(build_candles("BTC", "1m", &[], &[], PriceSource::Mid)
+            .unwrap()
+            .is_empty());
+    }
+}
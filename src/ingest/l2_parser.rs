@@ -108,10 +108,8 @@ pub async fn parse_l2_jsonl_file(file_path: impl AsRef<Path>) -> Result<Vec<L2Ev
     // Use buffered line-by-line reading instead of loading entire file
     let reader = TokioBufReader::new(file);
     let mut lines = reader.lines();
-    let mut events = Vec::new();
-    
     // Pre-allocate with estimated capacity (most files have similar event counts)
-    events.reserve(10000); // Estimate ~10k events per hour
+    let mut events = Vec::with_capacity(10000); // Estimate ~10k events per hour
     
     while let Some(line) = lines.next_line().await? {
         if line.trim().is_empty() {
@@ -126,7 +124,7 @@ pub async fn parse_l2_jsonl_file(file_path: impl AsRef<Path>) -> Result<Vec<L2Ev
 
         // Fall back to raw S3 format
         let entry: RawL2Entry = serde_json::from_str(&line)
-            .with_context(|| format!("Failed to parse JSON line"))?;
+            .context("Failed to parse JSON line")?;
 
         let levels: Result<Vec<Vec<OrderLevel>>, anyhow::Error> = entry.raw.data.levels
             .iter()
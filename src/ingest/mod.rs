@@ -1,6 +1,9 @@
 pub mod s3;
 pub mod l2_parser;
+pub mod candles;
+pub mod trades;
 
 pub use s3::S3Downloader;
 pub use l2_parser::{L2Event, OrderLevel, parse_l2_jsonl, parse_l2_file, parse_l2_jsonl_file};
-
+pub use candles::{build_candles, PriceSource};
+pub use trades::{parse_trades_jsonl, parse_trades_jsonl_file, TradePrint};
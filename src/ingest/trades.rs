@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, BufReader as TokioBufReader};
+
+/// A single trade print
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradePrint {
+    pub ts_ms: u64,
+    pub px: f64,
+    pub sz: f64,
+    /// Aggressor side as reported by Hyperliquid: "B" (buy) or "A" (sell)
+    #[serde(default)]
+    pub side: Option<String>,
+}
+
+/// Trade as published by Hyperliquid: string prices and sizes,
+/// `time` in milliseconds or as an RFC 3339 timestamp
+#[derive(Debug, Deserialize)]
+struct RawTrade {
+    px: String,
+    sz: String,
+    time: serde_json::Value,
+    #[serde(default)]
+    side: Option<String>,
+}
+
+fn parse_trade_line(line: &str) -> Result<TradePrint> {
+    // Simplified format first, as written by this tool
+    if let Ok(trade) = serde_json::from_str::<TradePrint>(line) {
+        return Ok(trade);
+    }
+
+    let raw: RawTrade =
+        serde_json::from_str(line).with_context(|| format!("Failed to parse trade: {}", line))?;
+    let ts_ms = match &raw.time {
+        serde_json::Value::Number(n) => n.as_u64(),
+        serde_json::Value::String(s) => chrono::DateTime::parse_from_rfc3339(s)
+            .ok()
+            .or_else(|| {
+                // Archive timestamps omit the offset; they are UTC
+                chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+                    .ok()
+                    .map(|dt| dt.and_utc().fixed_offset())
+            })
+            .map(|dt| dt.timestamp_millis() as u64),
+        _ => None,
+    }
+    .with_context(|| format!("Invalid trade time: {}", raw.time))?;
+
+    Ok(TradePrint {
+        ts_ms,
+        px: raw.px.parse().with_context(|| format!("Invalid price: {}", raw.px))?,
+        sz: raw.sz.parse().with_context(|| format!("Invalid size: {}", raw.sz))?,
+        side: raw.side,
+    })
+}
+
+/// Parse trade prints from a JSONL string
+pub fn parse_trades_jsonl(jsonl: &str) -> Result<Vec<TradePrint>> {
+    jsonl
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_trade_line)
+        .collect()
+}
+
+/// Parse a JSONL file of trade prints
+pub async fn parse_trades_jsonl_file(file_path: impl AsRef<Path>) -> Result<Vec<TradePrint>> {
+    let file = File::open(file_path.as_ref())
+        .await
+        .with_context(|| format!("Failed to open trades file: {:?}", file_path.as_ref()))?;
+
+    let mut lines = TokioBufReader::new(file).lines();
+    let mut trades = Vec::new();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        trades.push(parse_trade_line(&line)?);
+    }
+
+    Ok(trades)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trade_formats() {
+        let jsonl = r#"{"ts_ms":1000,"px":100.5,"sz":2.0,"side":"B"}
+{"coin":"BTC","side":"A","px":"101.0","sz":"0.5","time":2000}
+{"coin":"BTC","side":"B","px":"99.0","sz":"1","time":"2024-01-01T00:00:01.500"}
+"#;
+        let trades = parse_trades_jsonl(jsonl).unwrap();
+        assert_eq!(trades.len(), 3);
+        assert_eq!(trades[0].px, 100.5);
+        assert_eq!(trades[1].ts_ms, 2000);
+        assert_eq!(trades[1].side.as_deref(), Some("A"));
+        assert_eq!(trades[2].ts_ms, 1704067201500);
+
+        assert!(parse_trades_jsonl(r#"{"px":"abc","sz":"1","time":1}"#).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 pub mod cli;
 pub mod data;
+pub mod engine;
 pub mod fees;
 pub mod indicators2;
 pub mod ingest;
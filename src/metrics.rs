@@ -1,3 +1,153 @@
-// Metrics calculations are in orders/engine.rs
-// This module can be extended with additional metrics if needed
+//! Performance metrics shared by every backtest mode
 
+use crate::orders::types::{EquityPoint, SimResult, Trade};
+
+/// Assemble a [`SimResult`] with all metrics from the trades and equity curve
+pub fn summarize(
+    trades: Vec<Trade>,
+    equity_curve: Vec<EquityPoint>,
+    initial_capital: f64,
+    final_equity: f64,
+) -> SimResult {
+    let total_return = final_equity - initial_capital;
+    let total_return_pct = (total_return / initial_capital) * 100.0;
+
+    // Calculate win rate and PnL stats
+    let (win_rate, avg_win, avg_loss) = calculate_trade_stats(&trades, &equity_curve);
+
+    // Calculate drawdown
+    let (max_drawdown, max_drawdown_pct) = calculate_drawdown(&equity_curve, initial_capital);
+
+    // Calculate Sharpe and Sortino ratios
+    let sharpe_ratio = calculate_sharpe_ratio(&equity_curve);
+    let sortino_ratio = calculate_sortino_ratio(&equity_curve);
+
+    let num_trades = trades.len();
+    SimResult {
+        trades,
+        equity_curve,
+        final_equity,
+        total_return,
+        total_return_pct,
+        num_trades,
+        win_rate,
+        avg_win,
+        avg_loss,
+        max_drawdown,
+        max_drawdown_pct,
+        sharpe_ratio,
+        sortino_ratio,
+        sizing: Vec::new(),
+        round_trips: Vec::new(),
+    }
+}
+
+pub fn calculate_trade_stats(
+    trades: &[Trade],
+    equity_curve: &[EquityPoint],
+) -> (f64, f64, f64) {
+    if trades.is_empty() {
+        return (0.0, 0.0, 0.0);
+    }
+
+    let mut wins = 0;
+    let mut losses = 0;
+    let mut total_win = 0.0;
+    let mut total_loss = 0.0;
+
+    for i in 1..equity_curve.len() {
+        let change = equity_curve[i].equity - equity_curve[i - 1].equity;
+        if change > 0.0 {
+            wins += 1;
+            total_win += change;
+        } else if change < 0.0 {
+            losses += 1;
+            total_loss += change.abs();
+        }
+    }
+
+    let total_trades = wins + losses;
+    let win_rate = if total_trades > 0 {
+        wins as f64 / total_trades as f64
+    } else {
+        0.0
+    };
+    let avg_win = if wins > 0 { total_win / wins as f64 } else { 0.0 };
+    let avg_loss = if losses > 0 { total_loss / losses as f64 } else { 0.0 };
+
+    (win_rate, avg_win, avg_loss)
+}
+
+pub fn calculate_drawdown(equity_curve: &[EquityPoint], initial_capital: f64) -> (f64, f64) {
+    if equity_curve.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let mut max_equity = initial_capital;
+    let mut max_drawdown = 0.0;
+    let mut max_drawdown_pct = 0.0;
+
+    for point in equity_curve {
+        if point.equity > max_equity {
+            max_equity = point.equity;
+        }
+        let drawdown = max_equity - point.equity;
+        if drawdown > max_drawdown {
+            max_drawdown = drawdown;
+            max_drawdown_pct = (drawdown / max_equity) * 100.0;
+        }
+    }
+
+    (max_drawdown, max_drawdown_pct)
+}
+
+pub fn calculate_sharpe_ratio(equity_curve: &[EquityPoint]) -> f64 {
+    if equity_curve.len() < 2 {
+        return 0.0;
+    }
+
+    let returns: Vec<f64> = equity_curve
+        .windows(2)
+        .map(|w| (w[1].equity - w[0].equity) / w[0].equity)
+        .collect();
+
+    let mean_return = returns.iter().sum::<f64>() / returns.len() as f64;
+    let variance = returns
+        .iter()
+        .map(|r| (r - mean_return).powi(2))
+        .sum::<f64>()
+        / returns.len() as f64;
+    let std_dev = variance.sqrt();
+
+    if std_dev == 0.0 {
+        return 0.0;
+    }
+
+    mean_return / std_dev * (252.0_f64).sqrt()
+}
+
+pub fn calculate_sortino_ratio(equity_curve: &[EquityPoint]) -> f64 {
+    if equity_curve.len() < 2 {
+        return 0.0;
+    }
+
+    let returns: Vec<f64> = equity_curve
+        .windows(2)
+        .map(|w| (w[1].equity - w[0].equity) / w[0].equity)
+        .collect();
+
+    let mean_return = returns.iter().sum::<f64>() / returns.len() as f64;
+    let downside_variance = returns
+        .iter()
+        .filter(|r| **r < 0.0)
+        .map(|r| r.powi(2))
+        .sum::<f64>()
+        / returns.len() as f64;
+    let downside_std = downside_variance.sqrt();
+
+    if downside_std == 0.0 {
+        return 0.0;
+    }
+
+    mean_return / downside_std * (252.0_f64).sqrt()
+}
@@ -27,7 +27,7 @@ impl OrderBook {
         }
 
         // Bids (level 0) - sorted descending by price
-        if let Some(bid_levels) = levels.get(0) {
+        if let Some(bid_levels) = levels.first() {
             for level in bid_levels {
                 let price_scaled = (level.px * 1e8) as u64; // Scale for integer key
                 *self.bids.entry(price_scaled).or_insert(0.0) += level.sz;
@@ -59,6 +59,17 @@ impl OrderBook {
         Some((bid.0 + ask.0) / 2.0)
     }
 
+    /// Size-weighted mid: leans toward the side with less resting size at the touch
+    pub fn microprice(&self) -> Option<f64> {
+        let (bid_px, bid_sz) = self.best_bid()?;
+        let (ask_px, ask_sz) = self.best_ask()?;
+        let total = bid_sz + ask_sz;
+        if total <= 0.0 {
+            return Some((bid_px + ask_px) / 2.0);
+        }
+        Some((bid_px * ask_sz + ask_px * bid_sz) / total)
+    }
+
     /// Get cumulative depth up to a price level
     /// For bids: returns depth at prices >= price (better or equal)
     /// For asks: returns depth at prices <= price (better or equal)
@@ -183,6 +194,10 @@ mod tests {
 
         let mid = book.mid_price().unwrap();
         assert_eq!(mid, 25000.5);
+
+        // 1.5 bid vs 1.0 ask: buying pressure pulls the microprice toward the ask
+        let micro = book.microprice().unwrap();
+        assert!((micro - 25000.6).abs() < 1e-9);
     }
 
     #[test]
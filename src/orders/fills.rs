@@ -3,6 +3,18 @@ use crate::fees::FeeCalculator;
 use crate::orders::types::{Action, Order, OrderStatus, Side, Tif};
 use crate::portfolio::Portfolio;
 
+/// Result of an order execution attempt.
+///
+/// Contains information about how much of an order was filled, at what price,
+/// and the resulting order status.
+///
+/// # Fields
+///
+/// - `filled_sz`: Size that was filled in this execution
+/// - `fill_price`: Average fill price (for market orders, this is weighted average across levels)
+/// - `is_maker`: Whether this fill was a maker order (true for limit orders, false for market orders)
+/// - `order_status`: Resulting order status (`Filled`, `PartiallyFilled` or `Canceled`)
+#[derive(Debug, Clone)]
 pub struct FillResult {
     pub filled_sz: f64,
     pub fill_price: f64,
@@ -3,5 +3,5 @@ pub mod engine;
 pub mod fills;
 
 pub use types::*;
-pub use engine::simulate;
+pub use engine::{simulate, simulate_logic, simulate_with_references};
 
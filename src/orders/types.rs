@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::data::validate::DataPolicy;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Side {
@@ -65,6 +68,22 @@ pub enum Action {
     },
 }
 
+impl Action {
+    /// Side of the order; every action has one
+    pub fn side(&self) -> Side {
+        match self {
+            Action::Market { side, .. }
+            | Action::Limit { side, .. }
+            | Action::StopMarket { side, .. }
+            | Action::StopLimit { side, .. }
+            | Action::TakeMarket { side, .. }
+            | Action::TakeLimit { side, .. }
+            | Action::Scale { side, .. }
+            | Action::Twap { side, .. } => *side,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Order {
     pub id: u64,
@@ -72,6 +91,9 @@ pub struct Order {
     pub created_at: u64,
     pub filled_sz: f64,
     pub status: OrderStatus,
+    /// Label from the logic that placed the order; JSON strategies use the
+    /// name of the rule that fired
+    pub tag: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -98,10 +120,12 @@ pub struct SimConfig {
     pub maker_fee_bps: i16,
     pub taker_fee_bps: i16,
     pub slippage_bps: u16,
-    /// Minimum time between trades in milliseconds (cooldown period)
+    /// Minimum time between the last fill and a new entry in milliseconds
     /// Prevents excessive trading when strategy triggers frequently
-    /// Default: 15 minutes (900,000 ms)
+    /// `None` disables the cooldown (`run-perps` defaults to 15 minutes)
     pub trade_cooldown_ms: Option<u64>,
+    /// How input data that fails validation is handled before the simulation starts
+    pub data_policy: DataPolicy,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -113,6 +137,9 @@ pub struct Trade {
     pub price: f64,
     pub fee: f64,
     pub order_id: u64,
+    /// Tag of the order that filled, e.g. the strategy rule that placed it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -138,5 +165,72 @@ pub struct SimResult {
     pub max_drawdown_pct: f64,
     pub sharpe_ratio: f64,
     pub sortino_ratio: f64,
+    /// How each order of a JSON strategy was sized, in placement order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sizing: Vec<SizingRecord>,
+    /// Positions from open to close, oldest first; the last may still be open
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub round_trips: Vec<RoundTrip>,
+}
+
+/// A position from the fill that opened it to the fill that closed it,
+/// including the adds and partial exits in between
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoundTrip {
+    pub symbol: String,
+    /// "LONG" or "SHORT"
+    pub side: String,
+    pub open_ts: u64,
+    /// `None` while the position is open
+    pub close_ts: Option<u64>,
+    /// Fills that opened or added to the position
+    pub entries: usize,
+    /// Fills that reduced or closed it
+    pub exits: usize,
+    /// Open size (0 once closed) and the largest it has been
+    pub size: f64,
+    pub max_size: f64,
+    /// Total size of the exits so far
+    pub exited_size: f64,
+    /// Average cost of the open position, as tracked by the portfolio
+    pub entry_price: f64,
+    /// Average price of the exits so far
+    pub exit_price: f64,
+    /// Fill time and price of the last entry
+    pub last_entry_ts: u64,
+    pub last_entry_price: f64,
+    /// Notional of all entries
+    pub cost: f64,
+    pub fees: f64,
+    /// Realized PnL of the exits so far, after all fees
+    pub pnl: f64,
+    /// `pnl` over `cost`, in percent
+    pub return_pct: f64,
+}
+
+/// Audit record of how an order's size was chosen
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SizingRecord {
+    pub order_id: u64,
+    pub timestamp: u64,
+    /// Rule that placed the order
+    pub rule: String,
+    /// Sizing policy, e.g. "risk_per_trade", or "close" / "position_pct" for exits
+    pub policy: String,
+    /// Price and equity the size was computed at
+    pub price: f64,
+    pub equity: f64,
+    /// Size the policy asked for, before leverage caps and lot rounding
+    pub target_size: f64,
+    /// Size of the order
+    pub size: f64,
+    pub notional: f64,
+    /// Position notional over equity once the order fills at `price`
+    pub leverage: f64,
+    /// Values the policy used, e.g. the stop distance or the Kelly fraction
+    pub inputs: BTreeMap<String, f64>,
+    /// Why `size` differs from `target_size`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub adjustments: Vec<String>,
 }
 
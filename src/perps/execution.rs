@@ -1,6 +1,8 @@
 use crate::orderbook::OrderBook;
 use crate::orders::types::{Action, Order, OrderStatus, Side};
 
+pub use crate::orders::fills::FillResult;
+
 /// Execution engine for perpetual futures orders.
 ///
 /// Handles order execution logic against the order book, including:
@@ -42,9 +44,9 @@ impl PerpsExecution {
     /// # Example
     ///
     /// ```rust,no_run
-    /// use crate::perps::execution::PerpsExecution;
-    /// use crate::orders::types::{Order, Action, Side, OrderStatus};
-    /// use crate::orderbook::OrderBook;
+    /// use hl_backtest::perps::execution::PerpsExecution;
+    /// use hl_backtest::orders::types::{Order, Action, Side, OrderStatus};
+    /// use hl_backtest::orderbook::OrderBook;
     ///
     /// # fn example(mut order: Order, book: OrderBook) {
     /// let mut order = Order {
@@ -53,13 +55,13 @@ impl PerpsExecution {
     ///     created_at: 1000,
     ///     filled_sz: 0.0,
     ///     status: OrderStatus::Pending,
+    ///     tag: None,
     /// };
     ///
     /// if let Some(fill) = PerpsExecution::execute_market(&mut order, &book) {
     ///     println!("Filled: {} @ ${:.2}", fill.filled_sz, fill.fill_price);
     ///     if fill.order_status == OrderStatus::PartiallyFilled {
-    ///         println!("Partial fill - remaining: {:.6}",
-    ///                  order.action.size() - fill.filled_sz);
+    ///         println!("Partial fill - remaining: {:.6}", 1.0 - order.filled_sz);
     ///     }
     /// } else {
     ///     println!("No liquidity available");
@@ -160,9 +162,9 @@ impl PerpsExecution {
     /// # Example
     ///
     /// ```rust,no_run
-    /// use crate::perps::execution::PerpsExecution;
-    /// use crate::orders::types::{Order, Action, Side, OrderStatus, Tif};
-    /// use crate::orderbook::OrderBook;
+    /// use hl_backtest::perps::execution::PerpsExecution;
+    /// use hl_backtest::orders::types::{Order, Action, Side, OrderStatus, Tif};
+    /// use hl_backtest::orderbook::OrderBook;
     ///
     /// # fn example(mut order: Order, book: OrderBook) {
     /// let mut order = Order {
@@ -178,6 +180,7 @@ impl PerpsExecution {
     ///     created_at: 1000,
     ///     filled_sz: 0.0,
     ///     status: OrderStatus::Pending,
+    ///     tag: None,
     /// };
     ///
     /// // Check on each event
@@ -229,14 +232,11 @@ impl PerpsExecution {
                 order.filled_sz += filled_sz;
 
                 // Reduce remaining order size in the action (need to match again to get mutable access)
-                match &mut order.action {
-                    Action::Limit { ref mut sz, .. } => {
-                        *sz -= filled_sz;
-                        if *sz < 1e-10 {
-                            *sz = 0.0;
-                        }
+                if let Action::Limit { ref mut sz, .. } = &mut order.action {
+                    *sz -= filled_sz;
+                    if *sz < 1e-10 {
+                        *sz = 0.0;
                     }
-                    _ => {}
                 }
 
                 // Determine order status based on fill
@@ -263,22 +263,3 @@ impl PerpsExecution {
         }
     }
 }
-
-/// Result of an order execution attempt.
-///
-/// Contains information about how much of an order was filled, at what price,
-/// and the resulting order status.
-///
-/// # Fields
-///
-/// - `filled_sz`: Size that was filled in this execution
-/// - `fill_price`: Average fill price (for market orders, this is weighted average across levels)
-/// - `is_maker`: Whether this fill was a maker order (true for limit orders, false for market orders)
-/// - `order_status`: Resulting order status (`Filled` or `PartiallyFilled`)
-#[derive(Debug, Clone)]
-pub struct FillResult {
-    pub filled_sz: f64,
-    pub fill_price: f64,
-    pub is_maker: bool,
-    pub order_status: OrderStatus,
-}
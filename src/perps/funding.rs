@@ -1,6 +1,8 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
+use crate::data::source::{HyperliquidSource, MarketDataSource};
+
 /// Security: Validate coin parameter to prevent injection attacks
 fn validate_coin_for_api(coin: &str) -> Result<()> {
     // Prevent path traversal and injection
@@ -27,27 +29,6 @@ pub struct FundingPoint {
     pub rate: f64, // Funding rate (e.g., 0.0001 = 0.01%)
 }
 
-#[derive(Debug, Clone, Deserialize)]
-#[allow(dead_code)]
-struct FundingHistoryResponse {
-    coin: String,
-    #[serde(rename = "fundingRate")]
-    funding_rate: String,
-    premium: String,
-    time: u64,
-}
-
-#[derive(Debug, Clone, Serialize)]
-struct FundingHistoryRequest {
-    #[serde(rename = "type")]
-    request_type: String,
-    coin: String,
-    #[serde(rename = "startTime")]
-    start_time: u64,
-    #[serde(rename = "endTime")]
-    end_time: u64,
-}
-
 #[derive(Debug, Clone)]
 pub struct FundingSchedule {
     points: Vec<FundingPoint>,
@@ -91,7 +72,7 @@ impl FundingSchedule {
     /// # Example
     ///
     /// ```rust,no_run
-    /// use crate::perps::FundingSchedule;
+    /// use hl_backtest::perps::FundingSchedule;
     ///
     /// # async fn example() -> anyhow::Result<()> {
     /// let funding = FundingSchedule::from_api(
@@ -115,6 +96,20 @@ impl FundingSchedule {
         coin: &str,
         start_ts: u64,
         end_ts: u64,
+    ) -> Result<Self> {
+        let source = HyperliquidSource::mainnet()?;
+        Self::from_source(&source, coin, start_ts, end_ts).await
+    }
+
+    /// Fetches funding history from any [`MarketDataSource`].
+    ///
+    /// Applies the same coin and range validation as [`FundingSchedule::from_api`],
+    /// which uses the Hyperliquid mainnet API.
+    pub async fn from_source(
+        source: &dyn MarketDataSource,
+        coin: &str,
+        start_ts: u64,
+        end_ts: u64,
     ) -> Result<Self> {
         // Security: Validate coin parameter
         validate_coin_for_api(coin)?;
@@ -129,49 +124,15 @@ impl FundingSchedule {
         if end_ts.saturating_sub(start_ts) > MAX_RANGE_MS {
             anyhow::bail!("Timestamp range too large: maximum 1 year");
         }
-        
-        // Security: Create HTTP client with explicit certificate validation
-        // Note: reqwest validates certificates by default, but being explicit is better
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(false) // Explicitly require valid certificates
-            .timeout(std::time::Duration::from_secs(30)) // Add timeout to prevent hanging
-            .build()
-            .context("Failed to create HTTP client")?;
-        
-        let request = FundingHistoryRequest {
-            request_type: "fundingHistory".to_string(),
-            coin: coin.to_string(),
-            start_time: start_ts,
-            end_time: end_ts,
-        };
-
-        let response = client
-            .post("https://api.hyperliquid.xyz/info")
-            .json(&request)
-            .send()
-            .await
-            .context("Failed to send funding history request")?;
-
-        if !response.status().is_success() {
-            anyhow::bail!(
-                "Funding history API returned error: {}",
-                response.status()
-            );
-        }
 
-        let funding_data: Vec<FundingHistoryResponse> = response
-            .json()
+        let points = source
+            .funding(coin, start_ts, end_ts)
             .await
-            .context("Failed to parse funding history response")?;
+            .with_context(|| format!("Failed to fetch funding history from {}", source.name()))?;
 
         let mut schedule = Self::new();
-        
-        for entry in funding_data {
-            let rate = entry.funding_rate
-                .parse::<f64>()
-                .with_context(|| format!("Invalid funding rate: {}", entry.funding_rate))?;
-            
-            schedule.add_point(entry.time, rate);
+        for point in points {
+            schedule.add_point(point.ts_ms, point.rate);
         }
 
         Ok(schedule)
@@ -206,7 +167,7 @@ impl FundingSchedule {
     /// # Example
     ///
     /// ```rust
-    /// use crate::perps::FundingSchedule;
+    /// use hl_backtest::perps::FundingSchedule;
     ///
     /// let mut schedule = FundingSchedule::new();
     /// schedule.add_point(1000, 0.0001);
@@ -243,7 +204,7 @@ impl FundingSchedule {
     /// # Example
     ///
     /// ```rust
-    /// use crate::perps::FundingSchedule;
+    /// use hl_backtest::perps::FundingSchedule;
     ///
     /// let mut schedule = FundingSchedule::new();
     /// schedule.add_point(1000, 0.0001);  // 0.01% rate
@@ -260,6 +221,11 @@ impl FundingSchedule {
         }
     }
 
+    /// All funding points, sorted by timestamp
+    pub fn points(&self) -> &[FundingPoint] {
+        &self.points
+    }
+
     /// Get all funding timestamps in a range
     pub fn timestamps_in_range(&self, start_ts: u64, end_ts: u64) -> Vec<u64> {
         self.points
@@ -14,8 +14,8 @@ use crate::perps::execution::FillResult;
 /// # Example
 ///
 /// ```rust
-/// use crate::perps::trade_utils::side_to_string;
-/// use crate::orders::types::Side;
+/// use hl_backtest::perps::trade_utils::side_to_string;
+/// use hl_backtest::orders::types::Side;
 ///
 /// let side_str = side_to_string(Side::Buy);  // "BUY"
 /// assert_eq!(side_str, "BUY");
@@ -41,8 +41,8 @@ pub fn side_to_string(side: Side) -> &'static str {
 /// # Example
 ///
 /// ```rust
-/// use crate::perps::trade_utils::extract_side_from_action;
-/// use crate::orders::types::{Action, Side};
+/// use hl_backtest::perps::trade_utils::extract_side_from_action;
+/// use hl_backtest::orders::types::{Action, Side};
 ///
 /// let action = Action::Market { side: Side::Buy, sz: 1.0 };
 /// let side = extract_side_from_action(&action);
@@ -72,6 +72,7 @@ pub fn create_trade_from_fill(
         price: fill_result.fill_price,
         fee: 0.0, // Fee will be calculated separately
         order_id,
+        tag: None,
     }
 }
 
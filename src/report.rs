@@ -14,7 +14,7 @@ pub fn write_results(result: &SimResult, out_path: &Path) -> Result<()> {
     // Write trades CSV
     let trades_path = base_path.join(format!("{}_trades.csv", base_name));
     let mut wtr = csv::Writer::from_path(&trades_path)?;
-    wtr.write_record(&["timestamp", "symbol", "side", "size", "price", "fee", "order_id"])?;
+    wtr.write_record(["timestamp", "symbol", "side", "size", "price", "fee", "order_id", "tag"])?;
     for trade in &result.trades {
         wtr.write_record(&[
             trade.timestamp.to_string(),
@@ -24,6 +24,7 @@ pub fn write_results(result: &SimResult, out_path: &Path) -> Result<()> {
             trade.price.to_string(),
             trade.fee.to_string(),
             trade.order_id.to_string(),
+            trade.tag.clone().unwrap_or_default(),
         ])?;
     }
     wtr.flush()?;
@@ -31,7 +32,7 @@ pub fn write_results(result: &SimResult, out_path: &Path) -> Result<()> {
     // Write equity curve CSV
     let equity_path = base_path.join(format!("{}_equity.csv", base_name));
     let mut wtr = csv::Writer::from_path(&equity_path)?;
-    wtr.write_record(&["timestamp", "equity", "cash", "position_value"])?;
+    wtr.write_record(["timestamp", "equity", "cash", "position_value"])?;
     for point in &result.equity_curve {
         wtr.write_record(&[
             point.timestamp.to_string(),
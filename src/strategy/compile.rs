@@ -1,30 +1,528 @@
-use crate::indicators2::IndicatorRegistry;
+use crate::indicators2::{output_name, IndicatorRegistry};
+use crate::strategy::expr::Expr;
 use crate::strategy::types::*;
-use anyhow::{Context, Result};
+use crate::util::interval_to_ms;
+use anyhow::{bail, Context, Result};
+use std::collections::{HashMap, HashSet};
 
-/// Compile a strategy, resolving indicator lookbacks
+/// Compile a strategy with the built-in indicators, resolving indicator
+/// lookbacks and outputs and ordering indicators after the indicators they
+/// are computed on
 pub fn compile_strategy(strategy: &Strategy) -> Result<CompiledStrategy> {
-    let registry = IndicatorRegistry::new();
-    let mut compiled_indicators = Vec::new();
+    compile_strategy_with(strategy, &IndicatorRegistry::new())
+}
+
+/// [`compile_strategy`] with the indicator types of `registry`
+pub fn compile_strategy_with(strategy: &Strategy, registry: &IndicatorRegistry) -> Result<CompiledStrategy> {
+    let mut compiled_indicators: Vec<CompiledIndicator> = Vec::new();
+    // Warmup in bars of the indicator's timeframe, summed along its sources
+    let mut warmups: HashMap<&str, usize> = HashMap::new();
+    let mut outputs: HashMap<&str, &'static [&'static str]> = HashMap::new();
 
-    for ind_spec in &strategy.indicators {
-        let lookback = registry
-            .get_lookback(&ind_spec.indicator_type, &ind_spec.params)
-            .with_context(|| format!("Unknown indicator: {}", ind_spec.indicator_type))?;
+    for ind_spec in sort_indicators(&strategy.indicators)? {
+        let params = registry
+            .resolve_params(&ind_spec.indicator_type, &ind_spec.params)
+            .with_context(|| format!("Invalid indicator '{}'", ind_spec.id))?
+            .into_values();
+        let lookback = registry.get_lookback(&ind_spec.indicator_type, &params)?;
+        outputs.insert(&ind_spec.id, registry.outputs(&ind_spec.indicator_type)?);
+        let source = match &ind_spec.source {
+            Some(source) => Some(
+                resolve_source(source, &outputs)
+                    .with_context(|| format!("Invalid source for indicator '{}'", ind_spec.id))?,
+            ),
+            None => None,
+        };
+        // Sorted, so the source is already compiled
+        let parent = source
+            .as_ref()
+            .and_then(|s| compiled_indicators.iter().find(|c| c.id == s.indicator));
+        let timeframe = ind_spec
+            .timeframe
+            .as_deref()
+            .or_else(|| parent.and_then(|p| p.timeframe.as_deref()));
+        let (timeframe, bars_per_bar) = match timeframe {
+            Some(timeframe) => indicator_timeframe(timeframe, &strategy.instrument.timeframe)
+                .with_context(|| format!("Invalid timeframe for indicator '{}'", ind_spec.id))?,
+            None => (None, 1),
+        };
+        let coin = match ind_spec.coin.as_deref().map(str::trim) {
+            Some("") => bail!("Indicator '{}' has an empty coin", ind_spec.id),
+            Some(coin) if coin != strategy.instrument.coin => Some(coin.to_string()),
+            Some(_) => None,
+            None => parent.and_then(|p| p.coin.clone()),
+        };
+        if let Some(parent) = parent {
+            if timeframe != parent.timeframe || coin != parent.coin {
+                bail!(
+                    "Indicator '{}' must be on the same coin and timeframe as its source '{}'",
+                    ind_spec.id,
+                    parent.id
+                );
+            }
+        }
+        let warmup = lookback + source.as_ref().map_or(0, |s| warmups[s.indicator.as_str()]);
+        warmups.insert(&ind_spec.id, warmup);
 
         compiled_indicators.push(CompiledIndicator {
             id: ind_spec.id.clone(),
             indicator_type: ind_spec.indicator_type.clone(),
-            params: ind_spec.params.clone(),
-            outputs: ind_spec.outputs.clone(),
-            lookback,
+            params,
+            // Set once the rules are resolved
+            outputs: Vec::new(),
+            timeframe,
+            coin,
+            source,
+            // The first higher-timeframe bar may start before the data does and
+            // is dropped, so one more is needed
+            lookback: (warmup + 1) * bars_per_bar - 1,
         });
     }
 
+    let indicator_ids: HashSet<&str> = strategy.indicators.iter().map(|i| i.id.as_str()).collect();
+    if let Some(name) = strategy.params.keys().find(|p| indicator_ids.contains(p.as_str())) {
+        bail!("Parameter '{}' has the same name as an indicator", name);
+    }
+    if strategy.entries.is_empty() {
+        bail!("Strategy needs at least one entry rule");
+    }
+    if let Some(max) = strategy.instrument.max_leverage {
+        if !(max > 0.0 && max.is_finite()) {
+            bail!("max_leverage must be positive, got {}", max);
+        }
+    }
+    let mut entries = resolve_rules("entry", &strategy.entries, &indicator_ids, strategy)?;
+    let mut exits = resolve_rules("exit", &strategy.exits, &indicator_ids, strategy)?;
+    let mut names = HashSet::new();
+    if let Some(rule) = entries.iter().chain(&exits).find(|r| !names.insert(r.name.as_str())) {
+        bail!("Duplicate rule name '{}'", rule.name);
+    }
+
+    let mut slots = HashMap::new();
+    for rule in entries.iter_mut().chain(&mut exits) {
+        assign_slots(&mut rule.condition, &mut slots);
+    }
+
+    let pyramiding = match &strategy.pyramiding {
+        Some(pyramiding) => Some(
+            resolve_pyramiding(pyramiding, &indicator_ids, strategy).context("Invalid pyramiding")?,
+        ),
+        None => None,
+    };
+
+    let mut history_depths = HashMap::new();
+    let add_sizing = pyramiding.iter().flat_map(|p| &p.add_sizing);
+    let rule_sizing = entries.iter().chain(&exits).filter_map(|rule| match &rule.action {
+        Action::Buy { sizing, .. } => sizing.as_ref(),
+        _ => None,
+    });
+    for rule in entries.iter().chain(&exits) {
+        condition_depths(&rule.condition, 0, &mut history_depths);
+    }
+    for sizing in rule_sizing.chain(add_sizing) {
+        if let Sizing::RiskPerTrade { stop: expr, .. } | Sizing::VolatilityTarget { atr: expr, .. } = sizing {
+            expr.history_depths(0, &mut history_depths);
+        }
+    }
+
+    // Expose the outputs the rules read and the ones the indicators declare
+    let mut exposed: HashMap<&str, HashSet<&'static str>> = HashMap::new();
+    let mut references: Vec<&String> = history_depths.keys().collect();
+    references.sort();
+    let declared = strategy
+        .indicators
+        .iter()
+        .flat_map(|spec| spec.outputs.iter().map(move |output| (spec.id.as_str(), output.as_str())));
+    for (id, output) in references.into_iter().map(|name| split_output(name)).chain(declared) {
+        if let Some((id, listed)) = outputs.get_key_value(id) {
+            let output = indicator_output(id, output, listed)?;
+            exposed.entry(id).or_default().insert(output);
+        }
+    }
+    for indicator in &mut compiled_indicators {
+        let id = indicator.id.as_str();
+        let (Some(listed), Some(exposed)) = (outputs.get(id), exposed.get(id)) else {
+            continue;
+        };
+        indicator.outputs = listed
+            .iter()
+            .filter(|output| exposed.contains(*output))
+            .map(|output| output.to_string())
+            .collect();
+    }
+
     Ok(CompiledStrategy {
         instrument: strategy.instrument.clone(),
         indicators: compiled_indicators,
-        entry: strategy.entry.clone(),
-        exit: strategy.exit.clone(),
+        entries,
+        exits,
+        pyramiding,
+        history_depths,
     })
 }
+
+/// Split a reference to an indicator output, `id` or `id.output`, into the
+/// indicator's id and output, `value` if not given
+fn split_output(reference: &str) -> (&str, &str) {
+    reference.split_once('.').unwrap_or((reference, "value"))
+}
+
+/// Listed name of `output` of indicator `id`, with `value` naming its primary
+/// output
+fn indicator_output(id: &str, output: &str, outputs: &[&'static str]) -> Result<&'static str> {
+    output_name(outputs, output).with_context(|| {
+        format!(
+            "Indicator '{}' has no output '{}' (it has {})",
+            id,
+            output,
+            outputs.join(", ")
+        )
+    })
+}
+
+/// Order `indicators` so that each comes after its source, keeping the
+/// declared order otherwise; sources that form a cycle are an error
+fn sort_indicators(indicators: &[IndicatorSpec]) -> Result<Vec<&IndicatorSpec>> {
+    fn visit<'a>(
+        spec: &'a IndicatorSpec,
+        indicators: &'a [IndicatorSpec],
+        sorted: &mut Vec<&'a IndicatorSpec>,
+        path: &mut Vec<&'a str>,
+    ) -> Result<()> {
+        if sorted.iter().any(|s| s.id == spec.id) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|id| *id == spec.id) {
+            bail!("Indicator sources form a cycle: {} -> {}", path[start..].join(" -> "), spec.id);
+        }
+        // Unknown sources are reported when the source is resolved
+        let (id, _) = split_output(spec.source.as_deref().unwrap_or_default());
+        if let Some(source) = indicators.iter().find(|i| spec.source.is_some() && i.id == id) {
+            path.push(&spec.id);
+            visit(source, indicators, sorted, path)?;
+            path.pop();
+        }
+        sorted.push(spec);
+        Ok(())
+    }
+
+    let mut sorted = Vec::with_capacity(indicators.len());
+    for spec in indicators {
+        visit(spec, indicators, &mut sorted, &mut Vec::new())?;
+    }
+    Ok(sorted)
+}
+
+/// Resolve an indicator's `source` among the indicators compiled before it,
+/// checking that the indicator it names has the output
+fn resolve_source(source: &str, outputs: &HashMap<&str, &'static [&'static str]>) -> Result<IndicatorSource> {
+    let (id, output) = split_output(source);
+    let Some(listed) = outputs.get(id) else {
+        bail!("No indicator '{}'", id);
+    };
+    Ok(IndicatorSource {
+        indicator: id.to_string(),
+        output: indicator_output(id, output, listed)?.to_string(),
+    })
+}
+
+/// Higher timeframe of an indicator, `None` if it is the strategy's own, and
+/// how many strategy bars make one of its bars
+fn indicator_timeframe(timeframe: &str, base: &str) -> Result<(Option<String>, usize)> {
+    let timeframe_ms = interval_to_ms(timeframe)?;
+    let base_ms = interval_to_ms(base).context("The instrument's timeframe is needed")?;
+    if timeframe_ms < base_ms || !timeframe_ms.is_multiple_of(base_ms) {
+        bail!(
+            "{} is not a multiple of the instrument's timeframe {}",
+            timeframe,
+            base
+        );
+    }
+    let bars = (timeframe_ms / base_ms) as usize;
+    Ok(((bars > 1).then(|| timeframe.to_string()), bars))
+}
+
+/// Resolve `rules` of one `kind` ("entry" or "exit"), naming unnamed rules
+/// `<kind>_<n>` by position and ordering them by priority
+fn resolve_rules(
+    kind: &str,
+    rules: &[Rule],
+    indicators: &HashSet<&str>,
+    strategy: &Strategy,
+) -> Result<Vec<CompiledRule>> {
+    let mut compiled = rules
+        .iter()
+        .enumerate()
+        .map(|(i, rule)| {
+            let name = rule.name.clone().unwrap_or_else(|| format!("{}_{}", kind, i + 1));
+            let resolved = resolve_condition(&rule.condition, indicators, &strategy.params)
+                .and_then(|condition| {
+                    Ok((condition, resolve_action(&rule.action, indicators, strategy)?))
+                });
+            let (condition, action) =
+                resolved.with_context(|| format!("Invalid {} rule '{}'", kind, name))?;
+            Ok(CompiledRule {
+                name,
+                priority: rule.priority,
+                condition,
+                action,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // Stable, so rules of equal priority keep their order
+    compiled.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+    Ok(compiled)
+}
+
+fn window(kind: &str, bars: usize) -> Result<usize> {
+    if bars == 0 {
+        bail!("'{}' needs at least 1 bar", kind);
+    }
+    Ok(bars)
+}
+
+/// Past bars each series referenced by `condition` needs when it is evaluated
+/// `base` bars ago. A temporal window over a condition needs the condition's
+/// own history for each bar of the window, which counts toward the warmup.
+fn condition_depths(condition: &Condition, base: usize, depths: &mut HashMap<String, usize>) {
+    match condition {
+        Condition::Threshold { indicator, .. } => {
+            let depth = depths.entry(indicator.clone()).or_insert(0);
+            *depth = (*depth).max(base);
+        }
+        Condition::Compare { lhs, rhs, .. } => {
+            lhs.history_depths(base, depths);
+            rhs.history_depths(base, depths);
+        }
+        // Crossovers compare against the previous bar
+        Condition::Crossover { fast, slow, .. } => {
+            fast.history_depths(base + 1, depths);
+            slow.history_depths(base + 1, depths);
+        }
+        Condition::And { conditions } | Condition::Or { conditions } => {
+            for c in conditions {
+                condition_depths(c, base, depths);
+            }
+        }
+        Condition::Not { condition } => condition_depths(condition, base, depths),
+        Condition::For { bars, condition, .. } | Condition::Within { bars, condition, .. } => {
+            condition_depths(condition, base + bars - 1, depths)
+        }
+        Condition::CountOf { m, condition, .. } => condition_depths(condition, base + m - 1, depths),
+        // A latch remembers its state indefinitely; it needs no warmup of its own
+        Condition::Latch { set, reset, .. } => {
+            condition_depths(set, base, depths);
+            condition_depths(reset, base, depths);
+        }
+    }
+}
+
+/// Validate an action's sizing and resolve its expressions. A buy's `size_pct`
+/// becomes an `equity_pct` sizing, so compiled buys always carry a policy.
+fn resolve_action(action: &Action, indicators: &HashSet<&str>, strategy: &Strategy) -> Result<Action> {
+    let (size_pct, sizing) = match action {
+        Action::Buy { size_pct, sizing } => (size_pct, sizing),
+        _ => return Ok(action.clone()),
+    };
+    let sizing = match (size_pct, sizing) {
+        (Some(pct), None) => Sizing::EquityPct { pct: *pct },
+        (None, Some(sizing)) => sizing.clone(),
+        (Some(_), Some(_)) => bail!("A buy takes 'size_pct' or 'sizing', not both"),
+        (None, None) => bail!("A buy needs 'size_pct' or 'sizing'"),
+    };
+    Ok(Action::Buy {
+        size_pct: None,
+        sizing: Some(resolve_sizing(sizing, indicators, strategy)?),
+    })
+}
+
+fn resolve_pyramiding(
+    pyramiding: &Pyramiding,
+    indicators: &HashSet<&str>,
+    strategy: &Strategy,
+) -> Result<Pyramiding> {
+    if pyramiding.max_entries == 0 {
+        bail!("'max_entries' must be at least 1");
+    }
+    let move_pct = pyramiding.min_price_move_pct;
+    if !(move_pct >= 0.0 && move_pct.is_finite()) {
+        bail!("'min_price_move_pct' must not be negative, got {}", move_pct);
+    }
+    let add_sizing = pyramiding
+        .add_sizing
+        .iter()
+        .enumerate()
+        .map(|(i, sizing)| {
+            resolve_sizing(sizing.clone(), indicators, strategy)
+                .with_context(|| format!("In add_sizing[{}]", i))
+        })
+        .collect::<Result<_>>()?;
+    Ok(Pyramiding {
+        add_sizing,
+        ..pyramiding.clone()
+    })
+}
+
+/// Validate a sizing policy and resolve its expressions
+fn resolve_sizing(sizing: Sizing, indicators: &HashSet<&str>, strategy: &Strategy) -> Result<Sizing> {
+    let params = &strategy.params;
+    let resolve = |expr: &Expr| expr.resolve(indicators, params).with_context(|| format!("In '{}'", expr));
+
+    Ok(match sizing {
+        Sizing::EquityPct { pct } => Sizing::EquityPct { pct: positive("pct", pct)? },
+        Sizing::FixedNotional { notional } => Sizing::FixedNotional {
+            notional: positive("notional", notional)?,
+        },
+        Sizing::FixedQuantity { quantity } => Sizing::FixedQuantity {
+            quantity: positive("quantity", quantity)?,
+        },
+        Sizing::RiskPerTrade { risk_pct, stop } => Sizing::RiskPerTrade {
+            risk_pct: positive("risk_pct", risk_pct)?,
+            stop: resolve(&stop)?,
+        },
+        Sizing::VolatilityTarget { target_vol_pct, atr } => {
+            interval_to_ms(&strategy.instrument.timeframe)
+                .context("Volatility targeting needs the instrument's bar length")?;
+            Sizing::VolatilityTarget {
+                target_vol_pct: positive("target_vol_pct", target_vol_pct)?,
+                atr: resolve(&atr)?,
+            }
+        }
+        Sizing::Kelly { fraction, lookback, min_trades, fallback_pct } => {
+            if !(fraction > 0.0 && fraction <= 1.0) {
+                bail!("Kelly 'fraction' must be in (0, 1], got {}", fraction);
+            }
+            if min_trades == 0 || min_trades > lookback {
+                bail!(
+                    "Kelly needs 1 <= min_trades <= lookback, got min_trades = {}, lookback = {}",
+                    min_trades,
+                    lookback
+                );
+            }
+            Sizing::Kelly {
+                fraction,
+                lookback,
+                min_trades,
+                fallback_pct: positive("fallback_pct", fallback_pct)?,
+            }
+        }
+        Sizing::Leverage { leverage } => {
+            let leverage = positive("leverage", leverage)?;
+            if let Some(max) = strategy.instrument.max_leverage {
+                if leverage > max {
+                    bail!("Leverage {}x is above the instrument's max leverage of {}x", leverage, max);
+                }
+            }
+            Sizing::Leverage { leverage }
+        }
+    })
+}
+
+fn positive(field: &str, value: f64) -> Result<f64> {
+    if !(value > 0.0 && value.is_finite()) {
+        bail!("'{}' must be positive, got {}", field, value);
+    }
+    Ok(value)
+}
+
+/// Type-check the expressions in `condition` and substitute parameters
+fn resolve_condition(
+    condition: &Condition,
+    indicators: &HashSet<&str>,
+    params: &HashMap<String, f64>,
+) -> Result<Condition> {
+    Ok(match condition {
+        Condition::Threshold { indicator, .. } => {
+            let id = indicator.split_once('.').map_or(indicator.as_str(), |(id, _)| id);
+            if !indicators.contains(indicator.as_str()) && !indicators.contains(id) {
+                bail!("Unknown indicator '{}' in threshold condition", indicator);
+            }
+            condition.clone()
+        }
+        Condition::Compare { lhs, op, rhs } => Condition::Compare {
+            lhs: lhs.resolve(indicators, params).with_context(|| format!("In '{}'", lhs))?,
+            op: *op,
+            rhs: rhs.resolve(indicators, params).with_context(|| format!("In '{}'", rhs))?,
+        },
+        Condition::Crossover { fast, slow, direction } => Condition::Crossover {
+            fast: fast.resolve(indicators, params).with_context(|| format!("In '{}'", fast))?,
+            slow: slow.resolve(indicators, params).with_context(|| format!("In '{}'", slow))?,
+            direction: *direction,
+        },
+        Condition::And { conditions } => Condition::And {
+            conditions: conditions
+                .iter()
+                .map(|c| resolve_condition(c, indicators, params))
+                .collect::<Result<_>>()?,
+        },
+        Condition::Or { conditions } => Condition::Or {
+            conditions: conditions
+                .iter()
+                .map(|c| resolve_condition(c, indicators, params))
+                .collect::<Result<_>>()?,
+        },
+        Condition::Not { condition } => Condition::Not {
+            condition: Box::new(resolve_condition(condition, indicators, params)?),
+        },
+        Condition::For { bars, condition, .. } => Condition::For {
+            bars: window("for", *bars)?,
+            condition: Box::new(resolve_condition(condition, indicators, params)?),
+            slot: 0,
+        },
+        Condition::Within { bars, condition, .. } => Condition::Within {
+            bars: window("within", *bars)?,
+            condition: Box::new(resolve_condition(condition, indicators, params)?),
+            slot: 0,
+        },
+        Condition::CountOf { n, m, condition, .. } => {
+            if *n == 0 || n > m {
+                bail!("'count_of' needs 1 <= n <= m, got n = {}, m = {}", n, m);
+            }
+            Condition::CountOf {
+                n: *n,
+                m: *m,
+                condition: Box::new(resolve_condition(condition, indicators, params)?),
+                slot: 0,
+            }
+        }
+        Condition::Latch { set, reset, .. } => Condition::Latch {
+            set: Box::new(resolve_condition(set, indicators, params)?),
+            reset: Box::new(resolve_condition(reset, indicators, params)?),
+            slot: 0,
+        },
+    })
+}
+
+/// Number the temporal conditions inside `condition` for
+/// [`EvalState`](crate::strategy::EvalState), inner ones first. Equal
+/// conditions see the same values, so they share a slot.
+fn assign_slots(condition: &mut Condition, slots: &mut HashMap<String, usize>) {
+    match condition {
+        Condition::Threshold { .. } | Condition::Compare { .. } | Condition::Crossover { .. } => return,
+        Condition::And { conditions } | Condition::Or { conditions } => {
+            for c in conditions {
+                assign_slots(c, slots);
+            }
+            return;
+        }
+        Condition::Not { condition } => return assign_slots(condition, slots),
+        Condition::For { condition, .. }
+        | Condition::Within { condition, .. }
+        | Condition::CountOf { condition, .. } => assign_slots(condition, slots),
+        Condition::Latch { set, reset, .. } => {
+            assign_slots(set, slots);
+            assign_slots(reset, slots);
+        }
+    }
+
+    // Keyed while its own slot is unset, so equal conditions get equal keys
+    let key = format!("{:?}", condition);
+    let next = slots.len();
+    if let Condition::For { slot, .. }
+    | Condition::Within { slot, .. }
+    | Condition::CountOf { slot, .. }
+    | Condition::Latch { slot, .. } = condition
+    {
+        *slot = *slots.entry(key).or_insert(next);
+    }
+}
@@ -1,22 +1,120 @@
+use crate::strategy::expr::Expr;
 use crate::strategy::types::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
-/// State for tracking crossover conditions
+/// Evaluation state carried between bars: past values of the series that
+/// conditions look back on (crossovers, `[n]` offsets, `highest`/`lowest`) and
+/// past outcomes of temporal conditions (`for`, `within`, `count_of`, `latch`).
+///
+/// Per bar, call [`evaluate`](EvalState::evaluate) as needed,
+/// [`observe`](EvalState::observe) every rule whose temporal conditions should
+/// keep counting (whether or not it was evaluated), then
+/// [`update`](EvalState::update) once. Temporal conditions keep their state
+/// in the slot [`compile_strategy`](crate::strategy::compile_strategy) assigns
+/// them, so conditions built by hand need distinct slots.
 pub struct EvalState {
-    /// Previous indicator values for crossover detection
-    prev_values: HashMap<String, f64>,
+    /// Past values per series, most recent first
+    history: HashMap<String, VecDeque<f64>>,
+    /// Bars to keep per series; `None` keeps the previous bar of every value
+    depths: Option<HashMap<String, usize>>,
+    /// Outcomes of temporal conditions, by the slot `compile_strategy` gave them
+    temporal: HashMap<usize, Temporal>,
+    /// This bar's outcomes, applied by `update`
+    pending: HashMap<usize, Outcome>,
+}
+
+/// One bar's outcome of a temporal condition
+#[derive(Clone, Copy)]
+enum Outcome {
+    /// The inner condition's result, and how many past results the window keeps
+    Window { result: bool, keep: usize },
+    /// The latch's new state
+    Latch(bool),
+}
+
+/// Past outcomes of one temporal condition
+#[derive(Default)]
+struct Temporal {
+    /// Inner condition results on previous bars, most recent first
+    recent: VecDeque<bool>,
+    /// Latch state after the previous bar
+    latched: bool,
 }
 
 impl EvalState {
     pub fn new() -> Self {
         Self {
-            prev_values: HashMap::new(),
+            history: HashMap::new(),
+            depths: None,
+            temporal: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// State keeping `depths[name]` past bars of each series, as computed by
+    /// [`compile_strategy`](crate::strategy::compile_strategy)
+    pub fn with_depths(depths: HashMap<String, usize>) -> Self {
+        Self {
+            depths: Some(depths),
+            ..Self::new()
         }
     }
 
-    /// Update state with current indicator values
+    /// Push the current bar's values into the history and apply the outcomes
+    /// recorded by [`observe`](EvalState::observe)
     pub fn update(&mut self, current_values: &HashMap<String, f64>) {
-        self.prev_values = current_values.clone();
+        for (slot, outcome) in self.pending.drain() {
+            let state = self.temporal.entry(slot).or_default();
+            match outcome {
+                Outcome::Window { result, keep } => {
+                    state.recent.push_front(result);
+                    state.recent.truncate(keep);
+                }
+                Outcome::Latch(latched) => state.latched = latched,
+            }
+        }
+
+        match &self.depths {
+            Some(depths) => {
+                for (name, &depth) in depths {
+                    if depth == 0 {
+                        continue;
+                    }
+                    let series = self
+                        .history
+                        .entry(name.clone())
+                        .or_insert_with(|| VecDeque::with_capacity(depth + 1));
+                    // A missing value still takes a slot so offsets stay aligned
+                    series.push_front(current_values.get(name).copied().unwrap_or(f64::NAN));
+                    series.truncate(depth);
+                }
+            }
+            None => {
+                self.history = current_values
+                    .iter()
+                    .map(|(name, value)| (name.clone(), VecDeque::from([*value])))
+                    .collect();
+            }
+        }
+    }
+
+    /// Value of series `name` `bars` bars ago (0 is the current bar)
+    fn value(&self, values: &HashMap<String, f64>, name: &str, bars: usize) -> Option<f64> {
+        let value = if bars == 0 {
+            values.get(name).copied()
+        } else {
+            self.history.get(name)?.get(bars - 1).copied()
+        };
+        value.filter(|v| !v.is_nan())
+    }
+
+    fn eval(&self, expr: &Expr, values: &HashMap<String, f64>, bars: usize) -> Option<f64> {
+        expr.eval_at(&|name, b| self.value(values, name, b), bars)
+    }
+
+    /// Value of `expr` on the current bar, reading offsets from the history
+    pub fn evaluate_expr(&self, expr: &Expr, values: &HashMap<String, f64>) -> Option<f64> {
+        self.eval(expr, values, 0)
     }
 
     /// Evaluate a condition against current indicator values
@@ -33,15 +131,21 @@ impl EvalState {
                     false
                 }
             }
+            Condition::Compare { lhs, op, rhs } => {
+                match (self.eval(lhs, values, 0), self.eval(rhs, values, 0)) {
+                    (Some(l), Some(r)) => compare(l, *op, r),
+                    _ => false,
+                }
+            }
             Condition::Crossover {
                 fast,
                 slow,
                 direction,
             } => {
-                let curr_fast = values.get(fast).copied();
-                let curr_slow = values.get(slow).copied();
-                let prev_fast = self.prev_values.get(fast).copied();
-                let prev_slow = self.prev_values.get(slow).copied();
+                let curr_fast = self.eval(fast, values, 0);
+                let curr_slow = self.eval(slow, values, 0);
+                let prev_fast = self.eval(fast, values, 1);
+                let prev_slow = self.eval(slow, values, 1);
 
                 match (curr_fast, curr_slow, prev_fast, prev_slow) {
                     (Some(cf), Some(cs), Some(pf), Some(ps)) => match direction {
@@ -57,8 +161,66 @@ impl EvalState {
             Condition::Or { conditions } => {
                 conditions.iter().any(|c| self.evaluate(c, values))
             }
+            Condition::Not { condition } => !self.evaluate(condition, values),
+            Condition::For { bars, condition, slot } => {
+                let recent = self.recent(*slot);
+                self.evaluate(condition, values)
+                    && recent.len() + 1 >= *bars
+                    && recent.iter().take(bars - 1).all(|r| *r)
+            }
+            Condition::Within { bars, condition, slot } => {
+                self.evaluate(condition, values)
+                    || self.recent(*slot).iter().take(bars - 1).any(|r| *r)
+            }
+            Condition::CountOf { n, m, condition, slot } => {
+                let now = self.evaluate(condition, values) as usize;
+                now + self.recent(*slot).iter().take(m - 1).filter(|r| **r).count() >= *n
+            }
+            Condition::Latch { set, reset, slot } => {
+                let latched = self.temporal.get(slot).is_some_and(|t| t.latched);
+                !self.evaluate(reset, values) && (latched || self.evaluate(set, values))
+            }
+        }
+    }
+
+    /// Record this bar's outcome of every temporal condition inside `condition`.
+    /// Outcomes take effect at the next [`update`](EvalState::update); observing
+    /// the same condition twice in a bar counts once.
+    pub fn observe(&mut self, condition: &Condition, values: &HashMap<String, f64>) {
+        let mut outcomes = Vec::new();
+        self.collect(condition, values, &mut outcomes);
+        self.pending.extend(outcomes);
+    }
+
+    fn collect(&self, node: &Condition, values: &HashMap<String, f64>, out: &mut Vec<(usize, Outcome)>) {
+        match node {
+            Condition::Threshold { .. } | Condition::Compare { .. } | Condition::Crossover { .. } => {}
+            Condition::And { conditions } | Condition::Or { conditions } => {
+                for c in conditions {
+                    self.collect(c, values, out);
+                }
+            }
+            Condition::Not { condition } => self.collect(condition, values, out),
+            Condition::For { bars: window, condition, slot }
+            | Condition::Within { bars: window, condition, slot }
+            | Condition::CountOf { m: window, condition, slot, .. } => {
+                let result = self.evaluate(condition, values);
+                out.push((*slot, Outcome::Window { result, keep: window - 1 }));
+                self.collect(condition, values, out);
+            }
+            Condition::Latch { set, reset, slot } => {
+                out.push((*slot, Outcome::Latch(self.evaluate(node, values))));
+                self.collect(set, values, out);
+                self.collect(reset, values, out);
+            }
         }
     }
+
+    /// Past inner results of a windowed condition, most recent first
+    fn recent(&self, slot: usize) -> &VecDeque<bool> {
+        static EMPTY: VecDeque<bool> = VecDeque::new();
+        self.temporal.get(&slot).map_or(&EMPTY, |t| &t.recent)
+    }
 }
 
 impl Default for EvalState {
@@ -118,14 +280,77 @@ mod tests {
         .collect();
 
         let condition = Condition::Crossover {
-            fast: "fast_ma".to_string(),
-            slow: "slow_ma".to_string(),
+            fast: "fast_ma".into(),
+            slow: "slow_ma".into(),
             direction: CrossDirection::Above,
         };
 
         assert!(state.evaluate(&condition, &curr_values));
     }
 
+    #[test]
+    fn test_compare_expressions() {
+        let mut state = EvalState::new();
+        let values: HashMap<String, f64> = [
+            ("close".to_string(), 103.0),
+            ("sma".to_string(), 100.0),
+        ]
+        .into_iter()
+        .collect();
+
+        let condition = Condition::Compare {
+            lhs: "close".into(),
+            op: ComparisonOp::Gt,
+            rhs: "sma * 1.02".parse().unwrap(),
+        };
+        assert!(state.evaluate(&condition, &values));
+
+        // Crossovers work on expressions too: close - sma crossing above 2.5
+        let cross = Condition::Crossover {
+            fast: "close - sma".parse().unwrap(),
+            slow: 2.5.into(),
+            direction: CrossDirection::Above,
+        };
+        let prev: HashMap<String, f64> = [
+            ("close".to_string(), 101.0),
+            ("sma".to_string(), 100.0),
+        ]
+        .into_iter()
+        .collect();
+        state.update(&prev);
+        assert!(state.evaluate(&cross, &values));
+
+        // Undefined operands never match
+        let missing = Condition::Compare {
+            lhs: "atr".into(),
+            op: ComparisonOp::Lt,
+            rhs: 1.0.into(),
+        };
+        assert!(!state.evaluate(&missing, &values));
+    }
+
+    #[test]
+    fn test_history_offsets() {
+        let depths: HashMap<String, usize> = [("rsi".to_string(), 3)].into_iter().collect();
+        let mut state = EvalState::with_depths(depths);
+        let condition = Condition::Compare {
+            lhs: "rsi[3]".parse().unwrap(),
+            op: ComparisonOp::Lt,
+            rhs: 30.0.into(),
+        };
+
+        let bar = |rsi: f64| -> HashMap<String, f64> { [("rsi".to_string(), rsi)].into_iter().collect() };
+        for rsi in [25.0, 50.0, 60.0] {
+            // Not enough history yet
+            assert!(!state.evaluate(&condition, &bar(rsi)));
+            state.update(&bar(rsi));
+        }
+        // rsi three bars ago was 25
+        assert!(state.evaluate(&condition, &bar(70.0)));
+        state.update(&bar(70.0));
+        assert!(!state.evaluate(&condition, &bar(70.0)));
+    }
+
     #[test]
     fn test_and_condition() {
         let state = EvalState::new();
@@ -153,4 +378,146 @@ mod tests {
 
         assert!(state.evaluate(&condition, &values));
     }
+
+    fn rsi_below(level: f64) -> Condition {
+        Condition::Threshold {
+            indicator: "rsi".to_string(),
+            op: ComparisonOp::Lt,
+            value: level,
+        }
+    }
+
+    /// Evaluate `condition` over a series of RSI values, observing and updating
+    /// after each bar like the engine does
+    fn run(condition: &Condition, series: &[f64]) -> Vec<bool> {
+        let mut state = EvalState::new();
+        series
+            .iter()
+            .map(|rsi| {
+                let values: HashMap<String, f64> = [("rsi".to_string(), *rsi)].into_iter().collect();
+                let result = state.evaluate(condition, &values);
+                state.observe(condition, &values);
+                state.update(&values);
+                result
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_not_condition() {
+        let not = Condition::Not {
+            condition: Box::new(rsi_below(30.0)),
+        };
+        assert_eq!(run(&not, &[25.0, 35.0]), [false, true]);
+    }
+
+    #[test]
+    fn test_for_condition() {
+        let condition = Condition::For {
+            bars: 3,
+            condition: Box::new(rsi_below(30.0)),
+            slot: 0,
+        };
+        assert_eq!(
+            run(&condition, &[25.0, 25.0, 25.0, 25.0, 35.0, 25.0, 25.0, 25.0]),
+            [false, false, true, true, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn test_within_condition() {
+        let condition = Condition::Within {
+            bars: 3,
+            condition: Box::new(rsi_below(30.0)),
+            slot: 0,
+        };
+        assert_eq!(
+            run(&condition, &[35.0, 25.0, 35.0, 35.0, 35.0]),
+            [false, true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn test_within_crossover() {
+        // "crossover happened within the last 2 bars", over expressions
+        let condition = Condition::Within {
+            bars: 2,
+            condition: Box::new(Condition::Crossover {
+                fast: "rsi".into(),
+                slow: 50.0.into(),
+                direction: CrossDirection::Above,
+            }),
+            slot: 0,
+        };
+        assert_eq!(
+            run(&condition, &[40.0, 60.0, 65.0, 70.0]),
+            [false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn test_count_of_condition() {
+        let condition = Condition::CountOf {
+            n: 2,
+            m: 4,
+            condition: Box::new(rsi_below(30.0)),
+            slot: 0,
+        };
+        assert_eq!(
+            run(&condition, &[25.0, 35.0, 25.0, 35.0, 35.0, 35.0]),
+            [false, false, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn test_latch_condition() {
+        // Set when RSI drops below 30, reset when it rises above 70
+        let condition = Condition::Latch {
+            set: Box::new(rsi_below(30.0)),
+            reset: Box::new(Condition::Not {
+                condition: Box::new(rsi_below(70.0)),
+            }),
+            slot: 0,
+        };
+        assert_eq!(
+            run(&condition, &[50.0, 25.0, 50.0, 60.0, 75.0, 50.0, 20.0]),
+            [false, true, true, true, false, false, true]
+        );
+    }
+
+    #[test]
+    fn test_nested_temporal_conditions() {
+        // "below 30 for 2 bars" happened within the last 3 bars
+        let condition = Condition::Within {
+            bars: 3,
+            condition: Box::new(Condition::For {
+                bars: 2,
+                condition: Box::new(rsi_below(30.0)),
+                slot: 0,
+            }),
+            slot: 1,
+        };
+        assert_eq!(
+            run(&condition, &[25.0, 25.0, 35.0, 35.0, 35.0]),
+            [false, true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn test_temporal_state_counts_without_evaluation() {
+        // Observing keeps the count going on bars where the rule isn't evaluated
+        let condition = Condition::For {
+            bars: 2,
+            condition: Box::new(rsi_below(30.0)),
+            slot: 0,
+        };
+        let values: HashMap<String, f64> = [("rsi".to_string(), 25.0)].into_iter().collect();
+        let mut state = EvalState::new();
+        state.observe(&condition, &values);
+        // Observing the same condition twice in a bar counts once
+        state.observe(&condition, &values);
+        state.update(&values);
+        assert!(state.evaluate(&condition, &values));
+        assert_eq!(state.recent(0).len(), 1);
+    }
 }
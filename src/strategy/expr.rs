@@ -0,0 +1,727 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::data::types::Candle;
+
+/// Candle fields usable in expressions
+pub const CANDLE_FIELDS: [&str; 5] = ["open", "high", "low", "close", "volume"];
+
+/// Position fields usable in expressions. `position_size` and `position_entries`
+/// are 0 while flat; the others are only set while in a position.
+pub const POSITION_FIELDS: [&str; 5] = [
+    "position_size",
+    "position_entries",
+    "entry_price",
+    "unrealized_pnl_pct",
+    "bars_since_entry",
+];
+
+/// Functions usable in expressions, with their minimum and maximum argument count
+const FUNCTIONS: [(&str, usize, usize); 5] = [
+    ("abs", 1, 1),
+    ("min", 2, usize::MAX),
+    ("max", 2, usize::MAX),
+    ("highest", 2, 2),
+    ("lowest", 2, 2),
+];
+
+/// Arithmetic expression used as a condition operand.
+///
+/// Written in JSON as a number or a string such as `"sma_50 * 1.02"` or
+/// `"abs(ema_fast - ema_slow) / atr"`. Identifiers name indicators (`macd.signal`
+/// for a secondary output), strategy parameters, [`CANDLE_FIELDS`] or
+/// [`POSITION_FIELDS`], resolved in that order by
+/// [`compile_strategy`](crate::strategy::compile_strategy).
+///
+/// A `[n]` suffix reads any operand `n` bars ago (`rsi[3]`, `(close - open)[1]`),
+/// and `highest(x, n)` / `lowest(x, n)` take the extreme of `x` over the last
+/// `n` bars. Past values come from [`EvalState`](crate::strategy::EvalState)'s
+/// history, whose depth [`Expr::history_depths`] reports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ExprRepr", into = "ExprRepr")]
+pub enum Expr {
+    Num(f64),
+    Ref(String),
+    Neg(Box<Expr>),
+    Binary {
+        op: BinOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Call { func: String, args: Vec<Expr> },
+    /// `expr` as it was `bars` bars ago
+    Offset { expr: Box<Expr>, bars: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl BinOp {
+    fn symbol(self) -> char {
+        match self {
+            BinOp::Add => '+',
+            BinOp::Sub => '-',
+            BinOp::Mul => '*',
+            BinOp::Div => '/',
+        }
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            BinOp::Add | BinOp::Sub => 1,
+            BinOp::Mul | BinOp::Div => 2,
+        }
+    }
+}
+
+impl Expr {
+    /// Value of the expression on the current bar, or `None` if a referenced
+    /// value is missing or the result is not finite (e.g. division by zero).
+    /// Past values are unavailable here; see [`Expr::eval_at`].
+    pub fn eval(&self, values: &HashMap<String, f64>) -> Option<f64> {
+        self.eval_at(&|name, bars| if bars == 0 { values.get(name).copied() } else { None }, 0)
+    }
+
+    /// Value of the expression `bars` bars ago, reading series through
+    /// `lookup(name, bars_ago)`
+    pub fn eval_at(&self, lookup: &dyn Fn(&str, usize) -> Option<f64>, bars: usize) -> Option<f64> {
+        let value = match self {
+            Expr::Num(n) => *n,
+            Expr::Ref(name) => lookup(name, bars)?,
+            Expr::Neg(inner) => -inner.eval_at(lookup, bars)?,
+            Expr::Offset { expr, bars: offset } => expr.eval_at(lookup, bars + offset)?,
+            Expr::Call { func, args } if func == "highest" || func == "lowest" => {
+                let window = window(args)?;
+                let values = (bars..bars + window)
+                    .map(|b| args[0].eval_at(lookup, b))
+                    .collect::<Option<Vec<f64>>>()?;
+                if func == "highest" {
+                    values.into_iter().reduce(f64::max)?
+                } else {
+                    values.into_iter().reduce(f64::min)?
+                }
+            }
+            Expr::Binary { op, lhs, rhs } => {
+                let (l, r) = (lhs.eval_at(lookup, bars)?, rhs.eval_at(lookup, bars)?);
+                match op {
+                    BinOp::Add => l + r,
+                    BinOp::Sub => l - r,
+                    BinOp::Mul => l * r,
+                    BinOp::Div => l / r,
+                }
+            }
+            Expr::Call { func, args } => {
+                let args = args
+                    .iter()
+                    .map(|a| a.eval_at(lookup, bars))
+                    .collect::<Option<Vec<f64>>>()?;
+                match func.as_str() {
+                    "abs" => args.first()?.abs(),
+                    "min" => args.into_iter().reduce(f64::min)?,
+                    "max" => args.into_iter().reduce(f64::max)?,
+                    _ => return None,
+                }
+            }
+        };
+        Some(value).filter(|v| v.is_finite())
+    }
+
+    /// Resolve identifiers: indicator keys stay references, parameters become
+    /// constants and built-in fields stay references. Fails on unknown
+    /// identifiers and functions or wrong argument counts.
+    pub(crate) fn resolve(
+        &self,
+        indicators: &HashSet<&str>,
+        params: &HashMap<String, f64>,
+    ) -> Result<Expr> {
+        Ok(match self {
+            Expr::Num(n) => Expr::Num(*n),
+            Expr::Ref(name) => {
+                let indicator = name.split_once('.').map_or(name.as_str(), |(id, _)| id);
+                if indicators.contains(name.as_str()) || indicators.contains(indicator) {
+                    Expr::Ref(name.clone())
+                } else if let Some(value) = params.get(name) {
+                    Expr::Num(*value)
+                } else if CANDLE_FIELDS.contains(&name.as_str())
+                    || POSITION_FIELDS.contains(&name.as_str())
+                {
+                    Expr::Ref(name.clone())
+                } else {
+                    bail!(
+                        "Unknown identifier '{}': not an indicator, parameter or built-in field",
+                        name
+                    );
+                }
+            }
+            Expr::Neg(inner) => Expr::Neg(Box::new(inner.resolve(indicators, params)?)),
+            Expr::Binary { op, lhs, rhs } => Expr::Binary {
+                op: *op,
+                lhs: Box::new(lhs.resolve(indicators, params)?),
+                rhs: Box::new(rhs.resolve(indicators, params)?),
+            },
+            Expr::Call { func, args } => {
+                let Some(&(_, min, max)) = FUNCTIONS.iter().find(|(name, _, _)| name == func) else {
+                    let names: Vec<&str> = FUNCTIONS.iter().map(|(name, _, _)| *name).collect();
+                    bail!("Unknown function '{}' (expected one of: {})", func, names.join(", "));
+                };
+                if args.len() < min || args.len() > max {
+                    bail!("{}() takes {} argument(s), got {}", func, arity(min, max), args.len());
+                }
+                let args = args
+                    .iter()
+                    .map(|a| a.resolve(indicators, params))
+                    .collect::<Result<Vec<_>>>()?;
+                if (func == "highest" || func == "lowest") && window(&args).is_none() {
+                    bail!("{}() needs a whole number of bars of at least 1 as its second argument", func);
+                }
+                Expr::Call {
+                    func: func.clone(),
+                    args,
+                }
+            }
+            Expr::Offset { expr, bars } => Expr::Offset {
+                expr: Box::new(expr.resolve(indicators, params)?),
+                bars: *bars,
+            },
+        })
+    }
+
+    /// Past bars each referenced series must keep to evaluate this expression
+    /// `base` bars ago, merged into `depths` (the deepest requirement wins)
+    pub fn history_depths(&self, base: usize, depths: &mut HashMap<String, usize>) {
+        match self {
+            Expr::Num(_) => {}
+            Expr::Ref(name) => {
+                let depth = depths.entry(name.clone()).or_insert(0);
+                *depth = (*depth).max(base);
+            }
+            Expr::Neg(inner) => inner.history_depths(base, depths),
+            Expr::Binary { lhs, rhs, .. } => {
+                lhs.history_depths(base, depths);
+                rhs.history_depths(base, depths);
+            }
+            Expr::Call { func, args } if func == "highest" || func == "lowest" => {
+                let window = window(args).unwrap_or(1);
+                args[0].history_depths(base + window - 1, depths);
+            }
+            Expr::Call { args, .. } => {
+                for arg in args {
+                    arg.history_depths(base, depths);
+                }
+            }
+            Expr::Offset { expr, bars } => expr.history_depths(base + bars, depths),
+        }
+    }
+}
+
+/// Window of `highest`/`lowest`: the second argument as a whole number of bars
+fn window(args: &[Expr]) -> Option<usize> {
+    match args.get(1)? {
+        Expr::Num(n) if *n >= 1.0 && n.fract() == 0.0 => Some(*n as usize),
+        _ => None,
+    }
+}
+
+fn arity(min: usize, max: usize) -> String {
+    match (min, max) {
+        (min, max) if min == max => min.to_string(),
+        (min, usize::MAX) => format!("at least {}", min),
+        (min, max) => format!("{} to {}", min, max),
+    }
+}
+
+impl From<&str> for Expr {
+    /// A reference to `name`, without parsing it
+    fn from(name: &str) -> Self {
+        Expr::Ref(name.to_string())
+    }
+}
+
+impl From<String> for Expr {
+    fn from(name: String) -> Self {
+        Expr::Ref(name)
+    }
+}
+
+/// An expression equals a string when it references exactly that name
+impl PartialEq<str> for Expr {
+    fn eq(&self, other: &str) -> bool {
+        matches!(self, Expr::Ref(name) if name == other)
+    }
+}
+
+impl From<f64> for Expr {
+    fn from(value: f64) -> Self {
+        Expr::Num(value)
+    }
+}
+
+/// The open position, as seen by the [`POSITION_FIELDS`]
+pub struct PositionState {
+    /// Signed size
+    pub size: f64,
+    /// Average cost
+    pub entry_price: f64,
+    /// Fills that opened or added to the position
+    pub entries: usize,
+    /// Bars since the last of them
+    pub bars_since_entry: usize,
+}
+
+/// Set the [`CANDLE_FIELDS`] and [`POSITION_FIELDS`] in `values`, without
+/// overwriting indicators of the same name
+pub fn insert_builtin_fields(
+    values: &mut HashMap<String, f64>,
+    bar: &Candle,
+    position: Option<&PositionState>,
+) {
+    let candle = [bar.open, bar.high, bar.low, bar.close, bar.volume];
+    for (name, value) in CANDLE_FIELDS.iter().zip(candle) {
+        values.entry(name.to_string()).or_insert(value);
+    }
+
+    let position = position.filter(|p| p.size.abs() > 1e-10 && p.entry_price > 0.0);
+    let (size, entries) = position.map_or((0.0, 0.0), |p| (p.size, p.entries as f64));
+    values.entry("position_size".to_string()).or_insert(size);
+    values.entry("position_entries".to_string()).or_insert(entries);
+
+    let Some(position) = position else {
+        return;
+    };
+    let pnl_pct = (bar.close - position.entry_price) / position.entry_price * 100.0 * position.size.signum();
+    let fields = [position.entry_price, pnl_pct, position.bars_since_entry as f64];
+    for (name, value) in POSITION_FIELDS[2..].iter().zip(fields) {
+        values.entry(name.to_string()).or_insert(value);
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Num(n) => write!(f, "{}", n),
+            Expr::Ref(name) => write!(f, "{}", name),
+            Expr::Neg(inner) => match inner.as_ref() {
+                Expr::Binary { .. } => write!(f, "-({})", inner),
+                _ => write!(f, "-{}", inner),
+            },
+            Expr::Binary { op, lhs, rhs } => {
+                // Parenthesize operands that bind looser, and right operands of
+                // equal precedence (a - (b - c))
+                let wrap = |e: &Expr, right: bool| match e {
+                    Expr::Binary { op: inner, .. } => {
+                        inner.precedence() < op.precedence()
+                            || (right && inner.precedence() == op.precedence())
+                    }
+                    _ => false,
+                };
+                if wrap(lhs, false) {
+                    write!(f, "({})", lhs)?;
+                } else {
+                    write!(f, "{}", lhs)?;
+                }
+                write!(f, " {} ", op.symbol())?;
+                if wrap(rhs, true) {
+                    write!(f, "({})", rhs)
+                } else {
+                    write!(f, "{}", rhs)
+                }
+            }
+            Expr::Offset { expr, bars } => match expr.as_ref() {
+                Expr::Binary { .. } | Expr::Neg(_) => write!(f, "({})[{}]", expr, bars),
+                _ => write!(f, "{}[{}]", expr, bars),
+            },
+            Expr::Call { func, args } => {
+                write!(f, "{}(", func)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.expr()?;
+        if let Some(token) = parser.peek() {
+            bail!("Unexpected '{}' in expression '{}'", token, s);
+        }
+        Ok(expr)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ExprRepr {
+    Num(f64),
+    Text(String),
+}
+
+impl TryFrom<ExprRepr> for Expr {
+    type Error = anyhow::Error;
+
+    fn try_from(repr: ExprRepr) -> Result<Self> {
+        match repr {
+            ExprRepr::Num(n) => Ok(Expr::Num(n)),
+            ExprRepr::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl From<Expr> for ExprRepr {
+    fn from(expr: Expr) -> Self {
+        match expr {
+            Expr::Num(n) => ExprRepr::Num(n),
+            other => ExprRepr::Text(other.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Op(char),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Num(n) => write!(f, "{}", n),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Op(c) => write!(f, "{}", c),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::LBracket => write!(f, "["),
+            Token::RBracket => write!(f, "]"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            ' ' | '\t' | '\n' => i += 1,
+            '+' | '-' | '*' | '/' => {
+                tokens.push(Token::Op(c));
+                i += 1;
+            }
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '[' => {
+                tokens.push(Token::LBracket);
+                i += 1;
+            }
+            ']' => {
+                tokens.push(Token::RBracket);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                // Exponent, e.g. 1e-4
+                if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                    let mut j = i + 1;
+                    if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        i = j;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                let value = text
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid number '{}' in expression '{}'", text, s))?;
+                tokens.push(Token::Num(value));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            other => bail!("Unexpected character '{}' in expression '{}'", other, s),
+        }
+    }
+    if tokens.is_empty() {
+        bail!("Empty expression");
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over `expr := term (('+' | '-') term)*`,
+/// `term := unary (('*' | '/') unary)*`, `unary := '-' unary | postfix`,
+/// `postfix := atom ('[' integer ']')*`
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => bail!("Expected '{}', found '{}'", expected, token),
+            None => bail!("Expected '{}' at end of expression", expected),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let mut lhs = self.term()?;
+        while let Some(Token::Op(c @ ('+' | '-'))) = self.peek() {
+            let op = if *c == '+' { BinOp::Add } else { BinOp::Sub };
+            self.pos += 1;
+            let rhs = self.term()?;
+            lhs = Expr::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op(c @ ('*' | '/'))) = self.peek() {
+            let op = if *c == '*' { BinOp::Mul } else { BinOp::Div };
+            self.pos += 1;
+            let rhs = self.unary()?;
+            lhs = Expr::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if let Some(Token::Op('-')) = self.peek() {
+            self.pos += 1;
+            return Ok(match self.unary()? {
+                Expr::Num(n) => Expr::Num(-n),
+                inner => Expr::Neg(Box::new(inner)),
+            });
+        }
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Result<Expr> {
+        let mut expr = self.atom()?;
+        while self.peek() == Some(&Token::LBracket) {
+            self.pos += 1;
+            let bars = match self.next() {
+                Some(Token::Num(n)) if n >= 0.0 && n.fract() == 0.0 => n as usize,
+                Some(token) => bail!("Expected a whole number of bars in [], found '{}'", token),
+                None => bail!("Unexpected end of expression"),
+            };
+            self.expect(Token::RBracket)?;
+            expr = match expr {
+                Expr::Offset { expr, bars: inner } => Expr::Offset {
+                    expr,
+                    bars: inner + bars,
+                },
+                expr => Expr::Offset {
+                    expr: Box::new(expr),
+                    bars,
+                },
+            };
+        }
+        Ok(expr)
+    }
+
+    fn atom(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(Expr::Num(n)),
+            Some(Token::Ident(name)) => {
+                if self.peek() != Some(&Token::LParen) {
+                    return Ok(Expr::Ref(name));
+                }
+                self.pos += 1;
+                let mut args = Vec::new();
+                if self.peek() != Some(&Token::RParen) {
+                    args.push(self.expr()?);
+                    while self.peek() == Some(&Token::Comma) {
+                        self.pos += 1;
+                        args.push(self.expr()?);
+                    }
+                }
+                self.expect(Token::RParen)?;
+                Ok(Expr::Call { func: name, args })
+            }
+            Some(Token::LParen) => {
+                let inner = self.expr()?;
+                self.expect(Token::RParen)?;
+                Ok(inner)
+            }
+            Some(token) => bail!("Unexpected '{}'", token),
+            None => bail!("Unexpected end of expression"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, f64)]) -> HashMap<String, f64> {
+        pairs.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn test_parse_and_eval() {
+        let vals = values(&[("ema_fast", 105.0), ("ema_slow", 103.0), ("atr", 2.0), ("macd.signal", -1.5)]);
+        let cases = [
+            ("ema_fast - ema_slow", 2.0),
+            ("0.5 * atr + 1", 2.0),
+            ("(ema_fast - ema_slow) / atr", 1.0),
+            ("10 - 4 - 3", 3.0),
+            ("-atr * 2", -4.0),
+            ("abs(macd.signal)", 1.5),
+            ("max(atr, 3, -1) + min(1, 2)", 4.0),
+            ("1e-2 * 100", 1.0),
+        ];
+        for (text, expected) in cases {
+            let expr: Expr = text.parse().unwrap();
+            assert_eq!(expr.eval(&vals), Some(expected), "{}", text);
+        }
+
+        // Missing values and division by zero make the expression undefined
+        assert_eq!("rsi + 1".parse::<Expr>().unwrap().eval(&vals), None);
+        assert_eq!("atr / 0".parse::<Expr>().unwrap().eval(&vals), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for text in ["", "1 +", "(atr", "atr)", "max(1,)", "atr # 2", "1 2", "rsi[1.5]", "rsi[-1]", "rsi[2"] {
+            assert!(text.parse::<Expr>().is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        for text in [
+            "(a - b) * 2",
+            "a - (b - c)",
+            "a / (b * c)",
+            "-(a + b)",
+            "max(a, b * 2) - 1",
+            "(a - b)[2] + highest(c, 5)[1]",
+        ] {
+            let expr: Expr = text.parse().unwrap();
+            let printed = expr.to_string();
+            assert_eq!(printed.parse::<Expr>().unwrap(), expr, "{}", printed);
+        }
+        assert_eq!("(a - b) * 2".parse::<Expr>().unwrap().to_string(), "(a - b) * 2");
+        assert_eq!("a + (b * c)".parse::<Expr>().unwrap().to_string(), "a + b * c");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let indicators: HashSet<&str> = ["sma", "macd"].into_iter().collect();
+        let params = values(&[("k", 1.02)]);
+
+        let resolved = "close - sma * k + macd.signal"
+            .parse::<Expr>()
+            .unwrap()
+            .resolve(&indicators, &params)
+            .unwrap();
+        assert_eq!(resolved.to_string(), "close - sma * 1.02 + macd.signal");
+
+        for text in ["smaa", "sqrt(sma)", "abs(sma, 1)", "max(sma)", "highest(sma, 0)", "lowest(sma, k)"] {
+            let expr: Expr = text.parse().unwrap();
+            assert!(expr.resolve(&indicators, &params).is_err(), "{}", text);
+        }
+
+        let err = "sqrt(sma)".parse::<Expr>().unwrap().resolve(&indicators, &params).unwrap_err();
+        assert!(err.to_string().contains("abs, min, max, highest, lowest"), "{}", err);
+    }
+
+    #[test]
+    fn test_offsets_and_windows() {
+        // Series values, most recent first
+        let history: HashMap<&str, Vec<f64>> = [
+            ("close", vec![10.0, 12.0, 11.0, 15.0, 9.0]),
+            ("open", vec![9.0, 10.0, 12.0, 11.0, 15.0]),
+        ]
+        .into_iter()
+        .collect();
+        let lookup = |name: &str, bars: usize| history.get(name)?.get(bars).copied();
+        let eval = |text: &str| text.parse::<Expr>().unwrap().eval_at(&lookup, 0);
+
+        assert_eq!(eval("close[2]"), Some(11.0));
+        assert_eq!(eval("close[1][1]"), Some(11.0));
+        assert_eq!(eval("(close - open)[1]"), Some(2.0));
+        assert_eq!(eval("highest(close, 3)"), Some(12.0));
+        assert_eq!(eval("highest(close, 3)[1]"), Some(15.0));
+        assert_eq!(eval("lowest(open, 5)"), Some(9.0));
+        // Not enough history
+        assert_eq!(eval("close[5]"), None);
+        assert_eq!(eval("highest(close, 3)[3]"), None);
+
+        let mut depths = HashMap::new();
+        "close[2] - highest(high, 20)[1] + open"
+            .parse::<Expr>()
+            .unwrap()
+            .history_depths(0, &mut depths);
+        assert_eq!(depths["close"], 2);
+        assert_eq!(depths["high"], 20);
+        assert_eq!(depths["open"], 0);
+    }
+}
@@ -1,7 +1,10 @@
 pub mod types;
 pub mod compile;
 pub mod eval;
+pub mod expr;
+pub mod sizing;
 
 pub use types::*;
-pub use compile::compile_strategy;
+pub use compile::{compile_strategy, compile_strategy_with};
 pub use eval::EvalState;
+pub use expr::Expr;
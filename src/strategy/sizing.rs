@@ -0,0 +1,221 @@
+//! Position sizing for buy actions: turns a [`Sizing`] policy into an order size,
+//! then applies the instrument's leverage cap and lot size.
+
+use std::collections::BTreeMap;
+
+use crate::strategy::expr::Expr;
+use crate::strategy::types::{Instrument, Sizing};
+
+/// State of the market and the strategy a size is computed from
+pub struct SizingInputs<'a> {
+    pub price: f64,
+    pub equity: f64,
+    /// Signed position before the order
+    pub position: f64,
+    /// Returns (PnL over cost, after fees) of completed round trips, oldest first
+    pub round_trips: &'a [f64],
+    /// Bars per year of the strategy's timeframe, for volatility targeting
+    pub bars_per_year: Option<f64>,
+}
+
+/// Size `sizing` asks for, and the values it used. `eval` evaluates the
+/// policy's expressions on the current bar.
+///
+/// `None` if an input is missing or not positive (e.g. an ATR that has no
+/// value yet, a stop distance of zero, or no equity left).
+pub fn target_size(
+    sizing: &Sizing,
+    inputs: &SizingInputs,
+    eval: &dyn Fn(&Expr) -> Option<f64>,
+) -> Option<(f64, BTreeMap<String, f64>)> {
+    let SizingInputs { price, equity, .. } = *inputs;
+    if equity <= 0.0 {
+        return None;
+    }
+    let positive = |expr: &Expr| eval(expr).filter(|v| *v > 0.0);
+    let mut used = BTreeMap::new();
+
+    let size = match sizing {
+        Sizing::EquityPct { pct } => equity * pct / 100.0 / price,
+        Sizing::FixedNotional { notional } => notional / price,
+        Sizing::FixedQuantity { quantity } => *quantity,
+        Sizing::RiskPerTrade { risk_pct, stop } => {
+            let distance = positive(stop)?;
+            used.insert("stop_distance".to_string(), distance);
+            equity * risk_pct / 100.0 / distance
+        }
+        Sizing::VolatilityTarget { target_vol_pct, atr } => {
+            let atr = positive(atr)?;
+            let annual_vol = atr / price * inputs.bars_per_year?.sqrt();
+            used.insert("atr".to_string(), atr);
+            used.insert("annual_vol_pct".to_string(), annual_vol * 100.0);
+            equity * target_vol_pct / 100.0 / annual_vol / price
+        }
+        Sizing::Kelly {
+            fraction,
+            lookback,
+            min_trades,
+            fallback_pct,
+        } => {
+            let start = inputs.round_trips.len().saturating_sub(*lookback);
+            let trips = &inputs.round_trips[start..];
+            used.insert("round_trips".to_string(), trips.len() as f64);
+            let pct = if trips.len() < *min_trades {
+                *fallback_pct
+            } else {
+                let kelly = kelly_fraction(trips);
+                used.insert("kelly".to_string(), kelly);
+                kelly * fraction * 100.0
+            };
+            used.insert("equity_pct".to_string(), pct);
+            equity * pct / 100.0 / price
+        }
+        Sizing::Leverage { leverage } => equity * leverage / price,
+    };
+    (size.is_finite() && size > 0.0).then_some((size, used))
+}
+
+/// Cap `size` so the position stays within the instrument's `max_leverage`,
+/// then round it down to the lot size. Returns the size and what changed it.
+pub fn apply_limits(size: f64, inputs: &SizingInputs, instrument: &Instrument) -> (f64, Vec<String>) {
+    let mut adjustments = Vec::new();
+    let mut size = size;
+
+    if let Some(max) = instrument.max_leverage {
+        let room = inputs.equity * max / inputs.price - inputs.position.abs();
+        if size > room {
+            size = room.max(0.0);
+            adjustments.push(format!("capped at {}x max leverage", max));
+        }
+    }
+    if let Some(decimals) = instrument.sz_decimals {
+        let rounded = round_to_lot(size, decimals);
+        if rounded != size {
+            adjustments.push(format!("rounded down to the lot size of {}", lot_size(decimals)));
+        }
+        size = rounded;
+    }
+    (size, adjustments)
+}
+
+/// Size rounded down to a multiple of the lot size 10^-`sz_decimals`
+pub fn round_to_lot(size: f64, sz_decimals: u32) -> f64 {
+    let scale = 10f64.powi(sz_decimals as i32);
+    // The epsilon keeps sizes that are already whole lots from losing one
+    (size * scale + 1e-9).floor() / scale
+}
+
+fn lot_size(sz_decimals: u32) -> f64 {
+    10f64.powi(-(sz_decimals as i32))
+}
+
+/// Kelly criterion `W - (1 - W) / R` from round-trip returns, where `W` is the
+/// share of winners and `R` the average win over the average loss. Clamped to
+/// `0..=1`: no edge bets nothing, and the stake never exceeds equity.
+pub fn kelly_fraction(returns: &[f64]) -> f64 {
+    if returns.is_empty() {
+        return 0.0;
+    }
+    let wins: Vec<f64> = returns.iter().copied().filter(|r| *r > 0.0).collect();
+    let losses: Vec<f64> = returns.iter().filter(|r| **r < 0.0).map(|r| -r).collect();
+    let win_rate = wins.len() as f64 / returns.len() as f64;
+    if wins.is_empty() {
+        return 0.0;
+    }
+    if losses.is_empty() {
+        return win_rate;
+    }
+    let avg_win = wins.iter().sum::<f64>() / wins.len() as f64;
+    let avg_loss = losses.iter().sum::<f64>() / losses.len() as f64;
+    (win_rate - (1.0 - win_rate) / (avg_win / avg_loss)).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instrument(sz_decimals: Option<u32>, max_leverage: Option<f64>) -> Instrument {
+        Instrument {
+            symbol: "BTC-PERP".to_string(),
+            coin: "BTC".to_string(),
+            venue: "HL".to_string(),
+            timeframe: "1h".to_string(),
+            sz_decimals,
+            max_leverage,
+        }
+    }
+
+    fn inputs(round_trips: &[f64]) -> SizingInputs<'_> {
+        SizingInputs {
+            price: 100.0,
+            equity: 10000.0,
+            position: 0.0,
+            round_trips,
+            bars_per_year: Some(8760.0),
+        }
+    }
+
+    fn size(sizing: Sizing, round_trips: &[f64], atr: Option<f64>) -> Option<f64> {
+        target_size(&sizing, &inputs(round_trips), &|_| atr).map(|(size, _)| size)
+    }
+
+    #[test]
+    fn test_policies() {
+        let close = |a: Option<f64>, b: f64| (a.unwrap() - b).abs() < 1e-9;
+        assert!(close(size(Sizing::EquityPct { pct: 50.0 }, &[], None), 50.0));
+        assert!(close(size(Sizing::FixedNotional { notional: 2500.0 }, &[], None), 25.0));
+        assert!(close(size(Sizing::FixedQuantity { quantity: 3.0 }, &[], None), 3.0));
+        assert!(close(size(Sizing::Leverage { leverage: 3.0 }, &[], None), 300.0));
+
+        // 1% of 10000 at risk over a stop 4 away
+        let risk = Sizing::RiskPerTrade { risk_pct: 1.0, stop: "stop".into() };
+        assert!(close(size(risk.clone(), &[], Some(4.0)), 25.0));
+        assert_eq!(size(risk.clone(), &[], Some(0.0)), None);
+        assert_eq!(size(risk, &[], None), None);
+
+        // An hourly ATR of 1% is 93.6% a year; 20% target volatility
+        let vol = Sizing::VolatilityTarget { target_vol_pct: 20.0, atr: "atr".into() };
+        let expected = 10000.0 * 0.2 / (0.01 * 8760f64.sqrt()) / 100.0;
+        assert!(close(size(vol, &[], Some(1.0)), expected));
+    }
+
+    #[test]
+    fn test_kelly() {
+        // 60% winners averaging +2%, losers -1%: 0.6 - 0.4 / 2 = 0.4
+        let trips = [0.02, -0.01, 0.02, -0.01, 0.02];
+        assert!((kelly_fraction(&trips) - 0.4).abs() < 1e-9);
+        assert_eq!(kelly_fraction(&[-0.01, -0.02]), 0.0);
+        assert_eq!(kelly_fraction(&[0.01, 0.01]), 1.0);
+
+        let kelly = Sizing::Kelly {
+            fraction: 0.5,
+            lookback: 5,
+            min_trades: 3,
+            fallback_pct: 10.0,
+        };
+        // Falls back until three round trips are known; older ones are ignored
+        assert!((size(kelly.clone(), &trips[..2], None).unwrap() - 10.0).abs() < 1e-9);
+        let mut history = vec![-0.5; 4];
+        history.extend(trips);
+        assert!((size(kelly.clone(), &history, None).unwrap() - 20.0).abs() < 1e-9);
+        assert_eq!(size(kelly, &[-0.01; 5], None), None);
+    }
+
+    #[test]
+    fn test_limits() {
+        let inputs = inputs(&[]);
+        let (size, adjustments) = apply_limits(12.34567, &inputs, &instrument(Some(3), None));
+        assert!((size - 12.345).abs() < 1e-12);
+        assert_eq!(adjustments, ["rounded down to the lot size of 0.001"]);
+        assert_eq!(round_to_lot(0.3, 1), 0.3);
+
+        // 5x leverage on 10000 at 100 allows 500, less the 100 already held
+        let holding = SizingInputs { position: 100.0, ..inputs };
+        let (size, adjustments) = apply_limits(1000.0, &holding, &instrument(Some(0), Some(5.0)));
+        assert_eq!(size, 400.0);
+        assert_eq!(adjustments, ["capped at 5x max leverage"]);
+        let (size, adjustments) = apply_limits(10.0, &holding, &instrument(None, Some(5.0)));
+        assert_eq!(size, 10.0);
+        assert!(adjustments.is_empty());
+    }
+}
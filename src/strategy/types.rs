@@ -1,9 +1,17 @@
+use anyhow::{bail, Error, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::indicators2::ParamValue;
+use crate::strategy::expr::Expr;
+
 /// Simplified strategy definition replacing the complex IR system.
 /// Strategies define indicators, entry/exit conditions, and actions.
+///
+/// JSON may give a single `entry` and `exit` rule instead of the `entries` and
+/// `exits` lists; they are read as one-element lists.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "StrategyRepr")]
 pub struct Strategy {
     /// Strategy name
     pub name: String,
@@ -11,10 +19,84 @@ pub struct Strategy {
     pub instrument: Instrument,
     /// Indicators to compute
     pub indicators: Vec<IndicatorSpec>,
-    /// Entry rule (when to open a position)
-    pub entry: Rule,
-    /// Exit rule (when to close a position)
-    pub exit: Option<Rule>,
+    /// Entry rules (when to open a position); at least one
+    pub entries: Vec<Rule>,
+    /// Exit rules (when to close a position)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exits: Vec<Rule>,
+    /// Named constants usable in expressions
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub params: HashMap<String, f64>,
+    /// Allows entry rules to add to an open position; without it they only
+    /// open one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pyramiding: Option<Pyramiding>,
+}
+
+/// Serialized form of [`Strategy`], accepting the single-rule fields as well
+#[derive(Deserialize)]
+struct StrategyRepr {
+    name: String,
+    instrument: Instrument,
+    indicators: Vec<IndicatorSpec>,
+    #[serde(default)]
+    entry: Option<Rule>,
+    #[serde(default)]
+    exit: Option<Rule>,
+    #[serde(default)]
+    entries: Vec<Rule>,
+    #[serde(default)]
+    exits: Vec<Rule>,
+    #[serde(default)]
+    params: HashMap<String, f64>,
+    #[serde(default)]
+    pyramiding: Option<Pyramiding>,
+}
+
+impl TryFrom<StrategyRepr> for Strategy {
+    type Error = Error;
+
+    fn try_from(repr: StrategyRepr) -> Result<Self> {
+        if repr.entry.is_some() && !repr.entries.is_empty() {
+            bail!("Strategy has both 'entry' and 'entries'; use one");
+        }
+        if repr.exit.is_some() && !repr.exits.is_empty() {
+            bail!("Strategy has both 'exit' and 'exits'; use one");
+        }
+        let entries = match repr.entry {
+            Some(rule) => vec![rule],
+            None => repr.entries,
+        };
+        if entries.is_empty() {
+            bail!("Strategy needs an 'entry' rule or at least one in 'entries'");
+        }
+        Ok(Strategy {
+            name: repr.name,
+            instrument: repr.instrument,
+            indicators: repr.indicators,
+            entries,
+            exits: repr.exit.into_iter().chain(repr.exits).collect(),
+            params: repr.params,
+            pyramiding: repr.pyramiding,
+        })
+    }
+}
+
+/// When entry rules may add to an open position
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pyramiding {
+    /// Most entries per position, the opening one included
+    pub max_entries: usize,
+    /// Bars to wait after an entry before adding
+    #[serde(default)]
+    pub min_bars: usize,
+    /// Percent the price must have moved, either way, from the last entry
+    #[serde(default)]
+    pub min_price_move_pct: f64,
+    /// Sizing of the 1st, 2nd, ... add; the last one repeats. Empty uses the
+    /// rule's own sizing.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub add_sizing: Vec<Sizing>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +105,13 @@ pub struct Instrument {
     pub coin: String,
     pub venue: String,
     pub timeframe: String,
+    /// Decimals allowed in order sizes (lot size = 10^-sz_decimals); the CLI
+    /// fills it from the asset metadata when it is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sz_decimals: Option<u32>,
+    /// Largest position notional as a multiple of equity; entries are capped to it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_leverage: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,17 +119,44 @@ pub struct IndicatorSpec {
     pub id: String,
     #[serde(rename = "type")]
     pub indicator_type: String,
-    pub params: HashMap<String, f64>,
+    pub params: HashMap<String, ParamValue>,
+    /// Outputs to expose besides those the rules read; `value` names the
+    /// primary output
+    #[serde(default)]
     pub outputs: Vec<String>,
+    /// Timeframe to compute the indicator on, a multiple of the instrument's;
+    /// bars are built from the strategy's own. Defaults to the instrument's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeframe: Option<String>,
+    /// Coin whose market data the indicator is computed on, for signals from
+    /// another market. Defaults to the instrument's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coin: Option<String>,
+    /// Output of another indicator to compute this one on instead of prices,
+    /// as `id` for its `value` or `id.output`. The indicator is computed on
+    /// the source's coin and timeframe.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
 }
 
 /// A rule combines a condition with an action
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Rule {
+    /// Name recorded on the orders and trades the rule generates; defaults to
+    /// `entry_<n>` / `exit_<n>` by position
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Rules with a higher priority are evaluated first; ties keep their order
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub priority: i32,
     pub condition: Condition,
     pub action: Action,
 }
 
+fn is_zero(priority: &i32) -> bool {
+    *priority == 0
+}
+
 /// Condition for triggering an action
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -52,11 +168,15 @@ pub enum Condition {
         op: ComparisonOp,
         value: f64,
     },
-    /// Compare two indicators
+    /// Compare two expressions, e.g. `"close" > "sma_50 * 1.02"`
+    #[serde(rename = "compare")]
+    Compare { lhs: Expr, op: ComparisonOp, rhs: Expr },
+    /// `fast` crosses `slow` between the previous and the current bar; both are
+    /// expressions, usually indicator ids
     #[serde(rename = "crossover")]
     Crossover {
-        fast: String,
-        slow: String,
+        fast: Expr,
+        slow: Expr,
         direction: CrossDirection,
     },
     /// Logical AND of multiple conditions
@@ -65,6 +185,45 @@ pub enum Condition {
     /// Logical OR of multiple conditions
     #[serde(rename = "or")]
     Or { conditions: Vec<Condition> },
+    /// Negation
+    #[serde(rename = "not")]
+    Not { condition: Box<Condition> },
+    /// `condition` held on each of the last `bars` bars (including this one)
+    #[serde(rename = "for")]
+    For {
+        bars: usize,
+        condition: Box<Condition>,
+        /// Index of this condition's [`EvalState`](crate::strategy::EvalState)
+        /// slot, assigned by [`compile_strategy`](crate::strategy::compile_strategy)
+        #[serde(skip)]
+        slot: usize,
+    },
+    /// `condition` held on at least one of the last `bars` bars
+    #[serde(rename = "within")]
+    Within {
+        bars: usize,
+        condition: Box<Condition>,
+        #[serde(skip)]
+        slot: usize,
+    },
+    /// `condition` held on at least `n` of the last `m` bars
+    #[serde(rename = "count_of")]
+    CountOf {
+        n: usize,
+        m: usize,
+        condition: Box<Condition>,
+        #[serde(skip)]
+        slot: usize,
+    },
+    /// True from the bar `set` holds until the bar `reset` holds (reset wins
+    /// when both hold)
+    #[serde(rename = "latch")]
+    Latch {
+        set: Box<Condition>,
+        reset: Box<Condition>,
+        #[serde(skip)]
+        slot: usize,
+    },
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -88,9 +247,14 @@ pub enum CrossDirection {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Action {
-    /// Buy with a percentage of available capital
+    /// Buy `size_pct` percent of equity, or the size given by a `sizing` policy
     #[serde(rename = "buy")]
-    Buy { size_pct: f64 },
+    Buy {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size_pct: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sizing: Option<Sizing>,
+    },
     /// Sell with a percentage of position
     #[serde(rename = "sell")]
     Sell { size_pct: f64 },
@@ -99,18 +263,124 @@ pub enum Action {
     Close,
 }
 
-/// Compiled strategy ready for execution
+impl Action {
+    /// Buy `size_pct` percent of equity
+    pub fn buy_pct(size_pct: f64) -> Self {
+        Action::Buy {
+            size_pct: Some(size_pct),
+            sizing: None,
+        }
+    }
+}
+
+/// How an entry is sized. Sizes are rounded down to the lot size and capped at
+/// the instrument's `max_leverage`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Sizing {
+    /// `pct` percent of equity; above 100 is leveraged
+    EquityPct { pct: f64 },
+    /// A fixed notional in quote currency
+    FixedNotional { notional: f64 },
+    /// A fixed quantity of the coin
+    FixedQuantity { quantity: f64 },
+    /// Lose `risk_pct` percent of equity if price moves `stop` (an expression,
+    /// in price units) against the position
+    RiskPerTrade { risk_pct: f64, stop: Expr },
+    /// Annualized volatility of `target_vol_pct` percent of equity, estimating
+    /// the coin's volatility from `atr` (an expression, in price units per bar)
+    VolatilityTarget { target_vol_pct: f64, atr: Expr },
+    /// `fraction` of the Kelly criterion over the last `lookback` round trips;
+    /// `fallback_pct` percent of equity until `min_trades` round trips are known
+    Kelly {
+        fraction: f64,
+        lookback: usize,
+        min_trades: usize,
+        fallback_pct: f64,
+    },
+    /// Position notional of `leverage` times equity
+    Leverage { leverage: f64 },
+}
+
+impl Sizing {
+    /// Name used in sizing records
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sizing::EquityPct { .. } => "equity_pct",
+            Sizing::FixedNotional { .. } => "fixed_notional",
+            Sizing::FixedQuantity { .. } => "fixed_quantity",
+            Sizing::RiskPerTrade { .. } => "risk_per_trade",
+            Sizing::VolatilityTarget { .. } => "volatility_target",
+            Sizing::Kelly { .. } => "kelly",
+            Sizing::Leverage { .. } => "leverage",
+        }
+    }
+}
+
+/// Compiled strategy ready for execution; expressions in its rules are resolved
+/// (parameters substituted)
 pub struct CompiledStrategy {
     pub instrument: Instrument,
     pub indicators: Vec<CompiledIndicator>,
-    pub entry: Rule,
-    pub exit: Option<Rule>,
+    /// Entry rules, highest priority first
+    pub entries: Vec<CompiledRule>,
+    /// Exit rules, highest priority first
+    pub exits: Vec<CompiledRule>,
+    /// Pyramiding with `add_sizing` resolved
+    pub pyramiding: Option<Pyramiding>,
+    /// Past bars kept per series for offsets and crossovers
+    pub history_depths: HashMap<String, usize>,
+}
+
+impl CompiledStrategy {
+    /// Coins other than the instrument's that indicators read, sorted
+    pub fn reference_coins(&self) -> Vec<String> {
+        let coins: std::collections::BTreeSet<&String> =
+            self.indicators.iter().filter_map(|i| i.coin.as_ref()).collect();
+        coins.into_iter().cloned().collect()
+    }
+
+    /// Bars needed before every indicator is warmed up
+    pub fn indicator_lookback(&self) -> usize {
+        self.indicators.iter().map(|i| i.lookback).max().unwrap_or(0)
+    }
+
+    /// Bars needed before the rules can be evaluated: the indicator warmup plus
+    /// the deepest history any condition looks back on
+    pub fn lookback(&self) -> usize {
+        self.indicator_lookback() + self.history_depths.values().copied().max().unwrap_or(0)
+    }
+}
+
+/// A rule with its name assigned and its condition resolved
+pub struct CompiledRule {
+    pub name: String,
+    pub priority: i32,
+    pub condition: Condition,
+    pub action: Action,
 }
 
 pub struct CompiledIndicator {
     pub id: String,
     pub indicator_type: String,
-    pub params: HashMap<String, f64>,
+    /// Parameters checked against the indicator's schema, with defaults
+    pub params: HashMap<String, ParamValue>,
+    /// Outputs the rules read or the indicator declares, by their listed
+    /// names in the indicator's order; only these are exposed
     pub outputs: Vec<String>,
+    /// Higher timeframe the indicator updates on; `None` for every bar
+    pub timeframe: Option<String>,
+    /// Other coin the indicator reads; `None` for the instrument's
+    pub coin: Option<String>,
+    /// Indicator output the indicator is computed on; `None` for prices
+    pub source: Option<IndicatorSource>,
+    /// Warmup in bars of the instrument's timeframe, including its sources'
     pub lookback: usize,
 }
+
+/// Output of another indicator that an indicator is computed on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndicatorSource {
+    pub indicator: String,
+    pub output: String,
+}
@@ -1,16 +1,60 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+
+/// Candle intervals fetched from the Hyperliquid API, finest first
+pub const API_INTERVALS: &[&str] = &["1m", "5m", "15m", "1h", "4h", "1d", "1w"];
 
 /// Map timeframe string to Hyperliquid API interval string
 pub fn map_timeframe_to_interval(timeframe: &str) -> Result<String> {
-    match timeframe.to_lowercase().as_str() {
-        "1m" => Ok("1m".to_string()),
-        "5m" => Ok("5m".to_string()),
-        "15m" => Ok("15m".to_string()),
-        "1h" => Ok("1h".to_string()),
-        "4h" => Ok("4h".to_string()),
-        "1d" => Ok("1d".to_string()),
-        "1w" => Ok("1w".to_string()),
-        _ => anyhow::bail!("Unsupported timeframe: {}", timeframe),
+    let timeframe_lower = timeframe.to_lowercase();
+    match API_INTERVALS.iter().find(|i| **i == timeframe_lower) {
+        Some(interval) => Ok(interval.to_string()),
+        None => anyhow::bail!("Unsupported timeframe: {}", timeframe),
+    }
+}
+
+/// Duration of a timeframe string (e.g. "15m", "2h", "1d") in milliseconds
+pub fn interval_to_ms(timeframe: &str) -> Result<u64> {
+    let timeframe = timeframe.trim().to_lowercase();
+    let split = timeframe
+        .find(|c: char| !c.is_ascii_digit())
+        .with_context(|| format!("Invalid timeframe: {}", timeframe))?;
+    let (count, unit) = timeframe.split_at(split);
+
+    let count: u64 = count
+        .parse()
+        .with_context(|| format!("Invalid timeframe: {}", timeframe))?;
+    if count == 0 {
+        anyhow::bail!("Invalid timeframe: {}", timeframe);
     }
+
+    let unit_ms = match unit {
+        "s" => 1_000,
+        "m" => 60 * 1_000,
+        "h" => 60 * 60 * 1_000,
+        "d" => 24 * 60 * 60 * 1_000,
+        "w" => 7 * 24 * 60 * 60 * 1_000,
+        _ => anyhow::bail!("Unsupported timeframe unit: {}", timeframe),
+    };
+
+    count
+        .checked_mul(unit_ms)
+        .with_context(|| format!("Invalid timeframe: {}", timeframe))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_to_ms() {
+        assert_eq!(interval_to_ms("1m").unwrap(), 60_000);
+        assert_eq!(interval_to_ms("15m").unwrap(), 900_000);
+        assert_eq!(interval_to_ms("2h").unwrap(), 7_200_000);
+        assert_eq!(interval_to_ms("1D").unwrap(), 86_400_000);
+        assert_eq!(interval_to_ms("1w").unwrap(), 604_800_000);
+        assert!(interval_to_ms("0m").is_err());
+        assert!(interval_to_ms("m").is_err());
+        assert!(interval_to_ms("5y").is_err());
+        assert!(interval_to_ms("9999999999999w").is_err());
+    }
+}
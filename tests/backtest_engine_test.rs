@@ -1,7 +1,9 @@
 //! Integration tests for the backtesting engine
 
 use hl_backtest::data::types::Candle;
-use hl_backtest::orders::engine::simulate;
+use hl_backtest::data::DataPolicy;
+use hl_backtest::engine::JsonStrategy;
+use hl_backtest::orders::engine::{simulate, simulate_logic, simulate_with_references};
 use hl_backtest::orders::types::SimConfig;
 use hl_backtest::strategy::{
     Action, ComparisonOp, Condition, Instrument, IndicatorSpec, Rule, Strategy,
@@ -36,38 +38,49 @@ fn create_rsi_strategy() -> Strategy {
             coin: "BTC".to_string(),
             venue: "HL".to_string(),
             timeframe: "1h".to_string(),
+            sz_decimals: None,
+            max_leverage: None,
         },
         indicators: vec![IndicatorSpec {
             id: "rsi_14".to_string(),
             indicator_type: "RSI".to_string(),
-            params: [("period".to_string(), 14.0)].into_iter().collect(),
+            params: [("period".to_string(), 14.0.into())].into_iter().collect(),
             outputs: vec!["value".to_string()],
+            timeframe: None,
+            coin: None,
+            source: None,
         }],
-        entry: Rule {
+        entries: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::Threshold {
                 indicator: "rsi_14".to_string(),
                 op: ComparisonOp::Lt,
                 value: 30.0,
             },
-            action: Action::Buy { size_pct: 100.0 },
-        },
-        exit: Some(Rule {
+            action: Action::buy_pct(100.0),
+        }],
+        exits: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::Threshold {
                 indicator: "rsi_14".to_string(),
                 op: ComparisonOp::Gt,
                 value: 70.0,
             },
             action: Action::Close,
-        }),
+        }],
+        params: HashMap::new(),
+        pyramiding: None,
     }
 }
 
 fn create_sma_crossover_strategy() -> Strategy {
     let mut fast_params = HashMap::new();
-    fast_params.insert("period".to_string(), 10.0);
+    fast_params.insert("period".to_string(), 10.0.into());
 
     let mut slow_params = HashMap::new();
-    slow_params.insert("period".to_string(), 20.0);
+    slow_params.insert("period".to_string(), 20.0.into());
 
     Strategy {
         name: "SMA Crossover".to_string(),
@@ -76,6 +89,8 @@ fn create_sma_crossover_strategy() -> Strategy {
             coin: "BTC".to_string(),
             venue: "HL".to_string(),
             timeframe: "1h".to_string(),
+            sz_decimals: None,
+            max_leverage: None,
         },
         indicators: vec![
             IndicatorSpec {
@@ -83,30 +98,42 @@ fn create_sma_crossover_strategy() -> Strategy {
                 indicator_type: "SMA".to_string(),
                 params: fast_params,
                 outputs: vec!["value".to_string()],
+                timeframe: None,
+                coin: None,
+                source: None,
             },
             IndicatorSpec {
                 id: "sma_slow".to_string(),
                 indicator_type: "SMA".to_string(),
                 params: slow_params,
                 outputs: vec!["value".to_string()],
+                timeframe: None,
+                coin: None,
+                source: None,
             },
         ],
-        entry: Rule {
+        entries: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::Crossover {
-                fast: "sma_fast".to_string(),
-                slow: "sma_slow".to_string(),
+                fast: "sma_fast".into(),
+                slow: "sma_slow".into(),
                 direction: hl_backtest::strategy::CrossDirection::Above,
             },
-            action: Action::Buy { size_pct: 100.0 },
-        },
-        exit: Some(Rule {
+            action: Action::buy_pct(100.0),
+        }],
+        exits: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::Crossover {
-                fast: "sma_fast".to_string(),
-                slow: "sma_slow".to_string(),
+                fast: "sma_fast".into(),
+                slow: "sma_slow".into(),
                 direction: hl_backtest::strategy::CrossDirection::Below,
             },
             action: Action::Close,
-        }),
+        }],
+        params: HashMap::new(),
+        pyramiding: None,
     }
 }
 
@@ -117,6 +144,7 @@ fn default_sim_config() -> SimConfig {
         taker_fee_bps: 10,
         slippage_bps: 5,
         trade_cooldown_ms: None,
+        data_policy: DataPolicy::Warn,
     }
 }
 
@@ -136,6 +164,29 @@ async fn test_simulate_basic() {
     assert!(!result.equity_curve.is_empty());
 }
 
+#[tokio::test]
+async fn test_warn_policy_reports_issues_once() {
+    // The warning goes to stderr, so the backtest runs in a child test process
+    if std::env::var_os("HL_BACKTEST_WARN_ONCE_CHILD").is_some() {
+        let mut candles = create_mock_candles(100, 42000.0, 10.0);
+        candles.drain(50..53);
+        simulate(&candles, &create_rsi_strategy(), &default_sim_config())
+            .await
+            .unwrap();
+        return;
+    }
+
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["test_warn_policy_reports_issues_once", "--exact", "--nocapture"])
+        .env("HL_BACKTEST_WARN_ONCE_CHILD", "1")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert_eq!(stderr.matches("candle data has quality issues").count(), 1, "{}", stderr);
+    assert_eq!(stderr.matches("gaps: 1").count(), 1, "{}", stderr);
+}
+
 #[tokio::test]
 async fn test_simulate_sma_crossover() {
     let candles = create_mock_candles(100, 42000.0, 10.0);
@@ -190,6 +241,7 @@ async fn test_simulate_with_fees() {
         taker_fee_bps: 50, // 0.5% taker fee
         slippage_bps: 0,
         trade_cooldown_ms: None,
+        data_policy: DataPolicy::Warn,
     };
 
     let result = simulate(&candles, &strategy, &config).await.unwrap();
@@ -226,22 +278,31 @@ async fn test_simulate_no_exit_rule() {
             coin: "BTC".to_string(),
             venue: "HL".to_string(),
             timeframe: "1h".to_string(),
+            sz_decimals: None,
+            max_leverage: None,
         },
         indicators: vec![IndicatorSpec {
             id: "rsi_14".to_string(),
             indicator_type: "RSI".to_string(),
-            params: [("period".to_string(), 14.0)].into_iter().collect(),
+            params: [("period".to_string(), 14.0.into())].into_iter().collect(),
             outputs: vec!["value".to_string()],
+            timeframe: None,
+            coin: None,
+            source: None,
         }],
-        entry: Rule {
+        entries: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::Threshold {
                 indicator: "rsi_14".to_string(),
                 op: ComparisonOp::Lt,
                 value: 30.0,
             },
-            action: Action::Buy { size_pct: 100.0 },
-        },
-        exit: None, // No exit rule
+            action: Action::buy_pct(100.0),
+        }],
+        exits: vec![], // No exit rule
+        params: HashMap::new(),
+        pyramiding: None,
     };
 
     let candles = create_mock_candles(100, 42000.0, 10.0);
@@ -262,22 +323,32 @@ async fn test_simulate_and_condition() {
             coin: "BTC".to_string(),
             venue: "HL".to_string(),
             timeframe: "1h".to_string(),
+            sz_decimals: None,
+            max_leverage: None,
         },
         indicators: vec![
             IndicatorSpec {
                 id: "rsi".to_string(),
                 indicator_type: "RSI".to_string(),
-                params: [("period".to_string(), 14.0)].into_iter().collect(),
+                params: [("period".to_string(), 14.0.into())].into_iter().collect(),
                 outputs: vec!["value".to_string()],
+                timeframe: None,
+                coin: None,
+                source: None,
             },
             IndicatorSpec {
                 id: "sma".to_string(),
                 indicator_type: "SMA".to_string(),
-                params: [("period".to_string(), 20.0)].into_iter().collect(),
+                params: [("period".to_string(), 20.0.into())].into_iter().collect(),
                 outputs: vec!["value".to_string()],
+                timeframe: None,
+                coin: None,
+                source: None,
             },
         ],
-        entry: Rule {
+        entries: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::And {
                 conditions: vec![
                     Condition::Threshold {
@@ -292,16 +363,20 @@ async fn test_simulate_and_condition() {
                     },
                 ],
             },
-            action: Action::Buy { size_pct: 50.0 },
-        },
-        exit: Some(Rule {
+            action: Action::buy_pct(50.0),
+        }],
+        exits: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::Threshold {
                 indicator: "rsi".to_string(),
                 op: ComparisonOp::Gt,
                 value: 60.0,
             },
             action: Action::Close,
-        }),
+        }],
+        params: HashMap::new(),
+        pyramiding: None,
     };
 
     let candles = create_mock_candles(100, 42000.0, 10.0);
@@ -321,14 +396,21 @@ async fn test_simulate_or_condition() {
             coin: "BTC".to_string(),
             venue: "HL".to_string(),
             timeframe: "1h".to_string(),
+            sz_decimals: None,
+            max_leverage: None,
         },
         indicators: vec![IndicatorSpec {
             id: "rsi".to_string(),
             indicator_type: "RSI".to_string(),
-            params: [("period".to_string(), 14.0)].into_iter().collect(),
+            params: [("period".to_string(), 14.0.into())].into_iter().collect(),
             outputs: vec!["value".to_string()],
+            timeframe: None,
+            coin: None,
+            source: None,
         }],
-        entry: Rule {
+        entries: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::Or {
                 conditions: vec![
                     Condition::Threshold {
@@ -343,16 +425,20 @@ async fn test_simulate_or_condition() {
                     },
                 ],
             },
-            action: Action::Buy { size_pct: 100.0 },
-        },
-        exit: Some(Rule {
+            action: Action::buy_pct(100.0),
+        }],
+        exits: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::Threshold {
                 indicator: "rsi".to_string(),
                 op: ComparisonOp::Gt,
                 value: 50.0,
             },
             action: Action::Close,
-        }),
+        }],
+        params: HashMap::new(),
+        pyramiding: None,
     };
 
     let candles = create_mock_candles(100, 42000.0, 10.0);
@@ -376,29 +462,40 @@ async fn test_simulate_different_size_pcts() {
             coin: "BTC".to_string(),
             venue: "HL".to_string(),
             timeframe: "1h".to_string(),
+            sz_decimals: None,
+            max_leverage: None,
         },
         indicators: vec![IndicatorSpec {
             id: "rsi".to_string(),
             indicator_type: "RSI".to_string(),
-            params: [("period".to_string(), 14.0)].into_iter().collect(),
+            params: [("period".to_string(), 14.0.into())].into_iter().collect(),
             outputs: vec!["value".to_string()],
+            timeframe: None,
+            coin: None,
+            source: None,
         }],
-        entry: Rule {
+        entries: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::Threshold {
                 indicator: "rsi".to_string(),
                 op: ComparisonOp::Lt,
                 value: 50.0, // More likely to trigger
             },
-            action: Action::Buy { size_pct: 50.0 }, // Half position
-        },
-        exit: Some(Rule {
+            action: Action::buy_pct(50.0), // Half position
+        }],
+        exits: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::Threshold {
                 indicator: "rsi".to_string(),
                 op: ComparisonOp::Gt,
                 value: 60.0,
             },
             action: Action::Close,
-        }),
+        }],
+        params: HashMap::new(),
+        pyramiding: None,
     };
 
     let result = simulate(&candles, &strategy, &config).await.unwrap();
@@ -416,6 +513,7 @@ async fn test_simulate_trade_cooldown() {
         taker_fee_bps: 10,
         slippage_bps: 5,
         trade_cooldown_ms: None,
+        data_policy: DataPolicy::Warn,
     };
 
     let config_with_cooldown = SimConfig {
@@ -424,6 +522,7 @@ async fn test_simulate_trade_cooldown() {
         taker_fee_bps: 10,
         slippage_bps: 5,
         trade_cooldown_ms: Some(3600000), // 1 hour cooldown
+        data_policy: DataPolicy::Warn,
     };
 
     let strategy = create_rsi_strategy();
@@ -449,22 +548,31 @@ async fn test_simulate_preserves_initial_capital_no_trades() {
             coin: "BTC".to_string(),
             venue: "HL".to_string(),
             timeframe: "1h".to_string(),
+            sz_decimals: None,
+            max_leverage: None,
         },
         indicators: vec![IndicatorSpec {
             id: "rsi".to_string(),
             indicator_type: "RSI".to_string(),
-            params: [("period".to_string(), 14.0)].into_iter().collect(),
+            params: [("period".to_string(), 14.0.into())].into_iter().collect(),
             outputs: vec!["value".to_string()],
+            timeframe: None,
+            coin: None,
+            source: None,
         }],
-        entry: Rule {
+        entries: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::Threshold {
                 indicator: "rsi".to_string(),
                 op: ComparisonOp::Lt,
                 value: -100.0, // RSI can never be negative
             },
-            action: Action::Buy { size_pct: 100.0 },
-        },
-        exit: None,
+            action: Action::buy_pct(100.0),
+        }],
+        exits: vec![],
+        params: HashMap::new(),
+        pyramiding: None,
     };
 
     let candles = create_mock_candles(100, 42000.0, 10.0);
@@ -529,3 +637,393 @@ async fn test_simulate_json_strategy() {
 
     assert!(result.is_ok());
 }
+
+#[tokio::test]
+async fn test_simulate_expression_strategy() {
+    // Enter when close is 1% above its SMA, exit on a 0.5% gain
+    let json = r#"{
+        "name": "Expression Strategy",
+        "instrument": { "symbol": "BTCUSD", "coin": "BTC", "venue": "HL", "timeframe": "1h" },
+        "indicators": [
+            { "id": "sma", "type": "SMA", "params": { "length": 5 }, "outputs": ["value"] }
+        ],
+        "params": { "band": 0.01 },
+        "entry": {
+            "condition": { "type": "compare", "lhs": "close", "op": "gt", "rhs": "sma * (1 + band)" },
+            "action": { "type": "buy", "size_pct": 50.0 }
+        },
+        "exit": {
+            "condition": { "type": "compare", "lhs": "unrealized_pnl_pct", "op": "gt", "rhs": 0.5 },
+            "action": { "type": "close" }
+        }
+    }"#;
+
+    let strategy: Strategy = serde_json::from_str(json).unwrap();
+    let candles = create_mock_candles(100, 1000.0, 10.0);
+    let result = simulate(&candles, &strategy, &default_sim_config()).await.unwrap();
+
+    // A steady uptrend keeps close above the band and every entry reaches the target
+    assert!(result.num_trades >= 2);
+    assert!(result.final_equity > default_sim_config().initial_capital);
+}
+
+#[tokio::test]
+async fn test_simulate_breakout_with_offsets() {
+    // Buy a close above the previous 10 bars' highs, exit when close drops below
+    // where it was 3 bars ago
+    let json = r#"{
+        "name": "Breakout",
+        "instrument": { "symbol": "BTCUSD", "coin": "BTC", "venue": "HL", "timeframe": "1h" },
+        "indicators": [],
+        "entry": {
+            "condition": { "type": "compare", "lhs": "close", "op": "gt", "rhs": "highest(high, 10)[1]" },
+            "action": { "type": "buy", "size_pct": 50.0 }
+        },
+        "exit": {
+            "condition": { "type": "compare", "lhs": "close", "op": "lt", "rhs": "close[3]" },
+            "action": { "type": "close" }
+        }
+    }"#;
+    let strategy: Strategy = serde_json::from_str(json).unwrap();
+
+    // Flat for 20 bars, then a rally and a selloff
+    let mut candles = create_mock_candles(20, 42000.0, 0.0);
+    candles.extend(create_mock_candles(10, 42500.0, 200.0));
+    candles.extend(create_mock_candles(10, 44000.0, -200.0));
+    for (i, candle) in candles.iter_mut().enumerate() {
+        candle.time_open = 1704067200000 + i as u64 * 3600000;
+        candle.time_close = candle.time_open + 3600000;
+    }
+
+    let result = simulate(&candles, &strategy, &default_sim_config()).await.unwrap();
+    // Entered on the breakout and exited in the selloff
+    assert_eq!(result.num_trades, 2);
+    // History deeper than the data is an error, as for indicators
+    assert!(simulate(&candles[..9], &strategy, &default_sim_config()).await.is_err());
+}
+
+fn sizing_strategy(instrument: &str, sizing: &str) -> Strategy {
+    let json = format!(
+        r#"{{
+        "name": "Sizing",
+        "instrument": {},
+        "indicators": [],
+        "entry": {{
+            "condition": {{ "type": "and", "conditions": [] }},
+            "action": {{ "type": "buy", "sizing": {} }}
+        }},
+        "exit": {{
+            "condition": {{ "type": "compare", "lhs": "unrealized_pnl_pct", "op": "gt", "rhs": 0.1 }},
+            "action": {{ "type": "close" }}
+        }}
+    }}"#,
+        instrument, sizing
+    );
+    serde_json::from_str(&json).unwrap()
+}
+
+#[tokio::test]
+async fn test_simulate_risk_sizing_with_lots() {
+    // 1% of equity at risk over a stop of three bar ranges (600), in lots of 0.001
+    let strategy = sizing_strategy(
+        r#"{ "symbol": "BTCUSD", "coin": "BTC", "venue": "HL", "timeframe": "1h", "sz_decimals": 3 }"#,
+        r#"{ "type": "risk_per_trade", "risk_pct": 1.0, "stop": "3 * (high - low)" }"#,
+    );
+    let candles = create_mock_candles(30, 42000.0, 10.0);
+    let result = simulate(&candles, &strategy, &default_sim_config()).await.unwrap();
+
+    assert!(result.num_trades >= 2);
+    assert_eq!(result.sizing.len(), result.num_trades);
+    let entry = &result.sizing[0];
+    assert_eq!(entry.rule, "entry_1");
+    assert_eq!(entry.policy, "risk_per_trade");
+    assert_eq!(entry.inputs["stop_distance"], 600.0);
+    assert!((entry.target_size - 10000.0 * 0.01 / 600.0).abs() < 1e-9);
+    assert!((entry.size - 0.166).abs() < 1e-12);
+    assert_eq!(entry.adjustments, ["rounded down to the lot size of 0.001"]);
+    assert_eq!(result.sizing[1].policy, "close");
+
+    // Each record belongs to the order that filled
+    for (trade, record) in result.trades.iter().zip(&result.sizing) {
+        assert_eq!(trade.order_id, record.order_id);
+        assert_eq!(trade.size, record.size);
+    }
+}
+
+#[tokio::test]
+async fn test_simulate_sizing_respects_max_leverage() {
+    let instrument =
+        r#"{ "symbol": "BTCUSD", "coin": "BTC", "venue": "HL", "timeframe": "1h", "max_leverage": 2 }"#;
+    let candles = create_mock_candles(30, 42000.0, 10.0);
+
+    // 300% of equity is capped at 2x
+    let strategy = sizing_strategy(instrument, r#"{ "type": "equity_pct", "pct": 300.0 }"#);
+    let result = simulate(&candles, &strategy, &default_sim_config()).await.unwrap();
+    let entry = &result.sizing[0];
+    assert!((entry.leverage - 2.0).abs() < 1e-9);
+    assert_eq!(entry.adjustments, ["capped at 2x max leverage"]);
+
+    // Asking for more leverage than the instrument allows is an error
+    let strategy = sizing_strategy(instrument, r#"{ "type": "leverage", "leverage": 3.0 }"#);
+    assert!(simulate(&candles, &strategy, &default_sim_config()).await.is_err());
+    let strategy = sizing_strategy(instrument, r#"{ "type": "leverage", "leverage": 1.5 }"#);
+    let result = simulate(&candles, &strategy, &default_sim_config()).await.unwrap();
+    assert!((result.sizing[0].leverage - 1.5).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_simulate_kelly_sizing_learns_from_round_trips() {
+    let strategy = sizing_strategy(
+        r#"{ "symbol": "BTCUSD", "coin": "BTC", "venue": "HL", "timeframe": "1h" }"#,
+        r#"{ "type": "kelly", "fraction": 0.5, "lookback": 5, "min_trades": 1, "fallback_pct": 10.0 }"#,
+    );
+    // Without costs every round trip in the uptrend wins
+    let config = SimConfig {
+        taker_fee_bps: 0,
+        slippage_bps: 0,
+        ..default_sim_config()
+    };
+    let candles = create_mock_candles(30, 42000.0, 10.0);
+    let result = simulate(&candles, &strategy, &config).await.unwrap();
+
+    let entries: Vec<_> = result.sizing.iter().filter(|r| r.policy == "kelly").collect();
+    assert!(entries.len() >= 2);
+    // No history yet: the fallback; then a full Kelly bet of 1, halved
+    assert_eq!(entries[0].inputs["equity_pct"], 10.0);
+    assert_eq!(entries[1].inputs["round_trips"], 1.0);
+    assert_eq!(entries[1].inputs["kelly"], 1.0);
+    assert_eq!(entries[1].inputs["equity_pct"], 50.0);
+}
+
+fn pyramiding_strategy(pyramiding: &str) -> Strategy {
+    let json = format!(
+        r#"{{
+        "name": "Pyramid",
+        "instrument": {{ "symbol": "BTCUSD", "coin": "BTC", "venue": "HL", "timeframe": "1h" }},
+        "indicators": [],
+        "pyramiding": {},
+        "entries": [{{
+            "name": "scale_in",
+            "condition": {{ "type": "compare", "lhs": "position_size", "op": "lt", "rhs": 0.19 }},
+            "action": {{ "type": "buy", "sizing": {{ "type": "fixed_quantity", "quantity": 0.1 }} }}
+        }}],
+        "exits": [
+            {{
+                "name": "trim",
+                "condition": {{ "type": "compare", "lhs": "bars_since_entry", "op": "eq", "rhs": 3 }},
+                "action": {{ "type": "sell", "size_pct": 50.0 }}
+            }},
+            {{
+                "name": "out",
+                "condition": {{ "type": "compare", "lhs": "bars_since_entry", "op": "gte", "rhs": 4 }},
+                "action": {{ "type": "close" }}
+            }}
+        ]
+    }}"#,
+        pyramiding
+    );
+    serde_json::from_str(&json).unwrap()
+}
+
+#[tokio::test]
+async fn test_simulate_pyramiding_and_scale_out() {
+    let config = SimConfig {
+        taker_fee_bps: 0,
+        slippage_bps: 0,
+        ..default_sim_config()
+    };
+    // Entries fill at the open and bars close 50 above it, rising 10 a bar:
+    // a bar after an entry the close is 60 (0.143%) above it, two bars after
+    // 70 (0.167%), so both spacings allow an add every other bar
+    let candles = create_mock_candles(10, 42000.0, 10.0);
+    let spacings = [
+        r#"{ "max_entries": 5, "min_bars": 2, "add_sizing": [{ "type": "fixed_quantity", "quantity": 0.05 }] }"#,
+        r#"{ "max_entries": 5, "min_price_move_pct": 0.155, "add_sizing": [{ "type": "fixed_quantity", "quantity": 0.05 }] }"#,
+    ];
+    for pyramiding in spacings {
+        let strategy = pyramiding_strategy(pyramiding);
+        let result = simulate(&candles, &strategy, &config).await.unwrap();
+
+        // Entry, two adds until position_size < 0.19 stops them, half out, then the rest
+        let tags: Vec<_> = result.trades.iter().map(|t| t.tag.as_deref().unwrap()).collect();
+        assert_eq!(tags[..5], ["scale_in", "scale_in", "scale_in", "trim", "out"]);
+        let sizes: Vec<_> = result.trades.iter().map(|t| t.size).collect();
+        assert_eq!(sizes[..3], [0.1, 0.05, 0.05]);
+        assert!((sizes[3] - 0.1).abs() < 1e-12 && (sizes[4] - 0.1).abs() < 1e-12);
+        let bar = |i: usize| result.trades[i].timestamp - result.trades[0].timestamp;
+        assert_eq!([bar(1), bar(2), bar(3), bar(4)], [2, 4, 7, 8].map(|n| n * 3600000));
+        assert_eq!(result.sizing[1].inputs["add"], 1.0);
+        assert_eq!(result.sizing[2].inputs["add"], 2.0);
+
+        // The partial exit belongs to the same round trip
+        let trip = &result.round_trips[0];
+        assert_eq!((trip.entries, trip.exits), (3, 2));
+        assert!((trip.max_size - 0.2).abs() < 1e-12);
+        assert_eq!(trip.close_ts, Some(result.trades[4].timestamp));
+        assert!(trip.pnl > 0.0);
+    }
+
+    // Without pyramiding, entries only open positions
+    let mut strategy = pyramiding_strategy("null");
+    strategy.pyramiding = None;
+    let result = simulate(&candles, &strategy, &config).await.unwrap();
+    assert!(result.round_trips.iter().all(|trip| trip.entries == 1));
+}
+
+#[tokio::test]
+async fn test_simulate_higher_timeframe_indicator_updates_on_close() {
+    // A 1-bar SMA on 4h bars is the close of the last completed 4h bar; it
+    // matches the hourly close only on the last hour of each 4h bar
+    let json = r#"{
+        "name": "4h filter",
+        "instrument": { "symbol": "BTCUSD", "coin": "BTC", "venue": "HL", "timeframe": "1h" },
+        "indicators": [
+            { "id": "close_4h", "type": "SMA", "params": { "length": 1 }, "outputs": ["value"], "timeframe": "4h" }
+        ],
+        "entry": {
+            "condition": { "type": "compare", "lhs": "abs(close_4h - close)", "op": "lt", "rhs": 1 },
+            "action": { "type": "buy", "size_pct": 10.0 }
+        },
+        "exit": {
+            "condition": { "type": "compare", "lhs": "bars_since_entry", "op": "gte", "rhs": 1 },
+            "action": { "type": "close" }
+        }
+    }"#;
+    let strategy: Strategy = serde_json::from_str(json).unwrap();
+    let candles = create_mock_candles(24, 42000.0, 10.0);
+    let result = simulate(&candles, &strategy, &default_sim_config()).await.unwrap();
+
+    // Warm after two 4h bars (7 hourly bars), then an entry on every 4th hour
+    let entry_hours: Vec<u64> = result
+        .trades
+        .iter()
+        .filter(|t| t.side == "BUY")
+        .map(|t| (t.timestamp - candles[0].time_open) / 3600000)
+        .collect();
+    assert_eq!(entry_hours, [7, 11, 15, 19, 23]);
+}
+
+#[tokio::test]
+async fn test_simulate_indicator_on_indicator() {
+    // Closes rise 10 a bar, so a 2-bar SMA lags the close by 5 and a 3-bar SMA
+    // of that by another 10, once the 2-bar SMA is warm before feeding it
+    let json = r#"{
+        "name": "Chained",
+        "instrument": { "symbol": "BTCUSD", "coin": "BTC", "venue": "HL", "timeframe": "1h" },
+        "indicators": [
+            { "id": "smooth", "type": "SMA", "params": { "length": 3 }, "outputs": ["value"], "source": "sma2" },
+            { "id": "sma2", "type": "SMA", "params": { "length": 2 }, "outputs": ["value"] }
+        ],
+        "entry": {
+            "condition": { "type": "compare", "lhs": "abs(smooth - close + 15)", "op": "lt", "rhs": 0.001 },
+            "action": { "type": "buy", "size_pct": 10.0 }
+        },
+        "exit": {
+            "condition": { "type": "compare", "lhs": "bars_since_entry", "op": "gte", "rhs": 1 },
+            "action": { "type": "close" }
+        }
+    }"#;
+    let strategy: Strategy = serde_json::from_str(json).unwrap();
+    let candles = create_mock_candles(12, 42000.0, 10.0);
+    for parallel in [false, true] {
+        let mut logic = JsonStrategy::new(&strategy, parallel).unwrap();
+        let result = simulate_logic(&candles, "BTC", &mut logic, &default_sim_config()).unwrap();
+
+        // Warm after 2 + 3 bars, then an entry every other bar
+        let entry_hours: Vec<u64> = result
+            .trades
+            .iter()
+            .filter(|t| t.side == "BUY")
+            .map(|t| (t.timestamp - candles[0].time_open) / 3600000)
+            .collect();
+        assert_eq!(entry_hours, [5, 7, 9, 11]);
+    }
+}
+
+#[tokio::test]
+async fn test_simulate_reads_named_outputs() {
+    // In a steady uptrend +DI leads -DI, and the close sits high in the
+    // Stochastic range
+    let json = r#"{
+        "name": "Named outputs",
+        "instrument": { "symbol": "BTCUSD", "coin": "BTC", "venue": "HL", "timeframe": "1h" },
+        "indicators": [
+            { "id": "adx", "type": "ADX", "params": { "period": 14 } },
+            { "id": "stoch", "type": "Stochastic", "params": { "k_period": 14, "d_period": 3 } }
+        ],
+        "entry": {
+            "condition": { "type": "and", "conditions": [
+                { "type": "compare", "lhs": "adx.plus_di", "op": "gt", "rhs": "adx.minus_di" },
+                { "type": "compare", "lhs": "stoch.d", "op": "gt", "rhs": 80 }
+            ] },
+            "action": { "type": "buy", "size_pct": 10.0 }
+        }
+    }"#;
+    let strategy: Strategy = serde_json::from_str(json).unwrap();
+    let candles = create_mock_candles(40, 42000.0, 10.0);
+    let result = simulate(&candles, &strategy, &default_sim_config()).await.unwrap();
+
+    // On the first bar after the ADX's 28-bar warmup
+    assert_eq!(result.trades[0].timestamp, candles[28].time_open);
+}
+
+fn cross_asset_strategy() -> Strategy {
+    // Trades ETH when BTC's close is above 42100
+    let json = r#"{
+        "name": "BTC leads",
+        "instrument": { "symbol": "ETHUSD", "coin": "ETH", "venue": "HL", "timeframe": "1h" },
+        "indicators": [
+            { "id": "btc", "type": "SMA", "params": { "length": 1 }, "outputs": ["value"], "coin": "BTC" }
+        ],
+        "entry": {
+            "condition": { "type": "compare", "lhs": "btc", "op": "gt", "rhs": 42100 },
+            "action": { "type": "buy", "size_pct": 10.0 }
+        }
+    }"#;
+    serde_json::from_str(json).unwrap()
+}
+
+fn eth_candles(count: usize) -> Vec<Candle> {
+    create_mock_candles(count, 2000.0, 0.0)
+        .into_iter()
+        .map(|c| Candle { coin: "ETH".to_string(), ..c })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_simulate_with_indicators_on_another_coin() {
+    let strategy = cross_asset_strategy();
+    let eth = eth_candles(20);
+    // BTC closes 42050 + 10i: above 42100 from bar 6
+    let btc = create_mock_candles(20, 42000.0, 10.0);
+    let references = HashMap::from([("BTC".to_string(), btc.clone())]);
+
+    let result = simulate_with_references(&eth, &references, &strategy, &default_sim_config())
+        .await
+        .unwrap();
+    assert_eq!(result.trades[0].symbol, "ETH");
+    assert_eq!(result.trades[0].timestamp, eth[6].time_open);
+
+    // A missing BTC bar holds the last value; strict data fails on it
+    let mut gapped = btc.clone();
+    gapped.remove(6);
+    let references = HashMap::from([("BTC".to_string(), gapped)]);
+    let result = simulate_with_references(&eth, &references, &strategy, &default_sim_config())
+        .await
+        .unwrap();
+    assert_eq!(result.trades[0].timestamp, eth[7].time_open);
+    let strict = SimConfig {
+        data_policy: DataPolicy::Fail,
+        ..default_sim_config()
+    };
+    assert!(simulate_with_references(&eth, &references, &strategy, &strict).await.is_err());
+
+    // So does a series on another grid, and one that was never given
+    let shifted: Vec<Candle> = btc
+        .iter()
+        .map(|c| Candle { time_open: c.time_open + 1800000, ..c.clone() })
+        .collect();
+    let references = HashMap::from([("BTC".to_string(), shifted)]);
+    assert!(simulate_with_references(&eth, &references, &strategy, &strict).await.is_err());
+    assert!(simulate(&eth, &strategy, &default_sim_config()).await.is_err());
+}
@@ -18,7 +18,7 @@ mod tests {
         // Over 7 days, we should have at least 7 * 3 = 21 funding points
         let points = schedule.timestamps_in_range(start_ts, end_ts);
         assert!(
-            points.len() > 0,
+            !points.is_empty(),
             "Expected at least some funding points, got {}",
             points.len()
         );
@@ -1,9 +1,10 @@
 //! Tests for the indicators module
 
 use hl_backtest::data::types::Candle;
-use hl_backtest::indicators2::{create_indicator, IndicatorEvaluator, IndicatorRegistry};
+use hl_backtest::indicators2::{create_indicator, IndicatorRegistry, ParamValue};
 use std::collections::HashMap;
 
+#[allow(dead_code)]
 fn create_test_candle(close: f64, high: f64, low: f64, volume: f64) -> Candle {
     Candle {
         time_open: 1704067200000,
@@ -41,7 +42,7 @@ fn create_candle_series(prices: &[f64]) -> Vec<Candle> {
 #[test]
 fn test_sma_indicator_creation() {
     let mut params = HashMap::new();
-    params.insert("length".to_string(), 20.0);
+    params.insert("length".to_string(), 20.0.into());
 
     let indicator = create_indicator("SMA", &params);
     assert!(indicator.is_ok());
@@ -50,7 +51,7 @@ fn test_sma_indicator_creation() {
 #[test]
 fn test_sma_indicator_calculation() {
     let mut params = HashMap::new();
-    params.insert("length".to_string(), 5.0);
+    params.insert("length".to_string(), 5.0.into());
 
     let mut indicator = create_indicator("SMA", &params).unwrap();
 
@@ -70,7 +71,7 @@ fn test_sma_indicator_calculation() {
 #[test]
 fn test_ema_indicator_creation() {
     let mut params = HashMap::new();
-    params.insert("length".to_string(), 12.0);
+    params.insert("length".to_string(), 12.0.into());
 
     let indicator = create_indicator("EMA", &params);
     assert!(indicator.is_ok());
@@ -79,7 +80,7 @@ fn test_ema_indicator_creation() {
 #[test]
 fn test_ema_indicator_calculation() {
     let mut params = HashMap::new();
-    params.insert("length".to_string(), 5.0);
+    params.insert("length".to_string(), 5.0.into());
 
     let mut indicator = create_indicator("EMA", &params).unwrap();
 
@@ -99,7 +100,7 @@ fn test_ema_indicator_calculation() {
 #[test]
 fn test_wma_indicator_creation() {
     let mut params = HashMap::new();
-    params.insert("length".to_string(), 10.0);
+    params.insert("length".to_string(), 10.0.into());
 
     let indicator = create_indicator("WMA", &params);
     assert!(indicator.is_ok());
@@ -108,7 +109,7 @@ fn test_wma_indicator_creation() {
 #[test]
 fn test_rsi_indicator_creation() {
     let mut params = HashMap::new();
-    params.insert("length".to_string(), 14.0);
+    params.insert("length".to_string(), 14.0.into());
 
     let indicator = create_indicator("RSI", &params);
     assert!(indicator.is_ok());
@@ -117,7 +118,7 @@ fn test_rsi_indicator_creation() {
 #[test]
 fn test_rsi_indicator_range() {
     let mut params = HashMap::new();
-    params.insert("length".to_string(), 14.0);
+    params.insert("length".to_string(), 14.0.into());
 
     let mut indicator = create_indicator("RSI", &params).unwrap();
 
@@ -133,15 +134,15 @@ fn test_rsi_indicator_range() {
 
     let value = indicator.value("value").unwrap();
     // RSI should always be between 0 and 100
-    assert!(value >= 0.0 && value <= 100.0);
+    assert!((0.0..=100.0).contains(&value));
 }
 
 #[test]
 fn test_macd_indicator_creation() {
     let mut params = HashMap::new();
-    params.insert("fast".to_string(), 12.0);
-    params.insert("slow".to_string(), 26.0);
-    params.insert("signal".to_string(), 9.0);
+    params.insert("fast".to_string(), 12.0.into());
+    params.insert("slow".to_string(), 26.0.into());
+    params.insert("signal".to_string(), 9.0.into());
 
     let indicator = create_indicator("MACD", &params);
     assert!(indicator.is_ok());
@@ -150,9 +151,9 @@ fn test_macd_indicator_creation() {
 #[test]
 fn test_macd_indicator_outputs() {
     let mut params = HashMap::new();
-    params.insert("fast".to_string(), 12.0);
-    params.insert("slow".to_string(), 26.0);
-    params.insert("signal".to_string(), 9.0);
+    params.insert("fast".to_string(), 12.0.into());
+    params.insert("slow".to_string(), 26.0.into());
+    params.insert("signal".to_string(), 9.0.into());
 
     let mut indicator = create_indicator("MACD", &params).unwrap();
 
@@ -177,8 +178,8 @@ fn test_macd_indicator_outputs() {
 #[test]
 fn test_bbands_indicator_creation() {
     let mut params = HashMap::new();
-    params.insert("length".to_string(), 20.0);
-    params.insert("std".to_string(), 2.0);
+    params.insert("length".to_string(), 20.0.into());
+    params.insert("std".to_string(), 2.0.into());
 
     let indicator = create_indicator("BBANDS", &params);
     assert!(indicator.is_ok());
@@ -187,8 +188,8 @@ fn test_bbands_indicator_creation() {
 #[test]
 fn test_bbands_indicator_outputs() {
     let mut params = HashMap::new();
-    params.insert("length".to_string(), 20.0);
-    params.insert("std".to_string(), 2.0);
+    params.insert("length".to_string(), 20.0.into());
+    params.insert("std".to_string(), 2.0.into());
 
     let mut indicator = create_indicator("BBANDS", &params).unwrap();
 
@@ -219,8 +220,8 @@ fn test_bbands_indicator_outputs() {
 #[test]
 fn test_stoch_indicator_creation() {
     let mut params = HashMap::new();
-    params.insert("k_period".to_string(), 14.0);
-    params.insert("d_period".to_string(), 3.0);
+    params.insert("k_period".to_string(), 14.0.into());
+    params.insert("d_period".to_string(), 3.0.into());
 
     let indicator = create_indicator("STOCH", &params);
     assert!(indicator.is_ok());
@@ -229,9 +230,9 @@ fn test_stoch_indicator_creation() {
 #[test]
 fn test_stoch_indicator_range() {
     let mut params = HashMap::new();
-    params.insert("k_period".to_string(), 14.0);
-    params.insert("k_smooth".to_string(), 1.0);
-    params.insert("d_period".to_string(), 3.0);
+    params.insert("k_period".to_string(), 14.0.into());
+    params.insert("k_smooth".to_string(), 1.0.into());
+    params.insert("d_period".to_string(), 3.0.into());
 
     let mut indicator = create_indicator("STOCH", &params).unwrap();
 
@@ -248,14 +249,14 @@ fn test_stoch_indicator_range() {
     let k = indicator.value("value").unwrap();
     let d = indicator.value("d").unwrap();
 
-    assert!(k >= 0.0 && k <= 100.0);
-    assert!(d >= 0.0 && d <= 100.0);
+    assert!((0.0..=100.0).contains(&k));
+    assert!((0.0..=100.0).contains(&d));
 }
 
 #[test]
 fn test_atr_indicator_creation() {
     let mut params = HashMap::new();
-    params.insert("period".to_string(), 14.0);
+    params.insert("period".to_string(), 14.0.into());
 
     let indicator = create_indicator("ATR", &params);
     assert!(indicator.is_ok());
@@ -264,7 +265,7 @@ fn test_atr_indicator_creation() {
 #[test]
 fn test_atr_indicator_positive() {
     let mut params = HashMap::new();
-    params.insert("period".to_string(), 14.0);
+    params.insert("period".to_string(), 14.0.into());
 
     let mut indicator = create_indicator("ATR", &params).unwrap();
 
@@ -296,7 +297,7 @@ fn test_atr_indicator_positive() {
 #[test]
 fn test_adx_indicator_creation() {
     let mut params = HashMap::new();
-    params.insert("period".to_string(), 14.0);
+    params.insert("period".to_string(), 14.0.into());
 
     let indicator = create_indicator("ADX", &params);
     assert!(indicator.is_ok());
@@ -305,7 +306,7 @@ fn test_adx_indicator_creation() {
 #[test]
 fn test_adx_indicator_range() {
     let mut params = HashMap::new();
-    params.insert("period".to_string(), 14.0);
+    params.insert("period".to_string(), 14.0.into());
 
     let mut indicator = create_indicator("ADX", &params).unwrap();
 
@@ -331,7 +332,7 @@ fn test_adx_indicator_range() {
 
     let value = indicator.value("value").unwrap();
     // ADX should be between 0 and 100
-    assert!(value >= 0.0 && value <= 100.0);
+    assert!((0.0..=100.0).contains(&value));
 }
 
 #[test]
@@ -397,29 +398,39 @@ fn test_indicator_registry_lookback() {
 
     // Test SMA lookback
     let mut params = HashMap::new();
-    params.insert("length".to_string(), 20.0);
+    params.insert("length".to_string(), 20.0.into());
     let lookback = registry.get_lookback("SMA", &params).unwrap();
     assert_eq!(lookback, 20);
 
     // Test RSI lookback
     params.clear();
-    params.insert("length".to_string(), 14.0);
+    params.insert("length".to_string(), 14.0.into());
     let lookback = registry.get_lookback("RSI", &params).unwrap();
     assert_eq!(lookback, 15); // period + 1
 
     // Test MACD lookback
     params.clear();
-    params.insert("fast".to_string(), 12.0);
-    params.insert("slow".to_string(), 26.0);
-    params.insert("signal".to_string(), 9.0);
+    params.insert("fast".to_string(), 12.0.into());
+    params.insert("slow".to_string(), 26.0.into());
+    params.insert("signal".to_string(), 9.0.into());
     let lookback = registry.get_lookback("MACD", &params).unwrap();
     assert_eq!(lookback, 26 + 9 * 3); // slow + signal * 3
+
+    // Slow stochastic: %K smoothing delays %D
+    params.clear();
+    params.insert("k_period".to_string(), 14.0.into());
+    params.insert("k_smooth".to_string(), 3.0.into());
+    params.insert("d_period".to_string(), 3.0.into());
+    let lookback = registry.get_lookback("STOCH", &params).unwrap();
+    assert_eq!(lookback, 14 + 3 - 1 + 3 - 1);
+    let evaluator = registry.create("STOCH", &params).unwrap();
+    assert_eq!(evaluator.warmup(), lookback);
 }
 
 #[test]
 fn test_indicator_reset() {
     let mut params = HashMap::new();
-    params.insert("length".to_string(), 5.0);
+    params.insert("length".to_string(), 5.0.into());
 
     let mut indicator = create_indicator("SMA", &params).unwrap();
 
@@ -447,7 +458,7 @@ fn test_indicator_reset() {
 #[test]
 fn test_indicator_warmup() {
     let mut params = HashMap::new();
-    params.insert("length".to_string(), 20.0);
+    params.insert("length".to_string(), 20.0.into());
 
     let indicator = create_indicator("SMA", &params).unwrap();
     assert_eq!(indicator.warmup(), 20);
@@ -485,8 +496,8 @@ fn test_indicator_default_params() {
 #[test]
 fn test_bb_alias() {
     let mut params = HashMap::new();
-    params.insert("length".to_string(), 20.0);
-    params.insert("std".to_string(), 2.0);
+    params.insert("length".to_string(), 20.0.into());
+    params.insert("std".to_string(), 2.0.into());
 
     // Both "BBANDS" and "BB" should work
     assert!(create_indicator("BBANDS", &params).is_ok());
@@ -496,9 +507,76 @@ fn test_bb_alias() {
 #[test]
 fn test_stochastic_alias() {
     let mut params = HashMap::new();
-    params.insert("k_period".to_string(), 14.0);
+    params.insert("k_period".to_string(), 14.0.into());
 
     // Both "STOCH" and "STOCHASTIC" should work
     assert!(create_indicator("STOCH", &params).is_ok());
     assert!(create_indicator("STOCHASTIC", &params).is_ok());
 }
+
+#[test]
+fn test_indicator_price_source_param() {
+    // Highs are 10 above the close and lows 10 below, so hl2 is the close
+    let candles = create_candle_series(&[10.0, 20.0, 30.0]);
+    let sma_of = |source: &str| {
+        let params: HashMap<String, ParamValue> =
+            [("length".to_string(), 3.0.into()), ("source".to_string(), source.into())].into();
+        let mut indicator = create_indicator("SMA", &params).unwrap();
+        for candle in &candles {
+            indicator.update(candle).unwrap();
+        }
+        indicator.value("value").unwrap()
+    };
+    assert!((sma_of("high") - 30.0).abs() < 1e-9);
+    assert!((sma_of("HL2") - 20.0).abs() < 1e-9);
+    assert!((sma_of("open") - 15.0).abs() < 1e-9);
+
+    let params: HashMap<String, ParamValue> = [("source".to_string(), "vwap".into())].into();
+    assert!(create_indicator("SMA", &params).is_err());
+}
+
+#[test]
+fn test_indicator_param_schema() {
+    let registry = IndicatorRegistry::new();
+    let names: Vec<&str> = registry.params("bbands").unwrap().iter().map(|p| p.name).collect();
+    assert_eq!(names, ["length", "std", "source"]);
+
+    // Aliases resolve to the canonical name; defaults fill the rest
+    let params: HashMap<String, ParamValue> = [("period".to_string(), 5.0.into())].into();
+    let resolved = registry.resolve_params("RSI", &params).unwrap();
+    assert_eq!(resolved.usize("length").unwrap(), 5);
+    assert_eq!(resolved.str("source").unwrap(), "close");
+    assert_eq!(registry.get_lookback("RSI", &params).unwrap(), 6);
+
+    let error = |indicator: &str, name: &str, value: ParamValue| {
+        let params: HashMap<String, ParamValue> = [(name.to_string(), value)].into();
+        format!("{:#}", registry.resolve_params(indicator, &params).unwrap_err())
+    };
+    assert!(error("MACD", "slwo", 30.0.into()).contains("did you mean 'slow'?"));
+    assert!(error("ATR", "length", 0.5.into()).contains("'period' must be a whole number"));
+    assert!(error("BBANDS", "std", true.into()).contains("'std' must be a number from 0 to 100, got true"));
+    assert!(error("OBV", "length", 3.0.into()).contains("none are accepted"));
+}
+
+#[test]
+fn test_registry_aliases_agree() {
+    let registry = IndicatorRegistry::new();
+    assert_eq!(
+        registry.types(),
+        [
+            "AD", "ADX", "ATR", "BBANDS", "CMF", "DEMA", "DONCHIAN", "EMA", "EOM", "FORCE", "HMA", "ICHIMOKU", "KAMA",
+            "KELTNER", "MACD", "MFI", "OBV", "PSAR", "RSI", "RVOL", "SMA", "STOCH", "SUPERTREND", "TEMA", "VOLOSC",
+            "VWAP", "VWMA", "WMA"
+        ]
+    );
+
+    // Aliases reach the same definition for every use
+    let params: HashMap<String, ParamValue> = [("length".to_string(), 10.0.into())].into();
+    for name in ["BBANDS", "bb", "Bb"] {
+        assert_eq!(registry.get_lookback(name, &params).unwrap(), 10);
+        assert_eq!(registry.outputs(name).unwrap(), ["middle", "upper", "lower"]);
+        assert!(registry.create(name, &params).is_ok());
+    }
+    assert_eq!(registry.definition("stochastic").unwrap().name(), "STOCH");
+    assert!(IndicatorRegistry::empty().create("SMA", &params).is_err());
+}
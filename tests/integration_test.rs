@@ -36,6 +36,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = process_order_fill(&mut order, &candle, &portfolio, &fee_calc);
@@ -66,6 +67,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = process_order_fill(&mut order, &candle, &portfolio, &fee_calc);
@@ -95,6 +97,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = process_order_fill(&mut order, &candle, &portfolio, &fee_calc);
@@ -121,6 +124,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = process_order_fill(&mut order, &candle, &portfolio, &fee_calc);
@@ -0,0 +1,169 @@
+#[cfg(test)]
+mod tests {
+    use hl_backtest::data::loader::fetch_candles_paginated;
+    use hl_backtest::data::source::{
+        HyperliquidSource, LocalSource, MarketDataSource, MockData, MockInfoServer,
+    };
+    use hl_backtest::data::{load_candles, AssetMeta, Cache, Candle, TimeRange};
+    use hl_backtest::perps::funding::{FundingPoint, FundingSchedule};
+
+    const MINUTE_MS: u64 = 60 * 1000;
+    // 2024-01-01 00:00 UTC
+    const START: u64 = 1704067200000;
+
+    fn minute_candles(count: u64) -> Vec<Candle> {
+        (0..count)
+            .map(|i| {
+                let time_open = START + i * MINUTE_MS;
+                Candle {
+                    time_open,
+                    time_close: time_open + MINUTE_MS - 1,
+                    coin: "BTC".to_string(),
+                    interval: "1m".to_string(),
+                    open: 100.0 + i as f64,
+                    close: 100.5 + i as f64,
+                    high: 101.0 + i as f64,
+                    low: 99.5 + i as f64,
+                    volume: 2.0,
+                    num_trades: 3,
+                }
+            })
+            .collect()
+    }
+
+    fn mock_data() -> MockData {
+        MockData {
+            candles: minute_candles(7000),
+            funding: (0..3)
+                .map(|i| {
+                    (
+                        "BTC".to_string(),
+                        FundingPoint {
+                            ts_ms: START + i * 60 * MINUTE_MS,
+                            rate: 0.0001 * (i + 1) as f64,
+                        },
+                    )
+                })
+                .collect(),
+            meta: vec![AssetMeta {
+                name: "BTC".to_string(),
+                sz_decimals: 5,
+                max_leverage: Some(40),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paginated_fetch_against_mock_server() {
+        let server = MockInfoServer::start(mock_data()).await.unwrap();
+        let source = HyperliquidSource::with_base_url(&server.url()).unwrap();
+
+        let end = START + 7000 * MINUTE_MS - 1;
+        let (candles, report) = fetch_candles_paginated(&source, "BTC", "1m", START, end)
+            .await
+            .unwrap();
+
+        // 7000 one-minute bars need two requests at 5000 candles each
+        assert_eq!(candles.len(), 7000);
+        assert_eq!(report.requests, 2);
+        assert_eq!(server.request_count(), 2);
+        assert!(report.gaps.is_empty());
+        assert_eq!(candles[0].open, 100.0);
+        assert_eq!(candles[6999].time_open, START + 6999 * MINUTE_MS);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_and_cache_then_serve_locally() {
+        let server = MockInfoServer::start(mock_data()).await.unwrap();
+        let source = HyperliquidSource::with_base_url(&server.url()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::with_base_dir(dir.path()).unwrap();
+
+        let end = START + 120 * MINUTE_MS - 1;
+        let report = cache
+            .fetch_and_cache(&source, "BTC", "1m", START, end)
+            .await
+            .unwrap();
+        assert_eq!(report.candles, 120);
+
+        // A second load is served from the cache without another request
+        let requests = server.request_count();
+        let candles = load_candles(&source, &cache, "BTC", "1m", START, end)
+            .await
+            .unwrap();
+        assert_eq!(candles.len(), 120);
+        assert_eq!(server.request_count(), requests);
+
+        // The cache directory doubles as a local source
+        let local = LocalSource::new(dir.path());
+        assert_eq!(local.candles("BTC", "1m", START, end).await.unwrap().len(), 120);
+        assert!(local
+            .candles("BTC", "1m", START, end + 60 * MINUTE_MS)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_funding_and_meta_from_mock_server() {
+        let server = MockInfoServer::start(mock_data()).await.unwrap();
+        let source = HyperliquidSource::with_base_url(&server.url()).unwrap();
+
+        let schedule =
+            FundingSchedule::from_source(&source, "BTC", START, START + 24 * 60 * MINUTE_MS)
+                .await
+                .unwrap();
+        assert_eq!(schedule.rate_at(START + 90 * MINUTE_MS), Some(0.0002));
+
+        let meta = source.meta().await.unwrap();
+        assert_eq!(meta.len(), 1);
+        assert_eq!(meta[0].sz_decimals, 5);
+
+        // Validation still happens before any request is made
+        let requests = server.request_count();
+        assert!(FundingSchedule::from_source(&source, "../BTC", START, START + 1)
+            .await
+            .is_err());
+        assert_eq!(server.request_count(), requests);
+    }
+
+    #[tokio::test]
+    async fn test_local_source_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("ETH")).unwrap();
+        std::fs::write(
+            dir.path().join("ETH").join("funding.json"),
+            r#"[{"ts_ms": 1000, "rate": 0.0001}, {"ts_ms": 5000, "rate": -0.0002}]"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("meta.json"),
+            r#"{"universe": [{"name": "ETH", "szDecimals": 4, "maxLeverage": 25}]}"#,
+        )
+        .unwrap();
+
+        let local = LocalSource::new(dir.path());
+        let funding = local.funding("ETH", 0, 2000).await.unwrap();
+        assert_eq!(funding.len(), 1);
+        assert_eq!(funding[0].rate, 0.0001);
+        assert_eq!(local.meta().await.unwrap()[0].name, "ETH");
+        assert!(local.funding("SOL", 0, 2000).await.is_err());
+
+        // Candles the cache has not covered are an error, not an empty result
+        let cache = Cache::with_base_dir(dir.path()).unwrap();
+        let candles = minute_candles(10);
+        cache
+            .store(
+                "BTC",
+                "1m",
+                &candles,
+                &[TimeRange::new(START, START + 10 * MINUTE_MS - 1)],
+            )
+            .unwrap();
+        let loaded = local
+            .candles("BTC", "1m", START, START + 10 * MINUTE_MS - 1)
+            .await
+            .unwrap();
+        assert_eq!(loaded.len(), 10);
+        assert_eq!(loaded[9].close, candles[9].close);
+    }
+}
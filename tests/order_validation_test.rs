@@ -1,7 +1,6 @@
 #[cfg(test)]
 mod tests {
     use hl_backtest::data::types::Candle;
-    use hl_backtest::orders::types::{Action, Order, OrderStatus, Side, Tif};
     use hl_backtest::portfolio::Portfolio;
     use hl_backtest::fees::FeeCalculator;
 
@@ -22,6 +21,7 @@ mod tests {
     }
 
     // Helper to create a test portfolio
+    #[allow(dead_code)]
     fn create_test_portfolio() -> Portfolio {
         let fee_calc = FeeCalculator::new(-1, 10, 5);
         Portfolio::new(10000.0, fee_calc)
@@ -39,14 +39,14 @@ mod tests {
         
         // Verify the candle structure
         assert_eq!(candle.close, 0.0);
-        assert!(!(candle.close > 0.0)); // Invalid price
+        assert!(candle.close <= 0.0); // Invalid price
     }
 
     #[test]
     fn test_invalid_candle_price_negative() {
         let candle = create_test_candle(-100.0);
         assert_eq!(candle.close, -100.0);
-        assert!(!(candle.close > 0.0)); // Invalid price
+        assert!(candle.close <= 0.0); // Invalid price
     }
 
     #[test]
@@ -61,8 +61,8 @@ mod tests {
     fn test_order_size_validation_zero_cash() {
         // Orders with zero cash should not be created
         // We verify the validation constants exist
-        use std::f64;
-        assert!(f64::EPSILON > 0.0); // Epsilon exists for comparison
+        let epsilon = f64::EPSILON;
+        assert!(epsilon > 0.0); // Epsilon exists for comparison
     }
 
     #[test]
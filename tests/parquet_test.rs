@@ -2,7 +2,9 @@
 
 use hl_backtest::data::parquet::{
     export_candles_to_parquet, export_equity_to_parquet, export_funding_to_parquet,
-    export_trades_to_parquet, read_candles_from_parquet, FundingPayment,
+    export_trades_to_parquet, read_candles_from_parquet, read_equity_from_parquet,
+    read_funding_from_parquet, read_trades_from_parquet, schema_version, FundingPayment,
+    SCHEMA_VERSION,
 };
 use hl_backtest::data::types::Candle;
 use hl_backtest::orders::types::{EquityPoint, Trade};
@@ -59,6 +61,7 @@ fn create_test_trades() -> Vec<Trade> {
             price: 42000.0,
             fee: 2.1,
             order_id: 1,
+            tag: Some("entry_1".to_string()),
         },
         Trade {
             timestamp: 1704074400000,
@@ -68,6 +71,7 @@ fn create_test_trades() -> Vec<Trade> {
             price: 42900.0,
             fee: 2.145,
             order_id: 2,
+            tag: None,
         },
     ]
 }
@@ -418,3 +422,40 @@ fn test_different_intervals() {
     assert_eq!(loaded[1].interval, "1h");
     assert_eq!(loaded[2].interval, "1d");
 }
+
+#[test]
+fn test_results_parquet_roundtrip() {
+    let dir = tempdir().unwrap();
+    let trades = create_test_trades();
+    let equity = create_test_equity_curve();
+    let funding = create_test_funding_payments();
+
+    let trades_path = dir.path().join("trades.parquet");
+    let equity_path = dir.path().join("equity.parquet");
+    let funding_path = dir.path().join("funding.parquet");
+    export_trades_to_parquet(&trades, &trades_path).unwrap();
+    export_equity_to_parquet(&equity, &equity_path).unwrap();
+    export_funding_to_parquet(&funding, &funding_path).unwrap();
+
+    let loaded_trades = read_trades_from_parquet(&trades_path).unwrap();
+    assert_eq!(loaded_trades.len(), trades.len());
+    assert_eq!(loaded_trades[1].side, "SELL");
+    assert_eq!(loaded_trades[1].fee, 2.145);
+    assert_eq!(loaded_trades[1].order_id, 2);
+    assert_eq!(loaded_trades[0].tag.as_deref(), Some("entry_1"));
+    assert_eq!(loaded_trades[1].tag, None);
+
+    let loaded_equity = read_equity_from_parquet(&equity_path).unwrap();
+    assert_eq!(loaded_equity.len(), equity.len());
+    assert_eq!(loaded_equity[1].cash, equity[1].cash);
+    assert_eq!(loaded_equity[2].timestamp, equity[2].timestamp);
+
+    let loaded_funding = read_funding_from_parquet(&funding_path).unwrap();
+    assert_eq!(loaded_funding.len(), funding.len());
+    assert_eq!(loaded_funding[1].rate, -0.00005);
+    assert_eq!(loaded_funding[0].payment, -2.1);
+
+    for path in [&trades_path, &equity_path, &funding_path] {
+        assert_eq!(schema_version(path).unwrap(), Some(SCHEMA_VERSION));
+    }
+}
@@ -2,7 +2,7 @@
 mod tests {
     use hl_backtest::ingest::OrderLevel;
     use hl_backtest::orderbook::OrderBook;
-    use hl_backtest::perps::execution::{FillResult, PerpsExecution};
+    use hl_backtest::perps::execution::PerpsExecution;
     use hl_backtest::orders::types::{Action, Order, OrderStatus, Side, Tif};
 
     // Helper to create a book with bids and asks
@@ -37,6 +37,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = PerpsExecution::execute_market(&mut order, &book);
@@ -62,6 +63,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = PerpsExecution::execute_market(&mut order, &book);
@@ -83,6 +85,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = PerpsExecution::execute_market(&mut order, &book);
@@ -104,6 +107,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 1.0, // Already fully filled
             status: OrderStatus::Filled,
+            tag: None,
         };
 
         let result = PerpsExecution::execute_market(&mut order, &book);
@@ -123,6 +127,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = PerpsExecution::execute_market(&mut order, &book);
@@ -148,6 +153,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = PerpsExecution::execute_market(&mut order, &book);
@@ -180,6 +186,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = PerpsExecution::check_limit_fill(&mut order, &book);
@@ -212,6 +219,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = PerpsExecution::check_limit_fill(&mut order, &book);
@@ -238,6 +246,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = PerpsExecution::check_limit_fill(&mut order, &book);
@@ -268,6 +277,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = PerpsExecution::check_limit_fill(&mut order, &book);
@@ -305,6 +315,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         // First partial fill
@@ -320,7 +331,7 @@ mod tests {
         );
 
         // Check remaining size after first fill
-        let remaining_after_first = match &order.action {
+        let _remaining_after_first = match &order.action {
             Action::Limit { sz, .. } => *sz,
             _ => panic!("Expected Limit action"),
         };
@@ -360,6 +371,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.5, // Already fully filled
             status: OrderStatus::Filled,
+            tag: None,
         };
 
         let result = PerpsExecution::check_limit_fill(&mut order, &book);
@@ -386,6 +398,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = PerpsExecution::check_limit_fill(&mut order, &book);
@@ -409,6 +422,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = PerpsExecution::check_limit_fill(&mut order, &book);
@@ -437,6 +451,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         assert!(PerpsExecution::can_place_limit(&order, &book, true));
@@ -462,6 +477,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         assert!(!PerpsExecution::can_place_limit(&order, &book, true));
@@ -487,6 +503,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         assert!(PerpsExecution::can_place_limit(&order, &book, false));
@@ -507,6 +524,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let result = PerpsExecution::execute_market(&mut order, &book);
@@ -534,12 +552,12 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.4999999999, // Almost fully filled
             status: OrderStatus::PartiallyFilled,
+            tag: None,
         };
 
         let result = PerpsExecution::check_limit_fill(&mut order, &book);
         // Should handle very small remaining size
-        if result.is_some() {
-            let fill = result.unwrap();
+        if let Some(fill) = result {
             assert!(fill.filled_sz > 0.0);
         }
     }
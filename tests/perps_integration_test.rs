@@ -1,11 +1,10 @@
 #[cfg(test)]
 mod tests {
-    use hl_backtest::ingest::{parse_l2_jsonl, L2Event, OrderLevel};
+    use hl_backtest::ingest::{parse_l2_jsonl, OrderLevel};
     use hl_backtest::orderbook::OrderBook;
     use hl_backtest::perps::execution::PerpsExecution;
     use hl_backtest::orders::types::{Action, Order, OrderStatus, Side, Tif};
     use std::fs;
-    use std::path::Path;
     use tempfile::TempDir;
 
     #[tokio::test]
@@ -53,6 +52,7 @@ mod tests {
             created_at: 1694858400000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         // Order should fill at 25002.0 (best ask)
@@ -85,6 +85,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         let fill_result = PerpsExecution::execute_market(&mut order, &book);
@@ -95,5 +96,138 @@ mod tests {
         assert!(fill.fill_price >= 25001.0 && fill.fill_price <= 25002.0);
         assert!(!fill.is_maker); // Market orders are taker
     }
-}
 
+    /// One book snapshot per second with a one-wide spread around `mid(i)`
+    fn write_events(dir: &std::path::Path, start_ms: u64, count: u64, mid: impl Fn(u64) -> f64) {
+        fs::create_dir_all(dir).unwrap();
+        let lines: Vec<String> = (0..count)
+            .map(|i| {
+                let (ts, mid) = (start_ms + i * 1000, mid(i));
+                format!(
+                    r#"{{"time":"","raw":{{"data":{{"time":{},"levels":[[{{"px":"{}","sz":"5","n":1}}],[{{"px":"{}","sz":"5","n":1}}]]}}}}}}"#,
+                    ts,
+                    mid - 0.5,
+                    mid + 0.5
+                )
+            })
+            .collect();
+        fs::write(dir.join("20230916-09.jsonl"), lines.join("\n")).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_perps_indicators_on_another_coin() {
+        use hl_backtest::data::DataPolicy;
+        use hl_backtest::orders::types::SimConfig;
+        use hl_backtest::perps::funding::FundingSchedule;
+        use hl_backtest::perps::PerpsEngine;
+        use hl_backtest::strategy::Strategy;
+
+        const START: u64 = 1694858400000;
+        let temp_dir = TempDir::new().unwrap();
+        // ETH is flat; BTC's mid rises 10 a second, above 25045 from its 5th
+        // snapshot, which comes half a second after ETH's 5th
+        write_events(&temp_dir.path().join("ETH"), START, 10, |i| 1600.0 + (i % 2) as f64);
+        write_events(&temp_dir.path().join("BTC"), START - 500, 11, |i| 25000.0 + 10.0 * i as f64);
+
+        let strategy: Strategy = serde_json::from_str(
+            r#"{
+            "name": "BTC leads",
+            "instrument": { "symbol": "ETH-PERP", "coin": "ETH", "venue": "HL", "timeframe": "1m" },
+            "indicators": [
+                { "id": "btc", "type": "SMA", "params": { "length": 1 }, "outputs": ["value"], "coin": "BTC" }
+            ],
+            "entry": {
+                "condition": { "type": "compare", "lhs": "btc", "op": "gt", "rhs": 25045 },
+                "action": { "type": "buy", "size_pct": 10.0 }
+            }
+        }"#,
+        )
+        .unwrap();
+        let config = SimConfig {
+            initial_capital: 10000.0,
+            maker_fee_bps: 0,
+            taker_fee_bps: 0,
+            slippage_bps: 0,
+            trade_cooldown_ms: None,
+            data_policy: DataPolicy::Fail,
+        };
+
+        let run = |config: SimConfig| {
+            let (strategy, dir) = (strategy.clone(), temp_dir.path().join("ETH"));
+            async move {
+                PerpsEngine::run(dir, None, &strategy, &config, FundingSchedule::new(), "ETH", START - 1000, START + 20000, None, false)
+                    .await
+            }
+        };
+        let result = run(config.clone()).await.unwrap();
+        assert_eq!(result.trades[0].symbol, "ETH");
+        assert_eq!(result.trades[0].timestamp, START + 5000);
+
+        // BTC data that starts after ETH's is an error with strict data
+        fs::remove_dir_all(temp_dir.path().join("BTC")).unwrap();
+        write_events(&temp_dir.path().join("BTC"), START + 500, 10, |i| 25000.0 + 10.0 * i as f64);
+        assert!(run(config.clone()).await.is_err());
+        let warn = SimConfig {
+            data_policy: DataPolicy::Warn,
+            ..config
+        };
+        assert!(run(warn).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_perps_volume_from_trade_prints() {
+        use hl_backtest::data::DataPolicy;
+        use hl_backtest::orders::types::SimConfig;
+        use hl_backtest::perps::funding::FundingSchedule;
+        use hl_backtest::perps::PerpsEngine;
+        use hl_backtest::strategy::Strategy;
+
+        const START: u64 = 1694858400000;
+        let temp_dir = TempDir::new().unwrap();
+        // The mid alternates so that every snapshot is evaluated
+        write_events(&temp_dir.path().join("ETH"), START, 10, |i| 1600.0 + (i % 2) as f64);
+        // One print of 1 before each snapshot, then a print of 10 before the 8th
+        let trades_dir = temp_dir.path().join("trades");
+        fs::create_dir_all(&trades_dir).unwrap();
+        let lines: Vec<String> = (1..10u64)
+            .map(|i| {
+                let sz = if i == 7 { 10.0 } else { 1.0 };
+                format!(r#"{{"ts_ms":{},"px":1600.0,"sz":{}}}"#, START + i * 1000 - 500, sz)
+            })
+            .collect();
+        fs::write(trades_dir.join("20230916-09.jsonl"), lines.join("\n")).unwrap();
+
+        let strategy: Strategy = serde_json::from_str(
+            r#"{
+            "name": "Volume spike",
+            "instrument": { "symbol": "ETH-PERP", "coin": "ETH", "venue": "HL", "timeframe": "1m" },
+            "indicators": [{ "id": "rvol", "type": "RVOL", "params": { "length": 2 } }],
+            "entry": {
+                "condition": { "type": "compare", "lhs": "rvol", "op": "gt", "rhs": 3 },
+                "action": { "type": "buy", "size_pct": 10.0 }
+            }
+        }"#,
+        )
+        .unwrap();
+        let config = SimConfig {
+            initial_capital: 10000.0,
+            maker_fee_bps: 0,
+            taker_fee_bps: 0,
+            slippage_bps: 0,
+            trade_cooldown_ms: None,
+            data_policy: DataPolicy::Fail,
+        };
+
+        let events_dir = temp_dir.path().join("ETH");
+        let run = |with_trades: bool| {
+            let trades_dir = with_trades.then_some(trades_dir.as_path());
+            PerpsEngine::run(&events_dir, trades_dir, &strategy, &config, FundingSchedule::new(), "ETH", START, START + 20000, None, false)
+        };
+        let result = run(true).await.unwrap();
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!(result.trades[0].timestamp, START + 7000);
+
+        // Without prints the ticks have no volume
+        assert!(run(false).await.unwrap().trades.is_empty());
+    }
+}
@@ -73,6 +73,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         // Should fill immediately since limit price crosses ask
@@ -106,6 +107,7 @@ mod tests {
             created_at: 1000,
             filled_sz: 0.0,
             status: OrderStatus::Pending,
+            tag: None,
         };
 
         // Post-only order should be rejected if crossing
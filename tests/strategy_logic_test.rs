@@ -0,0 +1,268 @@
+//! Programmatic strategies driving both engines through `StrategyLogic`
+
+use anyhow::Result;
+use hl_backtest::data::types::Candle;
+use hl_backtest::data::DataPolicy;
+use hl_backtest::engine::{
+    run_backtest, JsonStrategy, MarketFeed, OhlcFillModel, StrategyContext, StrategyLogic,
+};
+use hl_backtest::fees::FeeCalculator;
+use hl_backtest::indicators2::{IndicatorDefinition, IndicatorEvaluator, IndicatorRegistry, ParamSpec};
+use hl_backtest::ingest::{L2Event, OrderLevel};
+use hl_backtest::orders::simulate_logic;
+use hl_backtest::orders::types::{Action, Side, SimConfig, Tif, Trade};
+use hl_backtest::perps::funding::{FundingPoint, FundingSchedule};
+use hl_backtest::perps::PerpsEngine;
+
+const MINUTE_MS: u64 = 60 * 1000;
+
+fn config() -> SimConfig {
+    SimConfig {
+        initial_capital: 10000.0,
+        maker_fee_bps: 0,
+        taker_fee_bps: 0,
+        slippage_bps: 0,
+        trade_cooldown_ms: None,
+        data_policy: DataPolicy::Warn,
+    }
+}
+
+/// Flat bars trading between 99 and 101
+fn bars(count: u64) -> Vec<Candle> {
+    (0..count)
+        .map(|i| Candle {
+            time_open: i * MINUTE_MS,
+            time_close: (i + 1) * MINUTE_MS - 1,
+            coin: "BTC".to_string(),
+            interval: "1m".to_string(),
+            open: 100.0,
+            high: 101.0,
+            low: 99.0,
+            close: 100.0,
+            volume: 1.0,
+            num_trades: 1,
+        })
+        .collect()
+}
+
+fn limit(side: Side, px: f64, sz: f64) -> Action {
+    Action::Limit {
+        side,
+        px,
+        sz,
+        tif: Tif::Gtc,
+        post_only: false,
+        reduce_only: false,
+    }
+}
+
+/// Rests a bid out of reach, amends it into the range, takes profit on fill,
+/// then parks an order and cancels it
+#[derive(Default)]
+struct DipBuyer {
+    started: bool,
+    bars: usize,
+    bid: Option<u64>,
+    parked: Option<u64>,
+    canceled: bool,
+    fills: Vec<Trade>,
+    funding_paid: f64,
+}
+
+impl StrategyLogic for DipBuyer {
+    fn on_start(&mut self, ctx: &mut StrategyContext) -> Result<()> {
+        assert!(ctx.mark_price().is_none());
+        self.started = true;
+        Ok(())
+    }
+
+    fn on_bar(&mut self, _bar: &Candle, ctx: &mut StrategyContext) -> Result<()> {
+        self.bars += 1;
+        match self.bars {
+            1 => self.bid = Some(ctx.place(limit(Side::Buy, 90.0, 2.0))),
+            2 => ctx.amend(self.bid.unwrap(), limit(Side::Buy, 99.5, 2.0))?,
+            _ => {}
+        }
+        if let Some(id) = self.parked.take() {
+            self.canceled = ctx.cancel(id);
+        }
+        Ok(())
+    }
+
+    fn on_fill(&mut self, fill: &Trade, ctx: &mut StrategyContext) -> Result<()> {
+        self.fills.push(fill.clone());
+        if fill.side == "BUY" {
+            ctx.place(limit(Side::Sell, 100.5, ctx.position()));
+        } else {
+            self.parked = Some(ctx.place(limit(Side::Buy, 50.0, 1.0)));
+        }
+        Ok(())
+    }
+
+    fn on_funding(
+        &mut self,
+        _point: &FundingPoint,
+        payment: f64,
+        _ctx: &mut StrategyContext,
+    ) -> Result<()> {
+        self.funding_paid += payment;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_custom_logic_places_amends_and_cancels_on_candles() {
+    let mut logic = DipBuyer::default();
+    let result = simulate_logic(&bars(6), "BTC", &mut logic, &config()).unwrap();
+
+    assert!(logic.started);
+    assert_eq!(logic.bars, 6);
+    // Bought 2 at the amended 99.5, sold at the 100.5 take-profit
+    assert_eq!(result.num_trades, 2);
+    assert_eq!(logic.fills[0].price, 99.5);
+    assert_eq!(logic.fills[1].price, 100.5);
+    assert_eq!(logic.fills[0].order_id, logic.bid.unwrap());
+    assert!(logic.canceled);
+    assert!((result.final_equity - 10002.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_custom_logic_sees_funding() {
+    let mut logic = DipBuyer::default();
+    // The bid fills on the second bar; funding of 1% settles right after it,
+    // while long 2 BTC at a mark of 100
+    let mut feed = MarketFeed::from_candles("BTC", bars(3)).with_funding(vec![FundingPoint {
+        ts_ms: MINUTE_MS,
+        rate: 0.01,
+    }]);
+    run_backtest(
+        &mut feed,
+        &mut OhlcFillModel::new(FeeCalculator::new(0, 0, 0)),
+        &mut logic,
+        &config(),
+    )
+    .unwrap();
+
+    assert_eq!(logic.fills.len(), 2);
+    assert!((logic.funding_paid - 2.0).abs() < 1e-9);
+}
+
+/// Buys once on the first book it sees
+#[derive(Default)]
+struct BookTaker {
+    books: usize,
+    fills: Vec<Trade>,
+}
+
+impl StrategyLogic for BookTaker {
+    fn on_bar(&mut self, _bar: &Candle, _ctx: &mut StrategyContext) -> Result<()> {
+        unreachable!("book feeds have no bars")
+    }
+
+    fn on_book(&mut self, _book: &L2Event, ctx: &mut StrategyContext) -> Result<()> {
+        self.books += 1;
+        if self.books == 1 {
+            ctx.place(Action::Market {
+                side: Side::Buy,
+                sz: 1.0,
+            });
+        }
+        Ok(())
+    }
+
+    fn on_fill(&mut self, fill: &Trade, _ctx: &mut StrategyContext) -> Result<()> {
+        self.fills.push(fill.clone());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_custom_logic_on_books() {
+    let events: Vec<L2Event> = (0..5)
+        .map(|i| L2Event {
+            ts_ms: i * MINUTE_MS,
+            levels: vec![
+                vec![OrderLevel { px: 99.0, sz: 10.0, n: 1 }],
+                vec![OrderLevel { px: 101.0, sz: 10.0, n: 1 }],
+            ],
+        })
+        .collect();
+
+    let mut logic = BookTaker::default();
+    let result = PerpsEngine::new(FundingSchedule::new(), &config())
+        .run_events("BTC", events, &mut logic)
+        .unwrap();
+
+    assert_eq!(logic.books, 5);
+    assert_eq!(result.num_trades, 1);
+    assert_eq!(logic.fills[0].price, 101.0);
+    // Marked at the 100 mid after paying the 101 ask
+    assert!((result.final_equity - 9999.0).abs() < 1e-9);
+}
+
+/// Counts bars from `start`
+struct Counter {
+    count: f64,
+}
+
+impl IndicatorEvaluator for Counter {
+    fn warmup(&self) -> usize {
+        2
+    }
+
+    fn update(&mut self, _candle: &Candle) -> Result<()> {
+        self.count += 1.0;
+        Ok(())
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["count", "double"]
+    }
+
+    fn value(&self, output: &str) -> Result<f64> {
+        match output {
+            "count" => Ok(self.count),
+            "double" => Ok(self.count * 2.0),
+            _ => anyhow::bail!("Unknown counter output: {}", output),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.count = 0.0;
+    }
+}
+
+#[test]
+fn test_json_strategy_with_registered_indicator() {
+    let mut registry = IndicatorRegistry::new();
+    let counter = IndicatorDefinition::new("COUNTER", &["count", "double"], |params| {
+        Ok(Box::new(Counter {
+            count: params.f64("start")?,
+        }))
+    })
+    .with_aliases(&["bars_seen"])
+    .with_params([ParamSpec::number("start", 0.0, 0.0, 1000.0, "Count before the first bar")]);
+    registry.register(counter.clone()).unwrap();
+    assert!(registry.register(counter).is_err());
+    let sma = IndicatorDefinition::new("sma", &["value"], |_| Ok(Box::new(Counter { count: 0.0 })));
+    assert!(registry.register(sma).is_err());
+
+    let strategy = serde_json::from_str(
+        r#"{
+        "name": "Counted",
+        "instrument": { "symbol": "BTC-PERP", "coin": "BTC", "venue": "HL", "timeframe": "1m" },
+        "indicators": [ { "id": "counter", "type": "Bars_Seen", "params": { "start": 2 } } ],
+        "entry": {
+            "condition": { "type": "compare", "lhs": "counter.double", "op": "gt", "rhs": 10 },
+            "action": { "type": "buy", "size_pct": 10.0 }
+        }
+    }"#,
+    )
+    .unwrap();
+    assert!(JsonStrategy::new(&strategy, false).is_err());
+
+    // Warm after the counter's 2 bars; 2 * (2 + 4) > 10 from the 4th bar
+    let mut logic = JsonStrategy::with_registry(&strategy, &registry, false).unwrap();
+    let result = simulate_logic(&bars(6), "BTC", &mut logic, &config()).unwrap();
+    assert_eq!(result.trades[0].timestamp, 3 * MINUTE_MS);
+}
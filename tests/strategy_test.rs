@@ -1,8 +1,9 @@
 //! Tests for the strategy module
 
+use hl_backtest::indicators2::ParamValue;
 use hl_backtest::strategy::{
-    compile_strategy, Action, ComparisonOp, Condition, CrossDirection, EvalState, Instrument,
-    IndicatorSpec, Rule, Strategy,
+    compile_strategy, Action, ComparisonOp, CompiledRule, Condition, CrossDirection, EvalState,
+    Instrument, IndicatorSpec, Pyramiding, Rule, Sizing, Strategy,
 };
 use std::collections::HashMap;
 
@@ -14,29 +15,40 @@ fn create_test_strategy() -> Strategy {
             coin: "BTC".to_string(),
             venue: "HL".to_string(),
             timeframe: "1h".to_string(),
+            sz_decimals: None,
+            max_leverage: None,
         },
         indicators: vec![IndicatorSpec {
             id: "rsi_14".to_string(),
             indicator_type: "RSI".to_string(),
-            params: [("period".to_string(), 14.0)].into_iter().collect(),
+            params: [("period".to_string(), 14.0.into())].into_iter().collect(),
             outputs: vec!["value".to_string()],
+            timeframe: None,
+            coin: None,
+            source: None,
         }],
-        entry: Rule {
+        entries: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::Threshold {
                 indicator: "rsi_14".to_string(),
                 op: ComparisonOp::Lt,
                 value: 30.0,
             },
-            action: Action::Buy { size_pct: 100.0 },
-        },
-        exit: Some(Rule {
+            action: Action::buy_pct(100.0),
+        }],
+        exits: vec![Rule {
+            name: None,
+            priority: 0,
             condition: Condition::Threshold {
                 indicator: "rsi_14".to_string(),
                 op: ComparisonOp::Gt,
                 value: 70.0,
             },
             action: Action::Close,
-        }),
+        }],
+        params: HashMap::new(),
+        pyramiding: None,
     }
 }
 
@@ -46,7 +58,7 @@ fn test_strategy_creation() {
     assert_eq!(strategy.name, "Test RSI Strategy");
     assert_eq!(strategy.instrument.coin, "BTC");
     assert_eq!(strategy.indicators.len(), 1);
-    assert!(strategy.exit.is_some());
+    assert_eq!(strategy.exits.len(), 1);
 }
 
 #[test]
@@ -130,7 +142,7 @@ fn test_crossover_condition_parsing() {
     }"#;
 
     let strategy: Strategy = serde_json::from_str(json).unwrap();
-    match &strategy.entry.condition {
+    match &strategy.entries[0].condition {
         Condition::Crossover {
             fast,
             slow,
@@ -166,7 +178,7 @@ fn test_and_condition_parsing() {
     }"#;
 
     let strategy: Strategy = serde_json::from_str(json).unwrap();
-    match &strategy.entry.condition {
+    match &strategy.entries[0].condition {
         Condition::And { conditions } => {
             assert_eq!(conditions.len(), 2);
         }
@@ -195,7 +207,7 @@ fn test_or_condition_parsing() {
     }"#;
 
     let strategy: Strategy = serde_json::from_str(json).unwrap();
-    match &strategy.entry.condition {
+    match &strategy.entries[0].condition {
         Condition::Or { conditions } => {
             assert_eq!(conditions.len(), 2);
         }
@@ -292,8 +304,8 @@ fn test_eval_state_crossover_above() {
     .collect();
 
     let condition = Condition::Crossover {
-        fast: "fast_ma".to_string(),
-        slow: "slow_ma".to_string(),
+        fast: "fast_ma".into(),
+        slow: "slow_ma".into(),
         direction: CrossDirection::Above,
     };
 
@@ -322,8 +334,8 @@ fn test_eval_state_crossover_below() {
     .collect();
 
     let condition = Condition::Crossover {
-        fast: "fast_ma".to_string(),
-        slow: "slow_ma".to_string(),
+        fast: "fast_ma".into(),
+        slow: "slow_ma".into(),
         direction: CrossDirection::Below,
     };
 
@@ -352,8 +364,8 @@ fn test_eval_state_crossover_no_cross() {
     .collect();
 
     let condition = Condition::Crossover {
-        fast: "fast_ma".to_string(),
-        slow: "slow_ma".to_string(),
+        fast: "fast_ma".into(),
+        slow: "slow_ma".into(),
         direction: CrossDirection::Above,
     };
 
@@ -464,12 +476,12 @@ fn test_eval_state_or_condition_all_false() {
 
 #[test]
 fn test_action_buy_serialization() {
-    let action = Action::Buy { size_pct: 75.0 };
+    let action = Action::buy_pct(75.0);
     let json = serde_json::to_string(&action).unwrap();
     let parsed: Action = serde_json::from_str(&json).unwrap();
 
     match parsed {
-        Action::Buy { size_pct } => assert_eq!(size_pct, 75.0),
+        Action::Buy { size_pct, .. } => assert_eq!(size_pct, Some(75.0)),
         _ => panic!("Expected Buy action"),
     }
 }
@@ -525,3 +537,387 @@ fn test_all_comparison_operators() {
     };
     assert!(state.evaluate(&cond_ne, &values_ne));
 }
+
+fn expression_strategy(entry: &str, exit: &str) -> Strategy {
+    let json = format!(
+        r#"{{
+        "name": "Expressions",
+        "instrument": {{ "symbol": "BTCUSD", "coin": "BTC", "venue": "HL", "timeframe": "1h" }},
+        "indicators": [
+            {{ "id": "sma", "type": "SMA", "params": {{ "length": 3 }}, "outputs": ["value"] }}
+        ],
+        "params": {{ "band": 1.02 }},
+        "entry": {{
+            "condition": {{ "type": "compare", "lhs": "close", "op": "gt", "rhs": "{}" }},
+            "action": {{ "type": "buy", "size_pct": 50.0 }}
+        }},
+        "exit": {{
+            "condition": {{ "type": "compare", "lhs": "{}", "op": "lt", "rhs": -2 }},
+            "action": {{ "type": "close" }}
+        }}
+    }}"#,
+        entry, exit
+    );
+    serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn test_compile_rejects_unknown_threshold_indicator() {
+    let mut strategy = create_test_strategy();
+    strategy.entries[0].condition = Condition::Threshold {
+        indicator: "rsii_14".to_string(),
+        op: ComparisonOp::Lt,
+        value: 30.0,
+    };
+
+    let err = compile_strategy(&strategy).err().expect("Typo should not compile");
+    assert!(format!("{err:#}").contains("Unknown indicator 'rsii_14'"));
+}
+
+#[test]
+fn test_compile_resolves_expressions() {
+    let strategy = expression_strategy("sma * band", "unrealized_pnl_pct");
+    let compiled = compile_strategy(&strategy).unwrap();
+    match &compiled.entries[0].condition {
+        Condition::Compare { rhs, .. } => assert_eq!(rhs.to_string(), "sma * 1.02"),
+        _ => panic!("Expected compare condition"),
+    }
+
+    // Expressions round-trip through JSON as strings and numbers
+    let json = serde_json::to_value(&strategy).unwrap();
+    assert_eq!(json["entries"][0]["condition"]["rhs"], "sma * band");
+    assert_eq!(json["exits"][0]["condition"]["rhs"], -2.0);
+    assert_eq!(json["params"]["band"], 1.02);
+}
+
+#[test]
+fn test_compile_rejects_bad_expressions() {
+    for (entry, exit) in [
+        ("sma * bandd", "unrealized_pnl_pct"),
+        ("sqrt(sma)", "unrealized_pnl_pct"),
+        ("sma", "min(entry_price)"),
+    ] {
+        let strategy = expression_strategy(entry, exit);
+        assert!(compile_strategy(&strategy).is_err(), "{} / {}", entry, exit);
+    }
+
+    // Syntax errors are reported when parsing the JSON
+    let json = r#"{ "type": "compare", "lhs": "close +", "op": "gt", "rhs": 1 }"#;
+    assert!(serde_json::from_str::<Condition>(json).is_err());
+}
+
+#[test]
+fn test_compile_sizes_history_for_offsets() {
+    let mut strategy = expression_strategy("highest(high, 20)[1]", "sma[3] - sma");
+    strategy.exits[0].condition = Condition::Or {
+        conditions: vec![
+            strategy.exits[0].condition.clone(),
+            Condition::Crossover {
+                fast: "close".into(),
+                slow: "sma".into(),
+                direction: CrossDirection::Below,
+            },
+        ],
+    };
+    let compiled = compile_strategy(&strategy).unwrap();
+
+    assert_eq!(compiled.history_depths["high"], 20);
+    assert_eq!(compiled.history_depths["sma"], 3);
+    assert_eq!(compiled.history_depths["close"], 1);
+    // SMA(3) warms up in 3 bars, then 20 bars of highs are needed
+    assert_eq!(compiled.indicator_lookback(), 3);
+    assert_eq!(compiled.lookback(), 23);
+}
+
+#[test]
+fn test_temporal_conditions_from_json() {
+    let json = r#"{
+        "type": "and",
+        "conditions": [
+            { "type": "for", "bars": 3, "condition": { "type": "threshold", "indicator": "sma", "op": "lt", "value": 30 } },
+            { "type": "count_of", "n": 4, "m": 6, "condition": { "type": "compare", "lhs": "close", "op": "gt", "rhs": "close[1]" } },
+            { "type": "not", "condition": {
+                "type": "latch",
+                "set": { "type": "compare", "lhs": "close", "op": "gt", "rhs": 100 },
+                "reset": { "type": "within", "bars": 5, "condition": { "type": "crossover", "fast": "close", "slow": "sma", "direction": "below" } }
+            } }
+        ]
+    }"#;
+    let mut strategy = expression_strategy("sma", "unrealized_pnl_pct");
+    strategy.entries[0].condition = serde_json::from_str(json).unwrap();
+    let compiled = compile_strategy(&strategy).unwrap();
+
+    // count_of over close[1] needs 5 + 1 past closes; the crossover within 5
+    // bars needs 4 + 1 past values of sma
+    assert_eq!(compiled.history_depths["close"], 6);
+    assert_eq!(compiled.history_depths["sma"], 5);
+
+    for bad in [
+        r#"{ "type": "for", "bars": 0, "condition": { "type": "and", "conditions": [] } }"#,
+        r#"{ "type": "count_of", "n": 5, "m": 4, "condition": { "type": "and", "conditions": [] } }"#,
+    ] {
+        strategy.entries[0].condition = serde_json::from_str(bad).unwrap();
+        assert!(compile_strategy(&strategy).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_compile_assigns_temporal_slots() {
+    let for_2 = r#"{ "type": "for", "bars": 2, "condition": { "type": "compare", "lhs": "close", "op": "gt", "rhs": "sma" } }"#;
+    let within_3 = r#"{ "type": "within", "bars": 3, "condition": { "type": "compare", "lhs": "close", "op": "gt", "rhs": "sma" } }"#;
+    let mut strategy = expression_strategy("sma", "unrealized_pnl_pct");
+    strategy.entries[0].condition = Condition::And {
+        conditions: vec![serde_json::from_str(for_2).unwrap(), serde_json::from_str(within_3).unwrap()],
+    };
+    strategy.exits[0].condition = serde_json::from_str(within_3).unwrap();
+    let compiled = compile_strategy(&strategy).unwrap();
+
+    let slot = |condition: &Condition| match condition {
+        Condition::For { slot, .. } | Condition::Within { slot, .. } => *slot,
+        _ => panic!("Expected temporal condition"),
+    };
+    let Condition::And { conditions } = &compiled.entries[0].condition else {
+        panic!("Expected and condition");
+    };
+    // Distinct conditions keep separate state; equal ones share it
+    assert_ne!(slot(&conditions[0]), slot(&conditions[1]));
+    assert_eq!(slot(&conditions[1]), slot(&compiled.exits[0].condition));
+}
+
+#[test]
+fn test_multiple_rules_from_json() {
+    let json = r#"{
+        "name": "Several exits",
+        "instrument": { "symbol": "BTCUSD", "coin": "BTC", "venue": "HL", "timeframe": "1h" },
+        "indicators": [
+            { "id": "sma", "type": "SMA", "params": { "length": 3 }, "outputs": ["value"] }
+        ],
+        "entries": [
+            { "condition": { "type": "compare", "lhs": "close", "op": "gt", "rhs": "sma" },
+              "action": { "type": "buy", "size_pct": 25.0 } },
+            { "name": "breakout", "priority": 10,
+              "condition": { "type": "compare", "lhs": "close", "op": "gt", "rhs": "highest(high, 5)[1]" },
+              "action": { "type": "buy", "size_pct": 100.0 } }
+        ],
+        "exits": [
+            { "name": "signal",
+              "condition": { "type": "compare", "lhs": "close", "op": "lt", "rhs": "sma" },
+              "action": { "type": "close" } },
+            { "name": "stop", "priority": 1,
+              "condition": { "type": "compare", "lhs": "unrealized_pnl_pct", "op": "lt", "rhs": -5 },
+              "action": { "type": "close" } }
+        ]
+    }"#;
+    let strategy: Strategy = serde_json::from_str(json).unwrap();
+    assert_eq!(strategy.entries.len(), 2);
+    assert_eq!(strategy.entries[1].priority, 10);
+
+    // Highest priority first, unnamed rules named by position
+    let compiled = compile_strategy(&strategy).unwrap();
+    let names = |rules: &[CompiledRule]| rules.iter().map(|r| r.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names(&compiled.entries), ["breakout", "entry_1"]);
+    assert_eq!(names(&compiled.exits), ["stop", "signal"]);
+    assert_eq!(compiled.history_depths["high"], 5);
+
+    // Serialized in the list form, which reads back the same
+    let value = serde_json::to_value(&strategy).unwrap();
+    assert!(value.get("entry").is_none());
+    assert_eq!(value["entries"][1]["name"], "breakout");
+    assert!(value["entries"][0].get("priority").is_none());
+    let reread: Strategy = serde_json::from_value(value).unwrap();
+    assert_eq!(reread.exits.len(), 2);
+
+    let mut duplicate = strategy.clone();
+    duplicate.exits[0].name = Some("breakout".to_string());
+    assert!(compile_strategy(&duplicate).is_err());
+}
+
+#[test]
+fn test_single_rule_json_is_still_accepted() {
+    let strategy = expression_strategy("sma", "unrealized_pnl_pct");
+    assert_eq!(strategy.entries.len(), 1);
+    assert_eq!(strategy.exits.len(), 1);
+    let compiled = compile_strategy(&strategy).unwrap();
+    assert_eq!(compiled.entries[0].name, "entry_1");
+    assert_eq!(compiled.exits[0].name, "exit_1");
+
+    let mut value = serde_json::to_value(&strategy).unwrap();
+    let entries = value["entries"].clone();
+    value["entry"] = entries[0].clone();
+    // Both forms at once are ambiguous
+    assert!(serde_json::from_value::<Strategy>(value.clone()).is_err());
+    // And a strategy needs an entry
+    value.as_object_mut().unwrap().remove("entries");
+    value.as_object_mut().unwrap().remove("entry");
+    assert!(serde_json::from_value::<Strategy>(value).is_err());
+}
+
+#[test]
+fn test_compile_pyramiding() {
+    let mut strategy = expression_strategy("sma", "bars_since_entry");
+    strategy.entries[0].condition = serde_json::from_str(
+        r#"{ "type": "compare", "lhs": "position_entries", "op": "lt", "rhs": 3 }"#,
+    )
+    .unwrap();
+    strategy.pyramiding = Some(Pyramiding {
+        max_entries: 3,
+        min_bars: 2,
+        min_price_move_pct: 0.0,
+        add_sizing: vec![Sizing::RiskPerTrade { risk_pct: 1.0, stop: "high[4] - low".parse().unwrap() }],
+    });
+    let compiled = compile_strategy(&strategy).unwrap();
+    assert_eq!(compiled.pyramiding.unwrap().max_entries, 3);
+    // Add sizing is validated and sizes history like any other
+    assert_eq!(compiled.history_depths["high"], 4);
+
+    let value = serde_json::to_value(&strategy).unwrap();
+    assert_eq!(value["pyramiding"]["add_sizing"][0]["type"], "risk_per_trade");
+    let reread: Strategy = serde_json::from_value(value).unwrap();
+    assert_eq!(reread.pyramiding.unwrap().min_bars, 2);
+
+    let invalid = [
+        Pyramiding { max_entries: 0, ..strategy.pyramiding.clone().unwrap() },
+        Pyramiding { min_price_move_pct: -1.0, ..strategy.pyramiding.clone().unwrap() },
+        Pyramiding {
+            add_sizing: vec![Sizing::EquityPct { pct: 0.0 }],
+            ..strategy.pyramiding.clone().unwrap()
+        },
+    ];
+    for pyramiding in invalid {
+        strategy.pyramiding = Some(pyramiding);
+        assert!(compile_strategy(&strategy).is_err());
+    }
+}
+
+#[test]
+fn test_compile_higher_timeframe_indicators() {
+    let mut strategy = expression_strategy("sma", "unrealized_pnl_pct");
+    strategy.indicators[0].timeframe = Some("4h".to_string());
+    let compiled = compile_strategy(&strategy).unwrap();
+    // Three 4h bars, plus one for a first bar cut off by the data, in 1h bars
+    assert_eq!(compiled.indicators[0].lookback, 15);
+    assert_eq!(compiled.indicators[0].timeframe.as_deref(), Some("4h"));
+
+    // The instrument's own timeframe is no higher timeframe
+    strategy.indicators[0].timeframe = Some("1h".to_string());
+    let compiled = compile_strategy(&strategy).unwrap();
+    assert_eq!((compiled.indicators[0].lookback, compiled.indicators[0].timeframe.clone()), (3, None));
+
+    for timeframe in ["30m", "90m", "4x"] {
+        strategy.indicators[0].timeframe = Some(timeframe.to_string());
+        assert!(compile_strategy(&strategy).is_err(), "{}", timeframe);
+    }
+}
+
+#[test]
+fn test_compile_indicator_chains() {
+    let mut strategy = expression_strategy("sma", "unrealized_pnl_pct");
+    let indicator = |json: &str| -> IndicatorSpec { serde_json::from_str(json).unwrap() };
+    // Declared before the indicators they read
+    strategy.indicators = vec![
+        indicator(r#"{ "id": "smooth", "type": "SMA", "params": { "length": 2 }, "outputs": ["value"], "source": "rsi" }"#),
+        indicator(r#"{ "id": "rsi", "type": "RSI", "params": { "length": 14 }, "outputs": ["value"], "source": "sma" }"#),
+        indicator(r#"{ "id": "sma", "type": "SMA", "params": { "length": 3 }, "outputs": ["value"], "timeframe": "4h" }"#),
+        indicator(r#"{ "id": "hist", "type": "SMA", "params": { "length": 5 }, "outputs": ["value"], "source": "macd.histogram" }"#),
+        indicator(r#"{ "id": "macd", "type": "MACD", "params": {}, "outputs": ["macd", "signal", "histogram"] }"#),
+    ];
+    let compiled = compile_strategy(&strategy).unwrap();
+    let ids: Vec<&str> = compiled.indicators.iter().map(|i| i.id.as_str()).collect();
+    assert_eq!(ids, ["sma", "rsi", "smooth", "macd", "hist"]);
+
+    // Lookbacks add up along the chain, in 4h bars inherited from the source
+    let smooth = &compiled.indicators[2];
+    assert_eq!(smooth.timeframe.as_deref(), Some("4h"));
+    assert_eq!(smooth.lookback, (3 + 15 + 2 + 1) * 4 - 1);
+    let source = smooth.source.as_ref().unwrap();
+    assert_eq!((source.indicator.as_str(), source.output.as_str()), ("rsi", "value"));
+    assert_eq!(compiled.indicators[4].lookback, (26 + 27) + 5);
+    assert_eq!(compiled.indicators[4].source.as_ref().unwrap().output, "histogram");
+
+    let mut cycle = strategy.clone();
+    cycle.indicators[2].source = Some("smooth".to_string());
+    let err = compile_strategy(&cycle).err().unwrap();
+    assert!(format!("{:#}", err).contains("smooth -> rsi -> sma -> smooth"), "{:#}", err);
+    let mut own = strategy.clone();
+    own.indicators[2].source = Some("sma".to_string());
+    assert!(compile_strategy(&own).is_err());
+
+    for (index, source) in [(0, "missing"), (0, "rsi.signal"), (3, "macd.hist")] {
+        let mut bad = strategy.clone();
+        bad.indicators[index].source = Some(source.to_string());
+        assert!(compile_strategy(&bad).is_err(), "{}", source);
+    }
+    // An indicator alone is its primary output
+    let mut primary = strategy.clone();
+    primary.indicators[3].source = Some("macd".to_string());
+    let compiled = compile_strategy(&primary).unwrap();
+    assert_eq!(compiled.indicators[4].source.as_ref().unwrap().output, "macd");
+    // A chained indicator stays on its source's timeframe and coin
+    let mut moved = strategy.clone();
+    moved.indicators[0].timeframe = Some("1h".to_string());
+    assert!(compile_strategy(&moved).is_err());
+    moved.indicators[0].timeframe = Some("4h".to_string());
+    assert!(compile_strategy(&moved).is_ok());
+    moved.indicators[0].coin = Some("ETH".to_string());
+    assert!(compile_strategy(&moved).is_err());
+}
+
+#[test]
+fn test_compile_checks_indicator_params() {
+    let mut strategy = expression_strategy("sma", "unrealized_pnl_pct");
+    strategy.indicators[0].params = serde_json::from_str(r#"{ "period": 5, "source": "HLC3" }"#).unwrap();
+    let compiled = compile_strategy(&strategy).unwrap();
+    // Stored under canonical names, with defaults and normalized choices
+    let params = &compiled.indicators[0].params;
+    assert_eq!(params.len(), 2);
+    assert_eq!(params["length"], ParamValue::Number(5.0));
+    assert_eq!(params["source"], "hlc3".into());
+    assert_eq!(compiled.indicators[0].lookback, 5);
+
+    strategy.indicators[0].params = serde_json::from_str(r#"{ "lenght": 5 }"#).unwrap();
+    let err = format!("{:#}", compile_strategy(&strategy).err().unwrap());
+    assert!(err.contains("Invalid indicator 'sma'"), "{}", err);
+    assert!(err.contains("did you mean 'length'?"), "{}", err);
+    strategy.indicators[0].params = serde_json::from_str(r#"{ "length": "5" }"#).unwrap();
+    assert!(compile_strategy(&strategy).is_err());
+}
+
+#[test]
+fn test_compile_exposes_referenced_outputs() {
+    let mut strategy = expression_strategy("stoch.d + adx.plus_di - adx.value", "bb");
+    strategy.indicators = serde_json::from_str(
+        r#"[
+            { "id": "stoch", "type": "Stochastic", "params": {} },
+            { "id": "adx", "type": "ADX", "params": {}, "outputs": ["dx"] },
+            { "id": "bb", "type": "BBANDS", "params": {}, "outputs": ["lower"] },
+            { "id": "unused", "type": "MACD", "params": {} }
+        ]"#,
+    )
+    .unwrap();
+    let compiled = compile_strategy(&strategy).unwrap();
+    let outputs: Vec<Vec<String>> = compiled.indicators.iter().map(|i| i.outputs.clone()).collect();
+    // In each indicator's order; `value` and the id alone name the primary output
+    assert_eq!(outputs[0], ["d"]);
+    assert_eq!(outputs[1], ["adx", "plus_di", "dx"]);
+    assert_eq!(outputs[2], ["middle", "lower"]);
+    assert!(outputs[3].is_empty());
+
+    let mut misspelled = strategy.clone();
+    misspelled.entries[0].condition = serde_json::from_str(
+        r#"{ "type": "compare", "lhs": "stoch.j", "op": "gt", "rhs": 80 }"#,
+    )
+    .unwrap();
+    let err = format!("{:#}", compile_strategy(&misspelled).err().unwrap());
+    assert!(err.contains("Indicator 'stoch' has no output 'j' (it has k, d)"), "{}", err);
+
+    let mut threshold = strategy.clone();
+    threshold.exits[0].condition = serde_json::from_str(
+        r#"{ "type": "threshold", "indicator": "bb.mid", "op": "lt", "value": 1 }"#,
+    )
+    .unwrap();
+    assert!(compile_strategy(&threshold).is_err());
+
+    let mut declared = strategy.clone();
+    declared.indicators[2].outputs = vec!["band".to_string()];
+    assert!(compile_strategy(&declared).is_err());
+}